@@ -112,6 +112,10 @@ fn main() -> Result<()> {
         &["proto"],
     )?;
 
+    prost_build::Config::new().compile_protos(&["proto/loki/push.proto"], &["proto"])?;
+    prost_build::Config::new()
+        .compile_protos(&["proto/datadog/agent_payload.proto"], &["proto"])?;
+
     // build information
     let output = Command::new("git")
         .args(["describe", "--tags", "--abbrev=0"])
@@ -25,8 +25,14 @@ use vector_enrichment::TableRegistry;
 
 use crate::common::meta::{
     alert::{AlertDestination, AlertList, DestinationTemplate, Trigger, TriggerTimer},
+    dead_letter::DeadLetterSettings,
+    fluent::FluentRoute,
     functions::{StreamFunctionsList, Transform},
     prom::ClusterLeader,
+    quota::IngestionQuota,
+    redaction::StreamRedaction,
+    routing::StreamRoute,
+    sampling::SamplingRule,
     syslog::SyslogRoute,
     user::User,
 };
@@ -46,6 +52,8 @@ pub static BUILD_DATE: &str = env!("GIT_BUILD_DATE");
 pub const HAS_FUNCTIONS: bool = true;
 pub const FILE_EXT_JSON: &str = ".json";
 pub const FILE_EXT_PARQUET: &str = ".parquet";
+pub const FILE_EXT_INDEX: &str = ".idx";
+pub const FILE_EXT_STATS: &str = ".stats";
 
 pub const PARQUET_BATCH_SIZE: usize = 8 * 1024;
 pub const PARQUET_PAGE_SIZE: usize = 1024 * 1024;
@@ -71,6 +79,8 @@ pub static STREAM_FUNCTIONS: Lazy<RwHashMap<String, StreamFunctionsList>> =
     Lazy::new(DashMap::default);
 pub static QUERY_FUNCTIONS: Lazy<RwHashMap<String, Transform>> = Lazy::new(DashMap::default);
 pub static USERS: Lazy<RwHashMap<String, User>> = Lazy::new(DashMap::default);
+/// `{org}/{passcode}` to the email of the user of the ingestion passcode
+pub static USER_PASSCODES: Lazy<RwHashMap<String, String>> = Lazy::new(DashMap::default);
 pub static ROOT_USER: Lazy<RwHashMap<String, User>> = Lazy::new(DashMap::default);
 pub static PASSWORD_HASH: Lazy<RwHashMap<String, String>> = Lazy::new(DashMap::default);
 pub static METRIC_CLUSTER_MAP: Lazy<Arc<RwAHashMap<String, Vec<String>>>> =
@@ -86,6 +96,22 @@ pub static ALERTS_DESTINATIONS: Lazy<RwHashMap<String, AlertDestination>> =
     Lazy::new(Default::default);
 pub static SYSLOG_ROUTES: Lazy<RwHashMap<String, SyslogRoute>> = Lazy::new(Default::default);
 pub static SYSLOG_ENABLED: Lazy<Arc<RwLock<bool>>> = Lazy::new(|| Arc::new(RwLock::new(false)));
+pub static FLUENT_ROUTES: Lazy<RwHashMap<String, FluentRoute>> = Lazy::new(Default::default);
+pub static STREAM_ROUTES: Lazy<RwHashMap<String, StreamRoute>> = Lazy::new(Default::default);
+pub static DEAD_LETTER_SETTINGS: Lazy<RwHashMap<String, DeadLetterSettings>> =
+    Lazy::new(Default::default);
+/// the sampling rules of the streams by `{org_id}/{stream_name}`, sorted by
+/// their order
+pub static STREAM_SAMPLING_RULES: Lazy<RwHashMap<String, Arc<Vec<SamplingRule>>>> =
+    Lazy::new(Default::default);
+pub static INGESTION_QUOTAS: Lazy<RwHashMap<String, IngestionQuota>> = Lazy::new(Default::default);
+/// the redaction settings of the streams by `{org_id}/{stream_type}/{stream_name}`
+pub static STREAM_REDACTIONS: Lazy<RwHashMap<String, StreamRedaction>> =
+    Lazy::new(Default::default);
+/// the latest version of the `_id`s of the streams with `upsert_by_id` by
+/// `{org_id}/{stream_name}`
+pub static DOC_VERSIONS: Lazy<RwHashMap<String, Arc<RwHashMap<String, i64>>>> =
+    Lazy::new(Default::default);
 pub static ENRICHMENT_TABLES: Lazy<RwHashMap<String, StreamTable>> = Lazy::new(Default::default);
 pub static ENRICHMENT_REGISTRY: Lazy<Arc<TableRegistry>> =
     Lazy::new(|| Arc::new(TableRegistry::default()));
@@ -161,6 +187,50 @@ pub struct TCP {
     pub tcp_port: u16,
     #[env_config(name = "ZO_UDP_PORT", default = 5514)]
     pub udp_port: u16,
+    #[env_config(name = "ZO_TCP_TLS_ENABLED", default = false)]
+    pub tcp_tls_enabled: bool,
+    #[env_config(name = "ZO_TCP_TLS_PORT", default = 6514)]
+    pub tcp_tls_port: u16,
+    #[env_config(name = "ZO_TCP_TLS_CERT_PATH", default = "")]
+    pub tcp_tls_cert_path: String,
+    #[env_config(name = "ZO_TCP_TLS_KEY_PATH", default = "")]
+    pub tcp_tls_key_path: String,
+    // senders wait in the backlog when all the connections are in use
+    #[env_config(name = "ZO_TCP_MAX_CONNECTIONS", default = 1024)]
+    pub tcp_max_connections: usize,
+    #[env_config(name = "ZO_SYSLOG_MAX_MESSAGE_SIZE", default = 65536)] // bytes
+    pub syslog_max_message_size: usize,
+    #[env_config(name = "ZO_FLUENT_FORWARD_ENABLED", default = false)]
+    pub fluent_forward_enabled: bool,
+    #[env_config(name = "ZO_FLUENT_FORWARD_PORT", default = 24224)]
+    pub fluent_forward_port: u16,
+    // shared key of the forward handshake, no handshake when empty
+    #[env_config(name = "ZO_FLUENT_FORWARD_SHARED_KEY", default = "")]
+    pub fluent_forward_shared_key: String,
+    #[env_config(name = "ZO_FLUENT_FORWARD_HOSTNAME", default = "openobserve")]
+    pub fluent_forward_hostname: String,
+    #[env_config(name = "ZO_GRAPHITE_ENABLED", default = false)]
+    pub graphite_enabled: bool,
+    // the same port is used for tcp and udp
+    #[env_config(name = "ZO_GRAPHITE_PORT", default = 2003)]
+    pub graphite_port: u16,
+    // organization of the graphite metrics, the protocol has no authentication
+    #[env_config(name = "ZO_GRAPHITE_ORG", default = "default")]
+    pub graphite_org: String,
+    // the protocol has no authentication, only the senders of these networks
+    // are accepted, comma separated
+    #[env_config(name = "ZO_GRAPHITE_ALLOWED_NETWORKS", default = "127.0.0.0/8,::1/128")]
+    pub graphite_allowed_networks: String,
+    #[env_config(name = "ZO_STATSD_ENABLED", default = false)]
+    pub statsd_enabled: bool,
+    // the same port is used for tcp and udp
+    #[env_config(name = "ZO_STATSD_PORT", default = 8125)]
+    pub statsd_port: u16,
+    // organization of the statsd metrics, the protocol has no authentication
+    #[env_config(name = "ZO_STATSD_ORG", default = "default")]
+    pub statsd_org: String,
+    #[env_config(name = "ZO_STATSD_FLUSH_INTERVAL", default = 10)] // seconds
+    pub statsd_flush_interval: u64,
 }
 
 #[derive(EnvConfig)]
@@ -213,6 +283,17 @@ pub struct Common {
     pub feature_per_thread_lock: bool,
     #[env_config(name = "ZO_FEATURE_FULLTEXT_ON_ALL_FIELDS", default = false)]
     pub feature_fulltext_on_all_fields: bool,
+    #[env_config(name = "ZO_FEATURE_FULLTEXT_INDEX_ENABLED", default = false)]
+    pub feature_fulltext_index_enabled: bool,
+    #[env_config(name = "ZO_COLUMN_STATS_ENABLED", default = false)]
+    pub column_stats_enabled: bool,
+    #[env_config(name = "ZO_COLUMN_STATS_MAX_FIELDS", default = 100)]
+    pub column_stats_max_fields: usize,
+    #[env_config(
+        name = "ZO_BLOOM_FILTER_FIELDS",
+        default = "trace_id,request_id,k8s_pod_name"
+    )]
+    pub bloom_filter_fields: String,
     #[env_config(name = "ZO_UI_ENABLED", default = true)]
     pub ui_enabled: bool,
     #[env_config(name = "ZO_UI_SQL_BASE64_ENABLED", default = false)]
@@ -248,6 +329,16 @@ pub struct Common {
     pub usage_org: String,
     #[env_config(name = "ZO_USAGE_BATCH_SIZE", default = 2000)]
     pub usage_batch_size: usize,
+    // key of the hmac hashing the redacted values, the hash mode is refused
+    // without it
+    #[env_config(name = "ZO_REDACTION_HMAC_KEY", default = "")]
+    pub redaction_hmac_key: String,
+    // label of the loki streams naming the target stream
+    #[env_config(name = "ZO_LOKI_STREAM_LABEL", default = "job")]
+    pub loki_stream_label: String,
+    // field of the datadog logs naming the target stream
+    #[env_config(name = "ZO_DATADOG_STREAM_FIELD", default = "service")]
+    pub datadog_stream_field: String,
 }
 
 #[derive(EnvConfig)]
@@ -277,6 +368,18 @@ pub struct Limit {
     pub metrics_leader_election_interval: i64,
     #[env_config(name = "ZO_METRICS_FILE_RETENTION", default = "daily")]
     pub metrics_file_retention: String,
+    // samples of new series are dropped once the aggregator holds this many
+    #[env_config(name = "ZO_STATSD_MAX_SERIES", default = 100000)]
+    pub statsd_max_series: usize,
+    // default ingestion quota of the organizations without one, 0 is no limit
+    #[env_config(name = "ZO_INGEST_QUOTA_EVENTS_PER_SEC", default = 0)]
+    pub ingest_quota_events_per_sec: u64,
+    #[env_config(name = "ZO_INGEST_QUOTA_BYTES_PER_SEC", default = 0)]
+    pub ingest_quota_bytes_per_sec: u64,
+    #[env_config(name = "ZO_INGEST_QUOTA_DAILY_BYTES", default = 0)]
+    pub ingest_quota_daily_bytes: u64,
+    #[env_config(name = "ZO_INGEST_QUOTA_SYNC_INTERVAL", default = 5)] // in seconds
+    pub ingest_quota_sync_interval: u64,
     #[env_config(name = "ZO_HEARTBEAT_INTERVAL", default = 30)] // in minutes
     pub hb_interval: i64,
     #[env_config(name = "ZO_COLS_PER_RECORD_LIMIT", default = 0)]
@@ -287,6 +390,28 @@ pub struct Limit {
     pub http_worker_max_blocking: usize,
     #[env_config(name = "ZO_CALCULATE_STATS_INTERVAL", default = 600)] // in seconds
     pub calculate_stats_interval: u64,
+    #[env_config(name = "ZO_SEARCH_MAX_CONCURRENT", default = 0)] // cluster wide
+    pub search_max_concurrent: usize,
+    #[env_config(name = "ZO_SEARCH_MAX_CONCURRENT_PER_ORG", default = 0)] // 0 is no limit
+    pub search_max_concurrent_per_org: usize,
+    #[env_config(name = "ZO_SEARCH_MAX_CONCURRENT_PER_USER", default = 0)] // 0 is no limit
+    pub search_max_concurrent_per_user: usize,
+    #[env_config(name = "ZO_SEARCH_MAX_QUEUE_SIZE", default = 1000)] // per querier
+    pub search_max_queue_size: usize,
+    #[env_config(name = "ZO_SEARCH_QUEUE_TIMEOUT", default = 60)] // in seconds
+    pub search_queue_timeout: u64,
+    #[env_config(name = "ZO_SEARCH_QUEUE_SYNC_INTERVAL", default = 1)] // in seconds
+    pub search_queue_sync_interval: u64,
+    #[env_config(name = "ZO_SEARCH_RETRY_AFTER", default = 5)] // in seconds
+    pub search_retry_after: u64,
+    #[env_config(name = "ZO_SEARCH_JOB_TIMEOUT", default = 86400)] // in seconds
+    pub search_job_timeout: u64,
+    #[env_config(name = "ZO_SEARCH_JOB_TTL", default = 72)] // in hours
+    pub search_job_ttl: i64,
+    #[env_config(name = "ZO_SEARCH_JOB_MAX_HITS", default = 1000000)]
+    pub search_job_max_hits: usize,
+    #[env_config(name = "ZO_SEARCH_JOB_MAX_RETRIES", default = 10)]
+    pub search_job_max_retries: usize,
 }
 
 #[derive(EnvConfig)]
@@ -328,6 +453,11 @@ pub struct MemoryCache {
     pub datafusion_max_size: usize,
     #[env_config(name = "ZO_MEMORY_CACHE_DATAFUSION_MEMORY_POOL", default = "")]
     pub datafusion_memory_pool: String,
+    #[env_config(name = "ZO_MEMORY_CACHE_RESULT_ENABLED", default = false)]
+    pub result_enabled: bool,
+    // MB, search results cached per time bucket, default is 5% of max_size
+    #[env_config(name = "ZO_MEMORY_CACHE_RESULT_MAX_SIZE", default = 0)]
+    pub result_max_size: usize,
 }
 
 #[derive(EnvConfig)]
@@ -677,6 +807,11 @@ fn check_memory_cache_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
     } else {
         cfg.memory_cache.datafusion_max_size *= 1024 * 1024;
     }
+    if cfg.memory_cache.result_max_size == 0 {
+        cfg.memory_cache.result_max_size = cfg.memory_cache.max_size / 20; // 5%
+    } else {
+        cfg.memory_cache.result_max_size *= 1024 * 1024;
+    }
     Ok(())
 }
 
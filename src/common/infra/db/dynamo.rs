@@ -183,7 +183,7 @@ pub fn get_dynamo_key(db_key: &str, operation: DbOperation) -> DynamoTableDetail
 
     match entity {
         "function" | "templates" | "destinations" | "dashboard" | "kv" | "metrics_members"
-        | "metrics_leader" => match operation {
+        | "metrics_leader" | "search_job" => match operation {
             DbOperation::Get | DbOperation::Put | DbOperation::Delete => DynamoTableDetails {
                 pk_value: parts[1].to_string(),
                 rk_value: format!("{}/{}", parts[0], parts[2]),
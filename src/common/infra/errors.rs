@@ -69,6 +69,8 @@ pub enum ErrorCodes {
     SearchParquetFileNotFound,
     SearchFieldHasNoCompatibleDataType(String),
     SearchSQLExecuteError(String),
+    SearchTooManyRequests(String),
+    SearchCancelled(String),
 }
 
 impl std::fmt::Display for ErrorCodes {
@@ -94,6 +96,8 @@ impl ErrorCodes {
             ErrorCodes::SearchParquetFileNotFound => 20006,
             ErrorCodes::SearchFieldHasNoCompatibleDataType(_) => 20007,
             ErrorCodes::SearchSQLExecuteError(_) => 20008,
+            ErrorCodes::SearchTooManyRequests(_) => 20009,
+            ErrorCodes::SearchCancelled(_) => 20010,
         }
     }
 
@@ -116,6 +120,8 @@ impl ErrorCodes {
                 format!("Search field has no compatible data type: {field}")
             }
             ErrorCodes::SearchSQLExecuteError(_) => "Search SQL execute error".to_string(),
+            ErrorCodes::SearchTooManyRequests(_) => "Too many search requests".to_string(),
+            ErrorCodes::SearchCancelled(_) => "Search cancelled".to_string(),
         }
     }
 
@@ -130,6 +136,8 @@ impl ErrorCodes {
             ErrorCodes::SearchParquetFileNotFound => "".to_string(),
             ErrorCodes::SearchFieldHasNoCompatibleDataType(field) => field.to_owned(),
             ErrorCodes::SearchSQLExecuteError(msg) => msg.to_owned(),
+            ErrorCodes::SearchTooManyRequests(msg) => msg.to_owned(),
+            ErrorCodes::SearchCancelled(msg) => msg.to_owned(),
         }
     }
 
@@ -144,6 +152,8 @@ impl ErrorCodes {
             ErrorCodes::SearchParquetFileNotFound => "".to_string(),
             ErrorCodes::SearchFieldHasNoCompatibleDataType(_) => "".to_string(),
             ErrorCodes::SearchSQLExecuteError(msg) => msg.to_owned(),
+            ErrorCodes::SearchTooManyRequests(msg) => msg.to_owned(),
+            ErrorCodes::SearchCancelled(msg) => msg.to_owned(),
         }
     }
 
@@ -191,6 +201,8 @@ impl ErrorCodes {
             20006 => Ok(ErrorCodes::SearchParquetFileNotFound),
             20007 => Ok(ErrorCodes::SearchFieldHasNoCompatibleDataType(message)),
             20008 => Ok(ErrorCodes::SearchSQLExecuteError(message)),
+            20009 => Ok(ErrorCodes::SearchTooManyRequests(message)),
+            20010 => Ok(ErrorCodes::SearchCancelled(message)),
             _ => Ok(ErrorCodes::ServerInternalError(json.to_string())),
         }
     }
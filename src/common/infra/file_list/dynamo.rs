@@ -79,6 +79,8 @@ impl super::FileList for DynamoFileList {
                 "compressed_size",
                 AttributeValue::N(meta.compressed_size.to_string()),
             )
+            .item("index_size", AttributeValue::N(meta.index_size.to_string()))
+            .item("stats_size", AttributeValue::N(meta.stats_size.to_string()))
             .item(
                 "created_at",
                 AttributeValue::N(Utc::now().timestamp_micros().to_string()),
@@ -242,6 +242,8 @@ pub struct FileRecord {
     pub records: i64,
     pub original_size: i64,
     pub compressed_size: i64,
+    pub index_size: i64,
+    pub stats_size: i64,
 }
 
 impl From<&FileRecord> for FileMeta {
@@ -252,6 +254,8 @@ impl From<&FileRecord> for FileMeta {
             records: record.records,
             original_size: record.original_size,
             compressed_size: record.compressed_size,
+            index_size: record.index_size,
+            stats_size: record.stats_size,
         }
     }
 }
@@ -69,8 +69,8 @@ impl super::FileList for PostgresFileList {
         let org_id = stream_key[..stream_key.find('/').unwrap()].to_string();
         match  sqlx::query(
             r#"
-INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size)
-    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);
+INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12);
             "#,
         )
         .bind(org_id)
@@ -83,6 +83,8 @@ INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records
         .bind(meta.records)
         .bind(meta.original_size)
         .bind(meta.compressed_size)
+        .bind(meta.index_size)
+        .bind(meta.stats_size)
         .execute(&pool)
         .await {
             Err(sqlx::Error::Database(e)) => if e.is_unique_violation() {
@@ -116,7 +118,7 @@ DELETE FROM file_list
         let pool = CLIENT.clone();
         let chunks = files.chunks(100);
         for files in chunks {
-            let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size)");
+            let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size)");
             query_builder.push_values(files, |mut b, item| {
                 let (stream_key, date_key, file_name) =
                     super::parse_file_key_columns(&item.key).expect("parse file key failed");
@@ -130,7 +132,9 @@ DELETE FROM file_list
                     .push_bind(item.meta.max_ts)
                     .push_bind(item.meta.records)
                     .push_bind(item.meta.original_size)
-                    .push_bind(item.meta.compressed_size);
+                    .push_bind(item.meta.compressed_size)
+                    .push_bind(item.meta.index_size)
+                    .push_bind(item.meta.stats_size);
             });
             match query_builder.build().execute(&pool).await {
                 Ok(_) => {}
@@ -173,7 +177,7 @@ DELETE FROM file_list
         let (stream_key, date_key, file_name) = super::parse_file_key_columns(file)?;
         let ret = sqlx::query_as::<_, super::FileRecord>(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size
     FROM file_list WHERE stream = $1 AND date = $2 AND file = $3;
             "#,
         )
@@ -190,7 +194,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
         let (stream_key, date_key, file_name) = super::parse_file_key_columns(file)?;
         let ret = sqlx::query(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size
     FROM file_list WHERE stream = $1 AND date = $2 AND file = $3;
             "#,
         )
@@ -209,7 +213,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
         let pool = CLIENT.clone();
         let ret = sqlx::query_as::<_, super::FileRecord>(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size
     FROM file_list;
             "#,
         )
@@ -249,7 +253,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
         let pool = CLIENT.clone();
         let ret = sqlx::query_as::<_, super::FileRecord>(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size
     FROM file_list 
     WHERE stream = $1 AND min_ts <= $2 AND max_ts >= $3;
             "#,
@@ -489,13 +493,17 @@ CREATE TABLE IF NOT EXISTS file_list
     max_ts   BIGINT not null,
     records  BIGINT not null,
     original_size   BIGINT not null,
-    compressed_size BIGINT not null
+    compressed_size BIGINT not null,
+    index_size      BIGINT default 0 not null,
+    stats_size      BIGINT default 0 not null
 );
         "#,
     )
     .execute(&pool)
     .await?;
 
+    add_file_list_columns().await?;
+
     sqlx::query(
         r#"
 CREATE TABLE IF NOT EXISTS stream_stats
@@ -518,6 +526,22 @@ CREATE TABLE IF NOT EXISTS stream_stats
     Ok(())
 }
 
+/// add the columns missing in the file_list table created by old versions
+async fn add_file_list_columns() -> Result<()> {
+    let pool = CLIENT.clone();
+    sqlx::query(
+        "ALTER TABLE file_list ADD COLUMN IF NOT EXISTS index_size BIGINT default 0 not null;",
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query(
+        "ALTER TABLE file_list ADD COLUMN IF NOT EXISTS stats_size BIGINT default 0 not null;",
+    )
+    .execute(&pool)
+    .await?;
+    Ok(())
+}
+
 pub async fn create_table_index() -> Result<()> {
     let pool = CLIENT.clone();
     let mut tx = pool.begin().await?;
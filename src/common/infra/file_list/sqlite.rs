@@ -76,8 +76,8 @@ impl super::FileList for SqliteFileList {
         let org_id = stream_key[..stream_key.find('/').unwrap()].to_string();
         match  sqlx::query(
             r#"
-INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size)
-    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);
+INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12);
         "#,
     )
         .bind(org_id)
@@ -90,6 +90,8 @@ INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records
         .bind(meta.records)
         .bind(meta.original_size)
         .bind(meta.compressed_size)
+        .bind(meta.index_size)
+        .bind(meta.stats_size)
         .execute(&pool)
         .await {
             Err(sqlx::Error::Database(e)) => if e.is_unique_violation() {
@@ -123,7 +125,7 @@ DELETE FROM file_list
         let pool = CLIENT.clone();
         let chunks = files.chunks(100);
         for files in chunks {
-            let mut query_builder: QueryBuilder<Sqlite> = QueryBuilder::new("INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size)");
+            let mut query_builder: QueryBuilder<Sqlite> = QueryBuilder::new("INSERT INTO file_list (org, stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size)");
             query_builder.push_values(files, |mut b, item| {
                 let (stream_key, date_key, file_name) =
                     super::parse_file_key_columns(&item.key).expect("parse file key failed");
@@ -137,7 +139,9 @@ DELETE FROM file_list
                     .push_bind(item.meta.max_ts)
                     .push_bind(item.meta.records)
                     .push_bind(item.meta.original_size)
-                    .push_bind(item.meta.compressed_size);
+                    .push_bind(item.meta.compressed_size)
+                    .push_bind(item.meta.index_size)
+                    .push_bind(item.meta.stats_size);
             });
             match query_builder.build().execute(&pool).await {
                 Ok(_) => {}
@@ -181,7 +185,7 @@ DELETE FROM file_list
         let (stream_key, date_key, file_name) = super::parse_file_key_columns(file)?;
         let ret = sqlx::query_as::<_, super::FileRecord>(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size
     FROM file_list WHERE stream = $1 AND date = $2 AND file = $3;
             "#,
         )
@@ -198,7 +202,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
         let (stream_key, date_key, file_name) = super::parse_file_key_columns(file)?;
         let ret = sqlx::query(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size
     FROM file_list WHERE stream = $1 AND date = $2 AND file = $3;
             "#,
         )
@@ -217,7 +221,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
         let pool = CLIENT.clone();
         let ret = sqlx::query_as::<_, super::FileRecord>(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size
     FROM file_list;
             "#,
         )
@@ -257,7 +261,7 @@ SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, comp
         let pool = CLIENT.clone();
         let ret = sqlx::query_as::<_, super::FileRecord>(
             r#"
-SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size
+SELECT stream, date, file, deleted, min_ts, max_ts, records, original_size, compressed_size, index_size, stats_size
     FROM file_list 
     WHERE stream = $1 AND min_ts <= $2 AND max_ts >= $3;
             "#,
@@ -496,13 +500,17 @@ CREATE TABLE IF NOT EXISTS file_list
     max_ts   BIGINT not null,
     records  BIGINT not null,
     original_size   BIGINT not null,
-    compressed_size BIGINT not null
+    compressed_size BIGINT not null,
+    index_size      BIGINT default 0 not null,
+    stats_size      BIGINT default 0 not null
 );
         "#,
     )
     .execute(&pool)
     .await?;
 
+    add_file_list_columns().await?;
+
     sqlx::query(
         r#"
 CREATE TABLE IF NOT EXISTS stream_stats
@@ -525,6 +533,30 @@ CREATE TABLE IF NOT EXISTS stream_stats
     Ok(())
 }
 
+/// add the columns missing in the file_list table created by old versions
+async fn add_file_list_columns() -> Result<()> {
+    let pool = CLIENT.clone();
+    for (column, definition) in [
+        ("index_size", "BIGINT default 0 not null"),
+        ("stats_size", "BIGINT default 0 not null"),
+    ] {
+        let has_column: i64 = sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM pragma_table_info('file_list') WHERE name = $1;"#,
+        )
+        .bind(column)
+        .fetch_one(&pool)
+        .await?;
+        if has_column == 0 {
+            sqlx::query(&format!(
+                "ALTER TABLE file_list ADD COLUMN {column} {definition};"
+            ))
+            .execute(&pool)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn create_table_index() -> Result<()> {
     let pool = CLIENT.clone();
     // create index for file_list
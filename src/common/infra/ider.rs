@@ -31,6 +31,11 @@ pub fn generate() -> String {
     format!("{}{}", id, generate_random_string(6))
 }
 
+/// a numeric id, unique in the cluster and increasing with the time
+pub fn generate_number() -> u64 {
+    unsafe { IDER.real_time_generate() as u64 }
+}
+
 fn generate_random_string(len: usize) -> String {
     let mut rng = thread_rng();
     iter::repeat(())
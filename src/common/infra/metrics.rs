@@ -123,6 +123,79 @@ pub static INGEST_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static INGEST_QUOTA_REJECTED_RECORDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_quota_rejected_records",
+            "Records rejected by the ingestion quotas. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "limit"],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_QUOTA_REJECTED_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_quota_rejected_bytes",
+            "Bytes rejected by the ingestion quotas. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "limit"],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_QUOTA_DAILY_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "ingest_quota_daily_bytes",
+            "Bytes counted against the daily ingestion quotas, cluster wide. ".to_owned()
+                + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream"],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_SAMPLING_DROPPED_RECORDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_sampling_dropped_records",
+            "Records dropped by the sampling rules. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "rule"],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_SAMPLING_SAMPLED_RECORDS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_sampling_sampled_records",
+            "Records kept by the sample rules. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "rule"],
+    )
+    .expect("Metric created")
+});
+pub static INGEST_REDACTED_VALUES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "ingest_redacted_values",
+            "Values redacted by the pii detectors. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "stream_type", "detector", "mode"],
+    )
+    .expect("Metric created")
+});
 pub static INGEST_WAL_USED_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
     IntGaugeVec::new(
         Opts::new(
@@ -160,6 +233,47 @@ pub static INGEST_WAL_READ_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
     .expect("Metric created")
 });
 
+// syslog server stats
+pub static SYSLOG_CONNECTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new("syslog_connections", "Syslog server open connections")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["protocol"],
+    )
+    .expect("Metric created")
+});
+pub static SYSLOG_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new("syslog_messages", "Syslog server received messages")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["protocol"],
+    )
+    .expect("Metric created")
+});
+pub static SYSLOG_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new("syslog_bytes", "Syslog server received bytes")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["protocol"],
+    )
+    .expect("Metric created")
+});
+pub static SYSLOG_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "syslog_errors",
+            "Syslog server connection and framing errors",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["protocol"],
+    )
+    .expect("Metric created")
+});
+
 // querier stats
 pub static QUERY_CACHE_LIMIT_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
     IntGaugeVec::new(
@@ -206,6 +320,42 @@ pub static QUERY_CACHE_RECORDS: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static QUERY_RESULT_CACHE_HITS: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_result_cache_hits",
+            "Querier result cache hit time buckets. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "stream_type"],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_RESULT_CACHE_MISSES: Lazy<IntCounterVec> = Lazy::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "query_result_cache_misses",
+            "Querier result cache missed time buckets. ".to_owned() + HELP_SUFFIX,
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &["organization", "stream", "stream_type"],
+    )
+    .expect("Metric created")
+});
+pub static QUERY_RESULT_CACHE_USED_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_result_cache_used_bytes",
+            "Querier result cache used bytes",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
 
 // compactor stats
 pub static COMPACT_USED_TIME: Lazy<CounterVec> = Lazy::new(|| {
@@ -463,6 +613,24 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(INGEST_BYTES.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_QUOTA_REJECTED_RECORDS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_QUOTA_REJECTED_BYTES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_QUOTA_DAILY_BYTES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_SAMPLING_DROPPED_RECORDS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_SAMPLING_SAMPLED_RECORDS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(INGEST_REDACTED_VALUES.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(INGEST_WAL_USED_BYTES.clone()))
         .expect("Metric registered");
@@ -473,6 +641,20 @@ fn register_metrics(registry: &Registry) {
         .register(Box::new(INGEST_WAL_READ_BYTES.clone()))
         .expect("Metric registered");
 
+    // syslog server stats
+    registry
+        .register(Box::new(SYSLOG_CONNECTIONS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(SYSLOG_MESSAGES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(SYSLOG_BYTES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(SYSLOG_ERRORS.clone()))
+        .expect("Metric registered");
+
     // querier stats
     registry
         .register(Box::new(QUERY_CACHE_LIMIT_BYTES.clone()))
@@ -486,6 +668,15 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(QUERY_CACHE_RECORDS.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_RESULT_CACHE_HITS.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_RESULT_CACHE_MISSES.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(QUERY_RESULT_CACHE_USED_BYTES.clone()))
+        .expect("Metric registered");
 
     // compactor stats
     registry
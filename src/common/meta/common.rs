@@ -77,6 +77,14 @@ impl From<&FileKey> for HashMap<String, AttributeValue> {
             "compressed_size".to_string(),
             AttributeValue::N(file_key.meta.compressed_size.to_string()),
         );
+        item.insert(
+            "index_size".to_string(),
+            AttributeValue::N(file_key.meta.index_size.to_string()),
+        );
+        item.insert(
+            "stats_size".to_string(),
+            AttributeValue::N(file_key.meta.stats_size.to_string()),
+        );
         item.insert(
             "created_at".to_string(),
             AttributeValue::N(chrono::Utc::now().timestamp_micros().to_string()),
@@ -115,6 +123,12 @@ impl From<&HashMap<String, AttributeValue>> for FileKey {
                 "compressed_size" => {
                     item.meta.compressed_size = v.as_n().unwrap().parse::<i64>().unwrap();
                 }
+                "index_size" => {
+                    item.meta.index_size = v.as_n().unwrap().parse::<i64>().unwrap();
+                }
+                "stats_size" => {
+                    item.meta.stats_size = v.as_n().unwrap().parse::<i64>().unwrap();
+                }
                 _ => {}
             }
         }
@@ -129,16 +143,22 @@ pub struct FileMeta {
     pub records: i64,
     pub original_size: i64,
     pub compressed_size: i64,
+    #[serde(default)]
+    pub index_size: i64, // size of the full text index file, 0 means no index
+    #[serde(default)]
+    pub stats_size: i64, // size of the column stats file, 0 means no stats
 }
 
 impl From<&FileMeta> for Vec<u8> {
     fn from(value: &FileMeta) -> Vec<u8> {
-        let mut bytes = [0; 40];
+        let mut bytes = [0; 56];
         LittleEndian::write_i64(&mut bytes[0..8], value.min_ts);
         LittleEndian::write_i64(&mut bytes[8..16], value.max_ts);
         LittleEndian::write_i64(&mut bytes[16..24], value.records);
         LittleEndian::write_i64(&mut bytes[24..32], value.original_size);
         LittleEndian::write_i64(&mut bytes[32..40], value.compressed_size);
+        LittleEndian::write_i64(&mut bytes[40..48], value.index_size);
+        LittleEndian::write_i64(&mut bytes[48..56], value.stats_size);
         bytes.to_vec()
     }
 }
@@ -158,12 +178,25 @@ impl TryFrom<&[u8]> for FileMeta {
         let records = LittleEndian::read_i64(&value[16..24]);
         let original_size = LittleEndian::read_i64(&value[24..32]);
         let compressed_size = LittleEndian::read_i64(&value[32..40]);
+        // old version meta has no index_size
+        let index_size = if value.len() >= 48 {
+            LittleEndian::read_i64(&value[40..48])
+        } else {
+            0
+        };
+        let stats_size = if value.len() >= 56 {
+            LittleEndian::read_i64(&value[48..56])
+        } else {
+            0
+        };
         Ok(Self {
             min_ts,
             max_ts,
             records,
             original_size,
             compressed_size,
+            index_size,
+            stats_size,
         })
     }
 }
@@ -0,0 +1,111 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::ingestion::StreamStatus;
+
+pub const DEFAULT_DEAD_LETTER_STREAM: &str = "_dead_letter";
+/// fields of the dead-letter records
+pub const DEAD_LETTER_STREAM_FIELD: &str = "stream";
+pub const DEAD_LETTER_STAGE_FIELD: &str = "stage";
+pub const DEAD_LETTER_REASON_FIELD: &str = "reason";
+pub const DEAD_LETTER_PAYLOAD_FIELD: &str = "payload";
+
+/// the rejected log records of the organization are stored in the dead-letter
+/// stream when enabled
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DeadLetterSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_stream_name")]
+    pub stream_name: String,
+}
+
+impl Default for DeadLetterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stream_name: default_stream_name(),
+        }
+    }
+}
+
+fn default_stream_name() -> String {
+    DEFAULT_DEAD_LETTER_STREAM.to_string()
+}
+
+/// the step of the ingestion pipeline which rejected the record
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeadLetterStage {
+    Function,
+    Timestamp,
+    Schema,
+}
+
+impl std::fmt::Display for DeadLetterStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeadLetterStage::Function => write!(f, "function"),
+            DeadLetterStage::Timestamp => write!(f, "timestamp"),
+            DeadLetterStage::Schema => write!(f, "schema"),
+        }
+    }
+}
+
+/// re-ingest the dead-letter records rejected between the start and end time
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeadLetterReplay {
+    pub start_time: i64,
+    pub end_time: i64,
+    /// only the records of this stream
+    #[serde(default)]
+    pub stream_name: Option<String>,
+    /// only the records rejected by this stage
+    #[serde(default)]
+    pub stage: Option<DeadLetterStage>,
+    #[serde(default = "default_replay_size")]
+    pub size: usize,
+}
+
+fn default_replay_size() -> usize {
+    10000
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeadLetterReplayResponse {
+    pub replayed: usize,
+    pub status: Vec<StreamStatus>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::utils::json;
+
+    #[test]
+    fn test_dead_letter_settings() {
+        let settings: DeadLetterSettings = json::from_str(r#"{"enabled":true}"#).unwrap();
+        assert!(settings.enabled);
+        assert_eq!(settings.stream_name, DEFAULT_DEAD_LETTER_STREAM);
+
+        let replay: DeadLetterReplay =
+            json::from_str(r#"{"start_time":1,"end_time":2,"stage":"schema"}"#).unwrap();
+        assert_eq!(replay.stage, Some(DeadLetterStage::Schema));
+        assert_eq!(replay.size, 10000);
+        assert_eq!(DeadLetterStage::Function.to_string(), "function");
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// route of the fluent forward events whose tag matches the pattern
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FluentRoute {
+    #[serde(default)]
+    pub org_id: String,
+    /// the tag is the stream name when empty
+    #[serde(default)]
+    pub stream_name: String,
+    /// fluentd match pattern, `*` matches a tag part and `**` any parts
+    #[serde(default)]
+    pub tag_pattern: String,
+    #[serde(default)]
+    pub id: String,
+}
+
+impl FluentRoute {
+    pub fn matches(&self, tag: &str) -> bool {
+        let pattern = self.tag_pattern.split('.').collect::<Vec<_>>();
+        let tag = tag.split('.').collect::<Vec<_>>();
+        match_parts(&pattern, &tag)
+    }
+
+    /// the more literal parts, then the more `*` instead of `**`, the more
+    /// specific the pattern is
+    pub fn specificity(&self) -> (usize, usize, usize) {
+        let parts = self.tag_pattern.split('.').collect::<Vec<_>>();
+        let literal = parts.iter().filter(|p| **p != "*" && **p != "**").count();
+        let single = parts.iter().filter(|p| **p == "*").count();
+        let multi = parts.iter().filter(|p| **p == "**").count();
+        (literal, single, usize::MAX - multi)
+    }
+}
+
+fn match_parts(pattern: &[&str], tag: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => tag.is_empty(),
+        Some((&"**", rest)) => (0..=tag.len()).any(|i| match_parts(rest, &tag[i..])),
+        Some((part, rest)) => match tag.split_first() {
+            Some((tag_part, tag_rest)) => {
+                (*part == "*" || part == tag_part) && match_parts(rest, tag_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct FluentRoutes {
+    pub routes: Vec<FluentRoute>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let route = |pattern: &str| FluentRoute {
+            org_id: "default".to_string(),
+            stream_name: "".to_string(),
+            tag_pattern: pattern.to_string(),
+            id: "".to_string(),
+        };
+        assert!(route("app.*").matches("app.web"));
+        assert!(!route("app.*").matches("app.web.err"));
+        assert!(!route("app.*").matches("app"));
+        assert!(route("app.**").matches("app"));
+        assert!(route("app.**").matches("app.web.err"));
+        assert!(route("**").matches("kube.var.log"));
+        assert!(route("*.err").matches("web.err"));
+        assert!(!route("db.*").matches("app.web"));
+    }
+
+    #[test]
+    fn test_specificity() {
+        let route = |pattern: &str| FluentRoute {
+            org_id: "default".to_string(),
+            stream_name: "".to_string(),
+            tag_pattern: pattern.to_string(),
+            id: "".to_string(),
+        };
+        assert!(route("app.web").specificity() > route("app.*").specificity());
+        assert!(route("app.*").specificity() > route("app.**").specificity());
+        assert!(route("app.**").specificity() > route("**").specificity());
+        assert!(route("*.*").specificity() > route("**").specificity());
+    }
+}
@@ -22,11 +22,19 @@ use crate::common::utils::json;
 pub struct RecordStatus {
     pub successful: u32,
     pub failed: u32,
+    /// the records dropped on purpose, by a function, they are not failures
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_zero")]
+    pub dropped: u32,
     #[serde(default)]
     #[serde(skip_serializing_if = "String::is_empty")]
     pub error: String,
 }
 
+fn is_zero(v: &u32) -> bool {
+    *v == 0
+}
+
 pub struct BulkStreamData {
     pub data: HashMap<String, Vec<String>>,
 }
@@ -276,3 +284,73 @@ pub struct GCPIngestionResponse {
     pub error_message: Option<String>,
     pub timestamp: String,
 }
+
+/// the query parameters of the splunk http event collector
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HecParams {
+    pub index: Option<String>,
+    pub sourcetype: Option<String>,
+    pub host: Option<String>,
+    pub source: Option<String>,
+    pub channel: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct HecResponse {
+    pub text: String,
+    pub code: u16,
+    #[serde(rename = "ackId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ack_id: Option<u64>,
+    #[serde(rename = "invalid-event-number")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invalid_event_number: Option<usize>,
+    /// seconds to wait when the server is busy, sent in the `Retry-After` header
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
+}
+
+impl HecResponse {
+    pub const SUCCESS: u16 = 0;
+    pub const TOKEN_REQUIRED: u16 = 2;
+    pub const INVALID_TOKEN: u16 = 4;
+    pub const NO_DATA: u16 = 5;
+    pub const INVALID_DATA_FORMAT: u16 = 6;
+    pub const SERVER_ERROR: u16 = 8;
+    pub const SERVER_BUSY: u16 = 9;
+    pub const CHANNEL_MISSING: u16 = 10;
+    pub const EVENT_REQUIRED: u16 = 12;
+    pub const EVENT_BLANK: u16 = 13;
+    pub const HEALTHY: u16 = 17;
+
+    pub fn new(code: u16, text: &str) -> Self {
+        HecResponse {
+            text: text.to_string(),
+            code,
+            ack_id: None,
+            invalid_event_number: None,
+            retry_after: None,
+        }
+    }
+
+    pub fn success() -> Self {
+        HecResponse::new(HecResponse::SUCCESS, "Success")
+    }
+
+    pub fn invalid_event(code: u16, text: &str, event_number: usize) -> Self {
+        HecResponse {
+            invalid_event_number: Some(event_number),
+            ..HecResponse::new(code, text)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct HecAckRequest {
+    pub acks: Vec<u64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct HecAckResponse {
+    pub acks: HashMap<String, bool>,
+}
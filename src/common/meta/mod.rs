@@ -18,12 +18,18 @@ use utoipa::ToSchema;
 pub mod alert;
 pub mod common;
 pub mod dashboards;
+pub mod dead_letter;
+pub mod fluent;
 pub mod functions;
 pub mod http;
 pub mod ingestion;
 pub mod meta_store;
 pub mod organization;
 pub mod prom;
+pub mod quota;
+pub mod redaction;
+pub mod routing;
+pub mod sampling;
 pub mod search;
 pub mod service;
 pub mod sql;
@@ -0,0 +1,95 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// ingestion quota of an organization or of one of its streams, a zero limit
+/// is no limit
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct IngestionQuota {
+    #[serde(default)]
+    pub events_per_second: u64,
+    #[serde(default)]
+    pub bytes_per_second: u64,
+    /// bytes per UTC day
+    #[serde(default)]
+    pub daily_bytes: u64,
+}
+
+impl IngestionQuota {
+    pub fn is_unlimited(&self) -> bool {
+        self.events_per_second == 0 && self.bytes_per_second == 0 && self.daily_bytes == 0
+    }
+}
+
+/// the quota of the organization and the quotas of its streams
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct OrgIngestionQuotas {
+    pub org: IngestionQuota,
+    pub streams: HashMap<String, IngestionQuota>,
+}
+
+/// the limit of the quota a request exceeded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaLimit {
+    EventsPerSecond,
+    BytesPerSecond,
+    DailyBytes,
+}
+
+impl std::fmt::Display for QuotaLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotaLimit::EventsPerSecond => write!(f, "events_per_second"),
+            QuotaLimit::BytesPerSecond => write!(f, "bytes_per_second"),
+            QuotaLimit::DailyBytes => write!(f, "daily_bytes"),
+        }
+    }
+}
+
+/// the demand and the daily bytes of a node, published to the other ingesters
+/// to share the quotas between them
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeQuotaUsage {
+    pub updated_at: i64,
+    /// days since the unix epoch, UTC
+    pub day: i64,
+    pub usage: HashMap<String, QuotaUsage>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    /// events and bytes requested since the last sync, rejected ones included
+    pub events: u64,
+    pub bytes: u64,
+    /// bytes accepted during the day
+    pub daily_bytes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::utils::json;
+
+    #[test]
+    fn test_ingestion_quota() {
+        let quota: IngestionQuota = json::from_str(r#"{"events_per_second":100}"#).unwrap();
+        assert_eq!(quota.events_per_second, 100);
+        assert_eq!(quota.daily_bytes, 0);
+        assert!(!quota.is_unlimited());
+        assert!(IngestionQuota::default().is_unlimited());
+        assert_eq!(QuotaLimit::DailyBytes.to_string(), "daily_bytes");
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// the personal data found by the detectors is redacted from the records of
+/// the stream before they are written
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StreamRedaction {
+    #[serde(default)]
+    pub rules: Vec<RedactionRule>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RedactionRule {
+    pub detector: PiiDetector,
+    #[serde(default)]
+    pub mode: RedactionMode,
+    /// the fields scanned by the detector, every string field when empty
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiDetector {
+    Email,
+    /// card numbers of 13 to 19 digits passing the luhn check
+    CreditCard,
+    Ipv4,
+    Ipv6,
+    /// the token of `Bearer <token>`
+    BearerToken,
+}
+
+impl std::fmt::Display for PiiDetector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PiiDetector::Email => write!(f, "email"),
+            PiiDetector::CreditCard => write!(f, "credit_card"),
+            PiiDetector::Ipv4 => write!(f, "ipv4"),
+            PiiDetector::Ipv6 => write!(f, "ipv6"),
+            PiiDetector::BearerToken => write!(f, "bearer_token"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionMode {
+    /// replace the detected value with `*`, the last 4 digits of the card
+    /// numbers are kept
+    #[default]
+    Mask,
+    /// replace the detected value with its keyed hmac, equal values keep equal
+    /// hashes so they can still be joined on
+    Hash,
+    /// remove the field holding the detected value
+    Drop,
+}
+
+impl std::fmt::Display for RedactionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedactionMode::Mask => write!(f, "mask"),
+            RedactionMode::Hash => write!(f, "hash"),
+            RedactionMode::Drop => write!(f, "drop"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::utils::json;
+
+    #[test]
+    fn test_redaction_rule_defaults() {
+        let settings: StreamRedaction = json::from_str(
+            r#"{"rules":[{"detector":"email"},{"detector":"credit_card","mode":"hash","fields":["card"]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(settings.rules[0].detector, PiiDetector::Email);
+        assert_eq!(settings.rules[0].mode, RedactionMode::Mask);
+        assert!(settings.rules[0].fields.is_empty());
+        assert_eq!(settings.rules[1].mode, RedactionMode::Hash);
+        assert_eq!(settings.rules[1].fields, vec!["card".to_string()]);
+        assert_eq!(settings.rules[1].detector.to_string(), "credit_card");
+    }
+}
@@ -0,0 +1,74 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// route of the log records of a stream matching the condition to another
+/// stream, evaluated after the functions of the stream
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamRoute {
+    #[serde(default)]
+    pub org_id: String,
+    /// the source stream
+    #[serde(default)]
+    pub stream_name: String,
+    #[serde(default)]
+    pub name: String,
+    pub destination: String,
+    /// sql where clause like `level = 'error' AND kubernetes_container_name LIKE 'nginx%'`
+    /// or a vrl program returning a boolean
+    pub condition: String,
+    #[serde(default)]
+    pub condition_type: RouteConditionType,
+    /// keep the record in the source stream as well
+    #[serde(default)]
+    pub copy: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteConditionType {
+    #[default]
+    Sql,
+    Vrl,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct StreamRoutes {
+    pub routes: Vec<StreamRoute>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::utils::json;
+
+    #[test]
+    fn test_stream_route_defaults() {
+        let route: StreamRoute =
+            json::from_str(r#"{"destination":"app_errors","condition":"level = 'error'"}"#)
+                .unwrap();
+        assert_eq!(route.condition_type, RouteConditionType::Sql);
+        assert!(!route.copy);
+
+        let route: StreamRoute = json::from_str(
+            r#"{"destination":"audit","condition":".audit == true","conditionType":"vrl","copy":true}"#,
+        )
+        .unwrap();
+        assert_eq!(route.condition_type, RouteConditionType::Vrl);
+        assert!(route.copy);
+    }
+}
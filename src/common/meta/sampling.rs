@@ -0,0 +1,82 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// rule keeping, dropping or sampling the log records of a stream matching the
+/// condition before they are written
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplingRule {
+    #[serde(default)]
+    pub org_id: String,
+    #[serde(default)]
+    pub stream_name: String,
+    #[serde(default)]
+    pub name: String,
+    /// sql where clause like `level = 'debug'`, an empty condition matches
+    /// every record
+    #[serde(default)]
+    pub condition: String,
+    pub action: SamplingAction,
+    /// fraction of the matching records kept by a `sample` rule
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// the records with the same value of this field are kept or dropped
+    /// together, the records are sampled at random without it
+    #[serde(default)]
+    pub sample_key: Option<String>,
+    /// the rules are evaluated by ascending order, the first matching rule
+    /// decides and the records matching no rule are kept
+    #[serde(default)]
+    pub order: i32,
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SamplingAction {
+    Keep,
+    Drop,
+    Sample,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct SamplingRules {
+    pub rules: Vec<SamplingRule>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::utils::json;
+
+    #[test]
+    fn test_sampling_rule_defaults() {
+        let rule: SamplingRule = json::from_str(r#"{"action":"drop"}"#).unwrap();
+        assert_eq!(rule.action, SamplingAction::Drop);
+        assert!(rule.condition.is_empty());
+        assert_eq!(rule.sample_rate, 1.0);
+
+        let rule: SamplingRule = json::from_str(
+            r#"{"condition":"level = 'debug'","action":"sample","sampleRate":0.01,"sampleKey":"trace_id"}"#,
+        )
+        .unwrap();
+        assert_eq!(rule.action, SamplingAction::Sample);
+        assert_eq!(rule.sample_key.as_deref(), Some("trace_id"));
+    }
+}
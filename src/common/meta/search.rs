@@ -16,13 +16,15 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use utoipa::ToSchema;
 
-use crate::common::utils::{base64, json};
+use super::StreamType;
+use crate::common::utils::{base64, cancel::CancelToken, json};
 use crate::service::search::datafusion::storage::StorageType;
 
 #[derive(Clone, Debug)]
 pub struct Session {
     pub id: String,
     pub storage_type: StorageType,
+    pub cancel: CancelToken,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
@@ -164,6 +166,10 @@ pub struct Response {
     #[serde(default)]
     #[serde(skip_serializing_if = "String::is_empty")]
     pub response_type: String,
+    /// id of the search session, can be passed to the cancel endpoint
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub session_id: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default, ToSchema)]
@@ -187,6 +193,7 @@ impl Response {
             hits: Vec::new(),
             aggs: HashMap::new(),
             response_type: "".to_string(),
+            session_id: "".to_string(),
         }
     }
 
@@ -230,6 +237,137 @@ impl Response {
     }
 }
 
+/// Message of a streaming search, every time slice of the query sends its
+/// hits and aggs as soon as it is done, newest slice first.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StreamEvent {
+    Start {
+        /// id to cancel the search with
+        session_id: String,
+        files: usize,
+        slices: usize,
+    },
+    Slice {
+        start_time: i64,
+        end_time: i64,
+        hits: Vec<json::Value>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "HashMap::is_empty")]
+        aggs: HashMap<String, Vec<json::Value>>,
+        total: usize,
+        #[serde(default)]
+        scan_files: usize,
+        scan_size: usize,
+    },
+    Done {
+        took: usize,
+        total: usize,
+        scan_size: usize,
+        wait_queue: usize,
+    },
+    Error {
+        code: u16,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error_detail: Option<String>,
+    },
+}
+
+/// Status of an asynchronous search job.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    #[default]
+    Pending,
+    Running,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Finished | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+/// An asynchronous search, the hits are kept as parquet files in the object
+/// storage until the job expires.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = SearchJob)]
+pub struct Job {
+    pub id: String,
+    pub org_id: String,
+    pub stream_type: StreamType,
+    #[serde(default)]
+    pub user_id: String,
+    /// uuid of the node running the job
+    #[serde(default)]
+    pub node: String,
+    /// session of the running search
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub session_id: String,
+    pub status: JobStatus,
+    #[schema(value_type = SearchRequest)]
+    pub request: Request,
+    pub created_at: i64,
+    #[serde(default)]
+    pub started_at: i64,
+    #[serde(default)]
+    pub finished_at: i64,
+    pub expires_at: i64,
+    #[serde(default)]
+    pub total_files: usize,
+    #[serde(default)]
+    pub scan_files: usize,
+    #[serde(default)]
+    pub scan_size: usize,
+    #[serde(default)]
+    pub total: usize,
+    #[serde(default)]
+    pub hits: usize,
+    #[serde(default)]
+    pub took: usize,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub results: Vec<JobResultFile>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub aggs_file: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A parquet file of job hits, the files are stored in the order of the hits.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct JobResultFile {
+    pub key: String,
+    pub hits: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = SearchJobs)]
+pub struct JobList {
+    pub list: Vec<Job>,
+}
+
+/// the searches running on a querier, published to the other queriers to
+/// apply the search concurrency limits cluster wide
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeSearchRunning {
+    pub updated_at: i64,
+    pub running: usize,
+    pub orgs: HashMap<String, usize>,
+    /// by `{org_id}/{user_id}`
+    pub users: HashMap<String, usize>,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -247,6 +385,29 @@ mod test {
         assert_eq!(res.total, 11);
     }
 
+    #[test]
+    fn test_stream_event() {
+        let event = StreamEvent::Done {
+            took: 10,
+            total: 2,
+            scan_size: 100,
+            wait_queue: 0,
+        };
+        let val = json::to_value(&event).unwrap();
+        assert_eq!(val.get("event").unwrap(), "done");
+        assert_eq!(val.get("total").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_job_status() {
+        assert!(!JobStatus::Running.is_done());
+        assert!(JobStatus::Cancelled.is_done());
+        assert_eq!(
+            json::to_string(&JobStatus::Finished).unwrap(),
+            r#""finished""#
+        );
+    }
+
     #[test]
     fn test_request_encoding() {
         let req = json::json!(
@@ -16,8 +16,9 @@ use chrono::DateTime;
 use regex::Regex;
 use serde::Serialize;
 use sqlparser::ast::{
-    BinaryOperator, Expr as SqlExpr, Function, FunctionArg, FunctionArgExpr, Offset as SqlOffset,
-    OrderByExpr, Select, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Value,
+    BinaryOperator, Expr as SqlExpr, Function, FunctionArg, FunctionArgExpr, JoinConstraint,
+    JoinOperator, Offset as SqlOffset, OrderByExpr, Select, SelectItem, SetExpr, Statement,
+    TableFactor, TableWithJoins, Value,
 };
 use sqlparser::parser::Parser;
 
@@ -37,6 +38,16 @@ pub struct Sql {
     pub(crate) full_text: Vec<(String, SqlOperator)>, // fulltext: value1 and value2, and: true / false
     pub(crate) time_range: Option<(i64, i64)>,
     pub(crate) field_alias: Vec<(String, String)>, // alias for select field
+    pub(crate) joins: Vec<Join>,                   // tables joined to the source
+}
+
+/// a table joined to the source, `stream_type.stream_name` selects a stream
+/// of another type
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Join {
+    pub(crate) stream_type: Option<String>,
+    pub(crate) stream_name: String,
+    pub(crate) alias: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
@@ -63,6 +74,7 @@ pub struct Quicktext<'a>(pub(crate) &'a Option<SqlExpr>);
 pub struct Fulltext<'a>(pub(crate) &'a Option<SqlExpr>);
 pub struct Timerange<'a>(pub(crate) &'a Option<SqlExpr>);
 pub struct Source<'a>(pub(crate) &'a [TableWithJoins]);
+pub struct Joins<'a>(pub(crate) &'a [TableWithJoins]);
 pub struct Order<'a>(pub(crate) &'a OrderByExpr);
 pub struct Group<'a>(pub(crate) &'a SqlExpr);
 pub struct Offset<'a>(pub(crate) &'a SqlOffset);
@@ -118,6 +130,7 @@ impl TryFrom<&Statement> for Sql {
                 };
 
                 let source = Source(table_with_joins).try_into()?;
+                let joins = Joins(table_with_joins).try_into()?;
 
                 let mut order_by = Vec::new();
                 for expr in orders {
@@ -153,6 +166,7 @@ impl TryFrom<&Statement> for Sql {
                     full_text,
                     time_range,
                     field_alias,
+                    joins,
                 })
             }
             _ => Err(anyhow::anyhow!("We only support Query at the moment")),
@@ -320,26 +334,65 @@ impl<'a> TryFrom<Source<'a>> for String {
             ));
         }
 
-        let table = &source.0[0];
-        if !table.joins.is_empty() {
-            return Err(anyhow::anyhow!(
-                "We do not support joint data source at the moment"
-            ));
-        }
-
-        match &table.relation {
+        match &source.0[0].relation {
             TableFactor::Table { name, .. } => Ok(name.0.first().unwrap().value.clone()),
             _ => Err(anyhow::anyhow!("We only support table")),
         }
     }
 }
 
+impl<'a> TryFrom<Joins<'a>> for Vec<Join> {
+    type Error = anyhow::Error;
+
+    fn try_from(source: Joins<'a>) -> Result<Self, Self::Error> {
+        let mut joins = Vec::new();
+        for join in source.0.iter().flat_map(|t| t.joins.iter()) {
+            match &join.join_operator {
+                JoinOperator::Inner(JoinConstraint::On(_))
+                | JoinOperator::LeftOuter(JoinConstraint::On(_)) => {}
+                JoinOperator::Inner(_) | JoinOperator::LeftOuter(_) => {
+                    return Err(anyhow::anyhow!(
+                        "We only support join with ON condition at the moment"
+                    ))
+                }
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "We only support INNER JOIN and LEFT JOIN at the moment"
+                    ))
+                }
+            }
+            let (name, alias) = match &join.relation {
+                TableFactor::Table { name, alias, .. } => (name, alias),
+                _ => return Err(anyhow::anyhow!("We only support join table")),
+            };
+            let (stream_type, stream_name) = match name.0.as_slice() {
+                [stream_name] => (None, stream_name.value.clone()),
+                [stream_type, stream_name] => {
+                    (Some(stream_type.value.clone()), stream_name.value.clone())
+                }
+                _ => return Err(anyhow::anyhow!("Invalid join table name: {name}")),
+            };
+            joins.push(Join {
+                stream_type,
+                stream_name,
+                alias: alias.as_ref().map(|a| a.name.value.clone()),
+            });
+        }
+        Ok(joins)
+    }
+}
+
 impl<'a> TryFrom<Order<'a>> for (String, bool) {
     type Error = anyhow::Error;
 
     fn try_from(order: Order) -> Result<Self, Self::Error> {
         match &order.0.expr {
             SqlExpr::Identifier(id) => Ok((id.to_string(), !order.0.asc.unwrap_or(true))),
+            // a column of a joined table
+            SqlExpr::CompoundIdentifier(ids) => Ok((
+                ids.last().unwrap().to_string(),
+                !order.0.asc.unwrap_or(true),
+            )),
             expr => Err(anyhow::anyhow!(
                 "We only support identifier for order by, got {expr}"
             )),
@@ -353,6 +406,7 @@ impl<'a> TryFrom<Group<'a>> for String {
     fn try_from(g: Group) -> Result<Self, Self::Error> {
         match &g.0 {
             SqlExpr::Identifier(id) => Ok(id.to_string()),
+            SqlExpr::CompoundIdentifier(ids) => Ok(ids.last().unwrap().to_string()),
             expr => Err(anyhow::anyhow!(
                 "We only support identifier for order by, got {expr}"
             )),
@@ -902,6 +956,14 @@ mod tests {
             ("select * from table1, table2 where a='b'", false),
             (
                 "select * from table1 left join table2 on table1.a=table2.b where a='b'",
+                true,
+            ),
+            (
+                "select * from table1 right join table2 on table1.a=table2.b where a='b'",
+                false,
+            ),
+            (
+                "select * from table1 join table2 using (a) where a='b'",
                 false,
             ),
             (
@@ -915,6 +977,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_joins() {
+        let sql = Sql::new(
+            "select l.a, t.b, g.c from logs1 l join traces.spans t on l.trace_id = t.trace_id left join enrichment_tables.geo as g on l.ip = g.ip",
+        )
+        .unwrap();
+        assert_eq!(sql.source, "logs1");
+        assert_eq!(
+            sql.joins,
+            vec![
+                Join {
+                    stream_type: Some("traces".to_string()),
+                    stream_name: "spans".to_string(),
+                    alias: Some("t".to_string()),
+                },
+                Join {
+                    stream_type: Some("enrichment_tables".to_string()),
+                    stream_name: "geo".to_string(),
+                    alias: Some("g".to_string()),
+                },
+            ]
+        );
+        let sql = Sql::new("select * from logs1 join logs2 on logs1.a = logs2.a").unwrap();
+        assert_eq!(sql.joins[0].stream_type, None);
+        assert_eq!(sql.joins[0].alias, None);
+        assert!(Sql::new("select * from logs1 cross join logs2").is_err());
+    }
+
     #[test]
     fn test_parse_timestamp() {
         let val = 1666093521151350;
@@ -197,14 +197,25 @@ pub struct StreamSettings {
     pub full_text_search_keys: Vec<String>,
     #[serde(default)]
     pub data_retention: i64,
+    /// keep only the latest version of the documents sharing an `_id`, and
+    /// hide the documents deleted by a tombstone
+    #[serde(default)]
+    pub upsert_by_id: bool,
 }
 
+/// the document id of the streams with `upsert_by_id`
+pub const ID_FIELD: &str = "_id";
+/// the ingestion order of the documents sharing an `_id`
+pub const VERSION_FIELD: &str = "_version";
+/// set on the tombstone of a deleted `_id`
+pub const DELETED_FIELD: &str = "_deleted";
+
 impl Serialize for StreamSettings {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("stream_settings", 4)?;
+        let mut state = serializer.serialize_struct("stream_settings", 5)?;
         let mut part_keys = HashMap::new();
         for (index, key) in self.partition_keys.iter().enumerate() {
             part_keys.insert(format!("L{index}"), key.to_string());
@@ -216,6 +227,7 @@ impl Serialize for StreamSettings {
         )?;
         state.serialize_field("full_text_search_keys", &self.full_text_search_keys)?;
         state.serialize_field("data_retention", &self.data_retention)?;
+        state.serialize_field("upsert_by_id", &self.upsert_by_id)?;
         state.end()
     }
 }
@@ -252,11 +264,17 @@ impl From<&str> for StreamSettings {
             data_retention = v.as_i64().unwrap();
         };
 
+        let upsert_by_id = settings
+            .get("upsert_by_id")
+            .and_then(|v| v.as_bool())
+            .unwrap_or_default();
+
         Self {
             partition_keys,
             partition_time_level,
             full_text_search_keys,
             data_retention,
+            upsert_by_id,
         }
     }
 }
@@ -363,4 +381,15 @@ mod test {
         let stats_frm_str = StreamStats::from(stats_str.as_str());
         assert_eq!(stats, stats_frm_str);
     }
+
+    #[test]
+    fn test_settings_upsert_by_id() {
+        let settings = StreamSettings {
+            upsert_by_id: true,
+            ..Default::default()
+        };
+        let settings = StreamSettings::from(json::to_string(&settings).unwrap().as_str());
+        assert!(settings.upsert_by_id);
+        assert!(!StreamSettings::from(r#"{"data_retention":1}"#).upsert_by_id);
+    }
 }
@@ -63,6 +63,7 @@ pub enum UsageEvent {
     Ingestion,
     Search,
     Functions,
+    QuotaExceeded,
     Other,
 }
 
@@ -72,6 +73,7 @@ impl std::fmt::Display for UsageEvent {
             UsageEvent::Ingestion => write!(f, "Ingestion"),
             UsageEvent::Search => write!(f, "Search"),
             UsageEvent::Functions => write!(f, "Functions"),
+            UsageEvent::QuotaExceeded => write!(f, "QuotaExceeded"),
             UsageEvent::Other => write!(f, "Other"),
         }
     }
@@ -83,18 +85,29 @@ impl From<UsageType> for UsageEvent {
             UsageType::Bulk
             | UsageType::Json
             | UsageType::Multi
+            | UsageType::OtlpLogs
+            | UsageType::Loki
+            | UsageType::SplunkHec
+            | UsageType::FluentForward
+            | UsageType::DatadogLogs
+            | UsageType::DeadLetterReplay
             | UsageType::Traces
             | UsageType::Metrics
             | UsageType::KinesisFirehose
             | UsageType::GCPSubscription
             | UsageType::EnrichmentTable
             | UsageType::Syslog
-            | UsageType::JsonMetrics => UsageEvent::Ingestion,
+            | UsageType::JsonMetrics
+            | UsageType::InfluxDb
+            | UsageType::Graphite
+            | UsageType::StatsD
+            | UsageType::DatadogMetrics => UsageEvent::Ingestion,
             UsageType::Search
             | UsageType::SearchAround
             | UsageType::SearchTopNValues
             | UsageType::MetricSearch => UsageEvent::Search,
             UsageType::Functions => UsageEvent::Functions,
+            UsageType::QuotaExceeded => UsageEvent::QuotaExceeded,
             UsageType::Retention => UsageEvent::Other,
         }
     }
@@ -108,6 +121,18 @@ pub enum UsageType {
     Json,
     #[serde(rename = "logs/_multi")]
     Multi,
+    #[serde(rename = "logs/v1/logs")]
+    OtlpLogs,
+    #[serde(rename = "logs/loki")]
+    Loki,
+    #[serde(rename = "logs/splunk_hec")]
+    SplunkHec,
+    #[serde(rename = "logs/fluent_forward")]
+    FluentForward,
+    #[serde(rename = "logs/datadog")]
+    DatadogLogs,
+    #[serde(rename = "logs/_dead_letter_replay")]
+    DeadLetterReplay,
     #[serde(rename = "/traces")]
     Traces,
     #[serde(rename = "/v1/write")]
@@ -124,12 +149,22 @@ pub enum UsageType {
     Functions,
     #[serde(rename = "data_retention")]
     Retention,
+    #[serde(rename = "ingestion_quota")]
+    QuotaExceeded,
     #[serde(rename = "/_kinesis_firehose")]
     KinesisFirehose,
     #[serde(rename = "/gcp/_sub")]
     GCPSubscription,
     #[serde(rename = "metrics/_json")]
     JsonMetrics,
+    #[serde(rename = "metrics/influxdb")]
+    InfluxDb,
+    #[serde(rename = "metrics/graphite")]
+    Graphite,
+    #[serde(rename = "metrics/statsd")]
+    StatsD,
+    #[serde(rename = "metrics/datadog")]
+    DatadogMetrics,
     Syslog,
     EnrichmentTable,
 }
@@ -140,12 +175,23 @@ impl ToString for UsageType {
             UsageType::Bulk => "logs/_bulk".to_owned(),
             UsageType::Json => "logs/_json".to_owned(),
             UsageType::JsonMetrics => "metrics/_json".to_owned(),
+            UsageType::InfluxDb => "metrics/influxdb".to_owned(),
+            UsageType::Graphite => "metrics/graphite".to_owned(),
+            UsageType::StatsD => "metrics/statsd".to_owned(),
+            UsageType::DatadogMetrics => "metrics/datadog".to_owned(),
             UsageType::Multi => "logs/_multi".to_owned(),
+            UsageType::OtlpLogs => "logs/v1/logs".to_owned(),
+            UsageType::Loki => "logs/loki".to_owned(),
+            UsageType::SplunkHec => "logs/splunk_hec".to_owned(),
+            UsageType::FluentForward => "logs/fluent_forward".to_owned(),
+            UsageType::DatadogLogs => "logs/datadog".to_owned(),
+            UsageType::DeadLetterReplay => "logs/_dead_letter_replay".to_owned(),
             UsageType::Traces => "/traces".to_owned(),
             UsageType::Metrics => "/v1/write".to_owned(),
             UsageType::Search => "/_search".to_owned(),
             UsageType::Functions => "functions".to_owned(),
             UsageType::Retention => "data_retention".to_owned(),
+            UsageType::QuotaExceeded => "ingestion_quota".to_owned(),
             UsageType::KinesisFirehose => "_kinesis_firehose".to_owned(),
             UsageType::Syslog => "syslog".to_owned(),
             UsageType::EnrichmentTable => "enrichment_table".to_owned(),
@@ -0,0 +1,692 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::Bytes;
+use datafusion::arrow::{
+    array::{ArrayRef, Float64Array, Int64Array, StringArray, UInt64Array},
+    compute,
+    datatypes::{DataType, Schema},
+    record_batch::RecordBatch,
+};
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ProjectionMask};
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{BinaryOperator, Expr as SqlExpr, UnaryOperator, Value};
+use std::{cmp::Ordering, collections::HashSet, f64::consts::LN_2, io::Write};
+
+use crate::common::{
+    infra::{
+        config::{CONFIG, FILE_EXT_PARQUET, FILE_EXT_STATS, PARQUET_BATCH_SIZE},
+        storage,
+    },
+    utils::{base64, json},
+};
+
+/// expected false positive probability of the bloom filters
+const BLOOM_FILTER_FPP: f64 = 0.01;
+/// upper limit of a bloom filter size, 32KB
+const BLOOM_FILTER_MAX_BITS: usize = 256 * 1024;
+/// the most distinct values a filter of the max size holds at the expected
+/// false positive probability, `max_bits * ln(2)^2 / -ln(fpp)`
+const BLOOM_FILTER_MAX_VALUES: usize = 27_000;
+/// strings longer than this don't keep min/max, the stats live in the file list
+const MAX_STRING_STATS_LEN: usize = 128;
+
+/// min/max and bloom filter statistics of a column in a file, the stats of a
+/// file are stored in a file next to it
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ColumnStats {
+    pub name: String,
+    #[serde(default)]
+    pub numeric: bool, // min/max are numbers
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bloom_filter: Option<String>, // base64 encoded
+}
+
+/// Bloom filter of the values of a string column, uses double hashing on the
+/// xxh3 hash of the value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BloomFilter {
+    num_hashes: u32,
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// build a bloom filter from the value hashes, returns None if the number
+    /// of distinct values is too high for the size limit
+    fn from_hashes(hashes: &HashSet<u64>) -> Option<Self> {
+        let num_values = hashes.len();
+        if num_values == 0 || num_values > BLOOM_FILTER_MAX_VALUES {
+            return None;
+        }
+        let num_bits =
+            (-(num_values as f64) * BLOOM_FILTER_FPP.ln() / (LN_2 * LN_2)).ceil() as usize;
+        let num_bits = num_bits.clamp(64, BLOOM_FILTER_MAX_BITS);
+        let num_words = (num_bits + 63) / 64;
+        let num_hashes = ((num_words * 64) as f64 / num_values as f64 * LN_2)
+            .round()
+            .clamp(1.0, 8.0) as u32;
+        let mut filter = BloomFilter {
+            num_hashes,
+            bits: vec![0; num_words],
+        };
+        for hash in hashes {
+            for pos in bit_positions(filter.num_hashes, filter.bits.len(), *hash) {
+                filter.bits[pos / 64] |= 1 << (pos % 64);
+            }
+        }
+        Some(filter)
+    }
+
+    pub fn contains(&self, value: &str) -> bool {
+        bit_positions(self.num_hashes, self.bits.len(), hash_value(value))
+            .all(|pos| self.bits[pos / 64] & (1 << (pos % 64)) != 0)
+    }
+
+    pub fn encode(&self) -> String {
+        let mut buf = Vec::with_capacity(1 + self.bits.len() * 8);
+        buf.push(self.num_hashes as u8);
+        for word in self.bits.iter() {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+        base64::encode(&buf)
+    }
+
+    pub fn decode(data: &str) -> Option<Self> {
+        let buf = base64::decode_raw(data).ok()?;
+        if buf.len() < 9 || (buf.len() - 1) % 8 != 0 || buf[0] == 0 {
+            return None;
+        }
+        let bits = buf[1..]
+            .chunks_exact(8)
+            .map(|v| u64::from_le_bytes(v.try_into().unwrap()))
+            .collect();
+        Some(BloomFilter {
+            num_hashes: buf[0] as u32,
+            bits,
+        })
+    }
+}
+
+#[inline]
+fn hash_value(value: &str) -> u64 {
+    xxhash_rust::xxh3::xxh3_64(value.as_bytes())
+}
+
+fn bit_positions(num_hashes: u32, num_words: usize, hash: u64) -> impl Iterator<Item = usize> {
+    let num_bits = (num_words * 64) as u64;
+    let (h1, h2) = (hash & 0xffff_ffff, hash >> 32);
+    (0..num_hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+}
+
+enum MinMax {
+    Empty,
+    Int(i64, i64),
+    UInt(u64, u64),
+    Float(f64, f64),
+    Str(String, String),
+}
+
+struct ColumnStatsBuilder {
+    name: String,
+    min_max: MinMax,
+    no_min_max: bool,
+    hashes: Option<HashSet<u64>>,
+}
+
+impl ColumnStatsBuilder {
+    fn new(name: &str, bloom_filter: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            min_max: MinMax::Empty,
+            no_min_max: false,
+            hashes: if bloom_filter {
+                Some(HashSet::new())
+            } else {
+                None
+            },
+        }
+    }
+
+    fn update(&mut self, array: &ArrayRef) {
+        let value = match array.data_type() {
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                let Ok(array) = compute::cast(array, &DataType::Int64) else {
+                    self.no_min_max = true;
+                    return;
+                };
+                let array = array.as_any().downcast_ref::<Int64Array>().unwrap();
+                match (compute::min(array), compute::max(array)) {
+                    (Some(min), Some(max)) => MinMax::Int(min, max),
+                    _ => MinMax::Empty,
+                }
+            }
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                let Ok(array) = compute::cast(array, &DataType::UInt64) else {
+                    self.no_min_max = true;
+                    return;
+                };
+                let array = array.as_any().downcast_ref::<UInt64Array>().unwrap();
+                match (compute::min(array), compute::max(array)) {
+                    (Some(min), Some(max)) => MinMax::UInt(min, max),
+                    _ => MinMax::Empty,
+                }
+            }
+            DataType::Float32 | DataType::Float64 => {
+                let Ok(array) = compute::cast(array, &DataType::Float64) else {
+                    self.no_min_max = true;
+                    return;
+                };
+                let array = array.as_any().downcast_ref::<Float64Array>().unwrap();
+                match (compute::min(array), compute::max(array)) {
+                    // NaN breaks the ordering, keep no stats for the column
+                    (Some(min), Some(max)) if min.is_nan() || max.is_nan() => {
+                        self.no_min_max = true;
+                        return;
+                    }
+                    (Some(min), Some(max)) => MinMax::Float(min, max),
+                    _ => MinMax::Empty,
+                }
+            }
+            DataType::Utf8 => {
+                let array = array.as_any().downcast_ref::<StringArray>().unwrap();
+                if let Some(hashes) = self.hashes.as_mut() {
+                    for value in array.iter().flatten() {
+                        hashes.insert(hash_value(value));
+                    }
+                    // too many distinct values for a bloom filter, stop collecting
+                    if hashes.len() > BLOOM_FILTER_MAX_VALUES {
+                        self.hashes = None;
+                    }
+                }
+                match (compute::min_string(array), compute::max_string(array)) {
+                    (Some(min), Some(max))
+                        if min.len() <= MAX_STRING_STATS_LEN
+                            && max.len() <= MAX_STRING_STATS_LEN =>
+                    {
+                        MinMax::Str(min.to_string(), max.to_string())
+                    }
+                    (Some(_), Some(_)) => {
+                        self.no_min_max = true;
+                        return;
+                    }
+                    _ => MinMax::Empty,
+                }
+            }
+            _ => {
+                self.no_min_max = true;
+                return;
+            }
+        };
+        self.merge(value);
+    }
+
+    fn merge(&mut self, value: MinMax) {
+        let min_max = std::mem::replace(&mut self.min_max, MinMax::Empty);
+        self.min_max = match (min_max, value) {
+            (MinMax::Empty, v) | (v, MinMax::Empty) => v,
+            (MinMax::Int(a, b), MinMax::Int(c, d)) => MinMax::Int(a.min(c), b.max(d)),
+            (MinMax::UInt(a, b), MinMax::UInt(c, d)) => MinMax::UInt(a.min(c), b.max(d)),
+            (MinMax::Float(a, b), MinMax::Float(c, d)) => MinMax::Float(a.min(c), b.max(d)),
+            (MinMax::Str(a, b), MinMax::Str(c, d)) => MinMax::Str(a.min(c), b.max(d)),
+            _ => {
+                // the column changed type between batches
+                self.no_min_max = true;
+                MinMax::Empty
+            }
+        };
+    }
+
+    fn finish(self) -> Option<ColumnStats> {
+        let (numeric, min, max) = match self.min_max {
+            _ if self.no_min_max => (false, None, None),
+            MinMax::Empty => (false, None, None),
+            MinMax::Int(min, max) => (true, Some(min.to_string()), Some(max.to_string())),
+            MinMax::UInt(min, max) => (true, Some(min.to_string()), Some(max.to_string())),
+            MinMax::Float(min, max) => (true, Some(min.to_string()), Some(max.to_string())),
+            MinMax::Str(min, max) => (false, Some(min), Some(max)),
+        };
+        let bloom_filter = self
+            .hashes
+            .as_ref()
+            .and_then(BloomFilter::from_hashes)
+            .map(|v| v.encode());
+        if min.is_none() && bloom_filter.is_none() {
+            return None;
+        }
+        Some(ColumnStats {
+            name: self.name,
+            numeric,
+            min,
+            max,
+            bloom_filter,
+        })
+    }
+}
+
+/// pick the columns to collect stats for, bloom filter columns go first
+fn stats_columns(schema: &Schema) -> Vec<(usize, bool)> {
+    let bloom_fields = CONFIG
+        .common
+        .bloom_filter_fields
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>();
+    let mut columns = Vec::new();
+    let mut others = Vec::new();
+    for (i, field) in schema.fields().iter().enumerate() {
+        if field.name() == &CONFIG.common.column_timestamp {
+            continue;
+        }
+        let bloom_filter =
+            field.data_type() == &DataType::Utf8 && bloom_fields.contains(&field.name().as_str());
+        if bloom_filter {
+            columns.push((i, true));
+        } else if field.data_type().is_numeric() || field.data_type() == &DataType::Utf8 {
+            others.push((i, false));
+        }
+    }
+    columns.extend(others);
+    columns.truncate(CONFIG.common.column_stats_max_fields);
+    columns
+}
+
+/// the object key of the column stats file for a parquet file
+pub fn stats_file_key(file: &str) -> String {
+    match file.strip_suffix(FILE_EXT_PARQUET) {
+        Some(name) => format!("{name}{FILE_EXT_STATS}"),
+        None => format!("{file}{FILE_EXT_STATS}"),
+    }
+}
+
+pub fn to_bytes(stats: &[ColumnStats]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut buf = zstd::Encoder::new(Vec::new(), 3)?;
+    buf.write_all(&json::to_vec(stats)?)?;
+    Ok(buf.finish()?)
+}
+
+pub fn from_bytes(data: &[u8]) -> Result<Vec<ColumnStats>, anyhow::Error> {
+    let uncompress = zstd::decode_all(data)?;
+    Ok(json::from_slice(&uncompress)?)
+}
+
+/// upload the column stats of a file next to it, returns the size of the
+/// stats file, 0 means the file has no stats
+pub async fn upload(file: &str, stats: &[ColumnStats]) -> Result<i64, anyhow::Error> {
+    if stats.is_empty() {
+        return Ok(0);
+    }
+    let buf = to_bytes(stats)?;
+    let stats_size = buf.len() as i64;
+    storage::put(&stats_file_key(file), buf.into()).await?;
+    Ok(stats_size)
+}
+
+/// collect the column stats of the record batches of a file
+pub fn from_batches(schema: &Schema, batches: &[RecordBatch]) -> Vec<ColumnStats> {
+    let columns = stats_columns(schema);
+    let mut builders = columns
+        .iter()
+        .map(|(i, bloom_filter)| ColumnStatsBuilder::new(schema.field(*i).name(), *bloom_filter))
+        .collect::<Vec<_>>();
+    for batch in batches {
+        for builder in builders.iter_mut() {
+            if let Some(array) = batch.column_by_name(&builder.name) {
+                builder.update(array);
+            }
+        }
+    }
+    builders.into_iter().filter_map(|b| b.finish()).collect()
+}
+
+/// collect the column stats of a parquet file
+pub fn from_parquet(data: &Bytes) -> Result<Vec<ColumnStats>, anyhow::Error> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(data.clone())?;
+    let schema = builder.schema().clone();
+    let columns = stats_columns(&schema);
+    if columns.is_empty() {
+        return Ok(vec![]);
+    }
+    let mask = ProjectionMask::roots(
+        builder.parquet_schema(),
+        columns.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+    );
+    let reader = builder
+        .with_projection(mask)
+        .with_batch_size(PARQUET_BATCH_SIZE)
+        .build()?;
+    let mut builders = columns
+        .iter()
+        .map(|(i, bloom_filter)| ColumnStatsBuilder::new(schema.field(*i).name(), *bloom_filter))
+        .collect::<Vec<_>>();
+    for batch in reader {
+        let batch = batch?;
+        for builder in builders.iter_mut() {
+            if let Some(array) = batch.column_by_name(&builder.name) {
+                builder.update(array);
+            }
+        }
+    }
+    Ok(builders.into_iter().filter_map(|b| b.finish()).collect())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum FilterOp {
+    Eq,
+    Gt,
+    GtEq,
+    Lt,
+    LtEq,
+}
+
+impl FilterOp {
+    /// the operator after swapping the two sides, `5 < a` is `a > 5`
+    fn flip(self) -> Self {
+        match self {
+            FilterOp::Eq => FilterOp::Eq,
+            FilterOp::Gt => FilterOp::Lt,
+            FilterOp::GtEq => FilterOp::LtEq,
+            FilterOp::Lt => FilterOp::Gt,
+            FilterOp::LtEq => FilterOp::GtEq,
+        }
+    }
+}
+
+/// a predicate on a column which every matched record must satisfy, multiple
+/// values are only used by `Eq` and mean any of them
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ColumnFilter {
+    pub field: String,
+    pub op: FilterOp,
+    pub numeric: bool,
+    pub values: Vec<String>,
+}
+
+/// collect the column predicates of the where clause, only predicates joined
+/// by AND are used, anything under OR / NOT can't prune files, qualified
+/// columns are only used when the qualifier is the source table
+pub fn column_filters(selection: Option<&SqlExpr>, source: &str) -> Vec<ColumnFilter> {
+    let mut filters = Vec::new();
+    if let Some(expr) = selection {
+        collect_filters(expr, source, &mut filters);
+    }
+    filters
+}
+
+fn collect_filters(expr: &SqlExpr, source: &str, filters: &mut Vec<ColumnFilter>) {
+    match expr {
+        SqlExpr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            collect_filters(left, source, filters);
+            collect_filters(right, source, filters);
+        }
+        SqlExpr::Nested(expr) => collect_filters(expr, source, filters),
+        SqlExpr::BinaryOp { left, op, right } => {
+            let op = match op {
+                BinaryOperator::Eq => FilterOp::Eq,
+                BinaryOperator::Gt => FilterOp::Gt,
+                BinaryOperator::GtEq => FilterOp::GtEq,
+                BinaryOperator::Lt => FilterOp::Lt,
+                BinaryOperator::LtEq => FilterOp::LtEq,
+                _ => return,
+            };
+            let (field, value, op) = match (field_name(left, source), literal(right)) {
+                (Some(field), Some(value)) => (field, value, op),
+                _ => match (field_name(right, source), literal(left)) {
+                    (Some(field), Some(value)) => (field, value, op.flip()),
+                    _ => return,
+                },
+            };
+            filters.push(ColumnFilter {
+                field,
+                op,
+                numeric: value.1,
+                values: vec![value.0],
+            });
+        }
+        SqlExpr::InList {
+            expr,
+            list,
+            negated: false,
+        } => {
+            let Some(field) = field_name(expr, source) else {
+                return;
+            };
+            let Some(values) = list.iter().map(literal).collect::<Option<Vec<_>>>() else {
+                return;
+            };
+            if values.is_empty() || values.iter().any(|v| v.1 != values[0].1) {
+                return;
+            }
+            filters.push(ColumnFilter {
+                field,
+                op: FilterOp::Eq,
+                numeric: values[0].1,
+                values: values.into_iter().map(|v| v.0).collect(),
+            });
+        }
+        _ => {}
+    }
+}
+
+fn field_name(expr: &SqlExpr, source: &str) -> Option<String> {
+    match expr {
+        SqlExpr::Identifier(ident) => Some(ident.value.clone()),
+        SqlExpr::CompoundIdentifier(idents) => match idents.as_slice() {
+            [table, field] if table.value == source => Some(field.value.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// returns the literal value and whether it is a number
+fn literal(expr: &SqlExpr) -> Option<(String, bool)> {
+    match expr {
+        SqlExpr::Value(Value::Number(v, _)) => Some((v.to_string(), true)),
+        SqlExpr::Value(Value::SingleQuotedString(v)) => Some((v.to_string(), false)),
+        SqlExpr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => match expr.as_ref() {
+            SqlExpr::Value(Value::Number(v, _)) => Some((format!("-{v}"), true)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn compare(a: &str, b: &str, numeric: bool) -> Option<Ordering> {
+    if !numeric {
+        return Some(a.cmp(b));
+    }
+    if let (Ok(a), Ok(b)) = (a.parse::<i128>(), b.parse::<i128>()) {
+        return Some(a.cmp(&b));
+    }
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b),
+        _ => None,
+    }
+}
+
+/// returns false only if the stats prove no record of the file can match
+pub fn may_match(stats: &[ColumnStats], filters: &[ColumnFilter]) -> bool {
+    if stats.is_empty() || filters.is_empty() {
+        return true;
+    }
+    for filter in filters {
+        let Some(stats) = stats.iter().find(|s| s.name == filter.field) else {
+            continue;
+        };
+        // the literal needs a cast, don't guess the result
+        if stats.numeric != filter.numeric {
+            continue;
+        }
+        let matched = match filter.op {
+            FilterOp::Eq => {
+                let bloom_filter = stats.bloom_filter.as_deref().and_then(BloomFilter::decode);
+                filter.values.iter().any(|v| {
+                    value_in_range(stats, v, filter.numeric)
+                        && bloom_filter.as_ref().map_or(true, |b| b.contains(v))
+                })
+            }
+            FilterOp::Gt => stats.max.as_ref().map_or(true, |max| {
+                compare(max, &filter.values[0], filter.numeric) != Some(Ordering::Less)
+                    && compare(max, &filter.values[0], filter.numeric) != Some(Ordering::Equal)
+            }),
+            FilterOp::GtEq => stats.max.as_ref().map_or(true, |max| {
+                compare(max, &filter.values[0], filter.numeric) != Some(Ordering::Less)
+            }),
+            FilterOp::Lt => stats.min.as_ref().map_or(true, |min| {
+                compare(min, &filter.values[0], filter.numeric) != Some(Ordering::Greater)
+                    && compare(min, &filter.values[0], filter.numeric) != Some(Ordering::Equal)
+            }),
+            FilterOp::LtEq => stats.min.as_ref().map_or(true, |min| {
+                compare(min, &filter.values[0], filter.numeric) != Some(Ordering::Greater)
+            }),
+        };
+        if !matched {
+            return false;
+        }
+    }
+    true
+}
+
+fn value_in_range(stats: &ColumnStats, value: &str, numeric: bool) -> bool {
+    if let Some(min) = stats.min.as_ref() {
+        if compare(value, min, numeric) == Some(Ordering::Less) {
+            return false;
+        }
+    }
+    if let Some(max) = stats.max.as_ref() {
+        if compare(value, max, numeric) == Some(Ordering::Greater) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::meta::sql::Sql as MetaSql;
+    use datafusion::arrow::datatypes::Field;
+    use std::sync::Arc;
+
+    fn build_stats() -> Vec<ColumnStats> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("trace_id", DataType::Utf8, true),
+            Field::new("code", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![Some("t100"), Some("t200"), None])),
+                Arc::new(Int64Array::from(vec![Some(200), None, Some(404)])),
+            ],
+        )
+        .unwrap();
+        from_batches(&schema, &[batch])
+    }
+
+    #[test]
+    fn test_column_stats_bloom_filter() {
+        let hashes = ["a", "b", "c"].iter().map(|v| hash_value(v)).collect();
+        let filter = BloomFilter::from_hashes(&hashes).unwrap();
+        assert!(filter.contains("a") && filter.contains("b") && filter.contains("c"));
+        let filter = BloomFilter::decode(&filter.encode()).unwrap();
+        assert!(filter.contains("a"));
+        assert!(BloomFilter::decode("").is_none());
+    }
+
+    #[test]
+    fn test_column_stats_bloom_filter_size() {
+        let hashes = (0..BLOOM_FILTER_MAX_VALUES)
+            .map(|i| hash_value(&format!("v{i}")))
+            .collect::<HashSet<_>>();
+        let filter = BloomFilter::from_hashes(&hashes).unwrap();
+        assert!(filter.bits.len() * 64 <= BLOOM_FILTER_MAX_BITS);
+        let false_positives = (0..10_000)
+            .filter(|i| filter.contains(&format!("x{i}")))
+            .count();
+        assert!(false_positives < 200, "{false_positives}");
+
+        let mut hashes = hashes;
+        hashes.insert(hash_value("one more"));
+        assert!(BloomFilter::from_hashes(&hashes).is_none());
+    }
+
+    #[test]
+    fn test_column_stats_from_batches() {
+        let stats = build_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].name, "trace_id");
+        assert_eq!(stats[0].min.as_deref(), Some("t100"));
+        assert_eq!(stats[0].max.as_deref(), Some("t200"));
+        assert!(stats[0].bloom_filter.is_some());
+        assert!(stats[1].numeric);
+        assert_eq!(stats[1].min.as_deref(), Some("200"));
+        assert_eq!(stats[1].max.as_deref(), Some("404"));
+        assert!(stats[1].bloom_filter.is_none());
+        assert_eq!(from_bytes(&to_bytes(&stats).unwrap()).unwrap(), stats);
+        assert_eq!(stats_file_key("files/a/1.parquet"), "files/a/1.stats");
+    }
+
+    #[test]
+    fn test_column_stats_may_match() {
+        let stats = build_stats();
+        let samples = [
+            ("select * from tbl where trace_id = 't100'", true),
+            ("select * from tbl where trace_id = 't150'", false),
+            ("select * from tbl where trace_id = 't300'", false),
+            ("select * from tbl where trace_id in ('t300', 't200')", true),
+            (
+                "select * from tbl where trace_id = 't300' or code = 200",
+                true,
+            ),
+            ("select * from tbl where code = '200'", true),
+            ("select * from tbl where code > 404", false),
+            ("select * from tbl where 404 <= code", true),
+            (
+                "select * from tbl where code < 200 and trace_id = 't100'",
+                false,
+            ),
+            (
+                "select * from tbl where (code >= -1 and level = 'info')",
+                true,
+            ),
+            ("select * from tbl where tbl.code > 404", false),
+            ("select * from tbl t where t.code > 404", true),
+            (
+                "select * from tbl join other o on tbl.id = o.id where o.code > 404",
+                true,
+            ),
+        ];
+        for (sql, expected) in samples {
+            let meta = MetaSql::new(sql).unwrap();
+            let filters = column_filters(meta.selection.as_ref(), &meta.source);
+            assert_eq!(may_match(&stats, &filters), expected, "{sql}");
+        }
+    }
+}
@@ -14,7 +14,9 @@
 
 pub mod auth;
 pub mod base64;
+pub mod cancel;
 pub mod cgroup;
+pub mod column_stats;
 pub mod file;
 pub mod flatten;
 pub mod functions;
@@ -23,6 +25,7 @@ pub mod http;
 pub mod json;
 pub mod notification;
 pub mod schema_ext;
+pub mod sql_condition;
 pub mod str;
 pub mod stream;
 pub mod time;
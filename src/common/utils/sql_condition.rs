@@ -0,0 +1,288 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sqlparser::{
+    ast::{
+        BinaryOperator, Expr as SqlExpr, FunctionArg, FunctionArgExpr, UnaryOperator,
+        Value as SqlValue,
+    },
+    dialect::GenericDialect,
+    parser::Parser,
+    tokenizer::Token,
+};
+use std::cmp::Ordering;
+
+use crate::common::utils::json::{Map, Value};
+
+/// parse a SQL condition on the flattened records, the expressions which
+/// can't be evaluated are rejected
+pub fn parse(condition: &str) -> Result<SqlExpr, anyhow::Error> {
+    let dialect = GenericDialect {};
+    let mut parser = Parser::new(&dialect).try_with_sql(condition)?;
+    let expr = parser.parse_expr()?;
+    if parser.peek_token().token != Token::EOF {
+        return Err(anyhow::anyhow!("unexpected {}", parser.peek_token().token));
+    }
+    eval(&expr, &Map::new())?;
+    Ok(expr)
+}
+
+/// whether the record matches the parsed condition
+pub fn matches(expr: &SqlExpr, record: &Map<String, Value>) -> bool {
+    matches!(eval(expr, record), Ok(Scalar::Bool(true)))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Scalar {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+}
+
+impl Scalar {
+    fn is_true(&self) -> bool {
+        matches!(self, Scalar::Bool(true))
+    }
+
+    fn as_str(&self) -> Option<String> {
+        match self {
+            Scalar::Null => None,
+            Scalar::Bool(v) => Some(v.to_string()),
+            Scalar::Number(v) => Some(v.to_string()),
+            Scalar::Str(v) => Some(v.clone()),
+        }
+    }
+}
+
+/// field of the flattened record, the parts of compound identifiers are joined
+/// like the flattened keys
+fn field(record: &Map<String, Value>, name: &str) -> Scalar {
+    match record.get(name) {
+        None | Some(Value::Null) => Scalar::Null,
+        Some(Value::Bool(v)) => Scalar::Bool(*v),
+        Some(Value::Number(v)) => v.as_f64().map(Scalar::Number).unwrap_or(Scalar::Null),
+        Some(Value::String(v)) => Scalar::Str(v.clone()),
+        Some(v) => Scalar::Str(v.to_string()),
+    }
+}
+
+fn field_name(ident: &sqlparser::ast::Ident) -> String {
+    if ident.quote_style.is_some() {
+        ident.value.clone()
+    } else {
+        ident.value.to_lowercase()
+    }
+}
+
+fn eval(expr: &SqlExpr, record: &Map<String, Value>) -> Result<Scalar, anyhow::Error> {
+    Ok(match expr {
+        SqlExpr::Identifier(ident) => field(record, &field_name(ident)),
+        SqlExpr::CompoundIdentifier(idents) => field(
+            record,
+            &idents.iter().map(field_name).collect::<Vec<_>>().join("_"),
+        ),
+        SqlExpr::Value(value) => match value {
+            SqlValue::Number(v, _) => Scalar::Number(v.parse()?),
+            SqlValue::SingleQuotedString(v) => Scalar::Str(v.clone()),
+            SqlValue::Boolean(v) => Scalar::Bool(*v),
+            SqlValue::Null => Scalar::Null,
+            _ => return Err(anyhow::anyhow!("unsupported value {value}")),
+        },
+        SqlExpr::Nested(expr) => eval(expr, record)?,
+        SqlExpr::UnaryOp { op, expr } => {
+            let value = eval(expr, record)?;
+            match op {
+                UnaryOperator::Not => Scalar::Bool(!value.is_true()),
+                UnaryOperator::Minus => match value {
+                    Scalar::Number(v) => Scalar::Number(-v),
+                    _ => Scalar::Null,
+                },
+                UnaryOperator::Plus => value,
+                _ => return Err(anyhow::anyhow!("unsupported operator {op}")),
+            }
+        }
+        SqlExpr::BinaryOp { left, op, right } => {
+            let (left, right) = (eval(left, record)?, eval(right, record)?);
+            let ord = compare(&left, &right);
+            Scalar::Bool(match op {
+                BinaryOperator::And => left.is_true() && right.is_true(),
+                BinaryOperator::Or => left.is_true() || right.is_true(),
+                BinaryOperator::Eq => ord == Some(Ordering::Equal),
+                BinaryOperator::NotEq => ord.is_some() && ord != Some(Ordering::Equal),
+                BinaryOperator::Gt => ord == Some(Ordering::Greater),
+                BinaryOperator::GtEq => matches!(ord, Some(Ordering::Greater | Ordering::Equal)),
+                BinaryOperator::Lt => ord == Some(Ordering::Less),
+                BinaryOperator::LtEq => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+                _ => return Err(anyhow::anyhow!("unsupported operator {op}")),
+            })
+        }
+        SqlExpr::IsNull(expr) => Scalar::Bool(eval(expr, record)? == Scalar::Null),
+        SqlExpr::IsNotNull(expr) => Scalar::Bool(eval(expr, record)? != Scalar::Null),
+        SqlExpr::IsTrue(expr) => Scalar::Bool(eval(expr, record)?.is_true()),
+        SqlExpr::IsNotTrue(expr) => Scalar::Bool(!eval(expr, record)?.is_true()),
+        SqlExpr::IsFalse(expr) => Scalar::Bool(eval(expr, record)? == Scalar::Bool(false)),
+        SqlExpr::IsNotFalse(expr) => Scalar::Bool(eval(expr, record)? != Scalar::Bool(false)),
+        SqlExpr::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            let value = eval(expr, record)?;
+            let mut found = false;
+            for item in list {
+                found |= compare(&value, &eval(item, record)?) == Some(Ordering::Equal);
+            }
+            Scalar::Bool(value != Scalar::Null && found != *negated)
+        }
+        SqlExpr::Between {
+            expr,
+            negated,
+            low,
+            high,
+        } => {
+            let value = eval(expr, record)?;
+            let (low, high) = (eval(low, record)?, eval(high, record)?);
+            match (compare(&value, &low), compare(&value, &high)) {
+                (Some(low), Some(high)) => {
+                    Scalar::Bool((low != Ordering::Less && high != Ordering::Greater) != *negated)
+                }
+                _ => Scalar::Bool(false),
+            }
+        }
+        SqlExpr::Like {
+            negated,
+            expr,
+            pattern,
+            ..
+        } => like(eval(expr, record)?, eval(pattern, record)?, false, *negated),
+        SqlExpr::ILike {
+            negated,
+            expr,
+            pattern,
+            ..
+        } => like(eval(expr, record)?, eval(pattern, record)?, true, *negated),
+        SqlExpr::Function(func) => {
+            let name = func.name.to_string().to_lowercase();
+            let mut args = vec![];
+            for arg in func.args.iter() {
+                match arg {
+                    FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => {
+                        args.push(eval(expr, record)?)
+                    }
+                    _ => return Err(anyhow::anyhow!("unsupported argument {arg}")),
+                }
+            }
+            match (name.as_str(), args.as_slice()) {
+                ("str_match", [value, pattern]) => match (value.as_str(), pattern.as_str()) {
+                    (Some(value), Some(pattern)) => Scalar::Bool(value.contains(&pattern)),
+                    _ => Scalar::Bool(false),
+                },
+                ("str_match_ignore_case", [value, pattern]) => {
+                    match (value.as_str(), pattern.as_str()) {
+                        (Some(value), Some(pattern)) => {
+                            Scalar::Bool(value.to_lowercase().contains(&pattern.to_lowercase()))
+                        }
+                        _ => Scalar::Bool(false),
+                    }
+                }
+                _ => return Err(anyhow::anyhow!("unsupported function {}", func.name)),
+            }
+        }
+        _ => return Err(anyhow::anyhow!("unsupported expression {expr}")),
+    })
+}
+
+/// numbers compare with numeric strings, null compares with nothing
+fn compare(left: &Scalar, right: &Scalar) -> Option<Ordering> {
+    match (left, right) {
+        (Scalar::Number(l), Scalar::Number(r)) => l.partial_cmp(r),
+        (Scalar::Number(l), Scalar::Str(r)) => l.partial_cmp(&r.parse::<f64>().ok()?),
+        (Scalar::Str(l), Scalar::Number(r)) => l.parse::<f64>().ok()?.partial_cmp(r),
+        (Scalar::Str(l), Scalar::Str(r)) => Some(l.cmp(r)),
+        (Scalar::Bool(l), Scalar::Bool(r)) => Some(l.cmp(r)),
+        _ => None,
+    }
+}
+
+fn like(value: Scalar, pattern: Scalar, ignore_case: bool, negated: bool) -> Scalar {
+    let (Some(mut value), Some(mut pattern)) = (value.as_str(), pattern.as_str()) else {
+        return Scalar::Bool(false);
+    };
+    if ignore_case {
+        value = value.to_lowercase();
+        pattern = pattern.to_lowercase();
+    }
+    let value = value.chars().collect::<Vec<_>>();
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    Scalar::Bool(like_match(&value, &pattern) != negated)
+}
+
+/// `%` matches any characters and `_` a single one
+fn like_match(value: &[char], pattern: &[char]) -> bool {
+    // matched[j]: the pattern prefix of length j matches the current value prefix
+    let mut matched = vec![false; pattern.len() + 1];
+    matched[0] = true;
+    for j in 0..pattern.len() {
+        matched[j + 1] = matched[j] && pattern[j] == '%';
+    }
+    for c in value {
+        let mut next = vec![false; pattern.len() + 1];
+        for j in 0..pattern.len() {
+            next[j + 1] = match pattern[j] {
+                '%' => next[j] || matched[j + 1],
+                '_' => matched[j],
+                p => matched[j] && p == *c,
+            };
+        }
+        matched = next;
+    }
+    matched[pattern.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::utils::json;
+
+    #[test]
+    fn test_parse() {
+        assert!(parse("level = 'error'").is_ok());
+        assert!(parse("level = 'error' extra").is_err());
+        assert!(parse("level = (SELECT 1)").is_err());
+        assert!(parse("unknown_fn(level)").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let record = json::json!({"level": "error", "code": 502});
+        let record = record.as_object().unwrap();
+        assert!(matches(
+            &parse("level = 'error' AND code > 500").unwrap(),
+            record
+        ));
+        assert!(!matches(&parse("missing = 1").unwrap(), record));
+    }
+
+    #[test]
+    fn test_like_match() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert!(like_match(&chars("nginx-1"), &chars("nginx%")));
+        assert!(like_match(&chars("nginx"), &chars("%")));
+        assert!(like_match(&chars("abc"), &chars("a_c")));
+        assert!(!like_match(&chars("abcd"), &chars("a_c")));
+        assert!(like_match(&chars("a.b.c"), &chars("%b%")));
+        assert!(!like_match(&chars(""), &chars("_")));
+    }
+}
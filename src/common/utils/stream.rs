@@ -176,6 +176,8 @@ mod tests {
             records: 0,
             original_size: 1000,
             compressed_size: 700,
+            index_size: 0,
+            stats_size: 0,
         };
         populate_file_meta(schema, vec![vec![batch]], &mut file_meta)
             .await
@@ -47,6 +47,7 @@ impl From<meta::search::Request> for cluster_rpc::SearchRequest {
             job: "".to_string(),
             stage: 0,
             partition: 0,
+            user_id: "".to_string(),
         };
 
         let mut aggs = Vec::new();
@@ -62,6 +63,8 @@ impl From<meta::search::Request> for cluster_rpc::SearchRequest {
             aggs,
             file_list: vec![],
             stream_type: "".to_string(),
+            joins: vec![],
+            streams: vec![],
         }
     }
 }
@@ -74,6 +77,8 @@ impl From<&meta::common::FileMeta> for cluster_rpc::FileMeta {
             records: req.records,
             original_size: req.original_size,
             compressed_size: req.compressed_size,
+            index_size: req.index_size,
+            stats_size: req.stats_size,
         }
     }
 }
@@ -86,6 +91,8 @@ impl From<&cluster_rpc::FileMeta> for meta::common::FileMeta {
             records: req.records,
             original_size: req.original_size,
             compressed_size: req.compressed_size,
+            index_size: req.index_size,
+            stats_size: req.stats_size,
         }
     }
 }
@@ -124,6 +131,7 @@ impl From<promql::MetricsQueryRequest> for cluster_rpc::MetricsQueryRequest {
             job: "".to_string(),
             stage: 0,
             partition: 0,
+            user_id: "".to_string(),
         };
 
         cluster_rpc::MetricsQueryRequest {
@@ -213,6 +221,8 @@ mod test {
             records: 300,
             original_size: 10,
             compressed_size: 1,
+            index_size: 1,
+            stats_size: 1,
         };
 
         let rpc_meta = cluster_rpc::FileMeta::from(&file_meta);
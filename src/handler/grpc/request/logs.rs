@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use actix_web::http;
 use async_trait::async_trait;
 use opentelemetry_proto::tonic::collector::logs::v1::{
     logs_service_server::LogsService, ExportLogsServiceRequest, ExportLogsServiceResponse,
@@ -20,6 +21,7 @@ use tonic::Response;
 use tonic::Status;
 
 use crate::common::infra::config::CONFIG;
+use crate::handler::grpc::request::quota_exceeded;
 
 #[derive(Default)]
 pub struct LogsServer;
@@ -51,10 +53,12 @@ impl LogsService for LogsServer {
             in_req,
         )
         .await;
-        if resp.is_ok() {
-            return Ok(Response::new(ExportLogsServiceResponse {}));
-        } else {
-            Err(Status::internal(resp.err().unwrap().to_string()))
+        match resp {
+            Ok(resp) if resp.status() == http::StatusCode::TOO_MANY_REQUESTS => {
+                Err(quota_exceeded(&resp))
+            }
+            Ok(_) => Ok(Response::new(ExportLogsServiceResponse {})),
+            Err(e) => Err(Status::internal(e.to_string())),
         }
     }
 }
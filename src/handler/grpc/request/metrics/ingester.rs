@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use actix_web::http;
 use async_trait::async_trait;
 use opentelemetry_proto::tonic::collector::metrics::v1::{
     metrics_service_server::MetricsService, ExportMetricsServiceRequest,
@@ -20,6 +21,7 @@ use opentelemetry_proto::tonic::collector::metrics::v1::{
 use tonic::{Response, Status};
 
 use crate::common::infra::config::CONFIG;
+use crate::handler::grpc::request::quota_exceeded;
 
 #[derive(Default)]
 pub struct Ingester;
@@ -51,10 +53,12 @@ impl MetricsService for Ingester {
             in_req,
         )
         .await;
-        if resp.is_ok() {
-            return Ok(Response::new(ExportMetricsServiceResponse {}));
-        } else {
-            Err(Status::internal(resp.err().unwrap().to_string()))
+        match resp {
+            Ok(resp) if resp.status() == http::StatusCode::TOO_MANY_REQUESTS => {
+                Err(quota_exceeded(&resp))
+            }
+            Ok(_) => Ok(Response::new(ExportMetricsServiceResponse {})),
+            Err(e) => Err(Status::internal(e.to_string())),
         }
     }
 }
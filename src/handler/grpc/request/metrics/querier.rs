@@ -81,8 +81,12 @@ impl Metrics for Querier {
         let end_time = req.get_ref().end_time;
         let org_id = &req.get_ref().org_id;
         let stream_name = &req.get_ref().stream_name;
+        let stream_type = match req.get_ref().stream_type.as_str() {
+            "" => meta::StreamType::Metrics,
+            v => meta::StreamType::from(v),
+        };
         let pattern = format!(
-            "{}files/{org_id}/metrics/{stream_name}/",
+            "{}files/{org_id}/{stream_type}/{stream_name}/",
             &CONFIG.common.data_wal_dir
         );
 
@@ -139,20 +143,32 @@ impl Metrics for Querier {
 
         // check wal memory mode
         if CONFIG.common.wal_memory_mode_enabled {
-            let mem_files =
-                wal::get_search_in_memory_files(org_id, stream_name, meta::StreamType::Metrics)
-                    .unwrap_or_default();
+            let mem_files = wal::get_search_in_memory_files(org_id, stream_name, stream_type)
+                .unwrap_or_default();
             for (name, body) in mem_files {
                 resp.files.push(MetricsWalFile { name, body });
             }
         }
 
         let time = start.elapsed().as_secs_f64();
+        let stream_type = stream_type.to_string();
         metrics::GRPC_RESPONSE_TIME
-            .with_label_values(&["/metrics/wal_file", "200", org_id, stream_name, "metrics"])
+            .with_label_values(&[
+                "/metrics/wal_file",
+                "200",
+                org_id,
+                stream_name,
+                &stream_type,
+            ])
             .observe(time);
         metrics::GRPC_INCOMING_REQUESTS
-            .with_label_values(&["/metrics/wal_file", "200", org_id, stream_name, "metrics"])
+            .with_label_values(&[
+                "/metrics/wal_file",
+                "200",
+                org_id,
+                stream_name,
+                &stream_type,
+            ])
             .inc();
 
         Ok(Response::new(resp))
@@ -21,6 +21,19 @@ pub mod search;
 pub mod traces;
 pub mod usage;
 
+/// the answer of an ingestion rejected by a quota, the seconds to wait before
+/// retrying are kept in the message
+pub(crate) fn quota_exceeded(resp: &actix_web::HttpResponse) -> tonic::Status {
+    let retry_after = resp
+        .headers()
+        .get(actix_web::http::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("1");
+    tonic::Status::resource_exhausted(format!(
+        "Ingestion quota exceeded, retry after {retry_after} seconds"
+    ))
+}
+
 pub struct MetadataMap<'a>(&'a tonic::metadata::MetadataMap);
 
 impl<'a> Extractor for MetadataMap<'a> {
@@ -12,21 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use futures::{Stream, StreamExt};
 use opentelemetry::global;
+use std::pin::Pin;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::common::infra::errors;
 use crate::common::infra::metrics;
 use crate::handler::grpc::cluster_rpc::search_server::Search;
+use crate::handler::grpc::cluster_rpc::CancelRequest;
+use crate::handler::grpc::cluster_rpc::CancelResponse;
 use crate::handler::grpc::cluster_rpc::SearchRequest;
 use crate::handler::grpc::cluster_rpc::SearchResponse;
+use crate::handler::grpc::cluster_rpc::SearchStreamRequest;
 use crate::service::search as SearchService;
 
 pub struct Searcher;
 
 #[tonic::async_trait]
 impl Search for Searcher {
+    type SearchStreamStream = Pin<Box<dyn Stream<Item = Result<SearchResponse, Status>> + Send>>;
+
     #[tracing::instrument(name = "grpc:search:enter", skip_all, fields(org_id = req.get_ref().org_id))]
     async fn search(
         &self,
@@ -41,9 +50,17 @@ impl Search for Searcher {
         let req = req.get_ref().to_owned();
         let org_id = req.org_id.clone();
         let stream_type = req.stream_type.clone();
-        let result = tokio::task::spawn(async move { SearchService::grpc::search(&req).await })
-            .await
-            .map_err(|e| Status::internal(e.to_string()))?;
+        // the search is cancelled if the leader drops the request
+        let (session_id, user_id) = req.job.as_ref().map_or(("", ""), |job| {
+            (job.session_id.as_str(), job.user_id.as_str())
+        });
+        let guard = SearchService::cancel::register(&org_id, user_id, session_id);
+        let cancel = guard.token();
+        let result =
+            tokio::task::spawn(async move { SearchService::grpc::search(&req, &cancel).await })
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+        drop(guard);
         let result = result.map_err(|err| {
             let time = start.elapsed().as_secs_f64();
             metrics::GRPC_RESPONSE_TIME
@@ -52,12 +69,7 @@ impl Search for Searcher {
             metrics::GRPC_INCOMING_REQUESTS
                 .with_label_values(&["/_search", "500", &org_id, "", &stream_type])
                 .inc();
-            let message = if let errors::Error::ErrorCode(code) = err {
-                code.to_json()
-            } else {
-                err.to_string()
-            };
-            Status::internal(message)
+            error_to_status(err)
         })?;
 
         let time = start.elapsed().as_secs_f64();
@@ -70,4 +82,74 @@ impl Search for Searcher {
 
         Ok(Response::new(result))
     }
+
+    #[tracing::instrument(name = "grpc:search_stream:enter", skip_all)]
+    async fn search_stream(
+        &self,
+        req: Request<SearchStreamRequest>,
+    ) -> Result<Response<Self::SearchStreamStream>, Status> {
+        let parent_cx = global::get_text_map_propagator(|prop| {
+            prop.extract(&super::MetadataMap(req.metadata()))
+        });
+        tracing::Span::current().set_parent(parent_cx);
+
+        let req = req.into_inner();
+        let (org_id, stream_type) = match req.request.as_ref() {
+            Some(v) => (v.org_id.clone(), v.stream_type.clone()),
+            None => return Err(Status::invalid_argument("search request is empty")),
+        };
+        let (session_id, user_id) = req
+            .request
+            .as_ref()
+            .and_then(|v| v.job.as_ref())
+            .map_or(("", ""), |job| {
+                (job.session_id.as_str(), job.user_id.as_str())
+            });
+        let guard = SearchService::cancel::register(&org_id, user_id, session_id);
+        let cancel = guard.token();
+        let (tx, rx) = mpsc::channel(1);
+        tokio::task::spawn(
+            async move { SearchService::grpc::search_stream(req, tx, cancel).await },
+        );
+
+        metrics::GRPC_INCOMING_REQUESTS
+            .with_label_values(&["/_search_stream", "200", &org_id, "", &stream_type])
+            .inc();
+
+        // the guard lives as long as the stream, the search is cancelled when
+        // the leader drops the stream
+        let stream = ReceiverStream::new(rx).map(move |result| {
+            let _guard = &guard;
+            result.map_err(error_to_status)
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    #[tracing::instrument(name = "grpc:search_cancel:enter", skip_all)]
+    async fn cancel(
+        &self,
+        req: Request<CancelRequest>,
+    ) -> Result<Response<CancelResponse>, Status> {
+        let req = req.into_inner();
+        // an empty user_id cancels the searches of any user, e.g. for an admin
+        let user_id = Some(req.user_id.as_str()).filter(|v| !v.is_empty());
+        let cancelled = SearchService::cancel::cancel(&req.org_id, &req.session_id, user_id);
+        if cancelled {
+            log::info!(
+                "grpc->search: cancelled session {}/{}",
+                req.org_id,
+                req.session_id
+            );
+        }
+        Ok(Response::new(CancelResponse { cancelled }))
+    }
+}
+
+fn error_to_status(err: errors::Error) -> Status {
+    let message = if let errors::Error::ErrorCode(code) = err {
+        code.to_json()
+    } else {
+        err.to_string()
+    };
+    Status::internal(message)
 }
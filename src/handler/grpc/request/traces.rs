@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use actix_web::http;
 use opentelemetry_proto::tonic::collector::trace::v1::{
     trace_service_server::TraceService, ExportTraceServiceRequest, ExportTraceServiceResponse,
 };
@@ -19,6 +20,7 @@ use tonic::Status;
 use tonic::{codegen::*, Response};
 
 use crate::common::infra::config::CONFIG;
+use crate::handler::grpc::request::quota_exceeded;
 use crate::service::traces::handle_trace_request;
 
 #[derive(Default)]
@@ -45,10 +47,12 @@ impl TraceService for TraceServer {
         }
 
         let resp = handle_trace_request(org_id.unwrap().to_str().unwrap(), 0, in_req, true).await;
-        if resp.is_ok() {
-            return Ok(Response::new(ExportTraceServiceResponse {}));
-        } else {
-            Err(Status::internal(resp.err().unwrap().to_string()))
+        match resp {
+            Ok(resp) if resp.status() == http::StatusCode::TOO_MANY_REQUESTS => {
+                Err(quota_exceeded(&resp))
+            }
+            Ok(_) => Ok(Response::new(ExportTraceServiceResponse {})),
+            Err(e) => Err(Status::internal(e.to_string())),
         }
     }
 }
@@ -14,21 +14,21 @@
 
 use actix_web::{
     dev::ServiceRequest,
-    error::{ErrorForbidden, ErrorUnauthorized},
+    error::{ErrorForbidden, ErrorUnauthorized, InternalError},
     http::header,
-    http::Method,
-    web, Error,
+    http::{Method, StatusCode},
+    web, Error, HttpResponse,
 };
 use actix_web_httpauth::extractors::basic::BasicAuth;
 
 use crate::common::infra::config::CONFIG;
-use crate::common::meta::ingestion::INGESTION_EP;
+use crate::common::meta::ingestion::{HecResponse, INGESTION_EP};
 use crate::common::meta::user::UserRole;
 use crate::common::utils::{
     auth::{get_hash, is_root_user},
-    base64,
+    base64, json,
 };
-use crate::service::{db, users};
+use crate::service::{db, organization, users};
 
 pub async fn validator(
     req: ServiceRequest,
@@ -224,6 +224,90 @@ pub async fn validator_gcp(
     }
 }
 
+/// splunk http event collector clients send `Authorization: Splunk <token>`,
+/// the token is the ingestion passcode of a user of the org
+pub async fn validator_splunk(
+    req: ServiceRequest,
+    _credentials: Option<BasicAuth>,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    let path = req
+        .request()
+        .path()
+        .strip_prefix(format!("{}/splunk/", CONFIG.common.base_uri).as_str())
+        .unwrap_or(req.request().path());
+    let org_id = path.split('/').next().unwrap_or_default().to_string();
+
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Splunk "))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    let Some(token) = token else {
+        let err = hec_error(
+            StatusCode::UNAUTHORIZED,
+            HecResponse::new(HecResponse::TOKEN_REQUIRED, "Token is required"),
+        );
+        return Err((err, req));
+    };
+    match organization::get_passcode_user(&org_id, &token).await {
+        Some(_) => Ok(req),
+        None => {
+            let err = hec_error(
+                StatusCode::FORBIDDEN,
+                HecResponse::new(HecResponse::INVALID_TOKEN, "Invalid token"),
+            );
+            Err((err, req))
+        }
+    }
+}
+
+fn hec_error(status: StatusCode, res: HecResponse) -> Error {
+    InternalError::from_response(res.text.clone(), HttpResponse::build(status).json(res)).into()
+}
+
+/// the datadog agent sends the ingestion passcode in the `DD-API-KEY` header,
+/// older agents in the `api_key` query parameter
+pub async fn validator_datadog(
+    req: ServiceRequest,
+    _credentials: Option<BasicAuth>,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    let path = req
+        .request()
+        .path()
+        .strip_prefix(format!("{}/datadog/", CONFIG.common.base_uri).as_str())
+        .unwrap_or(req.request().path());
+    let org_id = path.split('/').next().unwrap_or_default().to_string();
+
+    let api_key = req
+        .headers()
+        .get("DD-API-KEY")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .or_else(|| {
+            web::Query::<std::collections::HashMap<String, String>>::from_query(req.query_string())
+                .ok()
+                .and_then(|q| q.get("api_key").cloned())
+        })
+        .filter(|v| !v.is_empty());
+    let authenticated = match api_key {
+        Some(api_key) => organization::get_passcode_user(&org_id, &api_key)
+            .await
+            .is_some(),
+        None => false,
+    };
+    if authenticated {
+        Ok(req)
+    } else {
+        let err = InternalError::from_response(
+            "Forbidden",
+            HttpResponse::Forbidden().json(json::json!({"errors": ["Forbidden"]})),
+        );
+        Err((err.into(), req))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,112 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::{get, http, post, put, web, HttpResponse};
+use std::io::Error;
+
+use crate::common::meta::{
+    dead_letter::{DeadLetterReplay, DeadLetterSettings},
+    http::HttpResponse as MetaHttpResponse,
+};
+use crate::service::logs::dead_letter;
+
+/** GetDeadLetterSettings */
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "GetDeadLetterSettings",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description="Success", content_type = "application/json", body = DeadLetterSettings),
+    )
+)]
+#[get("/{org_id}/dead_letter")]
+pub async fn get_settings(org_id: web::Path<String>) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    Ok(HttpResponse::Ok().json(dead_letter::get_settings(&org_id).await))
+}
+
+/** SetDeadLetterSettings */
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "SetDeadLetterSettings",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = DeadLetterSettings, description = "Dead-letter stream settings", content_type = "application/json"),
+    responses(
+        (status = 200, description="Success", content_type = "application/json", body = DeadLetterSettings),
+        (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[put("/{org_id}/dead_letter")]
+pub async fn set_settings(
+    org_id: web::Path<String>,
+    settings: web::Json<DeadLetterSettings>,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    Ok(
+        match dead_letter::set_settings(&org_id, settings.into_inner()).await {
+            Ok(v) => HttpResponse::Ok().json(v),
+            Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                http::StatusCode::BAD_REQUEST.into(),
+                e.to_string(),
+            )),
+        },
+    )
+}
+
+/** ReplayDeadLetters */
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "ReplayDeadLetters",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = DeadLetterReplay, description = "Dead-letter records to replay", content_type = "application/json"),
+    responses(
+        (status = 200, description="Success", content_type = "application/json", body = DeadLetterReplayResponse, example = json!({"replayed": 2,"status": [{"name": "olympics","successful": 2,"failed": 0}]})),
+        (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/dead_letter/_replay")]
+pub async fn replay(
+    org_id: web::Path<String>,
+    req: web::Json<DeadLetterReplay>,
+    thread_id: web::Data<usize>,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    Ok(
+        match dead_letter::replay(&org_id, **thread_id, req.into_inner()).await {
+            Ok(v) => HttpResponse::Ok().json(v),
+            Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
+                http::StatusCode::BAD_REQUEST.into(),
+                e.to_string(),
+            )),
+        },
+    )
+}
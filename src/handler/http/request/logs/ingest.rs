@@ -12,12 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use actix_web::{http, post, web, HttpResponse};
+use actix_web::{get, http, post, web, HttpRequest, HttpResponse};
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceResponse;
+use prost::Message;
 use std::io::Error;
 
 use crate::common::meta::http::HttpResponse as MetaHttpResponse;
+use crate::common::utils::json;
+use crate::handler::http::request::traces::{CONTENT_TYPE_JSON, CONTENT_TYPE_PROTO};
 use crate::{
-    common::meta::ingestion::{GCPIngestionRequest, KinesisFHRequest},
+    common::meta::ingestion::{
+        GCPIngestionRequest, HecAckRequest, HecParams, HecResponse, KinesisFHRequest,
+    },
+    handler::http::request::quota::ingest_error,
     service::logs,
 };
 
@@ -35,6 +42,7 @@ use crate::{
     request_body(content = String, description = "Ingest data (ndjson)", content_type = "application/json"),
     responses(
         (status = 200, description="Success", content_type = "application/json", body = BulkResponse, example = json!({"took":2,"errors":true,"items":[{"index":{"_index":"olympics","_id":1,"status":200,"error":{"type":"Too old data, only last 5 hours data can be ingested. Data discarded.","reason":"Too old data, only last 5 hours data can be ingested. Data discarded.","index_uuid":"1","shard":"1","index":"olympics"},"original_record":{"athlete":"CHASAPIS, Spiridon","city":"BER","country":"USA","discipline":"Swimming","event":"100M Freestyle For Sailors","gender":"Men","medal":"Silver","onemore":1,"season":"summer","sport":"Aquatics","year":1986}}}]})),
+        (status = 429, description="Ingestion quota exceeded", content_type = "application/json", body = HttpResponse),
         (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -47,10 +55,7 @@ pub async fn bulk(
     let org_id = org_id.into_inner();
     Ok(match logs::bulk::ingest(&org_id, body, **thread_id).await {
         Ok(v) => HttpResponse::Ok().json(v),
-        Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
-            http::StatusCode::BAD_REQUEST.into(),
-            e.to_string(),
-        )),
+        Err(e) => ingest_error(e),
     })
 }
 
@@ -69,6 +74,7 @@ pub async fn bulk(
     request_body(content = String, description = "Ingest data (multiple line json)", content_type = "application/json"),
     responses(
         (status = 200, description="Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "olympics","successful": 3,"failed": 0}]})),
+        (status = 429, description="Ingestion quota exceeded", content_type = "application/json", body = HttpResponse),
         (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -82,10 +88,7 @@ pub async fn multi(
     Ok(
         match logs::multi::ingest(&org_id, &stream_name, body, **thread_id).await {
             Ok(v) => HttpResponse::Ok().json(v),
-            Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                e.to_string(),
-            )),
+            Err(e) => ingest_error(e),
         },
     )
 }
@@ -105,6 +108,7 @@ pub async fn multi(
     request_body(content = String, description = "Ingest data (json array)", content_type = "application/json", example = json!([{"Year": 1896, "City": "Athens", "Sport": "Aquatics", "Discipline": "Swimming", "Athlete": "Alfred", "Country": "HUN"},{"Year": 1896, "City": "Athens", "Sport": "Aquatics", "Discipline": "Swimming", "Athlete": "HERSCHMANN", "Country":"CHN"}])),
     responses(
         (status = 200, description="Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "olympics","successful": 3,"failed": 0}]})),
+        (status = 429, description="Ingestion quota exceeded", content_type = "application/json", body = HttpResponse),
         (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -118,14 +122,74 @@ pub async fn json(
     Ok(
         match logs::json::ingest(&org_id, &stream_name, body, **thread_id).await {
             Ok(v) => HttpResponse::Ok().json(v),
-            Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                e.to_string(),
-            )),
+            Err(e) => ingest_error(e),
         },
     )
 }
 
+/** OTLP/HTTP logs ingestion API */
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LogsIngestionOtlp",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream-name" = Option<String>, Header, description = "Stream name, defaults to the stream.name resource attribute or default"),
+    ),
+    request_body(content = String, description = "ExportLogsServiceRequest", content_type = "application/x-protobuf"),
+    responses(
+        (status = 200, description="Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "default","successful": 3,"failed": 0}]})),
+        (status = 429, description="Ingestion quota exceeded", content_type = "application/json", body = HttpResponse),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/v1/logs")]
+pub async fn otlp_logs_write(
+    org_id: web::Path<String>,
+    thread_id: web::Data<usize>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let content_type = req
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let stream_name = req
+        .headers()
+        .get(logs::otlp_http::STREAM_NAME_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty());
+    let is_proto = content_type.starts_with(CONTENT_TYPE_PROTO);
+    let ret = if is_proto {
+        logs::otlp_http::logs_proto(&org_id, **thread_id, stream_name, body).await
+    } else if content_type.starts_with(CONTENT_TYPE_JSON) {
+        logs::otlp_http::logs_json(&org_id, **thread_id, stream_name, body).await
+    } else {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            format!("Unsupported content type: {content_type}"),
+        )));
+    };
+    Ok(match ret {
+        // protobuf clients expect the protobuf response of the export
+        Ok(_) if is_proto => {
+            let res = ExportLogsServiceResponse {};
+            let mut out = Vec::with_capacity(res.encoded_len());
+            res.encode(&mut out).expect("Out of memory");
+            HttpResponse::Ok()
+                .content_type(CONTENT_TYPE_PROTO)
+                .body(out)
+        }
+        Ok(v) => HttpResponse::Ok().json(v),
+        Err(e) => ingest_error(e),
+    })
+}
+
 /** _kinesis_firehose ingestion API*/
 #[utoipa::path(
     context_path = "/api",
@@ -167,10 +231,7 @@ pub async fn handle_kinesis_request(
                     HttpResponse::Ok().json(v)
                 }
             }
-            Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                e.to_string(),
-            )),
+            Err(e) => ingest_error(e),
         },
     )
 }
@@ -193,10 +254,205 @@ pub async fn handle_gcp_request(
                     HttpResponse::Ok().json(v)
                 }
             }
-            Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                e.to_string(),
-            )),
+            Err(e) => ingest_error(e),
+        },
+    )
+}
+
+/** Loki compatible push API */
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Logs",
+    operation_id = "LogsIngestionLoki",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = String, description = "Snappy compressed PushRequest or its json form", content_type = "application/x-protobuf"),
+    responses(
+        (status = 200, description="Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "default","successful": 3,"failed": 0}]})),
+        (status = 429, description="Ingestion quota exceeded", content_type = "application/json", body = HttpResponse),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/loki/api/v1/push")]
+pub async fn loki_push(
+    org_id: web::Path<String>,
+    thread_id: web::Data<usize>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let content_type = req
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let ret = if content_type.starts_with(CONTENT_TYPE_PROTO) {
+        logs::loki::push_proto(&org_id, **thread_id, body).await
+    } else if content_type.starts_with(CONTENT_TYPE_JSON) {
+        logs::loki::push_json(&org_id, **thread_id, body).await
+    } else {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            format!("Unsupported content type: {content_type}"),
+        )));
+    };
+    Ok(match ret {
+        Ok(v) => HttpResponse::Ok().json(v),
+        Err(e) => ingest_error(e),
+    })
+}
+
+/** Splunk HTTP event collector compatible ingestion API */
+#[utoipa::path(
+    context_path = "/splunk",
+    tag = "Logs",
+    operation_id = "LogsIngestionSplunkHecEvent",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("X-Splunk-Request-Channel" = Option<String>, Header, description = "Channel of indexer acknowledgement"),
+        ("index" = Option<String>, Query, description = "Stream name of the events without index"),
+        ("sourcetype" = Option<String>, Query, description = "Sourcetype of the events without sourcetype"),
+        ("host" = Option<String>, Query, description = "Host of the events without host"),
+        ("source" = Option<String>, Query, description = "Source of the events without source"),
+    ),
+    request_body(content = String, description = "Ingest data (concatenated json events)", content_type = "application/json"),
+    responses(
+        (status = 200, description="Success", content_type = "application/json", body = HecResponse, example = json!({"text": "Success","code": 0})),
+        (status = 400, description="Failure", content_type = "application/json", body = HecResponse, example = json!({"text": "Event field is required","code": 12,"invalid-event-number": 1})),
+    )
+)]
+#[post("/{org_id}/services/collector/event")]
+pub async fn splunk_hec_event(
+    org_id: web::Path<String>,
+    thread_id: web::Data<usize>,
+    params: web::Query<HecParams>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let params = hec_params(params.into_inner(), &req);
+    let res = logs::splunk::event(&org_id, **thread_id, &params, body).await;
+    Ok(hec_response(res))
+}
+
+/** Splunk HTTP event collector compatible raw ingestion API */
+#[utoipa::path(
+    context_path = "/splunk",
+    tag = "Logs",
+    operation_id = "LogsIngestionSplunkHecRaw",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("X-Splunk-Request-Channel" = Option<String>, Header, description = "Channel of indexer acknowledgement"),
+        ("index" = Option<String>, Query, description = "Stream name of the events without index"),
+        ("sourcetype" = Option<String>, Query, description = "Sourcetype of the events without sourcetype"),
+        ("host" = Option<String>, Query, description = "Host of the events without host"),
+        ("source" = Option<String>, Query, description = "Source of the events without source"),
+    ),
+    request_body(content = String, description = "Ingest data (one event per line)", content_type = "text/plain"),
+    responses(
+        (status = 200, description="Success", content_type = "application/json", body = HecResponse, example = json!({"text": "Success","code": 0})),
+        (status = 400, description="Failure", content_type = "application/json", body = HecResponse, example = json!({"text": "No data","code": 5})),
+    )
+)]
+#[post("/{org_id}/services/collector/raw")]
+pub async fn splunk_hec_raw(
+    org_id: web::Path<String>,
+    thread_id: web::Data<usize>,
+    params: web::Query<HecParams>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let params = hec_params(params.into_inner(), &req);
+    let res = logs::splunk::raw(&org_id, **thread_id, &params, body).await;
+    Ok(hec_response(res))
+}
+
+#[post("/{org_id}/services/collector/ack")]
+pub async fn splunk_hec_ack(
+    org_id: web::Path<String>,
+    params: web::Query<HecParams>,
+    req: HttpRequest,
+    body: web::Json<HecAckRequest>,
+) -> Result<HttpResponse, Error> {
+    let params = hec_params(params.into_inner(), &req);
+    let Some(channel) = params.channel else {
+        return Ok(hec_response(HecResponse::new(
+            HecResponse::CHANNEL_MISSING,
+            "Data channel is missing",
+        )));
+    };
+    Ok(HttpResponse::Ok().json(logs::splunk::ack(&org_id, &channel, &body).await))
+}
+
+#[get("/{org_id}/services/collector/health")]
+pub async fn splunk_hec_health() -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(HecResponse::new(HecResponse::HEALTHY, "HEC is healthy")))
+}
+
+/// the channel is sent in a header or in the query
+fn hec_params(mut params: HecParams, req: &HttpRequest) -> HecParams {
+    if params.channel.is_none() {
+        params.channel = req
+            .headers()
+            .get("X-Splunk-Request-Channel")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+    }
+    params
+}
+
+fn hec_response(res: HecResponse) -> HttpResponse {
+    match res.code {
+        HecResponse::SUCCESS => HttpResponse::Ok().json(res),
+        HecResponse::SERVER_ERROR => HttpResponse::InternalServerError().json(res),
+        HecResponse::SERVER_BUSY => HttpResponse::TooManyRequests()
+            .insert_header((
+                http::header::RETRY_AFTER,
+                res.retry_after.unwrap_or(1).to_string(),
+            ))
+            .json(res),
+        _ => HttpResponse::BadRequest().json(res),
+    }
+}
+
+/** Datadog logs intake compatible ingestion API */
+#[utoipa::path(
+    context_path = "/datadog",
+    tag = "Logs",
+    operation_id = "LogsIngestionDatadog",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("DD-API-KEY" = String, Header, description = "Ingestion passcode of the organization"),
+    ),
+    request_body(content = String, description = "Ingest data (json array)", content_type = "application/json", example = json!([{"message":"GET / 200","ddsource":"nginx","service":"web","hostname":"web-1","ddtags":"env:prod"}])),
+    responses(
+        (status = 202, description="Success", content_type = "application/json", example = json!({})),
+        (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/api/v2/logs")]
+pub async fn datadog_logs(
+    org_id: web::Path<String>,
+    thread_id: web::Data<usize>,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    Ok(
+        match logs::datadog::ingest(&org_id, **thread_id, body).await {
+            Ok(_) => HttpResponse::Accepted().json(json::json!({})),
+            Err(e) => ingest_error(e),
         },
     )
 }
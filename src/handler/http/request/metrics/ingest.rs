@@ -12,10 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use actix_web::{http, post, web, HttpResponse};
-use std::io::Error;
+use actix_web::{get, http, post, web, HttpRequest, HttpResponse};
+use std::{collections::HashMap, io::Error};
 
 use crate::common::meta::http::HttpResponse as MetaHttpResponse;
+use crate::common::utils::json;
+use crate::handler::http::request::quota::ingest_error;
+use crate::handler::http::request::traces::{CONTENT_TYPE_JSON, CONTENT_TYPE_PROTO};
 use crate::service::metrics;
 
 /** _json ingestion API */
@@ -32,6 +35,7 @@ use crate::service::metrics;
     request_body(content = String, description = "Ingest data (json array)", content_type = "application/json", example = json!([{"__name__":"metrics stream name","__type__":"counter / gauge / histogram / summary","label_name1":"label_value1","label_name2":"label_value2", "_timestamp":1687175143,"value":1.2}])),
     responses(
         (status = 200, description="Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200,"status": [{"name": "up","successful": 3,"failed": 0}]})),
+        (status = 429, description="Ingestion quota exceeded", content_type = "application/json", body = HttpResponse),
         (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -45,10 +49,171 @@ pub async fn json(
     Ok(
         match metrics::json::ingest(&org_id, body, **thread_id).await {
             Ok(v) => HttpResponse::Ok().json(v),
-            Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                http::StatusCode::BAD_REQUEST.into(),
-                e.to_string(),
-            )),
+            Err(e) => ingest_error(e),
         },
     )
 }
+
+/** OTLP/HTTP metrics ingestion API */
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Metrics",
+    operation_id = "MetricsIngestionOtlp",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = String, description = "ExportMetricsServiceRequest", content_type = "application/x-protobuf"),
+    responses(
+        (status = 200, description="Success", content_type = "application/x-protobuf", body = String),
+        (status = 429, description="Ingestion quota exceeded", content_type = "application/json", body = HttpResponse),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/v1/metrics")]
+pub async fn otlp_metrics_write(
+    org_id: web::Path<String>,
+    thread_id: web::Data<usize>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let content_type = req
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let ret = if content_type.starts_with(CONTENT_TYPE_PROTO) {
+        metrics::otlp_http::metrics_proto(&org_id, **thread_id, body).await
+    } else if content_type.starts_with(CONTENT_TYPE_JSON) {
+        metrics::otlp_http::metrics_json(&org_id, **thread_id, body).await
+    } else {
+        return Ok(HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            format!("Unsupported content type: {content_type}"),
+        )));
+    };
+    Ok(match ret {
+        Ok(v) => v,
+        Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            e.to_string(),
+        )),
+    })
+}
+
+/** InfluxDB line protocol ingestion API */
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Metrics",
+    operation_id = "MetricsIngestionInfluxDb",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("precision" = Option<String>, Query, description = "Precision of the timestamps: ns, us, ms, s, m or h, defaults to ns"),
+    ),
+    request_body(content = String, description = "Ingest data (line protocol)", content_type = "text/plain", example = "cpu,host=server1 usage_idle=97.5 1465839830100400200"),
+    responses(
+        (status = 204, description="Success"),
+        (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/influxdb/write")]
+pub async fn influxdb_write(
+    org_id: web::Path<String>,
+    query: web::Query<HashMap<String, String>>,
+    body: web::Bytes,
+    thread_id: web::Data<usize>,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let precision = query.get("precision").map(|v| v.as_str());
+    Ok(
+        match metrics::influx::write(&org_id, precision, body, **thread_id).await {
+            Ok(_) => HttpResponse::NoContent().finish(),
+            Err(e) => ingest_error(e),
+        },
+    )
+}
+
+/** Datadog series v1 compatible ingestion API */
+#[utoipa::path(
+    context_path = "/datadog",
+    tag = "Metrics",
+    operation_id = "MetricsIngestionDatadogV1",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("DD-API-KEY" = String, Header, description = "Ingestion passcode of the organization"),
+    ),
+    request_body(content = String, description = "Ingest data (json)", content_type = "application/json", example = json!({"series":[{"metric":"system.load.1","points":[[1700000000,0.5]],"type":"gauge","host":"web-1","tags":["env:prod"]}]})),
+    responses(
+        (status = 202, description="Success", content_type = "application/json", example = json!({"status":"ok"})),
+        (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/api/v1/series")]
+pub async fn datadog_series_v1(
+    org_id: web::Path<String>,
+    body: web::Bytes,
+    thread_id: web::Data<usize>,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    Ok(
+        match metrics::datadog::series_v1(&org_id, body, **thread_id).await {
+            Ok(_) => HttpResponse::Accepted().json(json::json!({"status": "ok"})),
+            Err(e) => ingest_error(e),
+        },
+    )
+}
+
+/** Datadog series v2 compatible ingestion API */
+#[utoipa::path(
+    context_path = "/datadog",
+    tag = "Metrics",
+    operation_id = "MetricsIngestionDatadogV2",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("DD-API-KEY" = String, Header, description = "Ingestion passcode of the organization"),
+    ),
+    request_body(content = String, description = "Ingest data (MetricPayload protobuf or json)", content_type = "application/x-protobuf"),
+    responses(
+        (status = 202, description="Success", content_type = "application/json", example = json!({"errors":[]})),
+        (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/api/v2/series")]
+pub async fn datadog_series_v2(
+    org_id: web::Path<String>,
+    req: HttpRequest,
+    body: web::Bytes,
+    thread_id: web::Data<usize>,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let is_proto = req
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .starts_with(CONTENT_TYPE_PROTO);
+    Ok(
+        match metrics::datadog::series_v2(&org_id, body, is_proto, **thread_id).await {
+            Ok(_) => HttpResponse::Accepted().json(json::json!({"errors": []})),
+            Err(e) => ingest_error(e),
+        },
+    )
+}
+
+/// the agent checks its api key at startup
+#[get("/{org_id}/api/v1/validate")]
+pub async fn datadog_validate() -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(json::json!({"valid": true})))
+}
@@ -15,15 +15,20 @@
 pub mod alerts;
 pub mod dashboards;
 pub mod enrichment_table;
+pub mod fluent;
 pub mod functions;
 pub mod kv;
 pub mod logs;
 pub mod metrics;
 pub mod organization;
 pub mod prom;
+pub mod quota;
+pub mod redaction;
+pub mod sampling;
 pub mod search;
 pub mod status;
 pub mod stream;
+pub mod stream_routes;
 pub mod syslog;
 pub mod traces;
 pub mod users;
@@ -20,6 +20,7 @@ use crate::{
     common::infra::errors,
     common::meta::{self, http::HttpResponse as MetaHttpResponse},
     common::utils::time::{parse_milliseconds, parse_str_to_timestamp_micros},
+    handler::http::request::quota::ingest_error,
     service::{metrics, promql},
 };
 
@@ -37,6 +38,7 @@ use crate::{
     request_body(content = String, description = "prometheus WriteRequest", content_type = "application/x-protobuf"),
     responses(
         (status = 200, description="Success", content_type = "application/json", body = IngestionResponse, example = json!({"code": 200})),
+        (status = 429, description="Ingestion quota exceeded", content_type = "application/json", body = HttpResponse),
         (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -53,10 +55,7 @@ pub async fn remote_write(
         Ok(
             match metrics::prom::remote_write(&org_id, **thread_id, body).await {
                 Ok(_) => HttpResponse::Ok().into(),
-                Err(e) => HttpResponse::BadRequest().json(MetaHttpResponse::error(
-                    http::StatusCode::BAD_REQUEST.into(),
-                    e.to_string(),
-                )),
+                Err(e) => ingest_error(e),
             },
         )
     } else {
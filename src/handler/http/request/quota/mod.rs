@@ -0,0 +1,158 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use actix_web::{delete, get, http, put, web, HttpResponse, Responder};
+use std::io::Error;
+
+use crate::{
+    common::meta::{http::HttpResponse as MetaHttpResponse, quota::IngestionQuota},
+    service::ingestion::quota::{self, QuotaExceeded},
+};
+
+/// a request rejected by an ingestion quota is answered with a 429 and the
+/// seconds to wait before retrying
+pub(crate) fn ingest_error(e: anyhow::Error) -> HttpResponse {
+    match e.downcast_ref::<QuotaExceeded>() {
+        Some(quota) => quota.to_response(),
+        None => HttpResponse::BadRequest().json(MetaHttpResponse::error(
+            http::StatusCode::BAD_REQUEST.into(),
+            e.to_string(),
+        )),
+    }
+}
+
+/// GetIngestionQuotas
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Ingestion Quotas",
+    operation_id = "GetIngestionQuotas",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = OrgIngestionQuotas),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal Server Error", body = HttpResponse),
+    ),
+)]
+#[get("/{org_id}/ingestion_quota")]
+async fn get_quotas(path: web::Path<String>) -> impl Responder {
+    let org_id = path.into_inner();
+    quota::get_quotas(&org_id).await
+}
+
+/// SetOrgIngestionQuota
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Ingestion Quotas",
+    operation_id = "SetOrgIngestionQuota",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(
+        content = IngestionQuota,
+        description = "Ingestion quota of the organization, 0 is no limit",
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Quota saved", body = IngestionQuota),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal Server Error", body = HttpResponse),
+    ),
+)]
+#[put("/{org_id}/ingestion_quota")]
+pub async fn set_org_quota(
+    path: web::Path<String>,
+    details: web::Json<IngestionQuota>,
+) -> Result<HttpResponse, Error> {
+    let org_id = path.into_inner();
+    quota::set_quota(&org_id, None, details.into_inner()).await
+}
+
+/// DeleteOrgIngestionQuota
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Ingestion Quotas",
+    operation_id = "DeleteOrgIngestionQuota",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Quota deleted", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Quota not found", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/ingestion_quota")]
+async fn delete_org_quota(path: web::Path<String>) -> impl Responder {
+    let org_id = path.into_inner();
+    quota::delete_quota(&org_id, None).await
+}
+
+/// SetStreamIngestionQuota
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Ingestion Quotas",
+    operation_id = "SetStreamIngestionQuota",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(
+        content = IngestionQuota,
+        description = "Ingestion quota of the stream, 0 is no limit",
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Quota saved", body = IngestionQuota),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal Server Error", body = HttpResponse),
+    ),
+)]
+#[put("/{org_id}/{stream_name}/ingestion_quota")]
+pub async fn set_stream_quota(
+    path: web::Path<(String, String)>,
+    details: web::Json<IngestionQuota>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    quota::set_quota(&org_id, Some(&stream_name), details.into_inner()).await
+}
+
+/// DeleteStreamIngestionQuota
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Ingestion Quotas",
+    operation_id = "DeleteStreamIngestionQuota",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Quota deleted", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Quota not found", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/{stream_name}/ingestion_quota")]
+async fn delete_stream_quota(path: web::Path<(String, String)>) -> impl Responder {
+    let (org_id, stream_name) = path.into_inner();
+    quota::delete_quota(&org_id, Some(&stream_name)).await
+}
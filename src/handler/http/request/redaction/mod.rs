@@ -0,0 +1,134 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use actix_web::{delete, get, http, put, web, HttpRequest, HttpResponse};
+use ahash::AHashMap as HashMap;
+use std::io::Error;
+
+use crate::common::meta::{self, redaction::StreamRedaction, StreamType};
+use crate::common::utils::http::get_stream_type_from_request;
+use crate::service::ingestion::redaction;
+
+/// the stream type of the `type` query, logs by default
+fn stream_type(req: &HttpRequest) -> Result<StreamType, HttpResponse> {
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).unwrap();
+    match get_stream_type_from_request(&query) {
+        Ok(v) => Ok(v.unwrap_or(StreamType::Logs)),
+        Err(e) => Err(
+            HttpResponse::BadRequest().json(meta::http::HttpResponse::error(
+                http::StatusCode::BAD_REQUEST.into(),
+                e.to_string(),
+            )),
+        ),
+    }
+}
+
+/// GetStreamRedaction
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Redaction",
+    operation_id = "GetStreamRedaction",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type, logs by default"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = StreamRedaction),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid stream type", body = HttpResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal Server Error", body = HttpResponse),
+    ),
+)]
+#[get("/{org_id}/{stream_name}/redaction")]
+async fn get_settings(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let stream_type = match stream_type(&req) {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    redaction::get_settings(&org_id, stream_type, &stream_name).await
+}
+
+/// SetStreamRedaction
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Redaction",
+    operation_id = "SetStreamRedaction",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type, logs by default"),
+    ),
+    request_body(
+        content = StreamRedaction,
+        description = "Pii detectors and how the detected values are redacted",
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Redaction saved", body = StreamRedaction),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid stream type", body = HttpResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal Server Error", body = HttpResponse),
+    ),
+)]
+#[put("/{org_id}/{stream_name}/redaction")]
+async fn set_settings(
+    path: web::Path<(String, String)>,
+    details: web::Json<StreamRedaction>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let stream_type = match stream_type(&req) {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    redaction::set_settings(&org_id, stream_type, &stream_name, details.into_inner()).await
+}
+
+/// DeleteStreamRedaction
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Redaction",
+    operation_id = "DeleteStreamRedaction",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("type" = Option<String>, Query, description = "Stream type, logs by default"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Redaction deleted", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Redaction not found", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/{stream_name}/redaction")]
+async fn delete_settings(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    let stream_type = match stream_type(&req) {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    redaction::delete_settings(&org_id, stream_type, &stream_name).await
+}
@@ -0,0 +1,128 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use std::io::Error;
+
+use crate::{common::meta::sampling::SamplingRule, service::sampling};
+
+/** CreateSamplingRule */
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sampling Rules",
+    operation_id = "CreateSamplingRule",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    request_body(
+        content = SamplingRule,
+        description = "SamplingRule details",
+    ),
+    responses(
+        (status = StatusCode::CREATED, description = "Sampling rule created", body = SamplingRule),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid sampling rule", body = HttpResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal Server Error", body = HttpResponse),
+    ),
+)]
+#[post("/{org_id}/{stream_name}/sampling_rules")]
+pub async fn create_rule(
+    path: web::Path<(String, String)>,
+    details: web::Json<SamplingRule>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    sampling::create_rule(&org_id, &stream_name, details.into_inner()).await
+}
+
+/// UpdateSamplingRule
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sampling Rules",
+    operation_id = "UpdateSamplingRule",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("name" = String, Path, description = "Sampling rule name"),
+    ),
+    request_body(
+        content = SamplingRule,
+        description = "SamplingRule details",
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Sampling rule updated", body = SamplingRule),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid sampling rule", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Sampling rule not found", body = HttpResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Failed to update the sampling rule", body = HttpResponse),
+    ),
+)]
+#[put("/{org_id}/{stream_name}/sampling_rules/{name}")]
+async fn update_rule(
+    path: web::Path<(String, String, String)>,
+    details: web::Json<SamplingRule>,
+) -> impl Responder {
+    let (org_id, stream_name, name) = path.into_inner();
+    sampling::update_rule(&org_id, &stream_name, &name, details.into_inner()).await
+}
+
+/// ListSamplingRules
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sampling Rules",
+    operation_id = "ListSamplingRules",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = SamplingRules),
+    ),
+)]
+#[get("/{org_id}/{stream_name}/sampling_rules")]
+async fn list_rules(path: web::Path<(String, String)>) -> impl Responder {
+    let (org_id, stream_name) = path.into_inner();
+    sampling::list_rules(&org_id, &stream_name).await
+}
+
+/// DeleteSamplingRule
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Sampling Rules",
+    operation_id = "DeleteSamplingRule",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Stream name"),
+        ("name" = String, Path, description = "Sampling rule name"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Sampling rule deleted", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Sampling rule not found", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/{stream_name}/sampling_rules/{name}")]
+async fn delete_rule(path: web::Path<(String, String, String)>) -> impl Responder {
+    let (org_id, stream_name, name) = path.into_inner();
+    sampling::delete_rule(&org_id, &stream_name, &name).await
+}
@@ -0,0 +1,254 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use ahash::AHashMap;
+use std::io::Error;
+
+use super::{bad_request, search_error_response, search_error_status};
+use crate::common::meta::{self, http::HttpResponse as MetaHttpResponse, StreamType};
+use crate::common::utils::{base64, functions, http::get_stream_type_from_request, json};
+use crate::service::search::jobs;
+
+/** SubmitSearchJob*/
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SubmitSearchJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    request_body(content = SearchRequest, description = "Search query", content_type = "application/json"),
+    responses(
+        (status = 201, description="Job submitted", content_type = "application/json", body = SearchJob),
+        (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/_search_jobs")]
+pub async fn submit(
+    org_id: web::Path<String>,
+    in_req: HttpRequest,
+    credentials: BasicAuth,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let query = web::Query::<AHashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(bad_request(e)),
+    };
+
+    let mut req: meta::search::Request = match json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return Ok(bad_request(e)),
+    };
+    if let Err(e) = req.decode() {
+        return Ok(bad_request(e));
+    }
+
+    req.query.query_fn = req.query.query_fn.and_then(|v| base64::decode(&v).ok());
+
+    for fn_name in functions::get_all_transform_keys(&org_id).await {
+        if req.query.sql.contains(&fn_name) {
+            req.query.uses_zo_fn = true;
+            break;
+        }
+    }
+
+    match jobs::submit(&org_id, stream_type, credentials.user_id(), req).await {
+        Ok(job) => Ok(HttpResponse::Created().json(job)),
+        Err(err) => {
+            log::error!("search job submit error: {:?}", err);
+            Ok(search_error_response(search_error_status(&err), err))
+        }
+    }
+}
+
+/** ListSearchJobs*/
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "ListSearchJobs",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+    ),
+    responses(
+        (status = 200, description="Success", content_type = "application/json", body = SearchJobs),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/_search_jobs")]
+pub async fn list(
+    org_id: web::Path<String>,
+    credentials: BasicAuth,
+) -> Result<HttpResponse, Error> {
+    match jobs::list(&org_id.into_inner(), credentials.user_id()).await {
+        Ok(list) => Ok(HttpResponse::Ok().json(meta::search::JobList { list })),
+        Err(err) => Ok(search_error_response(search_error_status(&err), err)),
+    }
+}
+
+/** GetSearchJob*/
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "GetSearchJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("job_id" = String, Path, description = "Job id"),
+    ),
+    responses(
+        (status = 200, description="Status and progress of the job", content_type = "application/json", body = SearchJob),
+        (status = 404, description="Job not found", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/_search_jobs/{job_id}")]
+pub async fn get(
+    path: web::Path<(String, String)>,
+    credentials: BasicAuth,
+) -> Result<HttpResponse, Error> {
+    let (org_id, job_id) = path.into_inner();
+    match jobs::get(&org_id, credentials.user_id(), &job_id).await {
+        Some(job) => Ok(HttpResponse::Ok().json(job)),
+        None => Ok(not_found()),
+    }
+}
+
+/** GetSearchJobResult*/
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "GetSearchJobResult",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("job_id" = String, Path, description = "Job id"),
+        ("from" = Option<usize>, Query, description = "Offset of the first hit"),
+        ("size" = Option<usize>, Query, description = "Number of hits, default 100"),
+    ),
+    responses(
+        (status = 200, description="A page of the hits found so far", content_type = "application/json", body = SearchResponse),
+        (status = 404, description="Job not found", content_type = "application/json", body = HttpResponse),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[get("/{org_id}/_search_jobs/{job_id}/result")]
+pub async fn result(
+    path: web::Path<(String, String)>,
+    in_req: HttpRequest,
+    credentials: BasicAuth,
+) -> Result<HttpResponse, Error> {
+    let (org_id, job_id) = path.into_inner();
+    let query = web::Query::<AHashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let from = query
+        .get("from")
+        .map_or(0, |v| v.parse::<usize>().unwrap_or(0));
+    let size = query
+        .get("size")
+        .map_or(100, |v| v.parse::<usize>().unwrap_or(100));
+    match jobs::results(&org_id, credentials.user_id(), &job_id, from, size).await {
+        Ok(Some(resp)) => Ok(HttpResponse::Ok().json(resp)),
+        Ok(None) => Ok(not_found()),
+        Err(err) => {
+            log::error!("search job result error: {:?}", err);
+            Ok(search_error_response(search_error_status(&err), err))
+        }
+    }
+}
+
+/** CancelSearchJob*/
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "CancelSearchJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("job_id" = String, Path, description = "Job id"),
+    ),
+    responses(
+        (status = 200, description="Job cancelled", content_type = "application/json", body = SearchJob),
+        (status = 404, description="Job not found", content_type = "application/json", body = HttpResponse),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/_search_jobs/{job_id}/cancel")]
+pub async fn cancel(
+    path: web::Path<(String, String)>,
+    credentials: BasicAuth,
+) -> Result<HttpResponse, Error> {
+    let (org_id, job_id) = path.into_inner();
+    match jobs::cancel(&org_id, credentials.user_id(), &job_id).await {
+        Ok(Some(job)) => Ok(HttpResponse::Ok().json(job)),
+        Ok(None) => Ok(not_found()),
+        Err(err) => Ok(search_error_response(search_error_status(&err), err)),
+    }
+}
+
+/** DeleteSearchJob*/
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "DeleteSearchJob",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("job_id" = String, Path, description = "Job id"),
+    ),
+    responses(
+        (status = 200, description="Job cancelled and deleted with its results", content_type = "application/json", body = HttpResponse),
+        (status = 404, description="Job not found", content_type = "application/json", body = HttpResponse),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/_search_jobs/{job_id}")]
+pub async fn delete(
+    path: web::Path<(String, String)>,
+    credentials: BasicAuth,
+) -> Result<HttpResponse, Error> {
+    let (org_id, job_id) = path.into_inner();
+    match jobs::delete(&org_id, credentials.user_id(), &job_id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+            StatusCode::OK.into(),
+            "search job deleted".to_string(),
+        ))),
+        Ok(false) => Ok(not_found()),
+        Err(err) => Ok(search_error_response(search_error_status(&err), err)),
+    }
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(MetaHttpResponse::error(
+        StatusCode::NOT_FOUND.into(),
+        "search job not found".to_string(),
+    ))
+}
@@ -12,24 +12,33 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use actix_web::http::StatusCode;
-use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::basic::BasicAuth;
 use ahash::AHashMap;
 use chrono::Duration;
+use futures::StreamExt;
 use std::collections::HashMap;
 use std::io::Error;
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
 
 use crate::common::infra::config::CONFIG;
 use crate::common::infra::{errors, metrics};
 use crate::common::meta::http::HttpResponse as MetaHttpResponse;
 use crate::common::meta::usage::{RequestStats, UsageType};
-use crate::common::meta::{self, StreamType};
+use crate::common::meta::{self, user::UserRole, StreamType};
+use crate::common::utils::auth::is_root_user;
 use crate::common::utils::base64;
 use crate::common::utils::functions;
 use crate::common::utils::http::get_stream_type_from_request;
 use crate::common::utils::json;
 use crate::service::search as SearchService;
+use crate::service::search::queue::Priority;
 use crate::service::usage::report_request_usage_stats;
+use crate::service::users;
+
+pub mod job;
 
 /** SearchStreamData*/
 #[utoipa::path(
@@ -41,6 +50,8 @@ use crate::service::usage::report_request_usage_stats;
     ),
     params(
         ("org_id" = String, Path, description = "Organization name"),
+        ("priority" = Option<String>, Query, description = "Search priority: interactive or batch"),
+        ("session_id" = Option<String>, Query, description = "Session id (uuid) used to cancel the search, generated when missing"),
     ),
     request_body(content = SearchRequest, description = "Search query", content_type = "application/json", example = json!({
         "query": {
@@ -93,6 +104,7 @@ use crate::service::usage::report_request_usage_stats;
             "scan_size": 28943
         })),
         (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+        (status = 429, description="Too many searches", content_type = "application/json", body = HttpResponse),
         (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -100,6 +112,7 @@ use crate::service::usage::report_request_usage_stats;
 pub async fn search(
     org_id: web::Path<String>,
     in_req: HttpRequest,
+    credentials: BasicAuth,
     body: web::Bytes,
 ) -> Result<HttpResponse, Error> {
     let start = std::time::Instant::now();
@@ -128,13 +141,27 @@ pub async fn search(
         }
     }
 
-    // get a local search queue lock
-    let locker = SearchService::QUEUE_LOCKER.clone();
-    let _locker = locker.lock().await;
-    let took_wait = start.elapsed().as_millis() as usize;
+    let priority = match search_priority(&query) {
+        Ok(v) => v,
+        Err(e) => return Ok(bad_request(e)),
+    };
+
+    let session_id = match search_session_id(&query) {
+        Ok(v) => v,
+        Err(e) => return Ok(bad_request(e)),
+    };
 
     // do search
-    match SearchService::search(&org_id, stream_type, &req).await {
+    match SearchService::search_with_session(
+        &org_id,
+        stream_type,
+        &session_id,
+        credentials.user_id(),
+        priority,
+        &req,
+    )
+    .await
+    {
         Ok(mut res) => {
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
@@ -155,6 +182,7 @@ pub async fn search(
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
+            let took_wait = res.took_detail.as_ref().map_or(0, |v| v.cluster_wait_queue);
             res.set_local_took(start.elapsed().as_millis() as usize, took_wait);
 
             let req_stats = RequestStats {
@@ -177,11 +205,12 @@ pub async fn search(
             Ok(HttpResponse::Ok().json(res))
         }
         Err(err) => {
+            let status = search_error_status(&err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_search",
-                    "500",
+                    status.as_str(),
                     &org_id,
                     "",
                     stream_type.to_string().as_str(),
@@ -190,25 +219,169 @@ pub async fn search(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_search",
-                    "500",
+                    status.as_str(),
                     &org_id,
                     "",
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search error: {:?}", err);
-            Ok(match err {
-                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError()
-                    .json(meta::http::HttpResponse::error_code(code)),
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            })
+            Ok(search_error_response(status, err))
         }
     }
 }
 
+/** SearchStream*/
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "SearchSQLStream",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("priority" = Option<String>, Query, description = "Search priority: interactive or batch"),
+    ),
+    request_body(content = SearchRequest, description = "Search query", content_type = "application/json"),
+    responses(
+        (status = 200, description="One event per line, slices newest first, then a done event", content_type = "application/x-ndjson", body = String, example = json!({
+            "event": "slice",
+            "start_time": 1675184400000000i64,
+            "end_time": 1675185660872049i64,
+            "hits": [],
+            "aggs": {},
+            "total": 120,
+            "scan_files": 2,
+            "scan_size": 3
+        })),
+        (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+        (status = 429, description="Too many searches", content_type = "application/json", body = HttpResponse),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[post("/{org_id}/_search_stream")]
+pub async fn search_stream(
+    org_id: web::Path<String>,
+    in_req: HttpRequest,
+    credentials: BasicAuth,
+    body: web::Bytes,
+) -> Result<HttpResponse, Error> {
+    let org_id = org_id.into_inner();
+    let query = web::Query::<AHashMap<String, String>>::from_query(in_req.query_string()).unwrap();
+    let stream_type = match get_stream_type_from_request(&query) {
+        Ok(v) => v.unwrap_or(StreamType::Logs),
+        Err(e) => return Ok(bad_request(e)),
+    };
+
+    let mut req: meta::search::Request = match json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => return Ok(bad_request(e)),
+    };
+    if let Err(e) = req.decode() {
+        return Ok(bad_request(e));
+    }
+
+    req.query.query_fn = req.query.query_fn.and_then(|v| base64::decode(&v).ok());
+
+    for fn_name in functions::get_all_transform_keys(&org_id).await {
+        if req.query.sql.contains(&fn_name) {
+            req.query.uses_zo_fn = true;
+            break;
+        }
+    }
+
+    let priority = match search_priority(&query) {
+        Ok(v) => v,
+        Err(e) => return Ok(bad_request(e)),
+    };
+
+    // server sent events for browsers, newline delimited json for the rest
+    let sse = in_req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |v| v.contains("text/event-stream"));
+
+    let rx = match SearchService::streaming::search(
+        &org_id,
+        stream_type,
+        credentials.user_id(),
+        priority,
+        &req,
+    )
+    .await
+    {
+        Ok(rx) => rx,
+        Err(err) => {
+            log::error!("search stream error: {:?}", err);
+            return Ok(search_error_response(search_error_status(&err), err));
+        }
+    };
+
+    let events = ReceiverStream::new(rx).map(move |event| {
+        let data = json::to_string(&event).unwrap_or_default();
+        let data = if sse {
+            format!("data: {data}\n\n")
+        } else {
+            format!("{data}\n")
+        };
+        Ok::<_, Error>(web::Bytes::from(data))
+    });
+    Ok(HttpResponse::Ok()
+        .content_type(if sse {
+            "text/event-stream"
+        } else {
+            "application/x-ndjson"
+        })
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events))
+}
+
+/** CancelSearch*/
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Search",
+    operation_id = "CancelSearch",
+    security(
+        ("Authorization"= [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("session_id" = String, Path, description = "Session id of the search, sent by the start event of a streaming search"),
+    ),
+    responses(
+        (status = 200, description="Search cancelled on all the nodes", content_type = "application/json", body = HttpResponse),
+        (status = 404, description="Search not running, or not started by the user unless an admin", content_type = "application/json", body = HttpResponse),
+        (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
+    )
+)]
+#[delete("/{org_id}/_search/{session_id}")]
+pub async fn cancel(
+    path: web::Path<(String, String)>,
+    credentials: BasicAuth,
+) -> Result<HttpResponse, Error> {
+    let (org_id, session_id) = path.into_inner();
+    // the admins cancel the searches of any user, the others their own
+    let user_id = credentials.user_id();
+    let is_admin = is_root_user(user_id)
+        || users::get_user(Some(&org_id), user_id)
+            .await
+            .map_or(false, |user| user.role == UserRole::Admin);
+    let owner = if is_admin { None } else { Some(user_id) };
+    match SearchService::cancel(&org_id, &session_id, owner).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(MetaHttpResponse::message(
+            StatusCode::OK.into(),
+            "search cancelled".to_string(),
+        ))),
+        Ok(false) => Ok(HttpResponse::NotFound().json(MetaHttpResponse::error(
+            StatusCode::NOT_FOUND.into(),
+            "search not running".to_string(),
+        ))),
+        Err(err) => Ok(search_error_response(search_error_status(&err), err)),
+    }
+}
+
 /** SearchAround*/
 #[utoipa::path(
     context_path = "/api",
@@ -249,6 +422,7 @@ pub async fn search(
             "size": 10,
             "scan_size": 28943
         })),
+        (status = 429, description="Too many searches", content_type = "application/json", body = HttpResponse),
         (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -256,6 +430,7 @@ pub async fn search(
 pub async fn around(
     path: web::Path<(String, String)>,
     in_req: HttpRequest,
+    credentials: BasicAuth,
 ) -> Result<HttpResponse, Error> {
     let start = std::time::Instant::now();
     let mut uses_fn = false;
@@ -295,9 +470,6 @@ pub async fn around(
         .get("size")
         .map_or(10, |v| v.parse::<usize>().unwrap_or(0));
 
-    // get a local search queue lock
-    let locker = SearchService::QUEUE_LOCKER.clone();
-    let _locker = locker.lock().await;
     let query_context = if uses_fn {
         Some(around_sql.clone())
     } else {
@@ -323,14 +495,23 @@ pub async fn around(
         aggs: HashMap::new(),
         encoding: meta::search::RequestEncoding::Empty,
     };
-    let resp_forward = match SearchService::search(&org_id, stream_type, &req).await {
+    let resp_forward = match SearchService::search(
+        &org_id,
+        stream_type,
+        credentials.user_id(),
+        Priority::Interactive,
+        &req,
+    )
+    .await
+    {
         Ok(res) => res,
         Err(err) => {
+            let status = search_error_status(&err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_around",
-                    "500",
+                    status.as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
@@ -339,21 +520,14 @@ pub async fn around(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_around",
-                    "500",
+                    status.as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search around error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError()
-                    .json(meta::http::HttpResponse::error_code(code)),
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(search_error_response(status, err));
         }
     };
 
@@ -376,14 +550,23 @@ pub async fn around(
         aggs: HashMap::new(),
         encoding: meta::search::RequestEncoding::Empty,
     };
-    let resp_backward = match SearchService::search(&org_id, stream_type, &req).await {
+    let resp_backward = match SearchService::search(
+        &org_id,
+        stream_type,
+        credentials.user_id(),
+        Priority::Interactive,
+        &req,
+    )
+    .await
+    {
         Ok(res) => res,
         Err(err) => {
+            let status = search_error_status(&err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_around",
-                    "500",
+                    status.as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
@@ -392,21 +575,14 @@ pub async fn around(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_around",
-                    "500",
+                    status.as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search around error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError()
-                    .json(meta::http::HttpResponse::error_code(code)),
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(search_error_response(status, err));
         }
     };
 
@@ -494,6 +670,7 @@ pub async fn around(
             ]
         })),
         (status = 400, description="Failure", content_type = "application/json", body = HttpResponse),
+        (status = 429, description="Too many searches", content_type = "application/json", body = HttpResponse),
         (status = 500, description="Failure", content_type = "application/json", body = HttpResponse),
     )
 )]
@@ -501,6 +678,7 @@ pub async fn around(
 pub async fn values(
     path: web::Path<(String, String)>,
     in_req: HttpRequest,
+    credentials: BasicAuth,
 ) -> Result<HttpResponse, Error> {
     let start = std::time::Instant::now();
     let mut uses_fn = false;
@@ -548,10 +726,6 @@ pub async fn values(
         end_time = chrono::Utc::now().timestamp_micros();
     }
 
-    // get a local search queue lock
-    let locker = SearchService::QUEUE_LOCKER.clone();
-    let _locker = locker.lock().await;
-
     // search
     let mut req = meta::search::Request {
         query: meta::search::Query {
@@ -580,14 +754,23 @@ pub async fn values(
                 ),
             );
     }
-    let resp_search = match SearchService::search(&org_id, stream_type, &req).await {
+    let resp_search = match SearchService::search(
+        &org_id,
+        stream_type,
+        credentials.user_id(),
+        Priority::Interactive,
+        &req,
+    )
+    .await
+    {
         Ok(res) => res,
         Err(err) => {
+            let status = search_error_status(&err);
             let time = start.elapsed().as_secs_f64();
             metrics::HTTP_RESPONSE_TIME
                 .with_label_values(&[
                     "/api/org/_values",
-                    "500",
+                    status.as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
@@ -596,21 +779,14 @@ pub async fn values(
             metrics::HTTP_INCOMING_REQUESTS
                 .with_label_values(&[
                     "/api/org/_values",
-                    "500",
+                    status.as_str(),
                     &org_id,
                     &stream_name,
                     stream_type.to_string().as_str(),
                 ])
                 .inc();
             log::error!("search values error: {:?}", err);
-            return Ok(match err {
-                errors::Error::ErrorCode(code) => HttpResponse::InternalServerError()
-                    .json(meta::http::HttpResponse::error_code(code)),
-                _ => HttpResponse::InternalServerError().json(meta::http::HttpResponse::error(
-                    StatusCode::INTERNAL_SERVER_ERROR.into(),
-                    err.to_string(),
-                )),
-            });
+            return Ok(search_error_response(status, err));
         }
     };
 
@@ -675,3 +851,53 @@ fn bad_request(error: impl ToString) -> HttpResponse {
         error.to_string(),
     ))
 }
+
+/// the priority requested with `?priority`, the alert class is reserved for
+/// the alert evaluator
+fn search_priority(query: &AHashMap<String, String>) -> Result<Priority, String> {
+    match query.get("priority").map(|v| v.to_lowercase()).as_deref() {
+        None | Some("") | Some("interactive") => Ok(Priority::Interactive),
+        Some("batch") | Some("api") => Ok(Priority::Batch),
+        Some(v) => Err(format!(
+            "invalid priority: {v}, expected interactive or batch"
+        )),
+    }
+}
+
+/// the session id given by the client, so it can cancel the search while it
+/// is running, or a new one
+fn search_session_id(query: &AHashMap<String, String>) -> Result<String, String> {
+    match query.get("session_id").map(|v| v.trim()) {
+        None | Some("") => Ok(Uuid::new_v4().to_string()),
+        Some(v) => match Uuid::parse_str(v) {
+            Ok(id) => Ok(id.hyphenated().to_string()),
+            Err(_) => Err(format!("invalid session_id: {v}, expected a uuid")),
+        },
+    }
+}
+
+fn search_error_status(err: &errors::Error) -> StatusCode {
+    match err {
+        errors::Error::ErrorCode(errors::ErrorCodes::SearchTooManyRequests(_)) => {
+            StatusCode::TOO_MANY_REQUESTS
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn search_error_response(status: StatusCode, err: errors::Error) -> HttpResponse {
+    let mut resp = HttpResponse::build(status);
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        resp.insert_header((
+            header::RETRY_AFTER,
+            CONFIG.limit.search_retry_after.to_string(),
+        ));
+    }
+    match err {
+        errors::Error::ErrorCode(code) => resp.json(meta::http::HttpResponse::error_code(code)),
+        _ => resp.json(meta::http::HttpResponse::error(
+            status.into(),
+            err.to_string(),
+        )),
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use std::io::Error;
+
+use crate::{common::meta::routing::StreamRoute, service::stream_routes};
+
+/** CreateStreamRoute */
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Stream Routes",
+    operation_id = "CreateStreamRoute",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Source stream name"),
+    ),
+    request_body(
+        content = StreamRoute,
+        description = "StreamRoute details",
+    ),
+    responses(
+        (status = StatusCode::CREATED, description = "Route created", body = StreamRoute),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid route", body = HttpResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Internal Server Error", body = HttpResponse),
+    ),
+)]
+#[post("/{org_id}/{stream_name}/routes")]
+pub async fn create_route(
+    path: web::Path<(String, String)>,
+    details: web::Json<StreamRoute>,
+) -> Result<HttpResponse, Error> {
+    let (org_id, stream_name) = path.into_inner();
+    stream_routes::create_route(&org_id, &stream_name, details.into_inner()).await
+}
+
+/// UpdateStreamRoute
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Stream Routes",
+    operation_id = "UpdateStreamRoute",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Source stream name"),
+        ("name" = String, Path, description = "Route name"),
+    ),
+    request_body(
+        content = StreamRoute,
+        description = "StreamRoute details",
+    ),
+    responses(
+        (status = StatusCode::OK, description = "StreamRoute updated", body = StreamRoute),
+        (status = StatusCode::BAD_REQUEST, description = "Invalid route", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "StreamRoute not found", body = HttpResponse),
+        (status = StatusCode::INTERNAL_SERVER_ERROR, description = "Failed to update the StreamRoute", body = HttpResponse),
+    ),
+)]
+#[put("/{org_id}/{stream_name}/routes/{name}")]
+async fn update_route(
+    path: web::Path<(String, String, String)>,
+    details: web::Json<StreamRoute>,
+) -> impl Responder {
+    let (org_id, stream_name, name) = path.into_inner();
+    stream_routes::update_route(&org_id, &stream_name, &name, details.into_inner()).await
+}
+
+/// ListStreamRoutes
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Stream Routes",
+    operation_id = "ListStreamRoutes",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Source stream name"),
+    ),
+    responses(
+        (status = StatusCode::OK, body = StreamRoutes),
+    ),
+)]
+#[get("/{org_id}/{stream_name}/routes")]
+async fn list_routes(path: web::Path<(String, String)>) -> impl Responder {
+    let (org_id, stream_name) = path.into_inner();
+    stream_routes::list_routes(&org_id, &stream_name).await
+}
+
+/// DeleteStreamRoute
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Stream Routes",
+    operation_id = "DeleteStreamRoute",
+    security(
+        ("Authorization" = [])
+    ),
+    params(
+        ("org_id" = String, Path, description = "Organization name"),
+        ("stream_name" = String, Path, description = "Source stream name"),
+        ("name" = String, Path, description = "Route name"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "Route deleted", body = HttpResponse),
+        (status = StatusCode::NOT_FOUND, description = "Route not found", body = HttpResponse),
+    ),
+)]
+#[delete("/{org_id}/{stream_name}/routes/{name}")]
+async fn delete_route(path: web::Path<(String, String, String)>) -> impl Responder {
+    let (org_id, stream_name, name) = path.into_inner();
+    stream_routes::delete_route(&org_id, &stream_name, &name).await
+}
@@ -21,17 +21,22 @@ use std::sync::Arc;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-use super::auth::{validator, validator_aws, validator_gcp};
+use super::auth::{validator, validator_aws, validator_datadog, validator_gcp, validator_splunk};
 use super::request::dashboards::*;
+use super::request::fluent;
 use super::request::functions;
 use super::request::kv;
 use super::request::logs;
 use super::request::metrics;
 use super::request::organization;
 use super::request::prom;
+use super::request::quota;
+use super::request::redaction;
+use super::request::sampling;
 use super::request::search;
 use super::request::status;
 use super::request::stream;
+use super::request::stream_routes;
 use super::request::syslog;
 use super::request::traces::*;
 use super::request::users;
@@ -130,10 +135,31 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(logs::ingest::bulk)
             .service(logs::ingest::multi)
             .service(logs::ingest::json)
+            .service(logs::ingest::otlp_logs_write)
+            .service(logs::ingest::loki_push)
+            .service(logs::dead_letter::get_settings)
+            .service(logs::dead_letter::set_settings)
+            .service(logs::dead_letter::replay)
             .service(metrics::ingest::json)
+            .service(metrics::ingest::otlp_metrics_write)
+            .service(metrics::ingest::influxdb_write)
             .service(search::search)
+            .service(search::search_stream)
+            .service(search::cancel)
+            .service(search::job::submit)
+            .service(search::job::list)
+            .service(search::job::get)
+            .service(search::job::result)
+            .service(search::job::cancel)
+            .service(search::job::delete)
             .service(search::around)
             .service(search::values)
+            // before stream::delete, which matches `/{org_id}/ingestion_quota`
+            .service(quota::get_quotas)
+            .service(quota::set_org_quota)
+            .service(quota::delete_org_quota)
+            .service(quota::set_stream_quota)
+            .service(quota::delete_stream_quota)
             .service(stream::schema)
             .service(stream::settings)
             .service(stream::delete)
@@ -203,6 +229,21 @@ pub fn get_service_routes(cfg: &mut web::ServiceConfig) {
             .service(syslog::delete_route)
             .service(syslog::update_route)
             .service(syslog::toggle_state)
+            .service(fluent::list_routes)
+            .service(fluent::create_route)
+            .service(fluent::delete_route)
+            .service(fluent::update_route)
+            .service(stream_routes::list_routes)
+            .service(stream_routes::create_route)
+            .service(stream_routes::delete_route)
+            .service(stream_routes::update_route)
+            .service(sampling::list_rules)
+            .service(sampling::create_rule)
+            .service(sampling::delete_rule)
+            .service(sampling::update_rule)
+            .service(redaction::get_settings)
+            .service(redaction::set_settings)
+            .service(redaction::delete_settings)
             .service(enrichment_table::save_enrichment_table),
     );
 }
@@ -220,8 +261,30 @@ pub fn get_other_service_routes(cfg: &mut web::ServiceConfig) {
     let gcp_auth = HttpAuthentication::with_fn(validator_gcp);
     cfg.service(
         web::scope("/gcp")
-            .wrap(cors)
+            .wrap(cors.clone())
             .wrap(gcp_auth)
             .service(logs::ingest::handle_gcp_request),
     );
+
+    let splunk_auth = HttpAuthentication::with_fn(validator_splunk);
+    cfg.service(
+        web::scope("/splunk")
+            .wrap(cors.clone())
+            .wrap(splunk_auth)
+            .service(logs::ingest::splunk_hec_event)
+            .service(logs::ingest::splunk_hec_raw)
+            .service(logs::ingest::splunk_hec_ack)
+            .service(logs::ingest::splunk_hec_health),
+    );
+
+    let datadog_auth = HttpAuthentication::with_fn(validator_datadog);
+    cfg.service(
+        web::scope("/datadog")
+            .wrap(cors)
+            .wrap(datadog_auth)
+            .service(logs::ingest::datadog_logs)
+            .service(metrics::ingest::datadog_series_v1)
+            .service(metrics::ingest::datadog_series_v2)
+            .service(metrics::ingest::datadog_validate),
+    );
 }
@@ -29,13 +29,33 @@ use crate::handler::http::request;
         request::logs::ingest::handle_kinesis_request,
         request::logs::ingest::multi,
         request::logs::ingest::json,
+        request::logs::ingest::otlp_logs_write,
+        request::logs::ingest::loki_push,
+        request::logs::ingest::splunk_hec_event,
+        request::logs::ingest::splunk_hec_raw,
+        request::logs::ingest::datadog_logs,
+        request::logs::dead_letter::get_settings,
+        request::logs::dead_letter::set_settings,
+        request::logs::dead_letter::replay,
         request::metrics::ingest::json,
+        request::metrics::ingest::otlp_metrics_write,
+        request::metrics::ingest::influxdb_write,
+        request::metrics::ingest::datadog_series_v1,
+        request::metrics::ingest::datadog_series_v2,
         request::dashboards::create_dashboard,
         request::dashboards::update_dashboard,
         request::dashboards::list_dashboards,
         request::dashboards::get_dashboard,
         request::dashboards::delete_dashboard,
         request::search::search,
+        request::search::search_stream,
+        request::search::cancel,
+        request::search::job::submit,
+        request::search::job::list,
+        request::search::job::get,
+        request::search::job::result,
+        request::search::job::cancel,
+        request::search::job::delete,
         request::search::around,
         request::search::values,
         request::functions::list_functions,
@@ -87,6 +107,26 @@ use crate::handler::http::request;
         request::syslog::update_route,
         request::syslog::list_routes,
         request::syslog::delete_route,
+        request::fluent::create_route,
+        request::fluent::update_route,
+        request::fluent::list_routes,
+        request::fluent::delete_route,
+        request::stream_routes::create_route,
+        request::stream_routes::update_route,
+        request::stream_routes::list_routes,
+        request::stream_routes::delete_route,
+        request::sampling::create_rule,
+        request::sampling::update_rule,
+        request::sampling::list_rules,
+        request::sampling::delete_rule,
+        request::quota::get_quotas,
+        request::quota::set_org_quota,
+        request::quota::delete_org_quota,
+        request::quota::set_stream_quota,
+        request::quota::delete_stream_quota,
+        request::redaction::get_settings,
+        request::redaction::set_settings,
+        request::redaction::delete_settings,
         request::enrichment_table::save_enrichment_table,
     ),
     components(
@@ -104,6 +144,7 @@ use crate::handler::http::request;
             meta::ingestion::KFHRecordRequest,
             meta::ingestion::StreamStatus,
             meta::ingestion::IngestionResponse,
+            meta::ingestion::HecResponse,
             meta::dashboards::AggregationFunc,
             meta::dashboards::AxisItem,
             meta::dashboards::Dashboard,
@@ -118,6 +159,10 @@ use crate::handler::http::request;
             meta::search::RequestEncoding,
             meta::search::Response,
             meta::search::ResponseTook,
+            meta::search::Job,
+            meta::search::JobStatus,
+            meta::search::JobResultFile,
+            meta::search::JobList,
             meta::alert::Alert,
             meta::alert::AlertList,
             meta::alert::Condition,
@@ -152,8 +197,26 @@ use crate::handler::http::request;
             meta::ingestion::BulkResponseItem,
             meta::ingestion::ShardResponse,
             meta::ingestion::BulkResponseError,
+            meta::dead_letter::DeadLetterSettings,
+            meta::dead_letter::DeadLetterStage,
+            meta::dead_letter::DeadLetterReplay,
+            meta::dead_letter::DeadLetterReplayResponse,
             meta::syslog::SyslogRoute,
             meta::syslog::SyslogRoutes,
+            meta::fluent::FluentRoute,
+            meta::fluent::FluentRoutes,
+            meta::routing::StreamRoute,
+            meta::routing::RouteConditionType,
+            meta::routing::StreamRoutes,
+            meta::sampling::SamplingRule,
+            meta::sampling::SamplingAction,
+            meta::sampling::SamplingRules,
+            meta::quota::IngestionQuota,
+            meta::quota::OrgIngestionQuotas,
+            meta::redaction::StreamRedaction,
+            meta::redaction::RedactionRule,
+            meta::redaction::PiiDetector,
+            meta::redaction::RedactionMode,
          ),
     ),
     modifiers(&SecurityAddon),
@@ -172,6 +235,11 @@ use crate::handler::http::request;
         (name = "Metrics", description = "Metrics data ingestion operations"),
         (name = "Traces", description = "Traces data ingestion operations"),
         (name = "Syslog Routes", description = "Syslog Routes retrieval & management operations"),
+        (name = "Fluent Routes", description = "Fluent forward routes retrieval & management operations"),
+        (name = "Stream Routes", description = "Stream routing rules retrieval & management operations"),
+        (name = "Sampling Rules", description = "Ingestion sampling & drop rules retrieval & management operations"),
+        (name = "Ingestion Quotas", description = "Ingestion quotas retrieval & management operations"),
+        (name = "Redaction", description = "Ingestion pii redaction retrieval & management operations"),
     ),
     info(
         description = "OpenObserve API documents [https://openobserve.ai/docs/](https://openobserve.ai/docs/)",
@@ -0,0 +1,132 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::{Buf, BytesMut};
+use rmpv::Value as MsgValue;
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::common::infra::config::CONFIG;
+use crate::service::logs::fluent;
+
+/// connections counted to spread their writes over the wal threads
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+pub async fn fluent_server(listener: TcpListener) {
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("Fluent forward server - accept error: {}", e);
+                continue;
+            }
+        };
+        let thread_id = if CONFIG.common.feature_per_thread_lock {
+            CONNECTIONS.fetch_add(1, Ordering::Relaxed) % CONFIG.limit.cpu_num
+        } else {
+            0
+        };
+        tokio::task::spawn(async move {
+            if let Err(e) = handle_connection(stream, thread_id).await {
+                log::error!("Fluent forward connection from {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, thread_id: usize) -> Result<(), anyhow::Error> {
+    let mut buf = BytesMut::with_capacity(64 * 1024);
+    if !CONFIG.tcp.fluent_forward_shared_key.is_empty() {
+        handshake(&mut stream, &mut buf).await?;
+    }
+    while let Some(frame) = read_frame(&mut stream, &mut buf).await? {
+        let forward = fluent::decode_forward(frame)?;
+        let chunk = forward.chunk.clone();
+        // without an ack the client sends the chunk again
+        if let Err(e) = fluent::ingest(thread_id, forward).await {
+            log::error!("Fluent forward ingestion error: {}", e);
+            continue;
+        }
+        if let Some(chunk) = chunk {
+            write_value(&mut stream, &fluent::ack(&chunk)).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn handshake(stream: &mut TcpStream, buf: &mut BytesMut) -> Result<(), anyhow::Error> {
+    let nonce: [u8; 16] = rand::random();
+    write_value(stream, &fluent::helo(&nonce)).await?;
+    let Some(ping) = read_frame(stream, buf).await? else {
+        return Err(anyhow::anyhow!("connection closed before PING"));
+    };
+    let (authenticated, pong) = fluent::pong(&ping, &nonce, &CONFIG.tcp.fluent_forward_shared_key);
+    write_value(stream, &pong).await?;
+    if !authenticated {
+        return Err(anyhow::anyhow!("authentication failed"));
+    }
+    Ok(())
+}
+
+/// read the next msgpack value, `None` when the connection is closed
+async fn read_frame(
+    stream: &mut TcpStream,
+    buf: &mut BytesMut,
+) -> Result<Option<MsgValue>, anyhow::Error> {
+    loop {
+        if !buf.is_empty() {
+            let mut cursor = &buf[..];
+            match rmpv::decode::read_value(&mut cursor) {
+                Ok(value) => {
+                    let used = buf.len() - cursor.len();
+                    buf.advance(used);
+                    return Ok(Some(value));
+                }
+                Err(e) if is_incomplete(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if buf.len() > CONFIG.limit.req_payload_limit {
+            return Err(anyhow::anyhow!("frame exceeds the payload limit"));
+        }
+        if stream.read_buf(buf).await? == 0 {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            return Err(anyhow::anyhow!(
+                "connection closed in the middle of a frame"
+            ));
+        }
+    }
+}
+
+fn is_incomplete(e: &rmpv::decode::Error) -> bool {
+    match e {
+        rmpv::decode::Error::InvalidMarkerRead(e) | rmpv::decode::Error::InvalidDataRead(e) => {
+            e.kind() == ErrorKind::UnexpectedEof
+        }
+        _ => false,
+    }
+}
+
+async fn write_value(stream: &mut TcpStream, value: &MsgValue) -> Result<(), anyhow::Error> {
+    let mut out = vec![];
+    rmpv::encode::write_value(&mut out, value)?;
+    stream.write_all(&out).await?;
+    Ok(())
+}
@@ -0,0 +1,111 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::BytesMut;
+use ipnetwork::IpNetwork;
+use once_cell::sync::Lazy;
+use std::net::IpAddr;
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream, UdpSocket},
+};
+
+use crate::common::infra::config::CONFIG;
+use crate::service::metrics::graphite;
+
+/// the networks of the accepted senders, the metrics are written to
+/// `ZO_GRAPHITE_ORG` without any authentication
+static ALLOWED_NETWORKS: Lazy<Vec<IpNetwork>> = Lazy::new(|| {
+    CONFIG
+        .tcp
+        .graphite_allowed_networks
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .filter_map(|v| match v.parse() {
+            Ok(network) => Some(network),
+            Err(e) => {
+                log::error!("Graphite server - invalid allowed network {v}: {e}");
+                None
+            }
+        })
+        .collect()
+});
+
+fn is_allowed(ip: IpAddr) -> bool {
+    ALLOWED_NETWORKS.iter().any(|network| network.contains(ip))
+}
+
+pub async fn graphite_tcp_server(listener: TcpListener) {
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("Graphite server - accept error: {}", e);
+                continue;
+            }
+        };
+        if !is_allowed(addr.ip()) {
+            log::warn!("Graphite server - connection from {} not allowed", addr);
+            continue;
+        }
+        tokio::task::spawn(async move {
+            if let Err(e) = handle_connection(stream).await {
+                log::error!("Graphite connection from {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+pub async fn graphite_udp_server(socket: UdpSocket) {
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let (len, addr) = match socket.recv_from(&mut buf).await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("Graphite server - receive error: {}", e);
+                continue;
+            }
+        };
+        if !is_allowed(addr.ip()) {
+            log::warn!("Graphite server - datagram from {} not allowed", addr);
+            continue;
+        }
+        ingest(&buf[..len]).await;
+    }
+}
+
+/// the complete lines of every read are ingested together
+async fn handle_connection(mut stream: TcpStream) -> Result<(), anyhow::Error> {
+    let mut buf = BytesMut::with_capacity(64 * 1024);
+    loop {
+        if stream.read_buf(&mut buf).await? == 0 {
+            if !buf.is_empty() {
+                ingest(&buf.split()).await;
+            }
+            return Ok(());
+        }
+        if let Some(end) = buf.iter().rposition(|b| *b == b'\n') {
+            ingest(&buf.split_to(end + 1)).await;
+        } else if buf.len() > CONFIG.limit.req_payload_limit {
+            return Err(anyhow::anyhow!("line exceeds the payload limit"));
+        }
+    }
+}
+
+async fn ingest(lines: &[u8]) {
+    if let Err(e) = graphite::ingest(&String::from_utf8_lossy(lines)).await {
+        log::error!("Graphite ingestion error: {}", e);
+    }
+}
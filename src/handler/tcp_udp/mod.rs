@@ -12,27 +12,42 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use bytes::BytesMut;
+use bytes::{Buf, Bytes, BytesMut};
+use std::{fs::File, io::BufReader, net::SocketAddr, sync::Arc};
 use tokio::{
-    io::AsyncReadExt,
+    io::{AsyncRead, AsyncReadExt},
     net::{TcpListener, UdpSocket},
+    sync::{broadcast, Semaphore},
 };
+use tokio_rustls::{rustls, TlsAcceptor};
 
+use crate::common::infra::{config::CONFIG, metrics};
 use crate::{job::syslog_server::BROADCASTER, service::logs::syslog};
 
+pub mod fluent;
+pub mod graphite;
+pub mod statsd;
+
 pub static STOP_SRV: &str = "ZO_STOP_TCP_UDP";
 
+/// the longest octet count prefix of a message
+const MAX_OCTET_COUNT_LEN: usize = 10;
+
 pub async fn udp_server(socket: UdpSocket) {
-    let mut buf_udp = vec![0u8; 1024];
+    // a datagram is at most 64KB
+    let mut buf_udp = vec![0u8; 65535];
     let sender = BROADCASTER.read().await;
     let mut udp_receiver_rx = sender.subscribe();
     loop {
-        let (recv_len, addr) = socket.recv_from(&mut buf_udp).await.unwrap();
-        let message = BytesMut::from(&buf_udp[..recv_len]);
-        let input_str = String::from_utf8(message.to_vec()).unwrap();
-        if input_str != STOP_SRV {
-            let _ = syslog::ingest(&input_str, addr).await;
-        }
+        let (recv_len, addr) = match socket.recv_from(&mut buf_udp).await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("UDP server - receive error: {}", e);
+                metrics::SYSLOG_ERRORS.with_label_values(&["udp"]).inc();
+                continue;
+            }
+        };
+        ingest_message(&buf_udp[..recv_len], addr, "udp").await;
         if let Ok(val) = udp_receiver_rx.try_recv() {
             if !val {
                 log::warn!("UDP server - received the stop signal, exiting.");
@@ -43,33 +58,312 @@ pub async fn udp_server(socket: UdpSocket) {
     }
 }
 
-pub async fn tcp_server(listener: TcpListener) {
+/// accept the connections of the syslog senders, every connection is read by
+/// its own task until the sender closes it
+pub async fn tcp_server(listener: TcpListener, tls_acceptor: Option<TlsAcceptor>) {
+    let protocol = if tls_acceptor.is_some() { "tls" } else { "tcp" };
     let sender = BROADCASTER.read().await;
     let mut tcp_receiver_rx = sender.subscribe();
-    let mut buf_tcp = vec![0u8; 1024];
+    let connections = Arc::new(Semaphore::new(CONFIG.tcp.tcp_max_connections));
     loop {
-        let (mut stream, _) = listener.accept().await.unwrap();
-
-        match stream.peer_addr() {
-            Ok(addr) => {
-                let len = stream.read(&mut buf_tcp).await.unwrap();
-                let message = BytesMut::from(&buf_tcp[..len]);
-                let input_str = String::from_utf8(message.to_vec()).unwrap();
-                if input_str != STOP_SRV {
-                    let _ = syslog::ingest(&input_str, addr).await;
-                }
-                if let Ok(val) = tcp_receiver_rx.try_recv() {
-                    if !val {
-                        log::warn!("TCP server - received the stop signal, exiting.");
-                        drop(listener);
-                        break;
-                    }
-                };
-            }
+        let (stream, addr) = match listener.accept().await {
+            Ok(v) => v,
             Err(e) => {
-                log::error!("Error while writing to TCP stream: {}", e);
+                log::error!("TCP server - accept error: {}", e);
+                metrics::SYSLOG_ERRORS.with_label_values(&[protocol]).inc();
                 continue;
             }
         };
+        if let Ok(val) = tcp_receiver_rx.try_recv() {
+            if !val {
+                log::warn!("TCP server - received the stop signal, exiting.");
+                drop(listener);
+                break;
+            }
+        };
+        // the new senders are closed when all the connections are in use
+        let Ok(permit) = connections.clone().try_acquire_owned() else {
+            log::warn!(
+                "TCP server - connection from {} closed, {} connections in use",
+                addr,
+                CONFIG.tcp.tcp_max_connections
+            );
+            metrics::SYSLOG_ERRORS.with_label_values(&[protocol]).inc();
+            drop(stream);
+            continue;
+        };
+
+        let stop_rx = sender.subscribe();
+        let tls_acceptor = tls_acceptor.clone();
+        tokio::task::spawn(async move {
+            let ret = match tls_acceptor {
+                Some(acceptor) => match acceptor.accept(stream).await {
+                    Ok(stream) => handle_connection(stream, addr, protocol, stop_rx).await,
+                    Err(e) => Err(e.into()),
+                },
+                None => handle_connection(stream, addr, protocol, stop_rx).await,
+            };
+            if let Err(e) = ret {
+                log::error!("TCP server - connection from {}: {}", addr, e);
+                metrics::SYSLOG_ERRORS.with_label_values(&[protocol]).inc();
+            }
+            drop(permit);
+        });
+    }
+}
+
+/// the acceptor of the syslog over TLS connections, RFC 5425
+pub fn tls_acceptor(cert_path: &str, key_path: &str) -> Result<TlsAcceptor, anyhow::Error> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    let key = rustls_pemfile::read_all(&mut BufReader::new(File::open(key_path)?))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("no private key in {key_path}"))?;
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+async fn handle_connection<S: AsyncRead + Unpin>(
+    stream: S,
+    addr: SocketAddr,
+    protocol: &str,
+    stop_rx: broadcast::Receiver<bool>,
+) -> Result<(), anyhow::Error> {
+    let start = std::time::Instant::now();
+    metrics::SYSLOG_CONNECTIONS
+        .with_label_values(&[protocol])
+        .inc();
+    let ret = read_messages(stream, addr, protocol, stop_rx).await;
+    metrics::SYSLOG_CONNECTIONS
+        .with_label_values(&[protocol])
+        .dec();
+    if let Ok(messages) = &ret {
+        log::debug!(
+            "TCP server - connection from {} closed, {} messages in {:.2}s",
+            addr,
+            messages,
+            start.elapsed().as_secs_f64()
+        );
+    }
+    ret.map(|_| ())
+}
+
+/// read and ingest the messages one by one, the sender is slowed down by the
+/// TCP flow control while a message is ingested
+async fn read_messages<S: AsyncRead + Unpin>(
+    mut stream: S,
+    addr: SocketAddr,
+    protocol: &str,
+    mut stop_rx: broadcast::Receiver<bool>,
+) -> Result<u64, anyhow::Error> {
+    let max_size = CONFIG.tcp.syslog_max_message_size;
+    let mut buf = BytesMut::with_capacity(8192);
+    let mut skip = Skip::None;
+    let mut messages = 0;
+    loop {
+        while let Some(frame) = next_frame(&mut buf, max_size, &mut skip) {
+            ingest_message(&frame, addr, protocol).await;
+            messages += 1;
+        }
+        let len = tokio::select! {
+            len = stream.read_buf(&mut buf) => len?,
+            val = stop_rx.recv() => {
+                if matches!(val, Ok(false)) {
+                    return Ok(messages);
+                }
+                continue;
+            }
+        };
+        if len == 0 {
+            // the last message of the connection may not be terminated
+            if !buf.is_empty() && skip == Skip::None {
+                ingest_message(&buf.split(), addr, protocol).await;
+                messages += 1;
+            }
+            return Ok(messages);
+        }
+    }
+}
+
+/// the rest of a message over the size limit, dropped as it arrives
+#[derive(Debug, PartialEq)]
+enum Skip {
+    None,
+    Bytes(usize),
+    Line,
+}
+
+/// split the next message of the stream, with the RFC 6587 octet counting
+/// when it starts with an octet count and a space, else with the newline of
+/// the non-transparent framing, the messages over the size limit are skipped
+fn next_frame(buf: &mut BytesMut, max_size: usize, skip: &mut Skip) -> Option<Bytes> {
+    loop {
+        match *skip {
+            Skip::None => {}
+            Skip::Bytes(len) => {
+                let n = len.min(buf.len());
+                buf.advance(n);
+                if n < len {
+                    *skip = Skip::Bytes(len - n);
+                    return None;
+                }
+                *skip = Skip::None;
+            }
+            Skip::Line => match buf.iter().position(|b| *b == b'\n') {
+                Some(end) => {
+                    buf.advance(end + 1);
+                    *skip = Skip::None;
+                }
+                None => {
+                    buf.clear();
+                    return None;
+                }
+            },
+        }
+
+        let start = buf
+            .iter()
+            .position(|b| !b.is_ascii_whitespace() && *b != 0)
+            .unwrap_or(buf.len());
+        buf.advance(start);
+        if buf.is_empty() {
+            return None;
+        }
+
+        if buf[0].is_ascii_digit() {
+            let digits = buf.iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits == buf.len() && digits <= MAX_OCTET_COUNT_LEN {
+                // the octet count may continue in the next read
+                return None;
+            }
+            if digits <= MAX_OCTET_COUNT_LEN && buf[digits] == b' ' {
+                let len = std::str::from_utf8(&buf[..digits])
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap();
+                if len > max_size {
+                    log::warn!(
+                        "TCP server - skipped a message of {len} bytes over the limit of {max_size} bytes"
+                    );
+                    buf.advance(digits + 1);
+                    *skip = Skip::Bytes(len);
+                    continue;
+                }
+                if buf.len() < digits + 1 + len {
+                    return None;
+                }
+                buf.advance(digits + 1);
+                return Some(buf.split_to(len).freeze());
+            }
+            // no octet count, the message just starts with a digit
+        }
+
+        match buf.iter().position(|b| *b == b'\n') {
+            Some(end) if end <= max_size => {
+                let frame = buf.split_to(end).freeze();
+                buf.advance(1);
+                return Some(frame);
+            }
+            Some(end) => {
+                log::warn!(
+                    "TCP server - skipped a message of {end} bytes over the limit of {max_size} bytes"
+                );
+                buf.advance(end + 1);
+            }
+            None if buf.len() <= max_size => return None,
+            None => {
+                log::warn!("TCP server - skipped a message over the limit of {max_size} bytes");
+                buf.clear();
+                *skip = Skip::Line;
+                return None;
+            }
+        }
+    }
+}
+
+async fn ingest_message(message: &[u8], addr: SocketAddr, protocol: &str) {
+    let message = String::from_utf8_lossy(message);
+    let message = message.trim_end_matches(['\r', '\n', '\0']);
+    if message.is_empty() || message == STOP_SRV {
+        return;
+    }
+    metrics::SYSLOG_MESSAGES
+        .with_label_values(&[protocol])
+        .inc();
+    metrics::SYSLOG_BYTES
+        .with_label_values(&[protocol])
+        .inc_by(message.len() as u64);
+    let _ = syslog::ingest(message, addr).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_frame() {
+        let mut skip = Skip::None;
+        let mut buf = BytesMut::from("<13>first\n<13>second\r\n<13>part");
+        assert_eq!(next_frame(&mut buf, 1024, &mut skip).unwrap(), "<13>first");
+        assert_eq!(
+            next_frame(&mut buf, 1024, &mut skip).unwrap(),
+            "<13>second\r"
+        );
+        assert!(next_frame(&mut buf, 1024, &mut skip).is_none());
+        assert_eq!(&buf[..], b"<13>part");
+
+        let mut buf = BytesMut::from("9 <13>a\nb c11 <13>");
+        assert_eq!(next_frame(&mut buf, 1024, &mut skip).unwrap(), "<13>a\nb c");
+        assert!(next_frame(&mut buf, 1024, &mut skip).is_none());
+        buf.extend_from_slice(b"message");
+        assert_eq!(
+            next_frame(&mut buf, 1024, &mut skip).unwrap(),
+            "<13>message"
+        );
+        assert!(next_frame(&mut buf, 1024, &mut skip).is_none());
+
+        // a message starting with a digit without an octet count
+        let mut buf = BytesMut::from("2023-10-01 started\n12345678901234 id\n");
+        assert_eq!(
+            next_frame(&mut buf, 1024, &mut skip).unwrap(),
+            "2023-10-01 started"
+        );
+        assert_eq!(
+            next_frame(&mut buf, 1024, &mut skip).unwrap(),
+            "12345678901234 id"
+        );
+    }
+
+    #[test]
+    fn test_next_frame_limits() {
+        // the messages over the limit are skipped, the next ones are read
+        let mut skip = Skip::None;
+        let mut buf = BytesMut::from("2000 <13>");
+        assert!(next_frame(&mut buf, 1024, &mut skip).is_none());
+        assert_eq!(skip, Skip::Bytes(2000 - 4));
+        buf.extend_from_slice(&[b'a'; 1996]);
+        buf.extend_from_slice(b"4 <13>");
+        assert_eq!(next_frame(&mut buf, 1024, &mut skip).unwrap(), "<13>");
+
+        let mut buf = BytesMut::from("<13>".repeat(300).as_str());
+        assert!(next_frame(&mut buf, 1024, &mut skip).is_none());
+        assert_eq!(skip, Skip::Line);
+        buf.extend_from_slice(b"rest\n<13>next\n");
+        assert_eq!(next_frame(&mut buf, 1024, &mut skip).unwrap(), "<13>next");
+
+        let mut skip = Skip::None;
+        let mut buf = BytesMut::from(format!("{}\n<13>next\n", "<13>".repeat(300)).as_str());
+        assert_eq!(next_frame(&mut buf, 1024, &mut skip).unwrap(), "<13>next");
     }
 }
@@ -0,0 +1,77 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::BytesMut;
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream, UdpSocket},
+};
+
+use crate::common::infra::config::CONFIG;
+use crate::service::metrics::statsd;
+
+pub async fn statsd_tcp_server(listener: TcpListener) {
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("StatsD server - accept error: {}", e);
+                continue;
+            }
+        };
+        tokio::task::spawn(async move {
+            if let Err(e) = handle_connection(stream).await {
+                log::error!("StatsD connection from {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+pub async fn statsd_udp_server(socket: UdpSocket) {
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let len = match socket.recv(&mut buf).await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("StatsD server - receive error: {}", e);
+                continue;
+            }
+        };
+        ingest(&buf[..len]);
+    }
+}
+
+/// the complete lines of every read are aggregated together
+async fn handle_connection(mut stream: TcpStream) -> Result<(), anyhow::Error> {
+    let mut buf = BytesMut::with_capacity(64 * 1024);
+    loop {
+        if stream.read_buf(&mut buf).await? == 0 {
+            if !buf.is_empty() {
+                ingest(&buf.split());
+            }
+            return Ok(());
+        }
+        if let Some(end) = buf.iter().rposition(|b| *b == b'\n') {
+            ingest(&buf.split_to(end + 1));
+        } else if buf.len() > CONFIG.limit.req_payload_limit {
+            return Err(anyhow::anyhow!("line exceeds the payload limit"));
+        }
+    }
+}
+
+fn ingest(lines: &[u8]) {
+    if let Err(e) = statsd::ingest(&String::from_utf8_lossy(lines)) {
+        log::error!("StatsD ingestion error: {}", e);
+    }
+}
@@ -19,7 +19,7 @@ use tokio::{sync::Semaphore, task, time};
 
 use crate::common::infra::{config::CONFIG, metrics, storage, wal};
 use crate::common::meta::{common::FileMeta, StreamType};
-use crate::common::utils::{json, stream::populate_file_meta};
+use crate::common::utils::{column_stats, json, stream::populate_file_meta};
 use crate::service::{
     db, schema::schema_evolution, search::datafusion::new_writer, usage::report_compression_stats,
 };
@@ -225,8 +225,16 @@ async fn upload_file(
         records: 0,
         original_size: file_size as i64,
         compressed_size: buf_parquet.len() as i64,
+        index_size: 0,
+        stats_size: 0,
     };
 
+    // collect the column stats before the batches are moved
+    let stats = if CONFIG.common.column_stats_enabled {
+        column_stats::from_batches(&arrow_schema, &meta_batch)
+    } else {
+        vec![]
+    };
     populate_file_meta(arrow_schema.clone(), vec![meta_batch], &mut file_meta).await?;
 
     schema_evolution(
@@ -243,6 +251,16 @@ async fn upload_file(
     match storage::put(&new_file_name, bytes::Bytes::from(buf_parquet)).await {
         Ok(_output) => {
             log::info!("[JOB] memory file upload succeeded: {}", new_file_name);
+            match column_stats::upload(&new_file_name, &stats).await {
+                Ok(stats_size) => file_meta.stats_size = stats_size,
+                Err(e) => {
+                    log::error!(
+                        "[JOB] memory file upload column stats error: {}, {}",
+                        new_file_name,
+                        e
+                    );
+                }
+            }
             Ok((new_file_name, file_meta, stream_type))
         }
         Err(err) => {
@@ -21,10 +21,18 @@ use crate::common::{
         db::dynamo,
         file_list as infra_file_list, ider,
     },
-    meta::{meta_store::MetaStore, organization::DEFAULT_ORG, user::UserRequest},
+    meta::{
+        fluent::FluentRoute, meta_store::MetaStore, organization::DEFAULT_ORG,
+        quota::IngestionQuota, redaction::StreamRedaction, routing::StreamRoute,
+        sampling::SamplingRule, user::UserRequest,
+    },
     utils::file::clean_empty_dirs,
 };
-use crate::service::{db, users};
+use crate::handler::tcp_udp::{fluent::fluent_server, graphite, statsd as statsd_server};
+use crate::service::{
+    db::{self, doc_version::DocVersions},
+    users,
+};
 
 mod alert_manager;
 mod compact;
@@ -32,7 +40,11 @@ mod file_list;
 mod files;
 mod metrics;
 mod prom;
+mod quota;
+mod search_jobs;
+mod search_queue;
 mod stats;
+mod statsd;
 pub(crate) mod syslog_server;
 mod telemetry;
 
@@ -102,6 +114,12 @@ pub async fn init() -> Result<(), anyhow::Error> {
     // initialize metadata watcher
     tokio::task::spawn(async move { db::schema::watch().await });
     tokio::task::spawn(async move { db::functions::watch().await });
+    tokio::task::spawn(async move { db::rules::watch::<StreamRoute>().await });
+    tokio::task::spawn(async move { db::dead_letter::watch().await });
+    tokio::task::spawn(async move { db::rules::watch::<IngestionQuota>().await });
+    tokio::task::spawn(async move { db::rules::watch::<SamplingRule>().await });
+    tokio::task::spawn(async move { db::rules::watch::<StreamRedaction>().await });
+    tokio::task::spawn(async move { db::rules::watch::<DocVersions>().await });
     tokio::task::spawn(async move { db::compact::retention::watch().await });
     tokio::task::spawn(async move { db::metrics::watch_prom_cluster_leader().await });
     tokio::task::spawn(async move { db::alerts::templates::watch().await });
@@ -115,6 +133,24 @@ pub async fn init() -> Result<(), anyhow::Error> {
     db::functions::cache()
         .await
         .expect("functions cache failed");
+    db::rules::cache::<StreamRoute>()
+        .await
+        .expect("stream routes cache failed");
+    db::dead_letter::cache()
+        .await
+        .expect("dead letter settings cache failed");
+    db::rules::cache::<IngestionQuota>()
+        .await
+        .expect("ingestion quotas cache failed");
+    db::rules::cache::<SamplingRule>()
+        .await
+        .expect("sampling rules cache failed");
+    db::rules::cache::<StreamRedaction>()
+        .await
+        .expect("stream redactions cache failed");
+    db::rules::cache::<DocVersions>()
+        .await
+        .expect("document versions cache failed");
     db::compact::retention::cache()
         .await
         .expect("compact delete cache failed");
@@ -137,6 +173,9 @@ pub async fn init() -> Result<(), anyhow::Error> {
     db::syslog::cache_syslog_settings()
         .await
         .expect("syslog settings cache failed");
+    db::rules::cache::<FluentRoute>()
+        .await
+        .expect("fluent routes cache failed");
 
     // cache file list
     infra_file_list::create_table().await?;
@@ -168,6 +207,9 @@ pub async fn init() -> Result<(), anyhow::Error> {
     tokio::task::spawn(async move { metrics::run().await });
     tokio::task::spawn(async move { prom::run().await });
     tokio::task::spawn(async move { alert_manager::run().await });
+    tokio::task::spawn(async move { search_jobs::run().await });
+    tokio::task::spawn(async move { quota::run().await });
+    tokio::task::spawn(async move { search_queue::run().await });
 
     // Shouldn't serve request until initialization finishes
     log::info!("Job initialization complete");
@@ -183,5 +225,35 @@ pub async fn init() -> Result<(), anyhow::Error> {
             .expect("syslog server run failed");
     }
 
+    // Fluent forward server start
+    tokio::task::spawn(async move { db::rules::watch::<FluentRoute>().await });
+    if CONFIG.tcp.fluent_forward_enabled && cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        let addr = format!("0.0.0.0:{}", CONFIG.tcp.fluent_forward_port);
+        log::info!("Starting fluent forward server on {addr}");
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        tokio::task::spawn(async move { fluent_server(listener).await });
+    }
+
+    // Graphite server start
+    if CONFIG.tcp.graphite_enabled && cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        let addr = format!("0.0.0.0:{}", CONFIG.tcp.graphite_port);
+        log::info!("Starting graphite server on {addr}");
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        let socket = tokio::net::UdpSocket::bind(&addr).await?;
+        tokio::task::spawn(async move { graphite::graphite_tcp_server(listener).await });
+        tokio::task::spawn(async move { graphite::graphite_udp_server(socket).await });
+    }
+
+    // StatsD server start, the series are written by the statsd job
+    if CONFIG.tcp.statsd_enabled && cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        let addr = format!("0.0.0.0:{}", CONFIG.tcp.statsd_port);
+        log::info!("Starting statsd server on {addr}");
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        let socket = tokio::net::UdpSocket::bind(&addr).await?;
+        tokio::task::spawn(async move { statsd_server::statsd_tcp_server(listener).await });
+        tokio::task::spawn(async move { statsd_server::statsd_udp_server(socket).await });
+        tokio::task::spawn(async move { statsd::run().await });
+    }
+
     Ok(())
 }
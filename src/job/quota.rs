@@ -0,0 +1,35 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use tokio::time::{self, Duration};
+
+use crate::common::infra::{cluster, config::CONFIG};
+use crate::service::ingestion::quota;
+
+/// share the ingestion quotas with the other ingesters at every sync interval
+pub async fn run() -> Result<(), anyhow::Error> {
+    if !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        return Ok(()); // not an ingester, no need to init job
+    }
+
+    let mut interval = time::interval(Duration::from_secs(
+        CONFIG.limit.ingest_quota_sync_interval.max(1),
+    ));
+    interval.tick().await; // trigger the first run
+    loop {
+        interval.tick().await;
+        if let Err(e) = quota::sync().await {
+            log::error!("[JOB] Ingestion quota sync error: {}", e);
+        }
+    }
+}
@@ -21,7 +21,7 @@ use tokio::{
     sync::broadcast,
 };
 
-use crate::handler::tcp_udp::STOP_SRV;
+use crate::handler::tcp_udp::{tls_acceptor, STOP_SRV};
 use crate::service::db::syslog::toggle_syslog_setting;
 use crate::{
     common::infra::config::{CONFIG, SYSLOG_ENABLED},
@@ -39,13 +39,22 @@ pub async fn run(start_srv: bool, is_init: bool) -> Result<(), anyhow::Error> {
     let bind_addr = "0.0.0.0";
     let tcp_addr: SocketAddr = format!("{bind_addr}:{}", CONFIG.tcp.tcp_port).parse()?;
     let udp_addr: SocketAddr = format!("{bind_addr}:{}", CONFIG.tcp.udp_port).parse()?;
+    let tls_addr: SocketAddr = format!("{bind_addr}:{}", CONFIG.tcp.tcp_tls_port).parse()?;
     if (!server_running || is_init) && start_srv {
         log::info!("Starting TCP UDP server");
         let tcp_listener: TcpListener = TcpListener::bind(tcp_addr).await?;
         let udp_socket = UdpSocket::bind(udp_addr).await?;
         tokio::task::spawn(async move {
-            _ = tcp_server(tcp_listener).await;
+            _ = tcp_server(tcp_listener, None).await;
         });
+        if CONFIG.tcp.tcp_tls_enabled {
+            let acceptor =
+                tls_acceptor(&CONFIG.tcp.tcp_tls_cert_path, &CONFIG.tcp.tcp_tls_key_path)?;
+            let tls_listener: TcpListener = TcpListener::bind(tls_addr).await?;
+            tokio::task::spawn(async move {
+                _ = tcp_server(tls_listener, Some(acceptor)).await;
+            });
+        }
         tokio::task::spawn(async move {
             _ = udp_server(udp_socket).await;
         });
@@ -59,6 +68,10 @@ pub async fn run(start_srv: bool, is_init: bool) -> Result<(), anyhow::Error> {
         socket.send_to(STOP_SRV.as_bytes(), udp_addr).await?;
         let mut stream = TcpStream::connect(tcp_addr)?;
         stream.write_all(STOP_SRV.as_bytes())?;
+        if CONFIG.tcp.tcp_tls_enabled {
+            // wakes the accept loop, the handshake is never started
+            drop(TcpStream::connect(tls_addr)?);
+        }
 
         drop(socket);
         drop(stream);
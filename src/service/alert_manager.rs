@@ -21,7 +21,7 @@ use crate::common::meta;
 use crate::common::meta::alert::{Evaluate, Trigger, TriggerTimer};
 use crate::common::meta::search::Request;
 use crate::common::utils::notification::send_notification;
-use crate::service::search as SearchService;
+use crate::service::search::{self as SearchService, queue::Priority};
 use crate::service::triggers;
 
 #[cfg_attr(coverage_nightly, no_coverage)]
@@ -109,8 +109,14 @@ async fn handle_trigger(alert_name: &str, frequency: i64) {
                         encoding: meta::search::RequestEncoding::Empty,
                     };
                     // do search
-                    match SearchService::search(&trigger.org, alert.stream_type.unwrap(), &req)
-                        .await
+                    match SearchService::search(
+                        &trigger.org,
+                        alert.stream_type.unwrap(),
+                        "",
+                        Priority::Alert,
+                        &req,
+                    )
+                    .await
                     {
                         Ok(res) => {
                             if !res.hits.is_empty() {
@@ -14,6 +14,7 @@
 
 use ::datafusion::{arrow::datatypes::Schema, common::FileType, error::DataFusionError};
 use ahash::AHashMap;
+use bytes::Bytes;
 use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
 use std::{collections::HashMap, io::Write, sync::Arc};
 
@@ -25,12 +26,16 @@ use crate::common::{
     },
     meta::{
         common::{FileKey, FileMeta},
-        stream::StreamStats,
+        stream::{StreamSettings, StreamStats},
         StreamType,
     },
-    utils::json,
+    utils::{column_stats, json},
+};
+use crate::service::{
+    db, file_list,
+    search::{datafusion, fulltext_index},
+    stream,
 };
-use crate::service::{db, file_list, search::datafusion, stream};
 
 /// compactor run steps on a stream:
 /// 3. get a cluster lock for compactor stream
@@ -168,21 +173,26 @@ pub async fn merge_by_stream(
                 stream_name,
                 stream_type,
                 schema.clone(),
+                &stream_settings,
                 prefix,
                 files_with_size,
             )
             .await?;
-            if new_file_name.is_empty() {
+            if new_file_name.is_empty() && new_file_list.is_empty() {
                 break; // no file need to merge
             }
 
-            // delete small files keys & write big files keys, use transaction
+            // delete small files keys & write big files keys, use transaction,
+            // there is no big file when every document of the small files has
+            // a newer version
             let mut events = Vec::with_capacity(new_file_list.len() + 1);
-            events.push(FileKey {
-                key: new_file_name.clone(),
-                meta: new_file_meta,
-                deleted: false,
-            });
+            if !new_file_name.is_empty() {
+                events.push(FileKey {
+                    key: new_file_name.clone(),
+                    meta: new_file_meta,
+                    deleted: false,
+                });
+            }
             for file in new_file_list.iter() {
                 stream_stats = stream_stats - file.meta;
                 events.push(FileKey {
@@ -203,15 +213,9 @@ pub async fn merge_by_stream(
                 }
             }
 
-            // delete small files from storage
-            match storage::del(
-                &new_file_list
-                    .iter()
-                    .map(|v| v.key.as_str())
-                    .collect::<Vec<_>>(),
-            )
-            .await
-            {
+            // delete small files and their full text index from storage
+            let del_files = fulltext_index::file_keys_with_sidecars(&new_file_list);
+            match storage::del(&del_files.iter().map(|v| v.as_str()).collect::<Vec<_>>()).await {
                 Ok(_) => {}
                 Err(e) => {
                     log::error!("[COMPACT] delete file failed: {}", e);
@@ -222,6 +226,11 @@ pub async fn merge_by_stream(
         }
     }
 
+    // fold the latest versions of the documents saved by the requests
+    if stream_settings.upsert_by_id {
+        db::doc_version::fold(org_id, stream_name).await?;
+    }
+
     // write new offset
     let offset = offset_time_hour + Duration::hours(1).num_microseconds().unwrap();
     db::compact::files::set_offset(org_id, stream_name, stream_type, offset).await?;
@@ -256,6 +265,7 @@ async fn merge_files(
     stream_name: &str,
     stream_type: StreamType,
     schema: Arc<Schema>,
+    stream_settings: &StreamSettings,
     prefix: &str,
     files_with_size: &Vec<FileKey>,
 ) -> Result<(String, FileMeta, Vec<FileKey>), anyhow::Error> {
@@ -394,12 +404,20 @@ async fn merge_files(
         return Ok(("".to_string(), FileMeta::default(), vec![]));
     }
 
+    let index_fields =
+        fulltext_index::index_fields(&schema, &stream_settings.full_text_search_keys);
     let mut buf = Vec::new();
+    let versions = stream_settings
+        .upsert_by_id
+        .then(|| db::doc_version::latest(org_id, stream_name));
     let mut new_file_meta =
-        datafusion::exec::merge_parquet_files(tmp_dir.name(), &mut buf, schema).await?;
+        datafusion::exec::merge_parquet_files(tmp_dir.name(), &mut buf, schema, versions).await?;
     new_file_meta.original_size = new_file_size;
     new_file_meta.compressed_size = buf.len() as i64;
     if new_file_meta.records == 0 {
+        if stream_settings.upsert_by_id {
+            return Ok((String::new(), FileMeta::default(), new_file_list));
+        }
         return Err(anyhow::anyhow!("merge_parquet_files error: records is 0"));
     }
 
@@ -413,13 +431,76 @@ async fn merge_files(
         new_file_meta.compressed_size,
     );
 
+    let buf = Bytes::from(buf);
+
+    // build full text index, failing to build it only means no pruning
+    if CONFIG.common.feature_fulltext_index_enabled {
+        match build_fulltext_index(&new_file_key, &buf, &index_fields).await {
+            Ok(index_size) => new_file_meta.index_size = index_size,
+            Err(e) => {
+                log::error!(
+                    "[COMPACT] build fulltext index for file: {}, err: {}",
+                    new_file_key,
+                    e
+                );
+            }
+        }
+    }
+
+    // collect column stats, failing to collect them only means no pruning
+    if CONFIG.common.column_stats_enabled {
+        match build_column_stats(&new_file_key, &buf).await {
+            Ok(stats_size) => new_file_meta.stats_size = stats_size,
+            Err(e) => {
+                log::error!(
+                    "[COMPACT] collect column stats for file: {}, err: {}",
+                    new_file_key,
+                    e
+                );
+            }
+        }
+    }
+
     // upload file
-    match storage::put(&new_file_key, buf.into()).await {
+    match storage::put(&new_file_key, buf).await {
         Ok(_) => Ok((new_file_key, new_file_meta, new_file_list)),
         Err(e) => Err(e),
     }
 }
 
+/// build the full text index of a merged file and upload it next to the file,
+/// returns the index size, 0 means the file has no index
+async fn build_fulltext_index(
+    file_key: &str,
+    data: &Bytes,
+    fields: &[String],
+) -> Result<i64, anyhow::Error> {
+    if fields.is_empty() {
+        return Ok(0);
+    }
+    let data = data.clone();
+    let fields = fields.to_vec();
+    let index =
+        tokio::task::spawn_blocking(move || fulltext_index::FulltextIndex::build(&data, &fields))
+            .await??;
+    let Some(index) = index else {
+        log::info!("[COMPACT] skip fulltext index for file: {}, too many terms", file_key);
+        return Ok(0);
+    };
+    let buf = index.to_bytes()?;
+    let index_size = buf.len() as i64;
+    storage::put(&fulltext_index::index_file_key(file_key), buf.into()).await?;
+    Ok(index_size)
+}
+
+/// collect the column stats of a merged file and upload them next to the file,
+/// returns the stats size, 0 means the file has no stats
+async fn build_column_stats(file_key: &str, data: &Bytes) -> Result<i64, anyhow::Error> {
+    let data = data.clone();
+    let stats = tokio::task::spawn_blocking(move || column_stats::from_parquet(&data)).await??;
+    column_stats::upload(file_key, &stats).await
+}
+
 async fn write_file_list(events: &[FileKey]) -> Result<(), anyhow::Error> {
     if events.is_empty() {
         return Ok(());
@@ -32,7 +32,7 @@ use crate::common::{
     },
     utils::json,
 };
-use crate::service::{db, file_list};
+use crate::service::{db, file_list, search::fulltext_index};
 
 pub async fn delete_by_stream(
     lifecycle_end: &str,
@@ -112,7 +112,8 @@ pub async fn delete_all(
             0,
         )
         .await?;
-        match storage::del(&files.iter().map(|v| v.key.as_str()).collect::<Vec<_>>()).await {
+        let files = fulltext_index::file_keys_with_sidecars(&files);
+        match storage::del(&files.iter().map(|v| v.as_str()).collect::<Vec<_>>()).await {
             Ok(_) => {}
             Err(e) => {
                 log::error!("[COMPACT] delete file failed: {}", e);
@@ -221,7 +222,8 @@ pub async fn delete_by_date(
             time_range.1,
         )
         .await?;
-        match storage::del(&files.iter().map(|v| v.key.as_str()).collect::<Vec<_>>()).await {
+        let files = fulltext_index::file_keys_with_sidecars(&files);
+        match storage::del(&files.iter().map(|v| v.as_str()).collect::<Vec<_>>()).await {
             Ok(_) => {}
             Err(e) => {
                 log::error!("[COMPACT] delete file failed: {}", e);
@@ -0,0 +1,106 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::common::{
+    infra::{
+        config::DEAD_LETTER_SETTINGS,
+        db::{self, Event},
+    },
+    meta::dead_letter::DeadLetterSettings,
+    utils::json,
+};
+
+#[tracing::instrument(name = "service:db:dead_letter:get")]
+pub async fn get(org_id: &str) -> Result<DeadLetterSettings, anyhow::Error> {
+    let val = db::DEFAULT.get(&format!("/dead_letter/{org_id}")).await?;
+    Ok(json::from_slice(&val).unwrap())
+}
+
+#[tracing::instrument(name = "service:db:dead_letter:set", skip(settings))]
+pub async fn set(org_id: &str, settings: &DeadLetterSettings) -> Result<(), anyhow::Error> {
+    Ok(db::DEFAULT
+        .put(
+            &format!("/dead_letter/{org_id}"),
+            json::to_vec(settings).unwrap().into(),
+        )
+        .await?)
+}
+
+/// the `_timestamp` of the last dead-letter record replayed, by the stream and
+/// the stage of the records
+#[tracing::instrument(name = "service:db:dead_letter:get_replayed")]
+pub async fn get_replayed(org_id: &str) -> Result<HashMap<String, i64>, anyhow::Error> {
+    match db::DEFAULT
+        .get(&format!("/dead_letter_replayed/{org_id}"))
+        .await
+    {
+        Ok(val) => Ok(json::from_slice(&val)?),
+        Err(_) => Ok(HashMap::new()),
+    }
+}
+
+#[tracing::instrument(name = "service:db:dead_letter:set_replayed", skip(replayed))]
+pub async fn set_replayed(
+    org_id: &str,
+    replayed: &HashMap<String, i64>,
+) -> Result<(), anyhow::Error> {
+    Ok(db::DEFAULT
+        .put(
+            &format!("/dead_letter_replayed/{org_id}"),
+            json::to_vec(replayed)?.into(),
+        )
+        .await?)
+}
+
+pub async fn watch() -> Result<(), anyhow::Error> {
+    let key = "/dead_letter/";
+    let mut events = db::DEFAULT.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching dead letter settings");
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch_dead_letter: event channel closed");
+                break;
+            }
+        };
+        match ev {
+            Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                let item_value: DeadLetterSettings = json::from_slice(&ev.value.unwrap()).unwrap();
+                DEAD_LETTER_SETTINGS.insert(item_key.to_owned(), item_value);
+            }
+            Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                DEAD_LETTER_SETTINGS.remove(item_key);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache() -> Result<(), anyhow::Error> {
+    let key = "/dead_letter/";
+    let ret = db::DEFAULT.list(key).await?;
+    for (item_key, item_value) in ret {
+        let item_key = item_key.strip_prefix(key).unwrap();
+        let json_val: DeadLetterSettings = json::from_slice(&item_value).unwrap();
+        DEAD_LETTER_SETTINGS.insert(item_key.to_owned(), json_val);
+    }
+    log::info!("Dead letter settings Cached");
+    Ok(())
+}
@@ -0,0 +1,176 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ahash::AHashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::sync::Mutex;
+
+use crate::common::infra::{
+    config::{RwHashMap, CONFIG, DOC_VERSIONS},
+    db, dist_lock,
+};
+use crate::common::utils::json;
+use crate::service::db::rules::{self, Rule};
+
+/// the most `_id`s in a folded entry of the latest versions of a stream
+const FOLD_CHUNK_SIZE: usize = 10_000;
+/// the part of the keys of the folded entries after `{org_id}/{stream_name}/`
+const FOLDED: &str = "folded/";
+
+/// serializes the reservations of this node, the distributed lock does it
+/// between the nodes
+static RESERVE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// reserve `count` versions for the documents with an `_id` of the
+/// organization, returns the first one. the versions increase across the
+/// nodes in the order of the reservations, they start at the current time in
+/// microseconds, above the versions set with the ingestion time.
+pub async fn reserve(org_id: &str, count: i64) -> Result<i64, anyhow::Error> {
+    let _local = RESERVE_LOCK.lock().await;
+    let mut locker = dist_lock::lock(
+        &format!("/doc_version_lock/{org_id}"),
+        CONFIG.etcd.command_timeout,
+    )
+    .await?;
+    let ret = reserve_locked(&format!("/doc_version/{org_id}"), count).await;
+    dist_lock::unlock(&mut locker).await?;
+    ret
+}
+
+async fn reserve_locked(key: &str, count: i64) -> Result<i64, anyhow::Error> {
+    let first = match db::DEFAULT.get(key).await {
+        Ok(val) => json::from_slice(&val)?,
+        Err(_) => chrono::Utc::now().timestamp_micros(),
+    };
+    db::DEFAULT
+        .put(key, json::to_vec(&(first + count))?.into())
+        .await?;
+    Ok(first)
+}
+
+/// the latest versions of the `_id`s of a stream written by a request, stored
+/// at `{org_id}/{stream_name}/{first version}`, and the folded ones stored at
+/// `{org_id}/{stream_name}/folded/{chunk}`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DocVersions(pub AHashMap<String, i64>);
+
+impl Rule for DocVersions {
+    const PREFIX: &'static str = "/doc_latest/";
+    const NAME: &'static str = "document versions";
+
+    fn cache(key: &str, rule: Self) {
+        let mut parts = key.splitn(3, '/');
+        let (Some(org_id), Some(stream_name)) = (parts.next(), parts.next()) else {
+            return;
+        };
+        let latest = DOC_VERSIONS
+            .entry(format!("{org_id}/{stream_name}"))
+            .or_default()
+            .clone();
+        for (id, version) in rule.0 {
+            let mut entry = latest.entry(id).or_insert(version);
+            if *entry < version {
+                *entry = version;
+            }
+        }
+    }
+
+    // the entries are only deleted once folded, the versions of a deleted
+    // stream are lower than the ones reserved after
+    fn uncache(_key: &str) {}
+}
+
+/// the latest version of the `_id`s of the stream, an `_id` missing from it
+/// has a single version
+pub fn latest(org_id: &str, stream_name: &str) -> Arc<RwHashMap<String, i64>> {
+    DOC_VERSIONS
+        .get(&format!("{org_id}/{stream_name}"))
+        .map(|v| v.clone())
+        .unwrap_or_default()
+}
+
+/// save the versions of the `_id`s written by a request to the stream, they
+/// are the latest ones as the versions increase in the order of the writes
+pub async fn set(
+    org_id: &str,
+    stream_name: &str,
+    versions: AHashMap<String, i64>,
+) -> Result<(), anyhow::Error> {
+    let Some(first) = versions.values().min() else {
+        return Ok(());
+    };
+    let versions = DocVersions(versions);
+    rules::set(&format!("{org_id}/{stream_name}/{first}"), &versions).await?;
+    DocVersions::cache(&format!("{org_id}/{stream_name}/{first}"), versions);
+    Ok(())
+}
+
+/// fold the entries of the requests of the stream into chunks of
+/// `FOLD_CHUNK_SIZE` ids, the ids are never removed so the chunks written
+/// before are all overwritten
+pub async fn fold(org_id: &str, stream_name: &str) -> Result<(), anyhow::Error> {
+    let prefix = format!("{org_id}/{stream_name}/");
+    let entries = rules::list::<DocVersions>(&prefix).await?;
+    let folded_prefix = format!("{prefix}{FOLDED}");
+    if entries
+        .iter()
+        .all(|(key, _)| key.starts_with(&folded_prefix))
+    {
+        return Ok(());
+    }
+    let mut latest: BTreeMap<String, i64> = BTreeMap::new();
+    for (_, versions) in entries.iter() {
+        for (id, version) in versions.0.iter() {
+            let entry = latest.entry(id.clone()).or_insert(*version);
+            *entry = (*entry).max(*version);
+        }
+    }
+    let ids = latest.into_iter().collect::<Vec<_>>();
+    for (i, chunk) in ids.chunks(FOLD_CHUNK_SIZE).enumerate() {
+        let versions = DocVersions(chunk.iter().cloned().collect());
+        rules::set(&format!("{folded_prefix}{i}"), &versions).await?;
+    }
+    for (key, _) in entries {
+        if !key.starts_with(&folded_prefix) {
+            rules::delete::<DocVersions>(&key).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_latest() {
+        let versions = |v: &[(&str, i64)]| {
+            DocVersions(
+                v.iter()
+                    .map(|(id, version)| (id.to_string(), *version))
+                    .collect(),
+            )
+        };
+        DocVersions::cache("org/audit/5", versions(&[("a", 5), ("b", 6)]));
+        DocVersions::cache("org/audit/folded/0", versions(&[("a", 3), ("c", 1)]));
+        DocVersions::cache("org/audit/7", versions(&[("b", 7)]));
+        let latest = latest("org", "audit");
+        assert_eq!(latest.get("a").map(|v| *v), Some(5));
+        assert_eq!(latest.get("b").map(|v| *v), Some(7));
+        assert_eq!(latest.get("c").map(|v| *v), Some(1));
+        assert!(super::latest("org", "other").is_empty());
+    }
+}
@@ -20,7 +20,7 @@ use crate::common::{
     meta::{self, search::Request},
     utils::json,
 };
-use crate::service::search as SearchService;
+use crate::service::search::{self as SearchService, queue::Priority};
 
 pub async fn get(org_id: &str, name: &str) -> Result<Vec<vrl::value::Value>, anyhow::Error> {
     let stats = stats::get_stream_stats(org_id, name, meta::StreamType::EnrichmentTables);
@@ -43,7 +43,15 @@ pub async fn get(org_id: &str, name: &str) -> Result<Vec<vrl::value::Value>, any
         encoding: meta::search::RequestEncoding::Empty,
     };
     // do search
-    match SearchService::search(org_id, meta::StreamType::EnrichmentTables, &req).await {
+    match SearchService::search(
+        org_id,
+        meta::StreamType::EnrichmentTables,
+        "",
+        Priority::Batch,
+        &req,
+    )
+    .await
+    {
         Ok(res) => {
             if !res.hits.is_empty() {
                 Ok(res.hits.iter().map(convert_to_vrl).collect())
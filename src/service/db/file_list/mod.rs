@@ -21,6 +21,7 @@ use crate::common::infra::{
     file_list,
 };
 use crate::common::meta::common::FileMeta;
+use crate::service::search;
 
 pub mod broadcast;
 pub mod local;
@@ -38,6 +39,8 @@ pub async fn progress(
     delete: bool,
     download: bool,
 ) -> Result<(), anyhow::Error> {
+    // the cached search results of the time range are outdated
+    search::cache::invalidate(key, data);
     if delete {
         if let Err(e) = file_list::remove(key).await {
             log::error!(
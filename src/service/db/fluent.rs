@@ -0,0 +1,30 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::{infra::config::FLUENT_ROUTES, meta::fluent::FluentRoute};
+use crate::service::db::rules::Rule;
+
+/// the routes are stored at `{org_id}/{id}`
+impl Rule for FluentRoute {
+    const PREFIX: &'static str = "/fluent/route/";
+    const NAME: &'static str = "fluent routes";
+
+    fn cache(key: &str, rule: Self) {
+        FLUENT_ROUTES.insert(key.to_owned(), rule);
+    }
+
+    fn uncache(key: &str) {
+        FLUENT_ROUTES.remove(key);
+    }
+}
@@ -17,12 +17,23 @@ use crate::common::utils::json;
 pub mod alerts;
 pub mod compact;
 pub mod dashboard;
+pub mod dead_letter;
+pub mod doc_version;
 pub mod enrichment_table;
 pub mod file_list;
+pub mod fluent;
 pub mod functions;
 pub mod kv;
 pub mod metrics;
+pub mod quota;
+pub mod redaction;
+pub mod rules;
+pub mod sampling;
 pub mod schema;
+pub mod search_job;
+pub mod search_queue;
+pub mod splunk_ack;
+pub mod stream_routes;
 pub mod syslog;
 pub mod triggers;
 pub mod user;
@@ -0,0 +1,73 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use crate::common::{
+    infra::{config::INGESTION_QUOTAS, db},
+    meta::quota::{IngestionQuota, NodeQuotaUsage},
+    utils::json,
+};
+use crate::service::db::rules::Rule;
+
+/// the quota of the organization is stored at `{org_id}` and the quota of a
+/// stream at `{org_id}/{stream_name}`
+pub fn quota_key(org_id: &str, stream_name: Option<&str>) -> String {
+    match stream_name {
+        Some(stream_name) => format!("{org_id}/{stream_name}"),
+        None => org_id.to_string(),
+    }
+}
+
+impl Rule for IngestionQuota {
+    const PREFIX: &'static str = "/ingestion_quota/";
+    const NAME: &'static str = "ingestion quotas";
+
+    fn cache(key: &str, rule: Self) {
+        INGESTION_QUOTAS.insert(key.to_owned(), rule);
+    }
+
+    fn uncache(key: &str) {
+        INGESTION_QUOTAS.remove(key);
+    }
+}
+
+#[tracing::instrument(name = "service:db:quota:set_usage", skip(usage))]
+pub async fn set_usage(node: &str, usage: &NodeQuotaUsage) -> Result<(), anyhow::Error> {
+    Ok(db::DEFAULT
+        .put(
+            &format!("/ingestion_quota_usage/{node}"),
+            json::to_vec(usage).unwrap().into(),
+        )
+        .await?)
+}
+
+#[tracing::instrument(name = "service:db:quota:delete_usage")]
+pub async fn delete_usage(node: &str) -> Result<(), anyhow::Error> {
+    Ok(db::DEFAULT
+        .delete(&format!("/ingestion_quota_usage/{node}"), false)
+        .await?)
+}
+
+/// the usage published by every ingester, by node
+#[tracing::instrument(name = "service:db:quota:list_usage")]
+pub async fn list_usage() -> Result<Vec<(String, NodeQuotaUsage)>, anyhow::Error> {
+    let key = "/ingestion_quota_usage/";
+    Ok(db::DEFAULT
+        .list(key)
+        .await?
+        .into_iter()
+        .filter_map(|(item_key, item_value)| {
+            let usage = json::from_slice(&item_value).ok()?;
+            Some((item_key.strip_prefix(key).unwrap().to_string(), usage))
+        })
+        .collect())
+}
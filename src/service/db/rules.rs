@@ -0,0 +1,105 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
+
+use crate::common::{
+    infra::db::{self, Event},
+    utils::json,
+};
+
+/// the rules stored under a prefix of the db and cached on every node by
+/// their key under the prefix
+pub trait Rule: Serialize + DeserializeOwned {
+    /// the prefix of the keys of the rules, ends with a `/`
+    const PREFIX: &'static str;
+    /// the rules in the logs
+    const NAME: &'static str;
+
+    fn cache(key: &str, rule: Self);
+
+    fn uncache(key: &str);
+}
+
+/// the rule stored at the key, `None` when there is none
+pub async fn get<T: Rule>(key: &str) -> Result<Option<T>, anyhow::Error> {
+    match db::DEFAULT.get(&format!("{}{key}", T::PREFIX)).await {
+        Ok(val) => Ok(Some(json::from_slice(&val)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// the rules stored under the key prefix, with their keys
+pub async fn list<T: Rule>(prefix: &str) -> Result<Vec<(String, T)>, anyhow::Error> {
+    db::DEFAULT
+        .list(&format!("{}{prefix}", T::PREFIX))
+        .await?
+        .into_iter()
+        .map(|(item_key, item_value)| {
+            let item_key = item_key.strip_prefix(T::PREFIX).unwrap().to_string();
+            Ok((item_key, json::from_slice(&item_value)?))
+        })
+        .collect()
+}
+
+pub async fn set<T: Rule>(key: &str, rule: &T) -> Result<(), anyhow::Error> {
+    Ok(db::DEFAULT
+        .put(&format!("{}{key}", T::PREFIX), json::to_vec(rule)?.into())
+        .await?)
+}
+
+pub async fn delete<T: Rule>(key: &str) -> Result<(), anyhow::Error> {
+    Ok(db::DEFAULT
+        .delete(&format!("{}{key}", T::PREFIX), false)
+        .await?)
+}
+
+pub async fn watch<T: Rule>() -> Result<(), anyhow::Error> {
+    let key = T::PREFIX;
+    let mut events = db::DEFAULT.watch(key).await?;
+    let events = Arc::get_mut(&mut events).unwrap();
+    log::info!("Start watching {}", T::NAME);
+    loop {
+        let ev = match events.recv().await {
+            Some(ev) => ev,
+            None => {
+                log::error!("watch {}: event channel closed", T::NAME);
+                break;
+            }
+        };
+        match ev {
+            Event::Put(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                match json::from_slice(&ev.value.unwrap()) {
+                    Ok(item_value) => T::cache(item_key, item_value),
+                    Err(e) => log::error!("watch {}: invalid {item_key}: {e}", T::NAME),
+                }
+            }
+            Event::Delete(ev) => {
+                let item_key = ev.key.strip_prefix(key).unwrap();
+                T::uncache(item_key);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn cache<T: Rule>() -> Result<(), anyhow::Error> {
+    for (item_key, item_value) in list::<T>("").await? {
+        T::cache(&item_key, item_value);
+    }
+    log::info!("{} Cached", T::NAME);
+    Ok(())
+}
@@ -0,0 +1,61 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use crate::common::{infra::config::STREAM_SAMPLING_RULES, meta::sampling::SamplingRule};
+use crate::service::db::rules::Rule;
+
+impl Rule for SamplingRule {
+    const PREFIX: &'static str = "/sampling_rule/";
+    const NAME: &'static str = "sampling rules";
+
+    fn cache(_key: &str, rule: Self) {
+        cache_rule(rule);
+    }
+
+    fn uncache(key: &str) {
+        uncache_rule(key);
+    }
+}
+
+/// the rules of a stream are replaced as a whole, the compiled rules are
+/// compiled again when they change
+fn cache_rule(rule: SamplingRule) {
+    let key = format!("{}/{}", rule.org_id, rule.stream_name);
+    let mut rules = STREAM_SAMPLING_RULES
+        .get(&key)
+        .map(|v| v.as_ref().clone())
+        .unwrap_or_default();
+    rules.retain(|v| v.name != rule.name);
+    rules.push(rule);
+    rules.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.name.cmp(&b.name)));
+    STREAM_SAMPLING_RULES.insert(key, Arc::new(rules));
+}
+
+fn uncache_rule(item_key: &str) {
+    let Some((key, name)) = item_key.rsplit_once('/') else {
+        return;
+    };
+    let mut rules = match STREAM_SAMPLING_RULES.get(key) {
+        Some(v) => v.as_ref().clone(),
+        None => return,
+    };
+    rules.retain(|v| v.name != name);
+    if rules.is_empty() {
+        STREAM_SAMPLING_RULES.remove(key);
+    } else {
+        STREAM_SAMPLING_RULES.insert(key.to_string(), Arc::new(rules));
+    }
+}
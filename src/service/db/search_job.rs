@@ -0,0 +1,45 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::{infra::db, meta::search::Job, utils::json};
+
+pub async fn get(org_id: &str, job_id: &str) -> Result<Job, anyhow::Error> {
+    let key = format!("/search_job/{org_id}/{job_id}");
+    let bytes = db::DEFAULT.get(&key).await?;
+    Ok(json::from_slice(&bytes)?)
+}
+
+pub async fn put(job: &Job) -> Result<(), anyhow::Error> {
+    let key = format!("/search_job/{}/{}", job.org_id, job.id);
+    Ok(db::DEFAULT.put(&key, json::to_vec(job)?.into()).await?)
+}
+
+/// list the jobs of an organization, all the jobs if `org_id` is empty
+pub async fn list(org_id: &str) -> Result<Vec<Job>, anyhow::Error> {
+    let key = if org_id.is_empty() {
+        "/search_job/".to_string()
+    } else {
+        format!("/search_job/{org_id}/")
+    };
+    let mut jobs = Vec::new();
+    for val in db::DEFAULT.list(&key).await?.into_values() {
+        jobs.push(json::from_slice(&val)?);
+    }
+    Ok(jobs)
+}
+
+pub async fn delete(org_id: &str, job_id: &str) -> Result<(), anyhow::Error> {
+    let key = format!("/search_job/{org_id}/{job_id}");
+    Ok(db::DEFAULT.delete_if_exists(&key, false).await?)
+}
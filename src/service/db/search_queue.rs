@@ -0,0 +1,47 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::{infra::db, meta::search::NodeSearchRunning, utils::json};
+
+#[tracing::instrument(name = "service:db:search_queue:set_running", skip(running))]
+pub async fn set_running(node: &str, running: &NodeSearchRunning) -> Result<(), anyhow::Error> {
+    Ok(db::DEFAULT
+        .put(
+            &format!("/search_queue_running/{node}"),
+            json::to_vec(running).unwrap().into(),
+        )
+        .await?)
+}
+
+#[tracing::instrument(name = "service:db:search_queue:delete_running")]
+pub async fn delete_running(node: &str) -> Result<(), anyhow::Error> {
+    Ok(db::DEFAULT
+        .delete(&format!("/search_queue_running/{node}"), false)
+        .await?)
+}
+
+/// the searches published by every querier, by node
+#[tracing::instrument(name = "service:db:search_queue:list_running")]
+pub async fn list_running() -> Result<Vec<(String, NodeSearchRunning)>, anyhow::Error> {
+    let key = "/search_queue_running/";
+    Ok(db::DEFAULT
+        .list(key)
+        .await?
+        .into_iter()
+        .filter_map(|(item_key, item_value)| {
+            let running = json::from_slice(&item_value).ok()?;
+            Some((item_key.strip_prefix(key).unwrap().to_string(), running))
+        })
+        .collect())
+}
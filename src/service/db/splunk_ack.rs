@@ -0,0 +1,59 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::infra::db;
+
+/// the pending acks of a channel are stored at `{org_id}/{channel}/{ack_id}`,
+/// so any node of the cluster can answer the ack queries of a channel
+const PREFIX: &str = "/splunk_ack/";
+
+fn channel_key(org_id: &str, channel: &str) -> String {
+    format!("{PREFIX}{org_id}/{channel}/")
+}
+
+pub async fn set(org_id: &str, channel: &str, ack_id: u64) -> Result<(), anyhow::Error> {
+    let key = format!("{}{ack_id}", channel_key(org_id, channel));
+    Ok(db::DEFAULT.put(&key, bytes::Bytes::new()).await?)
+}
+
+/// removes a pending ack, returns whether it was pending
+pub async fn take(org_id: &str, channel: &str, ack_id: u64) -> Result<bool, anyhow::Error> {
+    let key = format!("{}{ack_id}", channel_key(org_id, channel));
+    let db = &db::DEFAULT;
+    if db.get(&key).await.is_err() {
+        return Ok(false);
+    }
+    db.delete(&key, false).await?;
+    Ok(true)
+}
+
+/// drops the oldest pending acks of a channel above `max`, the ack ids
+/// increase with the time
+pub async fn truncate(org_id: &str, channel: &str, max: usize) -> Result<(), anyhow::Error> {
+    let prefix = channel_key(org_id, channel);
+    let db = &db::DEFAULT;
+    let keys = db.list_keys(&prefix).await?;
+    if keys.len() <= max {
+        return Ok(());
+    }
+    let mut ids = keys
+        .iter()
+        .filter_map(|key| key.strip_prefix(&prefix)?.parse::<u64>().ok())
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    for id in &ids[..ids.len().saturating_sub(max)] {
+        db.delete(&format!("{prefix}{id}"), false).await?;
+    }
+    Ok(())
+}
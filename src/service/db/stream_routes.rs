@@ -0,0 +1,29 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::{infra::config::STREAM_ROUTES, meta::routing::StreamRoute};
+use crate::service::db::rules::Rule;
+
+impl Rule for StreamRoute {
+    const PREFIX: &'static str = "/stream_route/";
+    const NAME: &'static str = "stream routes";
+
+    fn cache(key: &str, rule: Self) {
+        STREAM_ROUTES.insert(key.to_owned(), rule);
+    }
+
+    fn uncache(key: &str) {
+        STREAM_ROUTES.remove(key);
+    }
+}
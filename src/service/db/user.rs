@@ -14,7 +14,7 @@
 
 use std::sync::Arc;
 
-use crate::common::infra::config::{CONFIG, ROOT_USER, USERS};
+use crate::common::infra::config::{CONFIG, ROOT_USER, USERS, USER_PASSCODES};
 use crate::common::infra::db::{Event, CLUSTER_COORDINATOR};
 use crate::common::meta::meta_store::MetaStore;
 use crate::common::meta::user::{DBUser, User, UserRole};
@@ -144,14 +144,14 @@ pub async fn watch() -> Result<(), anyhow::Error> {
                     if user.role.eq(&UserRole::Root) {
                         ROOT_USER.insert("root".to_string(), user.clone());
                     }
-                    USERS.insert(format!("{}/{}", user.org, item_key), user);
+                    cache_user(format!("{}/{}", user.org, item_key), user);
                 }
             }
             Event::Delete(ev) => {
                 let item_key = ev.key.strip_prefix(key).unwrap();
                 for user in USERS.clone() {
                     if user.1.email.eq(item_key) {
-                        USERS.remove(&format!("{}/{}", user.1.org, user.1.email));
+                        uncache_user(&format!("{}/{}", user.1.org, user.1.email));
                         break;
                     }
                 }
@@ -173,13 +173,37 @@ pub async fn cache() -> Result<(), anyhow::Error> {
             if user.role.eq(&UserRole::Root) {
                 ROOT_USER.insert("root".to_string(), user.clone());
             }
-            USERS.insert(format!("{}/{}", user.org, user.email), user);
+            cache_user(format!("{}/{}", user.org, user.email), user);
         }
     }
     log::info!("Users Cached");
     Ok(())
 }
 
+/// cache the user of the org under `{org}/{email}` and index its passcode
+pub fn cache_user(key: String, user: User) {
+    let passcode_key = format!("{}/{}", user.org, user.token);
+    let email = user.email.clone();
+    let has_token = !user.token.is_empty();
+    if let Some(old) = USERS.insert(key, user) {
+        if !old.token.is_empty() {
+            USER_PASSCODES.remove(&format!("{}/{}", old.org, old.token));
+        }
+    }
+    if has_token {
+        USER_PASSCODES.insert(passcode_key, email);
+    }
+}
+
+/// remove the cached user of the org and its passcode
+pub fn uncache_user(key: &str) {
+    if let Some((_, user)) = USERS.remove(key) {
+        if !user.token.is_empty() {
+            USER_PASSCODES.remove(&format!("{}/{}", user.org, user.token));
+        }
+    }
+}
+
 pub async fn root_user_exists() -> bool {
     let db = &crate::common::infra::db::DEFAULT;
     let key = "/user/";
@@ -41,7 +41,7 @@ use crate::common::{
 use crate::service::{
     compact::retention,
     db, format_stream_name,
-    ingestion::{chk_schema_by_record, write_file},
+    ingestion::{chk_schema_by_record, redaction, write_file},
     schema::stream_schema_exists,
     usage::report_request_usage_stats,
 };
@@ -120,6 +120,14 @@ pub async fn save_enrichment_data(
                         CONFIG.common.column_timestamp.clone(),
                         json::Value::Number(timestamp.into()),
                     );
+                    redaction::redact(
+                        &StreamParams {
+                            org_id,
+                            stream_name: stream_name,
+                            stream_type: StreamType::EnrichmentTables,
+                        },
+                        &mut json_record,
+                    );
                     let value_str = json::to_string(&json_record).unwrap();
                     chk_schema_by_record(
                         &mut stream_schema_map,
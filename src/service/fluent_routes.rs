@@ -0,0 +1,120 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::HttpResponse;
+use std::io;
+
+use crate::common::infra::config::FLUENT_ROUTES;
+use crate::common::meta::fluent::{FluentRoute, FluentRoutes};
+use crate::service::{
+    db,
+    rules::{self, Response},
+};
+
+const KIND: &str = "Fluent route";
+
+#[tracing::instrument(skip_all)]
+pub async fn create_route(org_id: &str, mut route: FluentRoute) -> Result<HttpResponse, io::Error> {
+    route.org_id = org_id.to_string();
+    if route.tag_pattern.trim().is_empty() {
+        return Ok(Response::BadRequest("Please provide tag pattern for route".to_owned()).into());
+    }
+    if pattern_exists(org_id, &route.tag_pattern, None) {
+        return Ok(Response::BadRequest(format!(
+            "Provided tag pattern exists for organization {org_id}"
+        ))
+        .into());
+    }
+
+    route.id = crate::common::infra::ider::generate();
+    if let Err(e) = db::rules::set(&format!("{org_id}/{}", route.id), &route).await {
+        return Ok(Response::InternalServerError(e).into());
+    }
+    tracing::info!(id = route.id, "Fluent Route created");
+    Ok(HttpResponse::Created().json(route))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn update_route(
+    org_id: &str,
+    id: &str,
+    route: &mut FluentRoute,
+) -> Result<HttpResponse, io::Error> {
+    let key = format!("{org_id}/{id}");
+    let old_route = match db::rules::get::<FluentRoute>(&key).await {
+        Ok(Some(route)) => route,
+        Ok(None) => return Ok(Response::NotFound(KIND).into()),
+        Err(e) => return Ok(Response::InternalServerError(e).into()),
+    };
+    route.id = id.to_owned();
+    route.org_id = org_id.to_owned();
+    if route.tag_pattern.is_empty() {
+        route.tag_pattern = old_route.tag_pattern.clone();
+    }
+
+    if route == &old_route {
+        return Ok(HttpResponse::Ok().json(route));
+    }
+    if pattern_exists(org_id, &route.tag_pattern, Some(id)) {
+        return Ok(Response::BadRequest(format!(
+            "Provided tag pattern exists for organization {org_id}"
+        ))
+        .into());
+    }
+
+    if let Err(error) = db::rules::set(&key, route).await {
+        tracing::error!(%error, id, "Failed to save the fluent route");
+        return Ok(Response::InternalServerError(error).into());
+    }
+    Ok(HttpResponse::Ok().json(route))
+}
+
+#[tracing::instrument]
+pub async fn list_routes(org_id: &str) -> Result<HttpResponse, io::Error> {
+    match db::rules::list::<FluentRoute>(&format!("{org_id}/")).await {
+        Ok(routes) => Ok(HttpResponse::Ok().json(FluentRoutes {
+            routes: routes.into_iter().map(|(_, route)| route).collect(),
+        })),
+        Err(e) => Ok(Response::InternalServerError(e).into()),
+    }
+}
+
+#[tracing::instrument]
+pub async fn delete_route(org_id: &str, id: &str) -> Result<HttpResponse, io::Error> {
+    Ok(rules::delete_rule::<FluentRoute>(&format!("{org_id}/{id}"), KIND).await)
+}
+
+/// the most specific route whose tag pattern matches the tag
+pub fn get_route_for_tag(tag: &str) -> Option<FluentRoute> {
+    FLUENT_ROUTES
+        .iter()
+        .filter(|route| route.value().matches(tag))
+        .max_by(|a, b| {
+            a.value()
+                .specificity()
+                .cmp(&b.value().specificity())
+                // the same pattern of more orgs is routed the same every time
+                .then_with(|| b.key().cmp(a.key()))
+        })
+        .map(|route| route.value().clone())
+}
+
+/// another route of the org has the tag pattern
+fn pattern_exists(org_id: &str, tag_pattern: &str, id: Option<&str>) -> bool {
+    FLUENT_ROUTES.iter().any(|r| {
+        r.value().org_id.eq(org_id)
+            && r.value().tag_pattern.eq(tag_pattern)
+            && id.map_or(true, |id| r.value().id.ne(id))
+    })
+}
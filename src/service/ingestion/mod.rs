@@ -46,6 +46,8 @@ use crate::common::{
 };
 use crate::service::{db, format_partition_key, stream::stream_settings, triggers};
 pub mod grpc;
+pub mod quota;
+pub mod redaction;
 
 pub fn compile_vrl_function(func: &str, org_id: &str) -> Result<VRLRuntimeConfig, std::io::Error> {
     if func.contains("get_env_var") {
@@ -80,6 +82,21 @@ pub fn compile_vrl_function(func: &str, org_id: &str) -> Result<VRLRuntimeConfig
 }
 
 pub fn apply_vrl_fn(runtime: &mut Runtime, vrl_runtime: &VRLRuntimeConfig, row: &Value) -> Value {
+    match try_apply_vrl_fn(runtime, vrl_runtime, row) {
+        Ok(val) => val,
+        Err(err) => {
+            log::error!("Returning original row, {err}");
+            row.clone()
+        }
+    }
+}
+
+/// run the function on the row, the errors of the function are returned
+pub fn try_apply_vrl_fn(
+    runtime: &mut Runtime,
+    vrl_runtime: &VRLRuntimeConfig,
+    row: &Value,
+) -> Result<Value, anyhow::Error> {
     let mut metadata = vrl::value::Value::from(BTreeMap::new());
     let mut target = TargetValueRef {
         value: &mut vrl::value::Value::from(row),
@@ -93,14 +110,10 @@ pub fn apply_vrl_fn(runtime: &mut Runtime, vrl_runtime: &VRLRuntimeConfig, row:
         }
     };
     match result {
-        Ok(res) => match res.try_into() {
-            Ok(val) => val,
-            Err(_) => row.clone(),
-        },
-        Err(err) => {
-            log::error!("Returning original row , got error from vrl {:?}", err);
-            row.clone()
-        }
+        Ok(res) => res
+            .try_into()
+            .map_err(|err| anyhow::anyhow!("function result is not json: {err:?}")),
+        Err(err) => Err(anyhow::anyhow!("function error: {err}")),
     }
 }
 
@@ -236,6 +249,8 @@ pub fn register_stream_transforms(
     (local_trans, stream_vrl_map)
 }
 
+/// apply the functions of the stream in order, fails with the error of the
+/// first function failing
 pub fn apply_stream_transform<'a>(
     local_trans: &Vec<StreamTransform>,
     value: &'a Value,
@@ -248,7 +263,8 @@ pub fn apply_stream_transform<'a>(
         let func_key = format!("{stream_name}/{}", trans.transform.name);
         if stream_vrl_map.contains_key(&func_key) && !value.is_null() {
             let vrl_runtime = stream_vrl_map.get(&func_key).unwrap();
-            value = apply_vrl_fn(runtime, vrl_runtime, &value);
+            value = try_apply_vrl_fn(runtime, vrl_runtime, &value)
+                .map_err(|err| anyhow::anyhow!("{}: {err}", trans.transform.name))?;
         }
     }
     flatten::flatten(&value)
@@ -0,0 +1,579 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use actix_web::{http::StatusCode, HttpResponse};
+use ahash::AHashMap;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::{collections::HashMap, io};
+
+use crate::common::infra::{
+    cluster::LOCAL_NODE_UUID,
+    config::{CONFIG, INGESTION_QUOTAS},
+    metrics,
+};
+use crate::common::meta::{
+    http::HttpResponse as MetaHttpResponse,
+    quota::{IngestionQuota, NodeQuotaUsage, OrgIngestionQuotas, QuotaLimit, QuotaUsage},
+};
+use crate::service::{
+    db, format_stream_name,
+    rules::{self, Response},
+    usage,
+};
+
+const SECOND_MICROS: i64 = 1_000_000;
+const DAY_MICROS: i64 = 86_400 * SECOND_MICROS;
+
+/// the token buckets of the quotas on this node
+static QUOTAS: Lazy<Mutex<Quotas>> = Lazy::new(|| {
+    Mutex::new(Quotas {
+        states: HashMap::new(),
+        nodes: 1,
+    })
+});
+
+/// a request rejected by an ingestion quota
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub key: String,
+    pub limit: QuotaLimit,
+    /// seconds to wait before retrying
+    pub retry_after: u64,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Ingestion quota exceeded for [{}]: {}, retry after {} seconds",
+            self.key, self.limit, self.retry_after
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+impl QuotaExceeded {
+    /// a 429 with the seconds to wait before retrying
+    pub fn to_response(&self) -> HttpResponse {
+        HttpResponse::TooManyRequests()
+            .insert_header((
+                actix_web::http::header::RETRY_AFTER,
+                self.retry_after.to_string(),
+            ))
+            .json(MetaHttpResponse::error(
+                StatusCode::TOO_MANY_REQUESTS.into(),
+                self.to_string(),
+            ))
+    }
+}
+
+struct Quotas {
+    states: HashMap<String, QuotaState>,
+    /// online ingesters at the last sync, the quotas are shared between them
+    nodes: usize,
+}
+
+/// tokens refill at the rate of the limit and the bucket holds one second of
+/// tokens
+struct TokenBucket {
+    tokens: f64,
+    updated_at: i64,
+}
+
+impl TokenBucket {
+    fn new(now: i64) -> Self {
+        Self {
+            tokens: f64::INFINITY,
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, rate: f64, now: i64) {
+        let elapsed = (now - self.updated_at).max(0) as f64 / SECOND_MICROS as f64;
+        self.tokens = (self.tokens + rate * elapsed).min(rate);
+        self.updated_at = now;
+    }
+
+    /// a request larger than the bucket is accepted when the bucket is full,
+    /// the next ones wait until its debt is paid back
+    fn is_available(&self, amount: f64, rate: f64) -> bool {
+        self.tokens >= amount || self.tokens >= rate
+    }
+
+    fn retry_after(&self, amount: f64, rate: f64) -> u64 {
+        let missing = amount.min(rate) - self.tokens;
+        ((missing / rate).ceil() as u64).max(1)
+    }
+}
+
+struct QuotaState {
+    events: TokenBucket,
+    bytes: TokenBucket,
+    /// share of the cluster wide rates this node may use
+    events_share: f64,
+    bytes_share: f64,
+    /// events and bytes requested since the last sync
+    demand_events: u64,
+    demand_bytes: u64,
+    /// days since the unix epoch of the daily bytes
+    day: i64,
+    daily_bytes: u64,
+    /// bytes accepted by the other nodes during the day, at the last sync
+    others_daily_bytes: u64,
+}
+
+impl QuotaState {
+    fn new(share: f64, now: i64) -> Self {
+        Self {
+            events: TokenBucket::new(now),
+            bytes: TokenBucket::new(now),
+            events_share: share,
+            bytes_share: share,
+            demand_events: 0,
+            demand_bytes: 0,
+            day: now / DAY_MICROS,
+            daily_bytes: 0,
+            others_daily_bytes: 0,
+        }
+    }
+
+    fn roll_day(&mut self, now: i64) {
+        let day = now / DAY_MICROS;
+        if day != self.day {
+            self.day = day;
+            self.daily_bytes = 0;
+            self.others_daily_bytes = 0;
+        }
+    }
+
+    /// returns the exceeded limit and the seconds to wait
+    fn admit(
+        &mut self,
+        quota: &IngestionQuota,
+        events: u64,
+        bytes: u64,
+        now: i64,
+    ) -> Result<(), (QuotaLimit, u64)> {
+        self.roll_day(now);
+        if quota.daily_bytes > 0
+            && self.daily_bytes + self.others_daily_bytes + bytes > quota.daily_bytes
+        {
+            let retry_after = (DAY_MICROS - now % DAY_MICROS) / SECOND_MICROS;
+            return Err((QuotaLimit::DailyBytes, retry_after.max(1) as u64));
+        }
+        if quota.events_per_second > 0 {
+            let rate = quota.events_per_second as f64 * self.events_share;
+            self.events.refill(rate, now);
+            if !self.events.is_available(events as f64, rate) {
+                let retry_after = self.events.retry_after(events as f64, rate);
+                return Err((QuotaLimit::EventsPerSecond, retry_after));
+            }
+        }
+        if quota.bytes_per_second > 0 {
+            let rate = quota.bytes_per_second as f64 * self.bytes_share;
+            self.bytes.refill(rate, now);
+            if !self.bytes.is_available(bytes as f64, rate) {
+                let retry_after = self.bytes.retry_after(bytes as f64, rate);
+                return Err((QuotaLimit::BytesPerSecond, retry_after));
+            }
+        }
+        Ok(())
+    }
+
+    fn consume(&mut self, quota: &IngestionQuota, events: u64, bytes: u64) {
+        if quota.events_per_second > 0 {
+            self.events.tokens -= events as f64;
+        }
+        if quota.bytes_per_second > 0 {
+            self.bytes.tokens -= bytes as f64;
+        }
+        self.daily_bytes += bytes;
+    }
+}
+
+/// the quota of the organizations without one
+fn default_quota() -> IngestionQuota {
+    IngestionQuota {
+        events_per_second: CONFIG.limit.ingest_quota_events_per_sec,
+        bytes_per_second: CONFIG.limit.ingest_quota_bytes_per_sec,
+        daily_bytes: CONFIG.limit.ingest_quota_daily_bytes,
+    }
+}
+
+/// the quota of the organization, or the default one
+fn org_quota(org_id: &str) -> Option<IngestionQuota> {
+    let quota = match INGESTION_QUOTAS.get(org_id) {
+        Some(quota) => quota.clone(),
+        None => default_quota(),
+    };
+    (!quota.is_unlimited()).then_some(quota)
+}
+
+/// a request to the streams is counted against the quota of each stream and
+/// against the quota of the organization for all of them
+fn quotas_of(org_id: &str, streams: &[(&str, u64, u64)]) -> Vec<Counted> {
+    let mut quotas = vec![];
+    if let Some(quota) = org_quota(org_id) {
+        let (events, bytes) = streams
+            .iter()
+            .fold((0, 0), |(events, bytes), v| (events + v.1, bytes + v.2));
+        quotas.push((org_id.to_string(), quota, events, bytes));
+    }
+    for (stream_name, events, bytes) in streams {
+        let key = db::quota::quota_key(org_id, Some(stream_name));
+        if let Some(quota) = INGESTION_QUOTAS.get(&key) {
+            if !quota.is_unlimited() {
+                quotas.push((key, quota.clone(), *events, *bytes));
+            }
+        }
+    }
+    quotas
+}
+
+/// the key of a quota, the quota and the events and bytes counted against it
+type Counted = (String, IngestionQuota, u64, u64);
+
+/// whether the organization or any of its streams has a quota, to skip
+/// measuring the requests of the others
+pub fn has_quotas(org_id: &str) -> bool {
+    org_quota(org_id).is_some()
+        || INGESTION_QUOTAS.iter().any(|item| {
+            item.key()
+                .strip_prefix(org_id)
+                .map_or(false, |v| v.starts_with('/'))
+        })
+}
+
+/// count the events and bytes of a request to the streams against their
+/// quotas and the quota of the organization, nothing is counted when one of
+/// them is exceeded
+pub async fn check(org_id: &str, streams: &[(&str, u64, u64)]) -> Result<(), QuotaExceeded> {
+    let quotas = quotas_of(org_id, streams);
+    if quotas.is_empty() {
+        return Ok(());
+    }
+    let ret = admit(&quotas, Utc::now().timestamp_micros());
+    if let Err(e) = &ret {
+        log::warn!("[QUOTA] {e}");
+        let limit = e.limit.to_string();
+        for (stream_name, events, bytes) in streams {
+            usage::report_quota_exceeded(org_id, stream_name, &limit, *events, *bytes).await;
+        }
+    }
+    ret
+}
+
+/// count the lines buffered for the streams, by their hour keys, against the
+/// quotas before writing them
+pub async fn check_buffered(
+    org_id: &str,
+    streams: &AHashMap<String, AHashMap<String, Vec<String>>>,
+) -> Result<(), QuotaExceeded> {
+    if !has_quotas(org_id) {
+        return Ok(());
+    }
+    let usage: Vec<_> = streams
+        .iter()
+        .map(|(stream_name, buf)| {
+            let (events, bytes) = buffered_usage(buf.values());
+            (stream_name.as_str(), events, bytes)
+        })
+        .collect();
+    check(org_id, &usage).await
+}
+
+/// the events and bytes of the lines buffered for a stream
+pub fn buffered_usage<'a>(buf: impl Iterator<Item = &'a Vec<String>>) -> (u64, u64) {
+    buf.fold((0, 0), |(events, bytes), lines| {
+        (
+            events + lines.len() as u64,
+            bytes + lines.iter().map(|v| v.len() as u64).sum::<u64>(),
+        )
+    })
+}
+
+fn admit(quotas: &[Counted], now: i64) -> Result<(), QuotaExceeded> {
+    let mut local = QUOTAS.lock();
+    let even_share = 1.0 / local.nodes as f64;
+    let mut exceeded = None;
+    for (key, quota, events, bytes) in quotas {
+        let state = local
+            .states
+            .entry(key.clone())
+            .or_insert_with(|| QuotaState::new(even_share, now));
+        state.demand_events += events;
+        state.demand_bytes += bytes;
+        if exceeded.is_none() {
+            if let Err((limit, retry_after)) = state.admit(quota, *events, *bytes, now) {
+                exceeded = Some(QuotaExceeded {
+                    key: key.clone(),
+                    limit,
+                    retry_after,
+                });
+            }
+        }
+    }
+    if let Some(e) = exceeded {
+        return Err(e);
+    }
+    for (key, quota, events, bytes) in quotas {
+        local
+            .states
+            .get_mut(key)
+            .unwrap()
+            .consume(quota, *events, *bytes);
+    }
+    Ok(())
+}
+
+/// publish the usage of this node and share the rates between the ingesters
+/// by their demand, the daily bytes are summed over the cluster
+pub async fn sync() -> Result<(), anyhow::Error> {
+    if INGESTION_QUOTAS.is_empty() && default_quota().is_unlimited() {
+        return Ok(());
+    }
+    let now = Utc::now().timestamp_micros();
+    let local = take_usage(&mut QUOTAS.lock(), now);
+    db::quota::set_usage(&LOCAL_NODE_UUID, &local).await?;
+    let mut others = vec![];
+    for (node, usage) in db::quota::list_usage().await? {
+        if node == *LOCAL_NODE_UUID {
+            continue;
+        }
+        // the usage of a node gone since yesterday counts no more
+        if usage.updated_at < now - DAY_MICROS {
+            db::quota::delete_usage(&node).await?;
+            continue;
+        }
+        others.push(usage);
+    }
+    let stale_at = now - 3 * CONFIG.limit.ingest_quota_sync_interval as i64 * SECOND_MICROS;
+    apply_usage(&mut QUOTAS.lock(), &local, &others, stale_at);
+    Ok(())
+}
+
+/// the usage since the last sync, the idle states are dropped
+fn take_usage(quotas: &mut Quotas, now: i64) -> NodeQuotaUsage {
+    let mut usage = HashMap::new();
+    quotas.states.retain(|key, state| {
+        state.roll_day(now);
+        if state.demand_events == 0 && state.daily_bytes == 0 {
+            return false;
+        }
+        usage.insert(
+            key.clone(),
+            QuotaUsage {
+                events: state.demand_events,
+                bytes: state.demand_bytes,
+                daily_bytes: state.daily_bytes,
+            },
+        );
+        state.demand_events = 0;
+        state.demand_bytes = 0;
+        true
+    });
+    NodeQuotaUsage {
+        updated_at: now,
+        day: now / DAY_MICROS,
+        usage,
+    }
+}
+
+fn apply_usage(
+    quotas: &mut Quotas,
+    local: &NodeQuotaUsage,
+    others: &[NodeQuotaUsage],
+    stale_at: i64,
+) {
+    let online: Vec<&NodeQuotaUsage> = others.iter().filter(|v| v.updated_at >= stale_at).collect();
+    quotas.nodes = online.len() + 1;
+    let even_share = 1.0 / quotas.nodes as f64;
+    for (key, state) in quotas.states.iter_mut() {
+        let demand = local.usage.get(key).cloned().unwrap_or_default();
+        let (others_events, others_bytes) = online
+            .iter()
+            .filter_map(|v| v.usage.get(key))
+            .fold((0, 0), |(events, bytes), v| {
+                (events + v.events, bytes + v.bytes)
+            });
+        state.events_share = share(demand.events, others_events, even_share);
+        state.bytes_share = share(demand.bytes, others_bytes, even_share);
+        // the bytes of a node which left still count for the day
+        state.others_daily_bytes = others
+            .iter()
+            .filter(|v| v.day == state.day)
+            .filter_map(|v| v.usage.get(key))
+            .map(|v| v.daily_bytes)
+            .sum();
+        let (org_id, stream_name) = key.split_once('/').unwrap_or((key, ""));
+        metrics::INGEST_QUOTA_DAILY_BYTES
+            .with_label_values(&[org_id, stream_name])
+            .set((state.daily_bytes + state.others_daily_bytes) as i64);
+    }
+}
+
+/// a node without demand keeps an even share to accept its first requests
+fn share(demand: u64, others_demand: u64, even_share: f64) -> f64 {
+    if demand == 0 {
+        even_share
+    } else {
+        demand as f64 / (demand + others_demand) as f64
+    }
+}
+
+#[tracing::instrument]
+pub async fn get_quotas(org_id: &str) -> Result<HttpResponse, io::Error> {
+    let org = match db::rules::get::<IngestionQuota>(org_id).await {
+        Ok(quota) => quota.unwrap_or_else(default_quota),
+        Err(e) => return Ok(Response::InternalServerError(e).into()),
+    };
+    let prefix = format!("{org_id}/");
+    match db::rules::list::<IngestionQuota>(&prefix).await {
+        Ok(streams) => Ok(HttpResponse::Ok().json(OrgIngestionQuotas {
+            org,
+            streams: streams
+                .into_iter()
+                .map(|(key, quota)| (key.strip_prefix(&prefix).unwrap().to_string(), quota))
+                .collect(),
+        })),
+        Err(e) => Ok(Response::InternalServerError(e).into()),
+    }
+}
+
+#[tracing::instrument(skip(quota))]
+pub async fn set_quota(
+    org_id: &str,
+    stream_name: Option<&str>,
+    quota: IngestionQuota,
+) -> Result<HttpResponse, io::Error> {
+    let stream_name = stream_name.map(format_stream_name);
+    let key = db::quota::quota_key(org_id, stream_name.as_deref());
+    if let Err(e) = db::rules::set(&key, &quota).await {
+        return Ok(Response::InternalServerError(e).into());
+    }
+    Ok(HttpResponse::Ok().json(quota))
+}
+
+#[tracing::instrument]
+pub async fn delete_quota(
+    org_id: &str,
+    stream_name: Option<&str>,
+) -> Result<HttpResponse, io::Error> {
+    let stream_name = stream_name.map(format_stream_name);
+    let key = db::quota::quota_key(org_id, stream_name.as_deref());
+    Ok(rules::delete_rule::<IngestionQuota>(&key, "Ingestion quota").await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quota(events_per_second: u64, bytes_per_second: u64, daily_bytes: u64) -> IngestionQuota {
+        IngestionQuota {
+            events_per_second,
+            bytes_per_second,
+            daily_bytes,
+        }
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let quota = quota(100, 0, 0);
+        let mut state = QuotaState::new(1.0, 0);
+        assert!(state.admit(&quota, 60, 0, 0).is_ok());
+        state.consume(&quota, 60, 0);
+        assert_eq!(
+            state.admit(&quota, 60, 0, 0),
+            Err((QuotaLimit::EventsPerSecond, 1))
+        );
+        // refilled after half a second
+        assert!(state.admit(&quota, 60, 0, SECOND_MICROS / 2).is_ok());
+
+        // a request larger than the bucket is accepted when it is full
+        let mut state = QuotaState::new(1.0, 0);
+        assert!(state.admit(&quota, 250, 0, 0).is_ok());
+        state.consume(&quota, 250, 0);
+        assert_eq!(
+            state.admit(&quota, 250, 0, SECOND_MICROS),
+            Err((QuotaLimit::EventsPerSecond, 2))
+        );
+        assert!(state.admit(&quota, 250, 0, 3 * SECOND_MICROS).is_ok());
+    }
+
+    #[test]
+    fn test_daily_bytes() {
+        let quota = quota(0, 0, 1000);
+        let mut state = QuotaState::new(1.0, 0);
+        state.others_daily_bytes = 600;
+        assert!(state.admit(&quota, 1, 300, 0).is_ok());
+        state.consume(&quota, 1, 300);
+        let now = DAY_MICROS - 10 * SECOND_MICROS;
+        assert_eq!(
+            state.admit(&quota, 1, 300, now),
+            Err((QuotaLimit::DailyBytes, 10))
+        );
+        // the next day
+        assert!(state.admit(&quota, 1, 300, DAY_MICROS).is_ok());
+    }
+
+    #[test]
+    fn test_buffered_usage() {
+        let mut buf = AHashMap::new();
+        buf.insert(
+            "2023/10/01/00".to_string(),
+            vec!["{}".to_string(), "{\"a\":1}".to_string()],
+        );
+        buf.insert("2023/10/01/01".to_string(), vec!["{}".to_string()]);
+        assert_eq!(buffered_usage(buf.values()), (3, 11));
+    }
+
+    #[test]
+    fn test_apply_usage() {
+        let mut quotas = Quotas {
+            states: HashMap::new(),
+            nodes: 1,
+        };
+        quotas
+            .states
+            .insert("default".to_string(), QuotaState::new(1.0, 0));
+        quotas.states.get_mut("default").unwrap().demand_events = 100;
+        quotas
+            .states
+            .insert("idle".to_string(), QuotaState::new(1.0, 0));
+        let local = take_usage(&mut quotas, 0);
+        assert!(!quotas.states.contains_key("idle"));
+
+        let usage = |updated_at, events, daily_bytes| NodeQuotaUsage {
+            updated_at,
+            day: 0,
+            usage: HashMap::from([(
+                "default".to_string(),
+                QuotaUsage {
+                    events,
+                    bytes: 0,
+                    daily_bytes,
+                },
+            )]),
+        };
+        let others = vec![usage(10, 300, 50), usage(-10, 1000, 70)];
+        apply_usage(&mut quotas, &local, &others, 0);
+        assert_eq!(quotas.nodes, 2);
+        let state = &quotas.states["default"];
+        assert_eq!(state.events_share, 0.25);
+        assert_eq!(state.bytes_share, 0.5);
+        assert_eq!(state.others_daily_bytes, 120);
+    }
+}
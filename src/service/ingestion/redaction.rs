@@ -0,0 +1,427 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use actix_web::HttpResponse;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::Sha256;
+use std::{io, net::Ipv6Addr, ops::Range};
+
+use crate::common::infra::{
+    config::{CONFIG, STREAM_REDACTIONS},
+    metrics,
+};
+use crate::common::meta::{
+    redaction::{PiiDetector, RedactionMode, RedactionRule, StreamRedaction},
+    stream::StreamParams,
+    StreamType,
+};
+use crate::common::utils::json::{self, Map, Value};
+use crate::service::{
+    db, format_stream_name,
+    rules::{self, Response},
+};
+
+static EMAIL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b").unwrap()
+});
+// the card numbers start with 2 to 6, which leaves out the epoch timestamps
+static CREDIT_CARD_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b[2-6](?:[ -]?\d){12,18}\b").unwrap());
+static IPV4_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"\b(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\b",
+    )
+    .unwrap()
+});
+static IPV6_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)[0-9a-f]{0,4}(?::[0-9a-f]{0,4}){2,7}").unwrap());
+static BEARER_TOKEN_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)\bbearer\s+([a-z0-9._~+/-]+=*)").unwrap());
+
+/// redacts the records of a stream before they are written, the redacted
+/// values are counted by rule and reported once the records are written
+pub struct Redactor {
+    rules: Vec<RedactionRule>,
+    key: Vec<u8>,
+    counts: Vec<u64>,
+}
+
+impl Redactor {
+    pub fn new(stream_params: &StreamParams) -> Option<Self> {
+        let key = db::redaction::redaction_key(
+            stream_params.org_id,
+            stream_params.stream_type,
+            stream_params.stream_name,
+        );
+        let mut rules = match STREAM_REDACTIONS.get(&key) {
+            Some(settings) if !settings.rules.is_empty() => settings.rules.clone(),
+            _ => return None,
+        };
+        let hmac_key = hmac_key();
+        if hmac_key.is_none() && rules.iter().any(|rule| rule.mode == RedactionMode::Hash) {
+            // the rules set before the key was removed, the values are never
+            // hashed with a known key
+            log::error!("[REDACTION] {key}: no hmac key, the values to hash are dropped");
+            for rule in rules.iter_mut() {
+                if rule.mode == RedactionMode::Hash {
+                    rule.mode = RedactionMode::Drop;
+                }
+            }
+        }
+        Some(Self::with_rules(rules, hmac_key.unwrap_or_default()))
+    }
+
+    fn with_rules(rules: Vec<RedactionRule>, key: Vec<u8>) -> Self {
+        let counts = vec![0; rules.len()];
+        Self { rules, key, counts }
+    }
+
+    /// returns whether anything was redacted
+    pub fn redact_record(&mut self, record: &mut Map<String, Value>) -> bool {
+        let mut redacted = false;
+        for (rule, count) in self.rules.iter().zip(self.counts.iter_mut()) {
+            let mut dropped = Vec::new();
+            for (field, value) in record.iter_mut() {
+                if !rule.fields.is_empty() && !rule.fields.contains(field) {
+                    continue;
+                }
+                let Value::String(val) = value else {
+                    continue;
+                };
+                let found = find(rule.detector, val);
+                if found.is_empty() {
+                    continue;
+                }
+                *count += found.len() as u64;
+                redacted = true;
+                match rule.mode {
+                    RedactionMode::Drop => dropped.push(field.to_string()),
+                    mode => *val = replace(val, &found, rule.detector, mode, &self.key),
+                }
+            }
+            for field in dropped {
+                record.remove(&field);
+            }
+        }
+        redacted
+    }
+
+    pub fn report(&self, stream_params: &StreamParams) {
+        let stream_type = stream_params.stream_type.to_string();
+        for (rule, count) in self.rules.iter().zip(self.counts.iter()) {
+            if *count == 0 {
+                continue;
+            }
+            metrics::INGEST_REDACTED_VALUES
+                .with_label_values(&[
+                    stream_params.org_id,
+                    stream_params.stream_name,
+                    stream_type.as_str(),
+                    &rule.detector.to_string(),
+                    &rule.mode.to_string(),
+                ])
+                .inc_by(*count);
+        }
+    }
+}
+
+/// redact a record of the stream before its schema is checked and the alerts
+/// are evaluated on it
+pub fn redact(stream_params: &StreamParams, record: &mut Map<String, Value>) {
+    if let Some(mut redactor) = Redactor::new(stream_params) {
+        redactor.redact_record(record);
+        redactor.report(stream_params);
+    }
+}
+
+fn hmac_key() -> Option<Vec<u8>> {
+    let key = &CONFIG.common.redaction_hmac_key;
+    (!key.is_empty()).then(|| key.as_bytes().to_vec())
+}
+
+/// the byte ranges of the values found by the detector
+fn find(detector: PiiDetector, value: &str) -> Vec<Range<usize>> {
+    match detector {
+        PiiDetector::Email => EMAIL_RE.find_iter(value).map(|m| m.range()).collect(),
+        PiiDetector::CreditCard => CREDIT_CARD_RE
+            .find_iter(value)
+            .filter(|m| luhn_check(m.as_str()))
+            .map(|m| m.range())
+            .collect(),
+        PiiDetector::Ipv4 => IPV4_RE.find_iter(value).map(|m| m.range()).collect(),
+        PiiDetector::Ipv6 => IPV6_RE
+            .find_iter(value)
+            .filter(|m| {
+                let before = value[..m.start()].chars().next_back();
+                let after = value[m.end()..].chars().next();
+                !before.map_or(false, |c| c.is_ascii_alphanumeric())
+                    && !after.map_or(false, |c| c.is_ascii_alphanumeric())
+                    && m.as_str().parse::<Ipv6Addr>().is_ok()
+            })
+            .map(|m| m.range())
+            .collect(),
+        PiiDetector::BearerToken => BEARER_TOKEN_RE
+            .captures_iter(value)
+            .filter_map(|caps| caps.get(1))
+            .map(|m| m.range())
+            .collect(),
+    }
+}
+
+fn luhn_check(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| match (i % 2, d * 2) {
+            (0, _) => *d,
+            (_, v) if v > 9 => v - 9,
+            (_, v) => v,
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+fn replace(
+    value: &str,
+    found: &[Range<usize>],
+    detector: PiiDetector,
+    mode: RedactionMode,
+    key: &[u8],
+) -> String {
+    let mut ret = String::with_capacity(value.len());
+    let mut last = 0;
+    for range in found {
+        ret.push_str(&value[last..range.start]);
+        let matched = &value[range.clone()];
+        match mode {
+            RedactionMode::Hash => ret.push_str(&hash(matched, key)),
+            _ => ret.push_str(&mask(matched, detector)),
+        }
+        last = range.end;
+    }
+    ret.push_str(&value[last..]);
+    ret
+}
+
+fn mask(value: &str, detector: PiiDetector) -> String {
+    if detector != PiiDetector::CreditCard {
+        return "*".repeat(value.chars().count());
+    }
+    let digits = value.chars().filter(|c| c.is_ascii_digit()).count();
+    let mut seen = 0;
+    value
+        .chars()
+        .map(|c| {
+            if !c.is_ascii_digit() {
+                return c;
+            }
+            seen += 1;
+            if seen + 4 > digits {
+                c
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+fn hash(value: &str, key: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts any key size");
+    mac.update(value.as_bytes());
+    format!("hmac:{}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[tracing::instrument]
+pub async fn get_settings(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<HttpResponse, io::Error> {
+    let stream_name = format_stream_name(stream_name);
+    let key = db::redaction::redaction_key(org_id, stream_type, &stream_name);
+    match db::rules::get::<StreamRedaction>(&key).await {
+        Ok(settings) => Ok(HttpResponse::Ok().json(settings.unwrap_or_default())),
+        Err(e) => Ok(Response::InternalServerError(e).into()),
+    }
+}
+
+#[tracing::instrument(skip(settings))]
+pub async fn set_settings(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    settings: StreamRedaction,
+) -> Result<HttpResponse, io::Error> {
+    if let Err(e) = check_settings(&settings, hmac_key().is_some()) {
+        return Ok(Response::BadRequest(e).into());
+    }
+    let stream_name = format_stream_name(stream_name);
+    let key = db::redaction::redaction_key(org_id, stream_type, &stream_name);
+    if let Err(e) = db::rules::set(&key, &settings).await {
+        return Ok(Response::InternalServerError(e).into());
+    }
+    Ok(HttpResponse::Ok().json(settings))
+}
+
+fn check_settings(settings: &StreamRedaction, has_hmac_key: bool) -> Result<(), String> {
+    if !has_hmac_key
+        && settings
+            .rules
+            .iter()
+            .any(|rule| rule.mode == RedactionMode::Hash)
+    {
+        return Err("The hash mode requires the ZO_REDACTION_HMAC_KEY".to_owned());
+    }
+    Ok(())
+}
+
+#[tracing::instrument]
+pub async fn delete_settings(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+) -> Result<HttpResponse, io::Error> {
+    let stream_name = format_stream_name(stream_name);
+    let key = db::redaction::redaction_key(org_id, stream_type, &stream_name);
+    Ok(rules::delete_rule::<StreamRedaction>(&key, "Stream redaction").await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(detector: PiiDetector, mode: RedactionMode, fields: &[&str]) -> RedactionRule {
+        RedactionRule {
+            detector,
+            mode,
+            fields: fields.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    fn found(detector: PiiDetector, value: &str) -> Vec<&str> {
+        find(detector, value)
+            .into_iter()
+            .map(|range| &value[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_detectors() {
+        assert_eq!(
+            found(PiiDetector::Email, "mail john.doe+x@mail.example.com now"),
+            vec!["john.doe+x@mail.example.com"]
+        );
+        assert_eq!(
+            found(PiiDetector::CreditCard, "card 4111 1111 1111 1111 paid"),
+            vec!["4111 1111 1111 1111"]
+        );
+        // fails the luhn check
+        assert!(found(PiiDetector::CreditCard, "card 4111 1111 1111 1112").is_empty());
+        assert!(found(PiiDetector::CreditCard, "at 1690000000000").is_empty());
+        assert_eq!(
+            found(PiiDetector::Ipv4, "from 10.0.0.1:8080 and 256.1.1.1"),
+            vec!["10.0.0.1"]
+        );
+        assert_eq!(
+            found(
+                PiiDetector::Ipv6,
+                "from 2001:db8::8a2e:370:7334 at 12:30:45"
+            ),
+            vec!["2001:db8::8a2e:370:7334"]
+        );
+        assert_eq!(found(PiiDetector::Ipv6, "local ::1"), vec!["::1"]);
+        assert_eq!(
+            found(
+                PiiDetector::BearerToken,
+                "Authorization: Bearer abc.DEF-123=="
+            ),
+            vec!["abc.DEF-123=="]
+        );
+    }
+
+    #[test]
+    fn test_luhn_check() {
+        assert!(luhn_check("4111111111111111"));
+        assert!(luhn_check("3782-822463-10005"));
+        assert!(!luhn_check("4111111111111112"));
+        assert!(!luhn_check("411111111"));
+    }
+
+    fn redacted(redactor: &mut Redactor, row: &str) -> (Map<String, Value>, bool) {
+        let mut record: Map<String, Value> = json::from_str(row).unwrap();
+        let redacted = redactor.redact_record(&mut record);
+        (record, redacted)
+    }
+
+    #[test]
+    fn test_redact_modes() {
+        let mut redactor = Redactor::with_rules(
+            vec![
+                rule(PiiDetector::CreditCard, RedactionMode::Mask, &[]),
+                rule(PiiDetector::Email, RedactionMode::Hash, &["user"]),
+                rule(PiiDetector::Ipv4, RedactionMode::Drop, &[]),
+            ],
+            b"secret".to_vec(),
+        );
+        let row = r#"{"card":"4111-1111-1111-1111","user":"a@b.io","email":"c@d.io","ip":"10.0.0.1","code":200}"#;
+        let (record, redacted) = redacted(&mut redactor, row);
+        assert!(redacted);
+        assert_eq!(record["card"], "****-****-****-1111");
+        assert_eq!(record["user"], hash("a@b.io", b"secret").as_str());
+        assert!(record["user"].as_str().unwrap().starts_with("hmac:"));
+        // not in the fields of the email rule
+        assert_eq!(record["email"], "c@d.io");
+        assert!(!record.contains_key("ip"));
+        assert_eq!(record["code"], 200);
+        assert_eq!(redactor.counts, vec![1, 1, 1]);
+
+        // equal values keep equal hashes
+        let (record, _) = redacted(&mut redactor, r#"{"user":"a@b.io"}"#);
+        assert_eq!(record["user"], hash("a@b.io", b"secret").as_str());
+        assert_ne!(hash("a@b.io", b"secret"), hash("a@b.io", b"other"));
+
+        let (record, redacted) = redacted(&mut redactor, r#"{"message":"nothing to see"}"#);
+        assert!(!redacted);
+        assert_eq!(record["message"], "nothing to see");
+    }
+
+    #[test]
+    fn test_check_settings() {
+        let mut settings = StreamRedaction {
+            rules: vec![rule(PiiDetector::Email, RedactionMode::Mask, &[])],
+        };
+        assert!(check_settings(&settings, false).is_ok());
+        settings
+            .rules
+            .push(rule(PiiDetector::Email, RedactionMode::Hash, &[]));
+        assert!(check_settings(&settings, false).is_err());
+        assert!(check_settings(&settings, true).is_ok());
+    }
+
+    #[test]
+    fn test_redact_bearer_token() {
+        let mut redactor = Redactor::with_rules(
+            vec![rule(PiiDetector::BearerToken, RedactionMode::Mask, &[])],
+            vec![],
+        );
+        let (record, _) = redacted(&mut redactor, r#"{"header":"Bearer abc123"}"#);
+        assert_eq!(record["header"], "Bearer ******");
+    }
+}
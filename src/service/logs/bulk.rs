@@ -13,32 +13,44 @@
 // limitations under the License.
 
 use actix_web::web;
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use chrono::{Duration, Utc};
 use datafusion::arrow::datatypes::Schema;
 use std::io::{BufRead, BufReader};
 
-use super::StreamMeta;
+use super::{dead_letter::DeadLetters, StreamMeta};
 use crate::common::infra::{cluster, config::CONFIG, metrics};
 use crate::common::meta::{
     alert::{Alert, Trigger},
+    dead_letter::DeadLetterStage,
     functions::{StreamTransform, VRLRuntimeConfig},
     ingestion::{
         BulkResponse, BulkResponseError, BulkResponseItem, BulkStreamData, RecordStatus,
         StreamSchemaChk,
     },
-    stream::StreamParams,
+    stream::{StreamParams, DELETED_FIELD, ID_FIELD, VERSION_FIELD},
     usage::UsageType,
     StreamType,
 };
 use crate::common::utils::{flatten, json, time::parse_timestamp_micro_from_value};
 use crate::service::{
-    db, ingestion::write_file, schema::stream_schema_exists, usage::report_request_usage_stats,
+    db,
+    ingestion::{quota, write_file},
+    schema::stream_schema_exists,
+    stream,
+    stream_routes::{self, CompiledRoute},
+    usage::report_request_usage_stats,
 };
 
 pub const TRANSFORM_FAILED: &str = "document_failed_transform";
 pub const TS_PARSE_FAILED: &str = "timestamp_parsing_failed";
 pub const SCHEMA_CONFORMANCE_FAILED: &str = "schema_conformance_failed";
+pub const DELETE_FAILED: &str = "delete_failed";
+pub const ROUTING_FAILED: &str = "routing_failed";
+const DELETE_FAILED_REASON: &str = "delete needs an _id and a stream with the upsert_by_id setting";
+
+/// the versions reserved at once for the documents with an `_id` of a request
+const VERSIONS_RESERVED: i64 = 1024;
 
 pub async fn ingest(
     org_id: &str,
@@ -54,6 +66,15 @@ pub async fn ingest(
         return Err(anyhow::anyhow!("Quota exceeded for this organization"));
     }
 
+    if quota::has_quotas(org_id) {
+        let usage = stream_usage(&body);
+        let streams: Vec<_> = usage
+            .iter()
+            .map(|(stream_name, (events, bytes))| (stream_name.as_str(), *events, *bytes))
+            .collect();
+        quota::check(org_id, &streams).await?;
+    }
+
     //let mut errors = false;
     let mut bulk_res = BulkResponse {
         took: 0,
@@ -74,11 +95,21 @@ pub async fn ingest(
     let mut stream_partition_keys_map: AHashMap<String, (StreamSchemaChk, Vec<String>)> =
         AHashMap::new();
     let mut stream_alerts_map: AHashMap<String, Vec<Alert>> = AHashMap::new();
+    let mut stream_routes_map: AHashMap<String, Vec<CompiledRoute>> = AHashMap::new();
+    let mut routed = vec![];
+    // the bulk item of every routed record, with the record of the item
+    let mut routed_items = vec![];
+    let mut routed_values = AHashMap::new();
+    let mut dead_letters = DeadLetters::new(org_id);
 
     let mut action = String::from("");
     let mut stream_name = String::from("");
     let mut doc_id = String::from("");
     let mut stream_trigger_map: AHashMap<String, Trigger> = AHashMap::new();
+    let mut upsert_streams: AHashSet<String> = AHashSet::new();
+    let mut versions = Versions::default();
+    // the versions of the documents written by stream, by `_id`
+    let mut stream_versions: AHashMap<String, AHashMap<String, i64>> = AHashMap::new();
 
     let mut next_line_is_data = false;
     let reader = BufReader::new(body.as_ref());
@@ -88,7 +119,7 @@ pub async fn ingest(
             continue;
         }
 
-        let value: json::Value = json::from_slice(line.as_bytes())?;
+        let mut value: json::Value = json::from_slice(line.as_bytes())?;
 
         if !next_line_is_data {
             // check bulk operate
@@ -97,7 +128,6 @@ pub async fn ingest(
                 continue; // skip
             }
             (action, stream_name, doc_id) = ret.unwrap();
-            next_line_is_data = true;
 
             // Start Register Transfoms for stream
 
@@ -135,6 +165,18 @@ pub async fn ingest(
                 }
                 stream_partition_keys_map
                     .insert(stream_name.clone(), (stream_schema, partition_keys.clone()));
+                if let Some(settings) = stream_schema_map
+                    .get(&stream_name)
+                    .and_then(stream::stream_settings)
+                {
+                    if settings.upsert_by_id {
+                        upsert_streams.insert(stream_name.clone());
+                    }
+                }
+                stream_routes_map.insert(
+                    stream_name.clone(),
+                    stream_routes::register_stream_routes(org_id, &stream_name),
+                );
             }
 
             stream_data_map
@@ -142,31 +184,63 @@ pub async fn ingest(
                 .or_insert(BulkStreamData {
                     data: AHashMap::new(),
                 });
+
+            if action != "delete" {
+                next_line_is_data = true;
+                continue;
+            }
+            // a delete has no source, the tombstone of the id is ingested at
+            // the current time as the latest version of the id
+            if !upsert_streams.contains(&stream_name) || doc_id.is_empty() {
+                bulk_res.errors = true;
+                add_record_status(
+                    stream_name.clone(),
+                    doc_id.clone(),
+                    action.clone(),
+                    value,
+                    &mut bulk_res,
+                    Some(DELETE_FAILED.to_string()),
+                    Some(DELETE_FAILED_REASON.to_string()),
+                );
+                continue;
+            }
+            value = tombstone();
         } else {
             next_line_is_data = false;
+        }
 
-            let stream_data = stream_data_map.get_mut(&stream_name).unwrap();
-            let buf = &mut stream_data.data;
-
-            //Start row based transform
+        let stream_data = stream_data_map.get_mut(&stream_name).unwrap();
+        let buf = &mut stream_data.data;
 
-            let key = format!("{org_id}/{}/{stream_name}", StreamType::Logs);
+        //Start row based transform
 
-            //JSON Flattening
-            let mut value = flatten::flatten(&value)?;
+        let key = format!("{org_id}/{}/{stream_name}", StreamType::Logs);
 
-            if let Some(transforms) = stream_transform_map.get(&key) {
-                let mut ret_value = value.clone();
-                ret_value = crate::service::ingestion::apply_stream_transform(
-                    transforms,
-                    &ret_value,
-                    &stream_vrl_map,
-                    &stream_name,
-                    &mut runtime,
-                )?;
+        let record = value;
+        //JSON Flattening
+        let mut value = flatten::flatten(&record)?;
 
-                if ret_value.is_null() || !ret_value.is_object() {
+        // tombstones are not transformed
+        let transforms = stream_transform_map
+            .get(&key)
+            .filter(|_| action != "delete");
+        if let Some(transforms) = transforms {
+            let ret_value = match crate::service::ingestion::apply_stream_transform(
+                transforms,
+                &value,
+                &stream_vrl_map,
+                &stream_name,
+                &mut runtime,
+            ) {
+                Ok(ret_value) => ret_value,
+                Err(e) => {
                     bulk_res.errors = true;
+                    dead_letters.push(
+                        &stream_name,
+                        &record,
+                        DeadLetterStage::Function,
+                        &e.to_string(),
+                    );
                     add_record_status(
                         stream_name.clone(),
                         doc_id.clone(),
@@ -174,100 +248,51 @@ pub async fn ingest(
                         value,
                         &mut bulk_res,
                         Some(TRANSFORM_FAILED.to_owned()),
-                        Some(TRANSFORM_FAILED.to_owned()),
+                        Some(e.to_string()),
                     );
                     continue;
-                } else {
-                    value = ret_value;
                 }
-            }
-            //End row based transform
-
-            // get json object
-            let local_val = value.as_object_mut().unwrap();
-            // set _id
-            if !doc_id.is_empty() {
-                local_val.insert("_id".to_string(), json::Value::String(doc_id.clone()));
-            }
-
-            // handle timestamp
-            let timestamp = match local_val.get(&CONFIG.common.column_timestamp) {
-                Some(v) => match parse_timestamp_micro_from_value(v) {
-                    Ok(t) => t,
-                    Err(_e) => {
-                        bulk_res.errors = true;
-                        add_record_status(
-                            stream_name.clone(),
-                            doc_id.clone(),
-                            action.clone(),
-                            value,
-                            &mut bulk_res,
-                            Some(TS_PARSE_FAILED.to_string()),
-                            Some(TS_PARSE_FAILED.to_string()),
-                        );
-                        continue;
-                    }
-                },
-                None => Utc::now().timestamp_micros(),
             };
-            // check ingestion time
-            let earliest_time = Utc::now() + Duration::hours(0 - CONFIG.limit.ingest_allowed_upto);
-            if timestamp < earliest_time.timestamp_micros() {
-                bulk_res.errors = true;
-                let failure_reason = Some(super::get_upto_discard_error());
-                add_record_status(
+
+            if ret_value.is_null() || !ret_value.is_object() {
+                add_record_dropped(
                     stream_name.clone(),
                     doc_id.clone(),
                     action.clone(),
-                    value,
                     &mut bulk_res,
-                    Some(TS_PARSE_FAILED.to_string()),
-                    failure_reason,
                 );
                 continue;
             }
-            if timestamp < min_ts {
-                min_ts = timestamp;
+            value = ret_value;
+        }
+        //End row based transform
+
+        // get json object
+        let local_val = value.as_object_mut().unwrap();
+        // set _id
+        let mut doc_version = None;
+        if !doc_id.is_empty() {
+            local_val.insert(ID_FIELD.to_string(), json::Value::String(doc_id.clone()));
+            if upsert_streams.contains(&stream_name) {
+                let version = versions.next(org_id).await?;
+                local_val.insert(VERSION_FIELD.to_string(), version.into());
+                doc_version = Some(version);
             }
-            local_val.insert(
-                CONFIG.common.column_timestamp.clone(),
-                json::Value::Number(timestamp.into()),
-            );
-            let partition_keys: Vec<String> = match stream_partition_keys_map.get(&stream_name) {
-                Some((_, partition_keys)) => partition_keys.to_vec(),
-                None => vec![],
-            };
+        }
 
-            // only for bulk insert
-            let mut status = RecordStatus::default();
-            let local_trigger = super::add_valid_record(
-                StreamMeta {
-                    org_id: org_id.to_string(),
-                    stream_name: stream_name.clone(),
-                    partition_keys,
-                    stream_alerts_map: stream_alerts_map.clone(),
-                },
-                &mut stream_schema_map,
-                &mut status,
-                buf,
-                local_val,
-            )
-            .await;
-            if local_trigger.is_some() {
-                stream_trigger_map.insert(stream_name.clone(), local_trigger.unwrap());
+        // tombstones are not routed
+        let routes = stream_routes_map
+            .get(&stream_name)
+            .filter(|routes| !routes.is_empty() && action != "delete");
+        if let Some(routes) = routes {
+            let first = routed.len();
+            let keep = stream_routes::route_record(routes, &value, &mut runtime, &mut routed);
+            if routed.len() > first {
+                let item = bulk_res.items.len();
+                routed_items.resize(routed.len(), item);
+                routed_values.insert(item, value.clone());
             }
-            if status.failed > 0 {
-                bulk_res.errors = true;
-                add_record_status(
-                    stream_name.clone(),
-                    doc_id.clone(),
-                    action.clone(),
-                    value,
-                    &mut bulk_res,
-                    Some(SCHEMA_CONFORMANCE_FAILED.to_string()),
-                    Some(status.error),
-                );
-            } else {
+            if !keep {
                 add_record_status(
                     stream_name.clone(),
                     doc_id.clone(),
@@ -277,10 +302,161 @@ pub async fn ingest(
                     None,
                     None,
                 );
+                continue;
+            }
+        }
+        let local_val = value.as_object_mut().unwrap();
+
+        // handle timestamp
+        let timestamp = match local_val.get(&CONFIG.common.column_timestamp) {
+            Some(v) => match parse_timestamp_micro_from_value(v) {
+                Ok(t) => t,
+                Err(e) => {
+                    bulk_res.errors = true;
+                    dead_letters.push(
+                        &stream_name,
+                        &record,
+                        DeadLetterStage::Timestamp,
+                        &e.to_string(),
+                    );
+                    add_record_status(
+                        stream_name.clone(),
+                        doc_id.clone(),
+                        action.clone(),
+                        value,
+                        &mut bulk_res,
+                        Some(TS_PARSE_FAILED.to_string()),
+                        Some(TS_PARSE_FAILED.to_string()),
+                    );
+                    continue;
+                }
+            },
+            None => Utc::now().timestamp_micros(),
+        };
+        // check ingestion time
+        let earliest_time = Utc::now() + Duration::hours(0 - CONFIG.limit.ingest_allowed_upto);
+        if timestamp < earliest_time.timestamp_micros() {
+            bulk_res.errors = true;
+            let failure_reason = Some(super::get_upto_discard_error());
+            dead_letters.push(
+                &stream_name,
+                &record,
+                DeadLetterStage::Timestamp,
+                failure_reason.as_deref().unwrap_or_default(),
+            );
+            add_record_status(
+                stream_name.clone(),
+                doc_id.clone(),
+                action.clone(),
+                value,
+                &mut bulk_res,
+                Some(TS_PARSE_FAILED.to_string()),
+                failure_reason,
+            );
+            continue;
+        }
+        if timestamp < min_ts {
+            min_ts = timestamp;
+        }
+        local_val.insert(
+            CONFIG.common.column_timestamp.clone(),
+            json::Value::Number(timestamp.into()),
+        );
+        let partition_keys: Vec<String> = match stream_partition_keys_map.get(&stream_name) {
+            Some((_, partition_keys)) => partition_keys.to_vec(),
+            None => vec![],
+        };
+
+        // only for bulk insert
+        let mut status = RecordStatus::default();
+        let local_trigger = super::add_valid_record(
+            StreamMeta {
+                org_id: org_id.to_string(),
+                stream_name: stream_name.clone(),
+                partition_keys,
+                stream_alerts_map: stream_alerts_map.clone(),
+            },
+            &mut stream_schema_map,
+            &mut status,
+            buf,
+            local_val,
+        )
+        .await;
+        if local_trigger.is_some() {
+            stream_trigger_map.insert(stream_name.clone(), local_trigger.unwrap());
+        }
+        if status.dropped > 0 {
+            add_record_dropped(
+                stream_name.clone(),
+                doc_id.clone(),
+                action.clone(),
+                &mut bulk_res,
+            );
+        } else if status.failed > 0 {
+            bulk_res.errors = true;
+            // a tombstone can not be replayed
+            if action != "delete" {
+                dead_letters.push(
+                    &stream_name,
+                    &record,
+                    DeadLetterStage::Schema,
+                    &status.error,
+                );
+            }
+            add_record_status(
+                stream_name.clone(),
+                doc_id.clone(),
+                action.clone(),
+                value,
+                &mut bulk_res,
+                Some(SCHEMA_CONFORMANCE_FAILED.to_string()),
+                Some(status.error),
+            );
+        } else {
+            if let Some(version) = doc_version {
+                stream_versions
+                    .entry(stream_name.clone())
+                    .or_default()
+                    .insert(doc_id.clone(), version);
             }
+            add_record_status(
+                stream_name.clone(),
+                doc_id.clone(),
+                action.clone(),
+                value,
+                &mut bulk_res,
+                None,
+                None,
+            );
         }
     }
 
+    // records sent to other streams by the stream routes, the item of a
+    // record fails when it is not written to one of its destinations
+    let routed_streams: Vec<String> = routed.iter().map(|(name, _)| name.clone()).collect();
+    let (_, routed_failed) = super::ingest_routed_records(
+        org_id,
+        thread_id,
+        routed,
+        UsageType::Bulk,
+        "/api/org/ingest/logs/_bulk",
+        &mut dead_letters,
+    )
+    .await?;
+    for (pos, error) in routed_failed {
+        let item = routed_items[pos];
+        let Some(value) = routed_values.remove(&item) else {
+            continue; // already failed with another destination
+        };
+        bulk_res.errors = true;
+        set_routing_failed(
+            &mut bulk_res.items[item],
+            &routed_streams[pos],
+            value,
+            error,
+        );
+    }
+
     let time = start.elapsed().as_secs_f64();
     for (stream_name, stream_data) in stream_data_map {
         // check if we are allowed to ingest
@@ -304,6 +480,13 @@ pub async fn ingest(
             None,
         );
         req_stats.response_time += time;
+        // the written documents are the latest versions of their `_id`, the
+        // older versions show until they are saved
+        if let Some(versions) = stream_versions.remove(&stream_name) {
+            if let Err(e) = db::doc_version::set(org_id, &stream_name, versions).await {
+                log::error!("save the document versions of stream [{stream_name}] failed: {e}");
+            }
+        }
         //metric + data usage
         let fns_length: usize = stream_transform_map.values().map(|v| v.len()).sum();
         report_request_usage_stats(
@@ -317,6 +500,15 @@ pub async fn ingest(
         .await;
     }
 
+    dead_letters
+        .flush(
+            org_id,
+            thread_id,
+            UsageType::Bulk,
+            "/api/org/ingest/logs/_bulk",
+        )
+        .await;
+
     // only one trigger per request, as it updates etcd
     for (_, entry) in &stream_trigger_map {
         super::evaluate_trigger(Some(entry.clone()), stream_alerts_map.clone()).await;
@@ -345,6 +537,69 @@ pub async fn ingest(
     Ok(bulk_res)
 }
 
+/// the versions of the documents with an `_id` of a request, reserved from
+/// the organization so the last document sent is the latest version whatever
+/// the node ingesting it
+#[derive(Default)]
+struct Versions {
+    next: i64,
+    end: i64,
+}
+
+impl Versions {
+    async fn next(&mut self, org_id: &str) -> Result<i64, anyhow::Error> {
+        if self.next == self.end {
+            self.next = db::doc_version::reserve(org_id, VERSIONS_RESERVED).await?;
+            self.end = self.next + VERSIONS_RESERVED;
+        }
+        self.next += 1;
+        Ok(self.next - 1)
+    }
+}
+
+/// the record deleting the `_id` set with the action, it is newer than every
+/// version ingested before and has the ingestion time
+fn tombstone() -> json::Value {
+    let mut rec = json::Map::new();
+    rec.insert(DELETED_FIELD.to_string(), true.into());
+    rec.insert(
+        CONFIG.common.column_timestamp.clone(),
+        Utc::now().timestamp_micros().into(),
+    );
+    json::Value::Object(rec)
+}
+
+/// the events and bytes of the request by stream, to count them against the
+/// quotas before ingesting any of them
+fn stream_usage(body: &[u8]) -> AHashMap<String, (u64, u64)> {
+    let mut usage: AHashMap<String, (u64, u64)> = AHashMap::new();
+    let mut data_stream: Option<String> = None;
+    for line in body.split(|c| *c == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(stream_name) = data_stream.take() {
+            let entry = usage.entry(stream_name).or_default();
+            entry.0 += 1;
+            entry.1 += line.len() as u64;
+            continue;
+        }
+        let Some((action, stream_name, _)) = json::from_slice(line)
+            .ok()
+            .and_then(|value| super::parse_bulk_index(&value))
+        else {
+            continue;
+        };
+        if action == "delete" {
+            usage.entry(stream_name).or_default().0 += 1;
+        } else {
+            data_stream = Some(stream_name);
+        }
+    }
+    usage
+}
+
 fn add_record_status(
     stream_name: String,
     doc_id: String,
@@ -386,6 +641,47 @@ fn add_record_status(
     bulk_res.items.push(item);
 }
 
+/// the item of a record dropped by a function or a sampling rule, it is not an
+/// error
+fn add_record_dropped(
+    stream_name: String,
+    doc_id: String,
+    action: String,
+    bulk_res: &mut BulkResponse,
+) {
+    let mut item =
+        BulkResponseItem::new(stream_name.clone(), doc_id, json::Value::Null, stream_name);
+    item.result = Some("noop".to_string());
+    let mut items = AHashMap::new();
+    items.insert(action, item);
+    bulk_res.items.push(items);
+}
+
+/// the item of a record which was not written to the stream it was routed
+/// to reports the error of that stream
+fn set_routing_failed(
+    item: &mut AHashMap<String, BulkResponseItem>,
+    destination: &str,
+    value: json::Value,
+    error: String,
+) {
+    for item in item.values_mut() {
+        let bulk_err = BulkResponseError::new(
+            ROUTING_FAILED.to_string(),
+            destination.to_string(),
+            format!("routed to stream [{destination}] failed: {error}"),
+            "0".to_owned(),
+        );
+        *item = BulkResponseItem::new_failed(
+            item._index.clone(),
+            item._id.clone(),
+            bulk_err,
+            value.clone(),
+            item._index.clone(),
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -408,4 +704,71 @@ mod tests {
         );
         assert!(bulk_res.items.len() == 1);
     }
+
+    #[test]
+    fn test_add_record_dropped() {
+        let mut bulk_res = BulkResponse {
+            took: 0,
+            errors: false,
+            items: vec![],
+        };
+        add_record_dropped(
+            "olympics".to_string(),
+            "1".to_string(),
+            "index".to_string(),
+            &mut bulk_res,
+        );
+        let item = &bulk_res.items[0]["index"];
+        assert_eq!(item.status, 200);
+        assert_eq!(item.result.as_deref(), Some("noop"));
+        assert!(!bulk_res.errors);
+    }
+
+    #[test]
+    fn test_set_routing_failed() {
+        let mut bulk_res = BulkResponse {
+            took: 0,
+            errors: false,
+            items: vec![],
+        };
+        add_record_status(
+            "olympics".to_string(),
+            "1".to_string(),
+            "index".to_string(),
+            json::Value::Null,
+            &mut bulk_res,
+            None,
+            None,
+        );
+        set_routing_failed(
+            &mut bulk_res.items[0],
+            "medals",
+            json::json!({"year": 1896}),
+            "stream [medals] is being deleted".to_string(),
+        );
+        let item = &bulk_res.items[0]["index"];
+        assert_eq!(item.status, 422);
+        assert_eq!(item._id, "1");
+        assert_eq!(item.error.as_ref().unwrap().index, "medals");
+    }
+
+    #[test]
+    fn test_stream_usage() {
+        let body = "{\"index\":{\"_index\":\"olympics\"}}\n{\"year\":1896}\n\n{\"create\":{\"_index\":\"k8s\"}}\r\n{\"log\":\"ok\"}\n{\"index\":{\"_index\":\"olympics\"}}\n{\"year\":1900}\n";
+        let usage = stream_usage(body.as_bytes());
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage.get("olympics"), Some(&(2, 26)));
+        assert_eq!(usage.get("k8s"), Some(&(1, 12)));
+    }
+
+    #[test]
+    fn test_tombstone() {
+        let start = Utc::now().timestamp_micros();
+        let rec = tombstone();
+        assert_eq!(rec[DELETED_FIELD], true);
+        let timestamp = rec[CONFIG.common.column_timestamp.as_str()]
+            .as_i64()
+            .unwrap();
+        assert!(timestamp >= start && timestamp <= Utc::now().timestamp_micros());
+    }
 }
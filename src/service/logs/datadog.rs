@@ -0,0 +1,129 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::web;
+
+use crate::common::infra::config::CONFIG;
+use crate::common::meta::{ingestion::IngestionResponse, usage::UsageType};
+use crate::common::utils::json::{self, Map, Value};
+use crate::service::format_stream_name;
+
+const DEFAULT_STREAM: &str = "default";
+
+/// field of the comma separated `<key>:<value>` tags
+const TAGS_FIELD: &str = "ddtags";
+
+/// field of the unix epoch in milliseconds
+const TIMESTAMP_FIELD: &str = "timestamp";
+
+/// the json array of logs sent to `/api/v2/logs`
+pub async fn ingest(
+    org_id: &str,
+    thread_id: usize,
+    body: web::Bytes,
+) -> Result<IngestionResponse, anyhow::Error> {
+    let records = records(json::from_slice(&body)?)?;
+    super::ingest_streams(
+        org_id,
+        thread_id,
+        super::group_by_stream(records),
+        UsageType::DatadogLogs,
+        "/datadog/org/api/v2/logs",
+    )
+    .await
+}
+
+/// `ddsource`, `service`, `hostname` and `status` are kept as fields, the tags
+/// are added as fields and the configured field names the stream
+fn records(body: Value) -> Result<Vec<(String, Map<String, Value>)>, anyhow::Error> {
+    let logs = match body {
+        Value::Array(v) => v,
+        v @ Value::Object(_) => vec![v],
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Invalid json: the body must be an array of logs"
+            ))
+        }
+    };
+    let mut records = vec![];
+    for log in logs {
+        let Value::Object(mut rec) = log else {
+            return Err(anyhow::anyhow!("Invalid json: a log must be an object"));
+        };
+        if let Some(Value::String(tags)) = rec.remove(TAGS_FIELD) {
+            for tag in tags.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()) {
+                let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+                // the fields of the log win over the tags
+                if !rec.contains_key(key) {
+                    rec.insert(key.to_string(), value.into());
+                }
+            }
+        }
+        if let Some(timestamp) = rec.get(TIMESTAMP_FIELD).and_then(|v| v.as_i64()) {
+            let timestamp = timestamp
+                .checked_mul(1000)
+                .ok_or_else(|| anyhow::anyhow!("Invalid timestamp: {timestamp} out of range"))?;
+            rec.remove(TIMESTAMP_FIELD);
+            rec.insert(CONFIG.common.column_timestamp.clone(), timestamp.into());
+        }
+        let stream = rec
+            .get(&CONFIG.common.datadog_stream_field)
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .unwrap_or(DEFAULT_STREAM);
+        records.push((format_stream_name(stream), rec));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records() {
+        let body = json::json!([
+            {
+                "message": "GET / 200",
+                "ddsource": "nginx",
+                "service": "web",
+                "hostname": "web-1",
+                "ddtags": "env:prod, team:core,canary",
+                "timestamp": 1700000000123_i64
+            },
+            {"message": "no service", "ddtags": "service:tagged"},
+            {"message": "plain"}
+        ]);
+        let records = records(body).unwrap();
+        assert_eq!(records.len(), 3);
+
+        let (stream, rec) = &records[0];
+        assert_eq!(stream, "web");
+        assert_eq!(rec["ddsource"], "nginx");
+        assert_eq!(rec["hostname"], "web-1");
+        assert_eq!(rec["env"], "prod");
+        assert_eq!(rec["team"], "core");
+        assert_eq!(rec["canary"], "");
+        assert!(!rec.contains_key(TAGS_FIELD));
+        assert_eq!(
+            rec[&CONFIG.common.column_timestamp],
+            1_700_000_000_123_000_i64
+        );
+
+        assert_eq!(records[1].0, "tagged");
+        assert_eq!(records[2].0, DEFAULT_STREAM);
+        assert!(super::records(json::json!("log")).is_err());
+        assert!(super::records(json::json!([{"timestamp": i64::MAX}])).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::Utc;
+use std::collections::HashMap;
+
+use crate::common::infra::config::{CONFIG, DEAD_LETTER_SETTINGS};
+use crate::common::meta::{
+    dead_letter::{
+        DeadLetterReplay, DeadLetterReplayResponse, DeadLetterSettings, DeadLetterStage,
+        DEAD_LETTER_PAYLOAD_FIELD, DEAD_LETTER_REASON_FIELD, DEAD_LETTER_STAGE_FIELD,
+        DEAD_LETTER_STREAM_FIELD,
+    },
+    search,
+    stream::StreamParams,
+    usage::UsageType,
+    StreamType,
+};
+use crate::common::utils::{
+    flatten,
+    json::{self, Map, Value},
+};
+use crate::service::{
+    db, format_stream_name,
+    ingestion::redaction::Redactor,
+    search::{self as SearchService, queue::Priority},
+};
+
+/// collects the records rejected by an ingestion request for the dead-letter
+/// stream of the organization, nothing is collected when it is not enabled
+#[derive(Default)]
+pub(crate) struct DeadLetters {
+    org_id: String,
+    stream_name: Option<String>,
+    records: Vec<(String, Map<String, Value>)>,
+    /// the redactors of the source streams
+    redactors: HashMap<String, Option<Redactor>>,
+}
+
+impl DeadLetters {
+    pub fn new(org_id: &str) -> Self {
+        let stream_name = DEAD_LETTER_SETTINGS
+            .get(org_id)
+            .filter(|settings| settings.enabled)
+            .map(|settings| format_stream_name(&settings.stream_name));
+        Self {
+            org_id: org_id.to_string(),
+            stream_name,
+            records: vec![],
+            redactors: HashMap::new(),
+        }
+    }
+
+    /// the original payload of the record rejected while ingesting it into the
+    /// stream
+    pub fn push(
+        &mut self,
+        stream_name: &str,
+        payload: &Value,
+        stage: DeadLetterStage,
+        reason: &str,
+    ) {
+        let Some(dead_letter_stream) = &self.stream_name else {
+            return;
+        };
+        // rejected dead letters are not sent to the dead-letter stream again
+        if dead_letter_stream == stream_name {
+            return;
+        }
+        let mut record = Map::new();
+        record.insert(
+            CONFIG.common.column_timestamp.clone(),
+            Utc::now().timestamp_micros().into(),
+        );
+        record.insert(DEAD_LETTER_STREAM_FIELD.to_string(), stream_name.into());
+        record.insert(
+            DEAD_LETTER_STAGE_FIELD.to_string(),
+            stage.to_string().into(),
+        );
+        record.insert(DEAD_LETTER_REASON_FIELD.to_string(), reason.into());
+        // a string keeps the schema of the dead-letter stream stable
+        record.insert(
+            DEAD_LETTER_PAYLOAD_FIELD.to_string(),
+            self.redact(stream_name, payload).into(),
+        );
+        self.records.push((dead_letter_stream.clone(), record));
+    }
+
+    /// the payload of a stream with redaction rules is redacted like its
+    /// records, flattened as the rules apply to the flattened fields. the
+    /// payload which is not a record is not kept.
+    fn redact(&mut self, stream_name: &str, payload: &Value) -> String {
+        let org_id = &self.org_id;
+        let redactor = self
+            .redactors
+            .entry(stream_name.to_string())
+            .or_insert_with(|| {
+                Redactor::new(&StreamParams {
+                    org_id,
+                    stream_name,
+                    stream_type: StreamType::Logs,
+                })
+            });
+        let Some(redactor) = redactor else {
+            return json::to_string(payload).unwrap_or_default();
+        };
+        match flatten::flatten(payload) {
+            Ok(Value::Object(mut record)) => {
+                redactor.redact_record(&mut record);
+                json::to_string(&record).unwrap_or_default()
+            }
+            _ => String::new(),
+        }
+    }
+
+    pub async fn flush(
+        self,
+        org_id: &str,
+        thread_id: usize,
+        usage_type: UsageType,
+        endpoint: &str,
+    ) {
+        if self.records.is_empty() {
+            return;
+        }
+        let streams = super::group_by_stream(self.records);
+        if let Err(e) = super::write_streams(
+            org_id,
+            thread_id,
+            streams,
+            true,
+            usage_type,
+            endpoint,
+            &mut DeadLetters::default(),
+            &mut vec![],
+        )
+        .await
+        {
+            log::error!("write dead letters of organization {org_id} error: {e}");
+        }
+    }
+}
+
+pub async fn get_settings(org_id: &str) -> DeadLetterSettings {
+    db::dead_letter::get(org_id).await.unwrap_or_default()
+}
+
+pub async fn set_settings(
+    org_id: &str,
+    mut settings: DeadLetterSettings,
+) -> Result<DeadLetterSettings, anyhow::Error> {
+    settings.stream_name = format_stream_name(settings.stream_name.trim());
+    if settings.stream_name.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Please provide the dead-letter stream name"
+        ));
+    }
+    db::dead_letter::set(org_id, &settings).await?;
+    Ok(settings)
+}
+
+/// re-ingest the original payloads of the dead-letter records into their
+/// streams, through the functions and routes of the streams. the records are
+/// replayed once, the `_timestamp` of the last one replayed is kept by the
+/// stream and the stage of the records.
+pub async fn replay(
+    org_id: &str,
+    thread_id: usize,
+    req: DeadLetterReplay,
+) -> Result<DeadLetterReplayResponse, anyhow::Error> {
+    let settings = DEAD_LETTER_SETTINGS
+        .get(org_id)
+        .map(|settings| settings.clone())
+        .unwrap_or_default();
+    let mut replayed_at = db::dead_letter::get_replayed(org_id).await?;
+    let mut start_time = req.start_time;
+    if let (Some(stream_name), Some(stage)) = (&req.stream_name, req.stage) {
+        if let Some(ts) = replayed_at.get(&replayed_key(
+            &format_stream_name(stream_name),
+            &stage.to_string(),
+        )) {
+            start_time = start_time.max(ts + 1);
+        }
+    }
+    if req.end_time > 0 && start_time >= req.end_time {
+        return Ok(DeadLetterReplayResponse {
+            replayed: 0,
+            status: vec![],
+        });
+    }
+    let sql = replay_sql(&format_stream_name(&settings.stream_name), &req);
+    let query = search::Request {
+        query: search::Query {
+            sql,
+            from: 0,
+            size: req.size,
+            start_time,
+            end_time: req.end_time,
+            sql_mode: "full".to_string(),
+            ..Default::default()
+        },
+        aggs: HashMap::new(),
+        encoding: search::RequestEncoding::Empty,
+    };
+    let resp = SearchService::search(org_id, StreamType::Logs, "", Priority::Batch, &query).await?;
+
+    let mut records = vec![];
+    for hit in resp.hits.iter() {
+        let Some((key, timestamp, record)) = replay_record(hit) else {
+            continue;
+        };
+        if replayed_at.get(&key).is_some_and(|ts| timestamp <= *ts) {
+            continue;
+        }
+        let ts = replayed_at.entry(key).or_insert(timestamp);
+        *ts = (*ts).max(timestamp);
+        records.push(record);
+    }
+    let replayed = records.len();
+    if replayed == 0 {
+        return Ok(DeadLetterReplayResponse {
+            replayed,
+            status: vec![],
+        });
+    }
+    // the records rejected again are sent to the dead-letter stream as new
+    // records, so the replayed ones are marked before ingesting them
+    db::dead_letter::set_replayed(org_id, &replayed_at).await?;
+    let resp = super::ingest_streams(
+        org_id,
+        thread_id,
+        super::group_by_stream(records),
+        UsageType::DeadLetterReplay,
+        "/api/org/dead_letter/_replay",
+    )
+    .await?;
+    Ok(DeadLetterReplayResponse {
+        replayed,
+        status: resp.status,
+    })
+}
+
+fn replay_sql(dead_letter_stream: &str, req: &DeadLetterReplay) -> String {
+    let mut filters = vec![];
+    if let Some(stream_name) = &req.stream_name {
+        filters.push(format!(
+            "{DEAD_LETTER_STREAM_FIELD} = '{}'",
+            format_stream_name(stream_name).replace('\'', "''")
+        ));
+    }
+    if let Some(stage) = req.stage {
+        filters.push(format!("{DEAD_LETTER_STAGE_FIELD} = '{stage}'"));
+    }
+    let mut sql = format!("SELECT * FROM \"{dead_letter_stream}\"");
+    if !filters.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&filters.join(" AND "));
+    }
+    // the oldest records first, the replayed ones are the ones before the
+    // last replayed timestamp
+    sql.push_str(&format!(" ORDER BY {} ASC", CONFIG.common.column_timestamp));
+    sql
+}
+
+fn replayed_key(stream_name: &str, stage: &str) -> String {
+    format!("{stream_name}/{stage}")
+}
+
+/// the replayed key and the timestamp of the dead-letter record, with the
+/// stream and the original payload of the record
+#[allow(clippy::type_complexity)]
+fn replay_record(hit: &Value) -> Option<(String, i64, (String, Map<String, Value>))> {
+    let stream_name = hit.get(DEAD_LETTER_STREAM_FIELD)?.as_str()?;
+    let stage = hit.get(DEAD_LETTER_STAGE_FIELD)?.as_str()?;
+    let timestamp = hit.get(&CONFIG.common.column_timestamp)?.as_i64()?;
+    let payload = hit.get(DEAD_LETTER_PAYLOAD_FIELD)?.as_str()?;
+    match json::from_str::<Value>(payload).ok()? {
+        Value::Object(record) => Some((
+            replayed_key(stream_name, stage),
+            timestamp,
+            (stream_name.to_string(), record),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::infra::config::STREAM_REDACTIONS;
+    use crate::common::meta::redaction::{
+        PiiDetector, RedactionMode, RedactionRule, StreamRedaction,
+    };
+
+    #[test]
+    fn test_dead_letters() {
+        let mut dead_letters = DeadLetters::new("dead_letter_test_disabled");
+        dead_letters.push(
+            "app",
+            &json::json!({"a": 1}),
+            DeadLetterStage::Schema,
+            "error",
+        );
+        assert!(dead_letters.records.is_empty());
+
+        DEAD_LETTER_SETTINGS.insert(
+            "dead_letter_test".to_string(),
+            DeadLetterSettings {
+                enabled: true,
+                stream_name: "rejected".to_string(),
+            },
+        );
+        let mut dead_letters = DeadLetters::new("dead_letter_test");
+        let payload = json::json!({"level": "error", "code": "x"});
+        dead_letters.push("app", &payload, DeadLetterStage::Schema, "cast error");
+        dead_letters.push("rejected", &payload, DeadLetterStage::Schema, "cast error");
+        assert_eq!(dead_letters.records.len(), 1);
+        let (stream_name, record) = &dead_letters.records[0];
+        assert_eq!(stream_name, "rejected");
+        assert_eq!(record[DEAD_LETTER_STREAM_FIELD], "app");
+        assert_eq!(record[DEAD_LETTER_STAGE_FIELD], "schema");
+        assert_eq!(record[DEAD_LETTER_REASON_FIELD], "cast error");
+
+        // the stored payload is replayed as it was sent
+        let hit = Value::Object(record.clone());
+        let (key, timestamp, (stream_name, replayed)) = replay_record(&hit).unwrap();
+        assert_eq!(key, "app/schema");
+        assert_eq!(record[CONFIG.common.column_timestamp.as_str()], timestamp);
+        assert_eq!(stream_name, "app");
+        assert_eq!(Value::Object(replayed), payload);
+    }
+
+    #[test]
+    fn test_dead_letters_redacted() {
+        DEAD_LETTER_SETTINGS.insert(
+            "dead_letter_redacted".to_string(),
+            DeadLetterSettings {
+                enabled: true,
+                stream_name: "rejected".to_string(),
+            },
+        );
+        STREAM_REDACTIONS.insert(
+            "dead_letter_redacted/logs/users".to_string(),
+            StreamRedaction {
+                rules: vec![RedactionRule {
+                    detector: PiiDetector::Email,
+                    mode: RedactionMode::Mask,
+                    fields: vec![],
+                }],
+            },
+        );
+        let mut dead_letters = DeadLetters::new("dead_letter_redacted");
+        let payload = json::json!({"user": {"email": "a@b.io"}, "code": "x"});
+        dead_letters.push("users", &payload, DeadLetterStage::Schema, "cast error");
+        dead_letters.push("app", &payload, DeadLetterStage::Schema, "cast error");
+        let (_, _, (_, redacted)) =
+            replay_record(&Value::Object(dead_letters.records[0].1.clone())).unwrap();
+        assert_eq!(redacted["user_email"], "******");
+        assert_eq!(redacted["code"], "x");
+        let (_, _, (_, kept)) =
+            replay_record(&Value::Object(dead_letters.records[1].1.clone())).unwrap();
+        assert_eq!(Value::Object(kept), payload);
+    }
+
+    #[test]
+    fn test_replay_sql() {
+        let mut req: DeadLetterReplay = json::from_str(r#"{"start_time":1,"end_time":2}"#).unwrap();
+        assert_eq!(
+            replay_sql("_dead_letter", &req),
+            "SELECT * FROM \"_dead_letter\" ORDER BY _timestamp ASC"
+        );
+        req.stream_name = Some("it's".to_string());
+        req.stage = Some(DeadLetterStage::Function);
+        assert_eq!(
+            replay_sql("_dead_letter", &req),
+            "SELECT * FROM \"_dead_letter\" WHERE stream = 'it''s' AND stage = 'function' ORDER BY _timestamp ASC"
+        );
+    }
+}
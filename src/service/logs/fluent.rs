@@ -0,0 +1,399 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use flate2::read::MultiGzDecoder;
+use rmpv::Value as MsgValue;
+use sha2::{Digest, Sha512};
+use std::io::Read;
+
+use crate::common::infra::config::CONFIG;
+use crate::common::meta::{ingestion::IngestionResponse, usage::UsageType};
+use crate::common::utils::json::{self, Map, Value};
+use crate::service::{fluent_routes, format_stream_name};
+
+/// extension type of the event time, seconds and nanoseconds
+const EVENT_TIME_EXT: i8 = 0;
+
+/// events of a forward frame, in any of the forward modes
+#[derive(Debug)]
+pub struct Forward {
+    pub tag: String,
+    pub entries: Vec<(Option<i64>, Map<String, Value>)>,
+    /// the client waits for an ack of the chunk
+    pub chunk: Option<String>,
+}
+
+/// decode a frame of the Message, Forward, PackedForward or
+/// CompressedPackedForward mode
+pub fn decode_forward(frame: MsgValue) -> Result<Forward, anyhow::Error> {
+    let MsgValue::Array(mut frame) = frame else {
+        return Err(anyhow::anyhow!("Invalid forward frame: not an array"));
+    };
+    if frame.len() < 2 {
+        return Err(anyhow::anyhow!("Invalid forward frame: too short"));
+    }
+    let tag = match &frame[0] {
+        MsgValue::String(v) => v.as_str().unwrap_or_default().to_string(),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Invalid forward frame: tag is not a string"
+            ))
+        }
+    };
+    let mut entries = vec![];
+    let option = match frame.remove(1) {
+        // Forward mode, [tag, [[time, record], ...], option]
+        MsgValue::Array(events) => {
+            for event in events {
+                entries.push(decode_entry(event)?);
+            }
+            frame.get(1).cloned()
+        }
+        // PackedForward mode, [tag, msgpack stream of [time, record], option]
+        packed @ (MsgValue::Binary(_) | MsgValue::String(_)) => {
+            let option = frame.get(1).cloned();
+            let mut data = match packed {
+                MsgValue::Binary(v) => v,
+                MsgValue::String(v) => v.into_bytes(),
+                _ => unreachable!(),
+            };
+            let compressed = option
+                .as_ref()
+                .and_then(|o| option_value(o, "compressed"))
+                .and_then(|v| v.as_str().map(|v| v.to_string()));
+            if let Some(compressed) = compressed {
+                if compressed != "gzip" {
+                    return Err(anyhow::anyhow!("Unsupported compression: {compressed}"));
+                }
+                let limit = CONFIG.limit.req_payload_limit;
+                let mut decoded = vec![];
+                MultiGzDecoder::new(&data[..])
+                    .take(limit as u64 + 1)
+                    .read_to_end(&mut decoded)?;
+                if decoded.len() > limit {
+                    return Err(anyhow::anyhow!(
+                        "Decompressed forward frame exceeds the payload limit"
+                    ));
+                }
+                data = decoded;
+            }
+            let mut cursor = &data[..];
+            while !cursor.is_empty() {
+                entries.push(decode_entry(rmpv::decode::read_value(&mut cursor)?)?);
+            }
+            option
+        }
+        // Message mode, [tag, time, record, option]
+        time => {
+            if frame.len() < 2 {
+                return Err(anyhow::anyhow!("Invalid forward frame: no record"));
+            }
+            let record = frame.remove(1);
+            entries.push((event_time(&time), to_record(record)?));
+            frame.get(1).cloned()
+        }
+    };
+    let chunk = option
+        .as_ref()
+        .and_then(|o| option_value(o, "chunk"))
+        .and_then(|v| v.as_str().map(|v| v.to_string()));
+    Ok(Forward {
+        tag,
+        entries,
+        chunk,
+    })
+}
+
+/// route the events by their tag and ingest them, fails when any event was
+/// not written
+pub async fn ingest(
+    thread_id: usize,
+    forward: Forward,
+) -> Result<IngestionResponse, anyhow::Error> {
+    let Some(route) = fluent_routes::get_route_for_tag(&forward.tag) else {
+        return Err(anyhow::anyhow!(
+            "Fluent forward tag [{}] has no route",
+            forward.tag
+        ));
+    };
+    let stream_name = if route.stream_name.is_empty() {
+        format_stream_name(&forward.tag.replace('.', "_"))
+    } else {
+        format_stream_name(&route.stream_name)
+    };
+    let records = forward
+        .entries
+        .into_iter()
+        .map(|(timestamp, mut rec)| {
+            if let Some(timestamp) = timestamp {
+                rec.insert(CONFIG.common.column_timestamp.clone(), timestamp.into());
+            }
+            rec
+        })
+        .collect();
+    let ret = super::ingest_streams(
+        &route.org_id,
+        thread_id,
+        vec![(stream_name, records)],
+        UsageType::FluentForward,
+        "/api/org/ingest/logs/_fluent_forward",
+    )
+    .await?;
+    if let Some(status) = ret.status.iter().find(|s| s.status.failed > 0) {
+        return Err(anyhow::anyhow!(
+            "Fluent forward stream [{}]: {} events failed, {}",
+            status.name,
+            status.status.failed,
+            status.status.error
+        ));
+    }
+    Ok(ret)
+}
+
+/// the ack response of a chunk
+pub fn ack(chunk: &str) -> MsgValue {
+    MsgValue::Map(vec![(MsgValue::from("ack"), MsgValue::from(chunk))])
+}
+
+/// the HELO message starting the shared key handshake
+pub fn helo(nonce: &[u8]) -> MsgValue {
+    MsgValue::Array(vec![
+        MsgValue::from("HELO"),
+        MsgValue::Map(vec![
+            (MsgValue::from("nonce"), MsgValue::Binary(nonce.to_vec())),
+            (MsgValue::from("auth"), MsgValue::Binary(vec![])),
+            (MsgValue::from("keepalive"), MsgValue::Boolean(true)),
+        ]),
+    ])
+}
+
+/// check the PING of the client, returns if it is authenticated and the PONG
+pub fn pong(ping: &MsgValue, nonce: &[u8], shared_key: &str) -> (bool, MsgValue) {
+    let parts = ping.as_array().map(|v| v.as_slice()).unwrap_or_default();
+    let part = |i: usize| parts.get(i).and_then(|v| v.as_str()).unwrap_or_default();
+    // the salt is random bytes, fluent-bit sends it as bin rather than str
+    let salt = match parts.get(2) {
+        Some(MsgValue::Binary(v)) => v.as_slice(),
+        Some(MsgValue::String(v)) => v.as_bytes(),
+        _ => &[],
+    };
+    let (hostname, digest) = (part(1), part(3));
+    let (authenticated, reason) = if part(0) != "PING" || parts.len() < 4 {
+        (false, "Invalid PING message")
+    } else if hostname == CONFIG.tcp.fluent_forward_hostname {
+        (
+            false,
+            "Same hostname between input and output: invalid configuration",
+        )
+    } else if digest != shared_key_digest(salt, hostname, nonce, shared_key) {
+        (false, "Shared key mismatch")
+    } else {
+        (true, "")
+    };
+    let hostname = &CONFIG.tcp.fluent_forward_hostname;
+    let pong = MsgValue::Array(vec![
+        MsgValue::from("PONG"),
+        MsgValue::Boolean(authenticated),
+        MsgValue::from(reason),
+        MsgValue::from(hostname.as_str()),
+        MsgValue::from(shared_key_digest(salt, hostname, nonce, shared_key)),
+    ]);
+    (authenticated, pong)
+}
+
+fn shared_key_digest(salt: &[u8], hostname: &str, nonce: &[u8], shared_key: &str) -> String {
+    let mut hasher = Sha512::new();
+    hasher.update(salt);
+    hasher.update(hostname.as_bytes());
+    hasher.update(nonce);
+    hasher.update(shared_key.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn decode_entry(entry: MsgValue) -> Result<(Option<i64>, Map<String, Value>), anyhow::Error> {
+    match entry {
+        MsgValue::Array(mut entry) if entry.len() >= 2 => {
+            let record = entry.remove(1);
+            Ok((event_time(&entry[0]), to_record(record)?))
+        }
+        _ => Err(anyhow::anyhow!(
+            "Invalid forward entry: it must be [time, record]"
+        )),
+    }
+}
+
+fn option_value<'a>(option: &'a MsgValue, name: &str) -> Option<&'a MsgValue> {
+    option
+        .as_map()?
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(name))
+        .map(|(_, v)| v)
+}
+
+/// the event time in microseconds, from seconds or the EventTime extension
+fn event_time(time: &MsgValue) -> Option<i64> {
+    match time {
+        MsgValue::Integer(v) => v.as_i64().and_then(|secs| secs.checked_mul(1_000_000)),
+        MsgValue::F32(v) => Some((*v as f64 * 1_000_000.0) as i64),
+        MsgValue::F64(v) => Some((v * 1_000_000.0) as i64),
+        MsgValue::Ext(EVENT_TIME_EXT, data) if data.len() == 8 => {
+            let secs = u32::from_be_bytes(data[0..4].try_into().unwrap()) as i64;
+            let nanos = u32::from_be_bytes(data[4..8].try_into().unwrap()) as i64;
+            Some(secs * 1_000_000 + nanos / 1000)
+        }
+        _ => None,
+    }
+}
+
+fn to_record(record: MsgValue) -> Result<Map<String, Value>, anyhow::Error> {
+    match to_json(record) {
+        Value::Object(v) => Ok(v),
+        _ => Err(anyhow::anyhow!(
+            "Invalid forward entry: record is not a map"
+        )),
+    }
+}
+
+fn to_json(value: MsgValue) -> Value {
+    match value {
+        MsgValue::Nil => Value::Null,
+        MsgValue::Boolean(v) => v.into(),
+        MsgValue::Integer(v) => match v.as_i64() {
+            Some(v) => v.into(),
+            None => v.as_u64().unwrap_or_default().into(),
+        },
+        MsgValue::F32(v) => json::Number::from_f64(v as f64).map_or(Value::Null, Value::Number),
+        MsgValue::F64(v) => json::Number::from_f64(v).map_or(Value::Null, Value::Number),
+        MsgValue::String(v) => String::from_utf8_lossy(v.as_bytes()).into_owned().into(),
+        MsgValue::Binary(v) => String::from_utf8_lossy(&v).into_owned().into(),
+        MsgValue::Array(v) => Value::Array(v.into_iter().map(to_json).collect()),
+        MsgValue::Map(v) => Value::Object(
+            v.into_iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        MsgValue::String(k) => String::from_utf8_lossy(k.as_bytes()).into_owned(),
+                        k => k.to_string(),
+                    };
+                    (key, to_json(v))
+                })
+                .collect(),
+        ),
+        MsgValue::Ext(_, v) => hex::encode(v).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    use super::*;
+
+    fn entry(time: u64, message: &str) -> MsgValue {
+        MsgValue::Array(vec![
+            MsgValue::from(time),
+            MsgValue::Map(vec![(MsgValue::from("message"), MsgValue::from(message))]),
+        ])
+    }
+
+    fn packed(entries: &[MsgValue]) -> Vec<u8> {
+        let mut buf = vec![];
+        for entry in entries {
+            rmpv::encode::write_value(&mut buf, entry).unwrap();
+        }
+        buf
+    }
+
+    #[test]
+    fn test_decode_message() {
+        let mut event_time = vec![];
+        event_time.extend(1_700_000_000_u32.to_be_bytes());
+        event_time.extend(500_000_000_u32.to_be_bytes());
+        let frame = MsgValue::Array(vec![
+            MsgValue::from("app.web"),
+            MsgValue::Ext(EVENT_TIME_EXT, event_time),
+            MsgValue::Map(vec![(MsgValue::from("code"), MsgValue::from(200))]),
+            MsgValue::Map(vec![(MsgValue::from("chunk"), MsgValue::from("abc"))]),
+        ]);
+        let forward = decode_forward(frame).unwrap();
+        assert_eq!(forward.tag, "app.web");
+        assert_eq!(forward.chunk.as_deref(), Some("abc"));
+        assert_eq!(forward.entries.len(), 1);
+        assert_eq!(forward.entries[0].0, Some(1_700_000_000_500_000));
+        assert_eq!(forward.entries[0].1["code"], 200);
+    }
+
+    #[test]
+    fn test_event_time() {
+        assert_eq!(event_time(&MsgValue::from(2)), Some(2_000_000));
+        assert_eq!(event_time(&MsgValue::from(i64::MAX)), None);
+    }
+
+    #[test]
+    fn test_decode_forward() {
+        let frame = MsgValue::Array(vec![
+            MsgValue::from("app"),
+            MsgValue::Array(vec![entry(1, "a"), entry(2, "b")]),
+        ]);
+        let forward = decode_forward(frame).unwrap();
+        assert_eq!(forward.chunk, None);
+        assert_eq!(forward.entries.len(), 2);
+        assert_eq!(forward.entries[1].0, Some(2_000_000));
+        assert_eq!(forward.entries[1].1["message"], "b");
+    }
+
+    #[test]
+    fn test_decode_packed_forward() {
+        let data = packed(&[entry(1, "a"), entry(2, "b")]);
+        let frame = MsgValue::Array(vec![MsgValue::from("app"), MsgValue::Binary(data.clone())]);
+        assert_eq!(decode_forward(frame).unwrap().entries.len(), 2);
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&data).unwrap();
+        let frame = MsgValue::Array(vec![
+            MsgValue::from("app"),
+            MsgValue::Binary(encoder.finish().unwrap()),
+            MsgValue::Map(vec![(MsgValue::from("compressed"), MsgValue::from("gzip"))]),
+        ]);
+        let forward = decode_forward(frame).unwrap();
+        assert_eq!(forward.entries.len(), 2);
+        assert_eq!(forward.entries[0].1["message"], "a");
+    }
+
+    #[test]
+    fn test_pong() {
+        let nonce = b"0123456789abcdef";
+        let digest = shared_key_digest(b"salt", "client", nonce, "secret");
+        let ping = MsgValue::Array(vec![
+            MsgValue::from("PING"),
+            MsgValue::from("client"),
+            MsgValue::from("salt"),
+            MsgValue::from(digest.as_str()),
+            MsgValue::from(""),
+            MsgValue::from(""),
+        ]);
+        assert!(pong(&ping, nonce, "secret").0);
+        assert!(!pong(&ping, nonce, "other").0);
+
+        let salt = vec![0xff, 0x00, 0x9c, 0x80];
+        let digest = shared_key_digest(&salt, "client", nonce, "secret");
+        let ping = MsgValue::Array(vec![
+            MsgValue::from("PING"),
+            MsgValue::from("client"),
+            MsgValue::Binary(salt),
+            MsgValue::from(digest.as_str()),
+        ]);
+        assert!(pong(&ping, nonce, "secret").0);
+    }
+}
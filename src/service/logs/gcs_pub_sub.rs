@@ -17,7 +17,9 @@ use crate::common::meta::{
 };
 use crate::common::utils::{flatten, json, time::parse_timestamp_micro_from_value};
 use crate::service::{
-    db, format_stream_name, ingestion::write_file, usage::report_request_usage_stats,
+    db, format_stream_name,
+    ingestion::{quota, write_file},
+    usage::report_request_usage_stats,
 };
 
 pub async fn process(
@@ -41,6 +43,9 @@ pub async fn process(
         )));
     }
 
+    let bytes = request.message.data.len() as u64;
+    quota::check(org_id, &[(stream_name.as_str(), 1, bytes)]).await?;
+
     let mut runtime = crate::service::ingestion::init_functions_runtime();
 
     let mut stream_schema_map: AHashMap<String, Schema> = AHashMap::new();
@@ -50,6 +55,7 @@ pub async fn process(
         status: RecordStatus {
             successful: 0,
             failed: 0,
+            dropped: 0,
             error: "".to_string(),
         },
     };
@@ -105,15 +111,29 @@ pub async fn process(
 
             // Start row based transform
 
-            let mut value = crate::service::ingestion::apply_stream_transform(
+            let mut value = match crate::service::ingestion::apply_stream_transform(
                 &local_trans,
                 &value,
                 &stream_vrl_map,
                 stream_name,
                 &mut runtime,
-            )?;
+            ) {
+                Ok(value) => value,
+                Err(e) => {
+                    return Ok(GCPIngestionResponse {
+                        request_id: request.message.message_id,
+                        error_message: Some(e.to_string()),
+                        timestamp: request.message.publish_time,
+                    });
+                }
+            };
+            // the message dropped by the function is done
             if value.is_null() || !value.is_object() {
-                stream_status.status.failed += 1; // transform failed or dropped
+                return Ok(GCPIngestionResponse {
+                    request_id: request.message.message_id,
+                    timestamp: request.message.publish_time,
+                    error_message: None,
+                });
             }
             // End row based transform
 
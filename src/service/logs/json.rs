@@ -17,10 +17,11 @@ use ahash::AHashMap;
 use chrono::{Duration, Utc};
 use datafusion::arrow::datatypes::Schema;
 
-use super::StreamMeta;
+use super::{dead_letter::DeadLetters, StreamMeta};
 use crate::common::infra::{cluster, config::CONFIG, metrics};
 use crate::common::meta::{
     alert::{Alert, Trigger},
+    dead_letter::DeadLetterStage,
     ingestion::{IngestionResponse, StreamStatus},
     stream::StreamParams,
     usage::UsageType,
@@ -28,7 +29,10 @@ use crate::common::meta::{
 };
 use crate::common::utils::{flatten, json, time::parse_timestamp_micro_from_value};
 use crate::service::{
-    db, format_stream_name, ingestion::write_file, schema::stream_schema_exists,
+    db, format_stream_name,
+    ingestion::{quota, write_file},
+    schema::stream_schema_exists,
+    stream_routes,
     usage::report_request_usage_stats,
 };
 
@@ -71,6 +75,7 @@ pub async fn ingest(
         stream_name,
     );
     // End Register Transforms for stream
+    let routes = stream_routes::register_stream_routes(org_id, stream_name);
 
     let stream_schema = stream_schema_exists(
         org_id,
@@ -94,27 +99,48 @@ pub async fn ingest(
     // End get stream alert
 
     let mut buf: AHashMap<String, Vec<String>> = AHashMap::new();
+    let mut routed = vec![];
+    let mut dead_letters = DeadLetters::new(org_id);
     let reader: Vec<json::Value> = json::from_slice(&body)?;
+    quota::check(
+        org_id,
+        &[(stream_name.as_str(), reader.len() as u64, body.len() as u64)],
+    )
+    .await?;
     for item in reader.iter() {
         //JSON Flattening
         let mut value = flatten::flatten(item)?;
 
         if !local_trans.is_empty() {
-            value = crate::service::ingestion::apply_stream_transform(
+            value = match crate::service::ingestion::apply_stream_transform(
                 &local_trans,
                 &value,
                 &stream_vrl_map,
                 stream_name,
                 &mut runtime,
-            )?;
+            ) {
+                Ok(value) => value,
+                Err(e) => {
+                    stream_status.status.failed += 1;
+                    stream_status.status.error = e.to_string();
+                    dead_letters.push(stream_name, item, DeadLetterStage::Function, &e.to_string());
+                    continue;
+                }
+            };
         }
 
         if value.is_null() || !value.is_object() {
-            stream_status.status.failed += 1; // transform failed or dropped
+            stream_status.status.dropped += 1; // dropped by the function
             continue;
         }
         // End row based transform
 
+        if !routes.is_empty()
+            && !stream_routes::route_record(&routes, &value, &mut runtime, &mut routed)
+        {
+            continue;
+        }
+
         // get json object
         let local_val = value.as_object_mut().unwrap();
 
@@ -125,6 +151,12 @@ pub async fn ingest(
                 Err(e) => {
                     stream_status.status.failed += 1;
                     stream_status.status.error = e.to_string();
+                    dead_letters.push(
+                        stream_name,
+                        item,
+                        DeadLetterStage::Timestamp,
+                        &e.to_string(),
+                    );
                     continue;
                 }
             },
@@ -135,6 +167,12 @@ pub async fn ingest(
         if timestamp < earliest_time.timestamp_micros() {
             stream_status.status.failed += 1; // to old data, just discard
             stream_status.status.error = super::get_upto_discard_error();
+            dead_letters.push(
+                stream_name,
+                item,
+                DeadLetterStage::Timestamp,
+                &stream_status.status.error,
+            );
             continue;
         }
         if timestamp < min_ts {
@@ -145,6 +183,7 @@ pub async fn ingest(
             json::Value::Number(timestamp.into()),
         );
 
+        let failed = stream_status.status.failed;
         let local_trigger = super::add_valid_record(
             StreamMeta {
                 org_id: org_id.to_string(),
@@ -159,11 +198,38 @@ pub async fn ingest(
         )
         .await;
 
+        if stream_status.status.failed > failed {
+            dead_letters.push(
+                stream_name,
+                item,
+                DeadLetterStage::Schema,
+                &stream_status.status.error,
+            );
+        }
         if local_trigger.is_some() {
             trigger = Some(local_trigger.unwrap());
         }
     }
 
+    // records sent to other streams by the stream routes
+    let routed_status = super::ingest_routed(
+        org_id,
+        thread_id,
+        routed,
+        UsageType::Json,
+        "/api/org/ingest/logs/_json",
+        &mut dead_letters,
+    )
+    .await?;
+    dead_letters
+        .flush(
+            org_id,
+            thread_id,
+            UsageType::Json,
+            "/api/org/ingest/logs/_json",
+        )
+        .await;
+
     // write to file
     let mut stream_file_name = "".to_string();
     let mut req_stats = write_file(
@@ -181,7 +247,7 @@ pub async fn ingest(
     if stream_file_name.is_empty() {
         return Ok(IngestionResponse::new(
             http::StatusCode::OK.into(),
-            vec![stream_status],
+            [vec![stream_status], routed_status].concat(),
         ));
     }
 
@@ -222,6 +288,6 @@ pub async fn ingest(
 
     Ok(IngestionResponse::new(
         http::StatusCode::OK.into(),
-        vec![stream_status],
+        [vec![stream_status], routed_status].concat(),
     ))
 }
@@ -33,7 +33,11 @@ use crate::common::meta::{
 
 use crate::common::utils::{flatten, json, time::parse_timestamp_micro_from_value};
 use crate::service::ingestion::grpc::get_val;
-use crate::service::{db, format_stream_name, ingestion::write_file, schema::stream_schema_exists};
+use crate::service::{
+    db, format_stream_name,
+    ingestion::{quota, write_file},
+    schema::stream_schema_exists,
+};
 use crate::{
     common::meta::{
         alert::{Alert, Trigger},
@@ -97,6 +101,11 @@ pub async fn ingest(
 
     let mut buf: AHashMap<String, Vec<String>> = AHashMap::new();
     let reader: Vec<json::Value> = json::from_slice(&body)?;
+    quota::check(
+        org_id,
+        &[(stream_name.as_str(), reader.len() as u64, body.len() as u64)],
+    )
+    .await?;
     for item in reader.iter() {
         //JSON Flattening
         let mut value = flatten::flatten(item)?;
@@ -353,6 +362,13 @@ pub async fn handle_grpc_request(
         }
     }
 
+    if quota::has_quotas(org_id) {
+        let (events, bytes) = quota::buffered_usage(data_buf.values());
+        if let Err(e) = quota::check(org_id, &[(stream_name, events, bytes)]).await {
+            return Ok(e.to_response());
+        }
+    }
+
     // write to file
     let mut stream_file_name = "".to_string();
     let mut req_stats = write_file(
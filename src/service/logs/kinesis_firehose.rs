@@ -18,7 +18,7 @@ use datafusion::arrow::datatypes::Schema;
 use flate2::read::GzDecoder;
 use std::io::Read;
 
-use super::StreamMeta;
+use super::{dead_letter::DeadLetters, StreamMeta};
 use crate::common::infra::{cluster, config::CONFIG, metrics};
 use crate::common::meta::{
     alert::{Alert, Trigger},
@@ -34,7 +34,10 @@ use crate::common::utils::{
     time::{parse_i64_to_timestamp_micros, parse_timestamp_micro_from_value},
 };
 use crate::service::{
-    db, format_stream_name, ingestion::write_file, usage::report_request_usage_stats,
+    db, format_stream_name,
+    ingestion::{quota, write_file},
+    stream_routes,
+    usage::report_request_usage_stats,
 };
 
 pub async fn process(
@@ -55,6 +58,13 @@ pub async fn process(
         return Err(anyhow::anyhow!("stream [{stream_name}] is being deleted"));
     }
 
+    let bytes: u64 = request.records.iter().map(|v| v.data.len() as u64).sum();
+    quota::check(
+        org_id,
+        &[(stream_name.as_str(), request.records.len() as u64, bytes)],
+    )
+    .await?;
+
     let mut runtime = crate::service::ingestion::init_functions_runtime();
 
     let mut min_ts =
@@ -73,6 +83,7 @@ pub async fn process(
         stream_name,
     );
     // End Register Transforms for stream
+    let routes = stream_routes::register_stream_routes(org_id, stream_name);
 
     let stream_schema = crate::service::schema::stream_schema_exists(
         org_id,
@@ -95,6 +106,7 @@ pub async fn process(
     // End get stream alert
 
     let mut buf: AHashMap<String, Vec<String>> = AHashMap::new();
+    let mut routed = vec![];
     for record in request.records {
         match decode_and_decompress(&record.data) {
             Err(err) => {
@@ -177,20 +189,33 @@ pub async fn process(
 
                 // Start row based transform
 
-                let mut value = crate::service::ingestion::apply_stream_transform(
+                let mut value = match crate::service::ingestion::apply_stream_transform(
                     &local_trans,
                     &value,
                     &stream_vrl_map,
                     stream_name,
                     &mut runtime,
-                )?;
+                ) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        stream_status.status.failed += 1;
+                        stream_status.status.error = e.to_string();
+                        continue;
+                    }
+                };
 
                 if value.is_null() || !value.is_object() {
-                    stream_status.status.failed += 1; // transform failed or dropped
+                    stream_status.status.dropped += 1; // dropped by the function
                     continue;
                 }
                 // End row based transform
 
+                if !routes.is_empty()
+                    && !stream_routes::route_record(&routes, &value, &mut runtime, &mut routed)
+                {
+                    continue;
+                }
+
                 // get json object
                 let local_val = value.as_object_mut().unwrap();
 
@@ -232,6 +257,26 @@ pub async fn process(
         }
     }
 
+    // records sent to other streams by the stream routes
+    let mut dead_letters = DeadLetters::new(org_id);
+    super::ingest_routed(
+        org_id,
+        thread_id,
+        routed,
+        UsageType::KinesisFirehose,
+        "/api/org/ingest/logs/_kinesis",
+        &mut dead_letters,
+    )
+    .await?;
+    dead_letters
+        .flush(
+            org_id,
+            thread_id,
+            UsageType::KinesisFirehose,
+            "/api/org/ingest/logs/_kinesis",
+        )
+        .await;
+
     // write to file
     let mut stream_file_name = "".to_string();
     let mut req_stats = write_file(
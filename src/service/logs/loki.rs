@@ -0,0 +1,238 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::web;
+use once_cell::sync::Lazy;
+use prost::Message;
+use regex::Regex;
+
+use crate::common::infra::config::CONFIG;
+use crate::common::meta::{ingestion::IngestionResponse, usage::UsageType};
+use crate::common::utils::json::{self, Map, Value};
+use crate::service::format_stream_name;
+
+pub(crate) mod logproto {
+    include!(concat!(env!("OUT_DIR"), "/logproto.rs"));
+}
+
+/// field of the log line
+const LINE_FIELD: &str = "message";
+
+const DEFAULT_STREAM: &str = "default";
+
+static RE_LABEL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"([a-zA-Z_][a-zA-Z0-9_]*)\s*=\s*"((?:[^"\\]|\\.)*)""#).unwrap());
+
+/// the snappy compressed protobuf `PushRequest` sent by promtail
+pub async fn push_proto(
+    org_id: &str,
+    thread_id: usize,
+    body: web::Bytes,
+) -> Result<IngestionResponse, anyhow::Error> {
+    let decoded = snap::raw::Decoder::new()
+        .decompress_vec(&body)
+        .map_err(|e| anyhow::anyhow!("Invalid snappy compressed data: {}", e.to_string()))?;
+    let request = logproto::PushRequest::decode(bytes::Bytes::from(decoded))?;
+    let mut records = vec![];
+    for stream in request.streams {
+        let labels = parse_labels(&stream.labels);
+        let stream_name = stream_name(&labels);
+        for entry in stream.entries {
+            let timestamp = entry.timestamp.map(proto_timestamp).transpose()?;
+            let metadata = entry
+                .structured_metadata
+                .into_iter()
+                .map(|l| (l.name, l.value));
+            let rec = record(&labels, metadata, timestamp, entry.line);
+            records.push((stream_name.clone(), rec));
+        }
+    }
+    ingest(org_id, thread_id, records).await
+}
+
+/// the json form, `{"streams":[{"stream":{..},"values":[["<ns>","<line>"]]}]}`
+pub async fn push_json(
+    org_id: &str,
+    thread_id: usize,
+    body: web::Bytes,
+) -> Result<IngestionResponse, anyhow::Error> {
+    let body: Value = json::from_slice(&body)?;
+    let Some(streams) = body.get("streams").and_then(|v| v.as_array()) else {
+        return Err(anyhow::anyhow!(
+            "Invalid json: the structure must be {{\"streams\":[]}}"
+        ));
+    };
+    let mut records = vec![];
+    for stream in streams {
+        let labels: Vec<(String, String)> = stream
+            .get("stream")
+            .and_then(|v| v.as_object())
+            .map(|v| v.iter().map(|(k, v)| (k.clone(), label_value(v))).collect())
+            .unwrap_or_default();
+        let stream_name = stream_name(&labels);
+        let values = stream.get("values").and_then(|v| v.as_array());
+        for value in values.into_iter().flatten() {
+            let Some(value) = value.as_array().filter(|v| v.len() >= 2) else {
+                return Err(anyhow::anyhow!(
+                    "Invalid json: a value must be [\"<unix epoch in nanoseconds>\", \"<log line>\"]"
+                ));
+            };
+            let timestamp = match &value[0] {
+                Value::String(v) => v.parse::<i64>().ok(),
+                Value::Number(v) => v.as_i64(),
+                _ => None,
+            }
+            .map(|ns| ns / 1000);
+            let line = value[1].as_str().unwrap_or_default().to_string();
+            let metadata = value
+                .get(2)
+                .and_then(|v| v.as_object())
+                .into_iter()
+                .flatten()
+                .map(|(k, v)| (k.clone(), label_value(v)));
+            let rec = record(&labels, metadata, timestamp, line);
+            records.push((stream_name.clone(), rec));
+        }
+    }
+    ingest(org_id, thread_id, records).await
+}
+
+async fn ingest(
+    org_id: &str,
+    thread_id: usize,
+    records: Vec<(String, Map<String, Value>)>,
+) -> Result<IngestionResponse, anyhow::Error> {
+    super::ingest_streams(
+        org_id,
+        thread_id,
+        super::group_by_stream(records),
+        UsageType::Loki,
+        "/api/org/loki/api/v1/push",
+    )
+    .await
+}
+
+/// the record of a log line, the labels and the structured metadata are fields
+fn record(
+    labels: &[(String, String)],
+    metadata: impl Iterator<Item = (String, String)>,
+    timestamp: Option<i64>,
+    line: String,
+) -> Map<String, Value> {
+    let mut rec = Map::new();
+    for (name, value) in labels {
+        rec.insert(name.clone(), value.clone().into());
+    }
+    for (name, value) in metadata {
+        rec.insert(name, value.into());
+    }
+    if let Some(timestamp) = timestamp {
+        rec.insert(CONFIG.common.column_timestamp.clone(), timestamp.into());
+    }
+    rec.insert(LINE_FIELD.to_string(), line.into());
+    rec
+}
+
+/// the timestamp of a proto entry in microseconds
+fn proto_timestamp(t: logproto::Timestamp) -> Result<i64, anyhow::Error> {
+    t.seconds
+        .checked_mul(1_000_000)
+        .and_then(|v| v.checked_add(t.nanos as i64 / 1000))
+        .ok_or_else(|| anyhow::anyhow!("Invalid timestamp: {}s out of range", t.seconds))
+}
+
+/// the target stream is the value of the configured label
+fn stream_name(labels: &[(String, String)]) -> String {
+    let stream = labels
+        .iter()
+        .find(|(name, _)| *name == CONFIG.common.loki_stream_label)
+        .map(|(_, value)| value.as_str())
+        .filter(|v| !v.is_empty())
+        .unwrap_or(DEFAULT_STREAM);
+    format_stream_name(stream)
+}
+
+/// parse labels in the prometheus format, e.g. `{job="app", env="prod"}`
+fn parse_labels(labels: &str) -> Vec<(String, String)> {
+    RE_LABEL
+        .captures_iter(labels)
+        .map(|cap| {
+            let raw = cap.get(2).unwrap().as_str();
+            // the escaping of label values is the one of go strings
+            let value =
+                json::from_str::<String>(&format!("\"{raw}\"")).unwrap_or_else(|_| raw.to_string());
+            (cap.get(1).unwrap().as_str().to_string(), value)
+        })
+        .collect()
+}
+
+fn label_value(value: &Value) -> String {
+    match value {
+        Value::String(v) => v.clone(),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_labels() {
+        assert_eq!(
+            parse_labels(r#"{job="varlogs", filename="/var/log/a \"b\".log"}"#),
+            vec![
+                ("job".to_string(), "varlogs".to_string()),
+                ("filename".to_string(), "/var/log/a \"b\".log".to_string()),
+            ]
+        );
+        assert!(parse_labels("{}").is_empty());
+    }
+
+    #[test]
+    fn test_proto_timestamp() {
+        let t = logproto::Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 5_000,
+        };
+        assert_eq!(proto_timestamp(t).unwrap(), 1_700_000_000_000_005);
+        let t = logproto::Timestamp {
+            seconds: i64::MAX,
+            nanos: 0,
+        };
+        assert!(proto_timestamp(t).is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        let labels = parse_labels(r#"{job="nginx", host="web1"}"#);
+        assert_eq!(stream_name(&labels), "nginx");
+        assert_eq!(stream_name(&[]), DEFAULT_STREAM);
+
+        let metadata = vec![("trace_id".to_string(), "abc".to_string())];
+        let rec = record(
+            &labels,
+            metadata.into_iter(),
+            Some(1_700_000_000_000_000),
+            "GET /".to_string(),
+        );
+        assert_eq!(rec["host"], "web1");
+        assert_eq!(rec["trace_id"], "abc");
+        assert_eq!(rec[LINE_FIELD], "GET /");
+        assert_eq!(
+            rec[&CONFIG.common.column_timestamp],
+            1_700_000_000_000_000_i64
+        );
+    }
+}
@@ -12,37 +12,56 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use actix_web::http;
 use ahash::AHashMap;
 use arrow_schema::{DataType, Field};
+use chrono::{Duration, Utc};
 use datafusion::arrow::datatypes::Schema;
 
 use crate::common::{
-    infra::config::CONFIG,
+    infra::{cluster, config::CONFIG, metrics},
     meta::{
         alert::{Alert, Evaluate, Trigger},
-        ingestion::RecordStatus,
-        stream::PartitionTimeLevel,
+        dead_letter::DeadLetterStage,
+        ingestion::{IngestionResponse, RecordStatus, StreamStatus},
+        stream::{PartitionTimeLevel, StreamParams, DELETED_FIELD},
+        usage::UsageType,
         StreamType,
     },
     utils::{
         self,
         hasher::get_fields_key_xxh3,
         json::{Map, Value},
+        time::parse_timestamp_micro_from_value,
     },
 };
-use crate::service::schema::check_for_schema;
+use crate::service::{
+    db,
+    ingestion::{quota, redaction, write_file},
+    sampling,
+    schema::{check_for_schema, stream_schema_exists},
+    stream_routes,
+    usage::report_request_usage_stats,
+};
 
+use self::dead_letter::DeadLetters;
 use super::ingestion::get_wal_time_key;
 
 pub mod bulk;
+pub mod datadog;
+pub mod dead_letter;
+pub mod fluent;
 pub mod gcs_pub_sub;
 pub mod json;
 pub mod json_no_fn;
 pub mod kinesis_firehose;
+pub mod loki;
 pub mod multi;
+pub mod otlp_http;
+pub mod splunk;
 pub mod syslog;
 
-static BULK_OPERATORS: [&str; 3] = ["create", "index", "update"];
+static BULK_OPERATORS: [&str; 4] = ["create", "index", "update", "delete"];
 
 pub(crate) fn get_upto_discard_error() -> String {
     format!(
@@ -222,6 +241,22 @@ async fn add_valid_record(
     local_val: &mut Map<String, Value>,
 ) -> Option<Trigger> {
     let mut trigger: Option<Trigger> = None;
+    // a tombstone is kept whatever the sampling rules, its upsert may be kept
+    if !local_val.contains_key(DELETED_FIELD)
+        && !sampling::keep_record(&stream_meta.org_id, &stream_meta.stream_name, local_val)
+    {
+        status.dropped += 1;
+        return trigger;
+    }
+    // the values to redact never reach the schema and the alerts
+    redaction::redact(
+        &StreamParams {
+            org_id: &stream_meta.org_id,
+            stream_name: &stream_meta.stream_name,
+            stream_type: StreamType::Logs,
+        },
+        local_val,
+    );
     let timestamp: i64 = local_val
         .get(&CONFIG.common.column_timestamp)
         .unwrap()
@@ -320,6 +355,7 @@ async fn add_valid_record(
         };
     } else {
         status.failed += 1;
+        status.error = "record is not compatible with the stream schema".to_string();
     }
     trigger
 }
@@ -361,6 +397,430 @@ struct StreamMeta {
     stream_alerts_map: AHashMap<String, Vec<Alert>>,
 }
 
+/// Ingest the records grouped by stream through the pipeline of the json
+/// endpoint: flattening, stream functions, stream routes, the timestamp checks
+/// and the real time alerts.
+pub(crate) async fn ingest_streams(
+    org_id: &str,
+    thread_id: usize,
+    streams: Vec<(String, Vec<Map<String, Value>>)>,
+    usage_type: UsageType,
+    endpoint: &str,
+) -> Result<IngestionResponse, anyhow::Error> {
+    if !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
+        return Err(anyhow::anyhow!("not an ingester"));
+    }
+
+    if !db::file_list::BLOCKED_ORGS.is_empty() && db::file_list::BLOCKED_ORGS.contains(&org_id) {
+        return Err(anyhow::anyhow!("Quota exceeded for this organization"));
+    }
+
+    if quota::has_quotas(org_id) {
+        let usage: Vec<_> = streams
+            .iter()
+            .map(|(stream_name, records)| {
+                (
+                    stream_name.as_str(),
+                    records.len() as u64,
+                    records_bytes(records),
+                )
+            })
+            .collect();
+        quota::check(org_id, &usage).await?;
+    }
+
+    let mut dead_letters = DeadLetters::new(org_id);
+    let (mut stream_status_list, routed) = write_streams(
+        org_id,
+        thread_id,
+        streams,
+        false,
+        usage_type,
+        endpoint,
+        &mut dead_letters,
+        &mut vec![],
+    )
+    .await?;
+    stream_status_list.extend(
+        ingest_routed(
+            org_id,
+            thread_id,
+            routed,
+            usage_type,
+            endpoint,
+            &mut dead_letters,
+        )
+        .await?,
+    );
+    dead_letters
+        .flush(org_id, thread_id, usage_type, endpoint)
+        .await;
+
+    Ok(IngestionResponse::new(
+        http::StatusCode::OK.into(),
+        stream_status_list,
+    ))
+}
+
+/// Ingest the records the stream routes sent to other streams, they skip the
+/// functions and the routes of their destination stream.
+pub(crate) async fn ingest_routed(
+    org_id: &str,
+    thread_id: usize,
+    routed: Vec<(String, Map<String, Value>)>,
+    usage_type: UsageType,
+    endpoint: &str,
+    dead_letters: &mut DeadLetters,
+) -> Result<Vec<StreamStatus>, anyhow::Error> {
+    let (stream_status_list, _) = ingest_routed_records(
+        org_id,
+        thread_id,
+        routed,
+        usage_type,
+        endpoint,
+        dead_letters,
+    )
+    .await?;
+    Ok(stream_status_list)
+}
+
+/// Ingest the records the stream routes sent to other streams like
+/// `ingest_routed`, also returns the positions in `routed` of the records
+/// which were not written with the error of their stream.
+pub(crate) async fn ingest_routed_records(
+    org_id: &str,
+    thread_id: usize,
+    routed: Vec<(String, Map<String, Value>)>,
+    usage_type: UsageType,
+    endpoint: &str,
+    dead_letters: &mut DeadLetters,
+) -> Result<(Vec<StreamStatus>, Vec<(usize, String)>), anyhow::Error> {
+    if routed.is_empty() {
+        return Ok((vec![], vec![]));
+    }
+    let mut streams: Vec<(String, Vec<Map<String, Value>>)> = vec![];
+    let mut positions: Vec<Vec<usize>> = vec![];
+    for (i, (stream_name, record)) in routed.into_iter().enumerate() {
+        match streams.iter().position(|(name, _)| *name == stream_name) {
+            Some(j) => {
+                streams[j].1.push(record);
+                positions[j].push(i);
+            }
+            None => {
+                streams.push((stream_name, vec![record]));
+                positions.push(vec![i]);
+            }
+        }
+    }
+    let mut failed_records = vec![];
+    let (stream_status_list, _) = write_streams(
+        org_id,
+        thread_id,
+        streams,
+        true,
+        usage_type,
+        endpoint,
+        dead_letters,
+        &mut failed_records,
+    )
+    .await?;
+    let failed = failed_records
+        .into_iter()
+        .map(|(j, i)| (positions[j][i], stream_status_list[j].status.error.clone()))
+        .collect();
+    Ok((stream_status_list, failed))
+}
+
+/// the bytes of the records, as counted against the quotas
+fn records_bytes(records: &[Map<String, Value>]) -> u64 {
+    records
+        .iter()
+        .map(|v| utils::json::to_string(v).map_or(0, |v| v.len() as u64))
+        .sum()
+}
+
+/// returns the status of the streams and the records routed to other streams,
+/// the rejected records are collected in the dead letters and their positions,
+/// the stream first then the record, in `failed_records`
+#[allow(clippy::too_many_arguments)]
+async fn write_streams(
+    org_id: &str,
+    thread_id: usize,
+    streams: Vec<(String, Vec<Map<String, Value>>)>,
+    routed: bool,
+    usage_type: UsageType,
+    endpoint: &str,
+    dead_letters: &mut DeadLetters,
+    failed_records: &mut Vec<(usize, usize)>,
+) -> Result<(Vec<StreamStatus>, Vec<(String, Map<String, Value>)>), anyhow::Error> {
+    let mut runtime = crate::service::ingestion::init_functions_runtime();
+    let mut stream_status_list = vec![];
+    let mut routed_records = vec![];
+    for (stream_pos, (stream_name, records)) in streams.into_iter().enumerate() {
+        let start = std::time::Instant::now();
+        let stream_name = &stream_name;
+        let mut stream_status = StreamStatus::new(stream_name);
+
+        // check if we are allowed to ingest
+        if db::compact::retention::is_deleting_stream(org_id, stream_name, StreamType::Logs, None) {
+            stream_status.status.failed += records.len() as u32;
+            stream_status.status.error = format!("stream [{stream_name}] is being deleted");
+            failed_records.extend((0..records.len()).map(|i| (stream_pos, i)));
+            stream_status_list.push(stream_status);
+            continue;
+        }
+
+        // the records routed to the stream are counted against its quota
+        if routed && quota::has_quotas(org_id) {
+            let usage = [(
+                stream_name.as_str(),
+                records.len() as u64,
+                records_bytes(&records),
+            )];
+            if let Err(e) = quota::check(org_id, &usage).await {
+                stream_status.status.failed += records.len() as u32;
+                stream_status.status.error = e.to_string();
+                failed_records.extend((0..records.len()).map(|i| (stream_pos, i)));
+                stream_status_list.push(stream_status);
+                continue;
+            }
+        }
+
+        let mut min_ts =
+            (Utc::now() + Duration::hours(CONFIG.limit.ingest_allowed_upto)).timestamp_micros();
+        let mut stream_schema_map: AHashMap<String, Schema> = AHashMap::new();
+        let mut stream_alerts_map: AHashMap<String, Vec<Alert>> = AHashMap::new();
+        let mut trigger: Option<Trigger> = None;
+
+        // Start Register Transforms for stream
+        let (local_trans, stream_vrl_map) = if routed {
+            (vec![], AHashMap::new())
+        } else {
+            crate::service::ingestion::register_stream_transforms(
+                org_id,
+                StreamType::Logs,
+                stream_name,
+            )
+        };
+        let routes = if routed {
+            vec![]
+        } else {
+            stream_routes::register_stream_routes(org_id, stream_name)
+        };
+        // End Register Transforms for stream
+
+        let stream_schema = stream_schema_exists(
+            org_id,
+            stream_name,
+            StreamType::Logs,
+            &mut stream_schema_map,
+        )
+        .await;
+
+        let mut partition_keys: Vec<String> = vec![];
+        if stream_schema.has_partition_keys {
+            let partition_det = crate::service::ingestion::get_stream_partition_keys(
+                stream_name,
+                &stream_schema_map,
+            )
+            .await;
+            partition_keys = partition_det.partition_keys;
+        }
+
+        // Start get stream alerts
+        let key = format!("{}/{}/{}", &org_id, StreamType::Logs, &stream_name);
+        crate::service::ingestion::get_stream_alerts(key, &mut stream_alerts_map).await;
+        // End get stream alert
+
+        let mut buf: AHashMap<String, Vec<String>> = AHashMap::new();
+        for (record_pos, record) in records.into_iter().enumerate() {
+            let record = Value::Object(record);
+            //JSON Flattening
+            let mut value = utils::flatten::flatten(&record)?;
+
+            if !local_trans.is_empty() {
+                value = match crate::service::ingestion::apply_stream_transform(
+                    &local_trans,
+                    &value,
+                    &stream_vrl_map,
+                    stream_name,
+                    &mut runtime,
+                ) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        stream_status.status.failed += 1;
+                        stream_status.status.error = e.to_string();
+                        failed_records.push((stream_pos, record_pos));
+                        dead_letters.push(
+                            stream_name,
+                            &record,
+                            DeadLetterStage::Function,
+                            &e.to_string(),
+                        );
+                        continue;
+                    }
+                };
+            }
+
+            if value.is_null() || !value.is_object() {
+                stream_status.status.dropped += 1; // dropped by the function
+                continue;
+            }
+            // End row based transform
+
+            if !routes.is_empty()
+                && !stream_routes::route_record(&routes, &value, &mut runtime, &mut routed_records)
+            {
+                continue;
+            }
+
+            // get json object
+            let local_val = value.as_object_mut().unwrap();
+
+            // handle timestamp
+            let timestamp = match local_val.get(&CONFIG.common.column_timestamp) {
+                Some(v) => match parse_timestamp_micro_from_value(v) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        stream_status.status.failed += 1;
+                        stream_status.status.error = e.to_string();
+                        failed_records.push((stream_pos, record_pos));
+                        dead_letters.push(
+                            stream_name,
+                            &record,
+                            DeadLetterStage::Timestamp,
+                            &e.to_string(),
+                        );
+                        continue;
+                    }
+                },
+                None => Utc::now().timestamp_micros(),
+            };
+            // check ingestion time
+            let earliest_time = Utc::now() + Duration::hours(0 - CONFIG.limit.ingest_allowed_upto);
+            if timestamp < earliest_time.timestamp_micros() {
+                stream_status.status.failed += 1; // to old data, just discard
+                stream_status.status.error = get_upto_discard_error();
+                failed_records.push((stream_pos, record_pos));
+                dead_letters.push(
+                    stream_name,
+                    &record,
+                    DeadLetterStage::Timestamp,
+                    &stream_status.status.error,
+                );
+                continue;
+            }
+            if timestamp < min_ts {
+                min_ts = timestamp;
+            }
+            local_val.insert(
+                CONFIG.common.column_timestamp.clone(),
+                Value::Number(timestamp.into()),
+            );
+
+            let failed = stream_status.status.failed;
+            let local_trigger = add_valid_record(
+                StreamMeta {
+                    org_id: org_id.to_string(),
+                    stream_name: stream_name.to_string(),
+                    partition_keys: partition_keys.clone(),
+                    stream_alerts_map: stream_alerts_map.clone(),
+                },
+                &mut stream_schema_map,
+                &mut stream_status.status,
+                &mut buf,
+                local_val,
+            )
+            .await;
+
+            if stream_status.status.failed > failed {
+                failed_records.push((stream_pos, record_pos));
+                dead_letters.push(
+                    stream_name,
+                    &record,
+                    DeadLetterStage::Schema,
+                    &stream_status.status.error,
+                );
+            }
+            if local_trigger.is_some() {
+                trigger = Some(local_trigger.unwrap());
+            }
+        }
+
+        // write to file
+        let mut stream_file_name = "".to_string();
+        let mut req_stats = write_file(
+            buf,
+            thread_id,
+            StreamParams {
+                org_id,
+                stream_name,
+                stream_type: StreamType::Logs,
+            },
+            &mut stream_file_name,
+            None,
+        );
+
+        if stream_file_name.is_empty() {
+            stream_status_list.push(stream_status);
+            continue;
+        }
+
+        // only one trigger per stream, as it updates etcd
+        evaluate_trigger(trigger, stream_alerts_map).await;
+
+        let time = start.elapsed().as_secs_f64();
+        metrics::HTTP_RESPONSE_TIME
+            .with_label_values(&[
+                endpoint,
+                "200",
+                org_id,
+                stream_name,
+                StreamType::Logs.to_string().as_str(),
+            ])
+            .observe(time);
+        metrics::HTTP_INCOMING_REQUESTS
+            .with_label_values(&[
+                endpoint,
+                "200",
+                org_id,
+                stream_name,
+                StreamType::Logs.to_string().as_str(),
+            ])
+            .inc();
+
+        req_stats.response_time = time;
+        //metric + data usage
+        report_request_usage_stats(
+            req_stats,
+            org_id,
+            stream_name,
+            StreamType::Logs,
+            usage_type,
+            local_trans.len() as u16,
+        )
+        .await;
+
+        stream_status_list.push(stream_status);
+    }
+
+    Ok((stream_status_list, routed_records))
+}
+
+/// group the records by stream name, keeping the order of the streams
+pub(crate) fn group_by_stream(
+    records: impl IntoIterator<Item = (String, Map<String, Value>)>,
+) -> Vec<(String, Vec<Map<String, Value>>)> {
+    let mut streams: Vec<(String, Vec<Map<String, Value>>)> = vec![];
+    for (stream_name, record) in records {
+        match streams.iter_mut().find(|(name, _)| *name == stream_name) {
+            Some((_, list)) => list.push(record),
+            None => streams.push((stream_name, vec![record])),
+        }
+    }
+    streams
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
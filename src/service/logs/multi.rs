@@ -18,9 +18,11 @@ use chrono::{Duration, Utc};
 use datafusion::arrow::datatypes::Schema;
 use std::io::{BufRead, BufReader};
 
+use super::dead_letter::DeadLetters;
 use crate::common::infra::{cluster, config::CONFIG, metrics};
 use crate::common::meta::{
     alert::{Alert, Trigger},
+    dead_letter::DeadLetterStage,
     ingestion::{IngestionResponse, StreamStatus},
     stream::StreamParams,
     usage::UsageType,
@@ -28,7 +30,11 @@ use crate::common::meta::{
 };
 use crate::common::utils::{flatten, json, time::parse_timestamp_micro_from_value};
 use crate::service::{
-    db, format_stream_name, ingestion::write_file, logs::StreamMeta, schema::stream_schema_exists,
+    db, format_stream_name,
+    ingestion::{quota, write_file},
+    logs::StreamMeta,
+    schema::stream_schema_exists,
+    stream_routes,
     usage::report_request_usage_stats,
 };
 
@@ -53,6 +59,18 @@ pub async fn ingest(
     if db::compact::retention::is_deleting_stream(org_id, stream_name, StreamType::Logs, None) {
         return Err(anyhow::anyhow!("stream [{stream_name}] is being deleted"));
     }
+
+    if quota::has_quotas(org_id) {
+        let events = body
+            .split(|c| *c == b'\n')
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .count();
+        quota::check(
+            org_id,
+            &[(stream_name.as_str(), events as u64, body.len() as u64)],
+        )
+        .await?;
+    }
     let mut runtime = crate::service::ingestion::init_functions_runtime();
 
     let mut min_ts =
@@ -71,6 +89,7 @@ pub async fn ingest(
         stream_name,
     );
     // End Register Transforms for stream
+    let routes = stream_routes::register_stream_routes(org_id, stream_name);
 
     let stream_schema = stream_schema_exists(
         org_id,
@@ -94,6 +113,8 @@ pub async fn ingest(
     // End get stream alert
 
     let mut buf: AHashMap<String, Vec<String>> = AHashMap::new();
+    let mut routed = vec![];
+    let mut dead_letters = DeadLetters::new(org_id);
     let reader = BufReader::new(body.as_ref());
     for line in reader.lines() {
         let line = line?;
@@ -101,28 +122,47 @@ pub async fn ingest(
             continue;
         }
 
-        let mut value: json::Value = json::from_slice(line.as_bytes())?;
+        let record: json::Value = json::from_slice(line.as_bytes())?;
 
         // JSON Flattening
-        value = flatten::flatten(&value)?;
+        let mut value = flatten::flatten(&record)?;
         // Start row based transform
 
         if !local_trans.is_empty() {
-            value = crate::service::ingestion::apply_stream_transform(
+            value = match crate::service::ingestion::apply_stream_transform(
                 &local_trans,
                 &value,
                 &stream_vrl_map,
                 stream_name,
                 &mut runtime,
-            )?;
+            ) {
+                Ok(value) => value,
+                Err(e) => {
+                    stream_status.status.failed += 1;
+                    stream_status.status.error = e.to_string();
+                    dead_letters.push(
+                        stream_name,
+                        &record,
+                        DeadLetterStage::Function,
+                        &e.to_string(),
+                    );
+                    continue;
+                }
+            };
         }
 
         if value.is_null() || !value.is_object() {
-            stream_status.status.failed += 1; // transform failed or dropped
+            stream_status.status.dropped += 1; // dropped by the function
             continue;
         }
         // End row based transform
 
+        if !routes.is_empty()
+            && !stream_routes::route_record(&routes, &value, &mut runtime, &mut routed)
+        {
+            continue;
+        }
+
         // get json object
         let local_val = value.as_object_mut().unwrap();
 
@@ -133,6 +173,12 @@ pub async fn ingest(
                 Err(e) => {
                     stream_status.status.failed += 1;
                     stream_status.status.error = e.to_string();
+                    dead_letters.push(
+                        stream_name,
+                        &record,
+                        DeadLetterStage::Timestamp,
+                        &e.to_string(),
+                    );
                     continue;
                 }
             },
@@ -143,6 +189,12 @@ pub async fn ingest(
         if timestamp < earliest_time.timestamp_micros() {
             stream_status.status.failed += 1; // to old data, just discard
             stream_status.status.error = super::get_upto_discard_error();
+            dead_letters.push(
+                stream_name,
+                &record,
+                DeadLetterStage::Timestamp,
+                &stream_status.status.error,
+            );
             continue;
         }
         if timestamp < min_ts {
@@ -154,6 +206,7 @@ pub async fn ingest(
         );
 
         // write data
+        let failed = stream_status.status.failed;
         let local_trigger = super::add_valid_record(
             StreamMeta {
                 org_id: org_id.to_string(),
@@ -168,11 +221,38 @@ pub async fn ingest(
         )
         .await;
 
+        if stream_status.status.failed > failed {
+            dead_letters.push(
+                stream_name,
+                &record,
+                DeadLetterStage::Schema,
+                &stream_status.status.error,
+            );
+        }
         if local_trigger.is_some() {
             trigger = Some(local_trigger.unwrap());
         }
     }
 
+    // records sent to other streams by the stream routes
+    let routed_status = super::ingest_routed(
+        org_id,
+        thread_id,
+        routed,
+        UsageType::Multi,
+        "/api/org/ingest/logs/_multi",
+        &mut dead_letters,
+    )
+    .await?;
+    dead_letters
+        .flush(
+            org_id,
+            thread_id,
+            UsageType::Multi,
+            "/api/org/ingest/logs/_multi",
+        )
+        .await;
+
     // write to file
     let mut stream_file_name = "".to_string();
 
@@ -191,7 +271,7 @@ pub async fn ingest(
     if stream_file_name.is_empty() {
         return Ok(IngestionResponse::new(
             http::StatusCode::OK.into(),
-            vec![stream_status],
+            [vec![stream_status], routed_status].concat(),
         ));
     }
 
@@ -231,6 +311,6 @@ pub async fn ingest(
 
     Ok(IngestionResponse::new(
         http::StatusCode::OK.into(),
-        vec![stream_status],
+        [vec![stream_status], routed_status].concat(),
     ))
 }
@@ -0,0 +1,360 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::web;
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use prost::Message;
+
+use crate::common::infra::config::CONFIG;
+use crate::common::meta::{ingestion::IngestionResponse, usage::UsageType};
+use crate::common::utils::json;
+use crate::service::{
+    format_stream_name,
+    ingestion::grpc::{get_severity_value, get_val},
+};
+
+/// header selecting the stream of the logs
+pub const STREAM_NAME_HEADER: &str = "stream-name";
+/// resource attribute selecting the stream of its logs, the header wins
+pub const STREAM_NAME_ATTR: &str = "stream.name";
+
+const DEFAULT_STREAM: &str = "default";
+
+/// a log record and the stream named by its resource
+type Record = (Option<String>, json::Map<String, json::Value>);
+
+pub async fn logs_proto(
+    org_id: &str,
+    thread_id: usize,
+    in_stream_name: Option<&str>,
+    body: web::Bytes,
+) -> Result<IngestionResponse, anyhow::Error> {
+    let request = ExportLogsServiceRequest::decode(body)?;
+    ingest(org_id, thread_id, in_stream_name, proto_records(&request)).await
+}
+
+pub async fn logs_json(
+    org_id: &str,
+    thread_id: usize,
+    in_stream_name: Option<&str>,
+    body: web::Bytes,
+) -> Result<IngestionResponse, anyhow::Error> {
+    let body: json::Value = json::from_slice(&body)?;
+    ingest(org_id, thread_id, in_stream_name, json_records(&body)?).await
+}
+
+async fn ingest(
+    org_id: &str,
+    thread_id: usize,
+    in_stream_name: Option<&str>,
+    records: Vec<Record>,
+) -> Result<IngestionResponse, anyhow::Error> {
+    let streams = super::group_by_stream(records.into_iter().map(|(stream, record)| {
+        let stream_name = in_stream_name
+            .or(stream.as_deref())
+            .unwrap_or(DEFAULT_STREAM);
+        (format_stream_name(stream_name), record)
+    }));
+    super::ingest_streams(
+        org_id,
+        thread_id,
+        streams,
+        UsageType::OtlpLogs,
+        "/api/org/v1/logs",
+    )
+    .await
+}
+
+/// remove the stream attribute from the resource attributes
+fn take_stream(resource: &mut json::Map<String, json::Value>) -> Option<String> {
+    match resource.remove(STREAM_NAME_ATTR) {
+        Some(json::Value::String(v)) if !v.is_empty() => Some(v),
+        _ => None,
+    }
+}
+
+fn proto_records(request: &ExportLogsServiceRequest) -> Vec<Record> {
+    let mut records = vec![];
+    for resource_log in &request.resource_logs {
+        let mut resource = json::Map::new();
+        if let Some(res) = &resource_log.resource {
+            for item in &res.attributes {
+                resource.insert(item.key.clone(), get_val(&item.value));
+            }
+        }
+        let stream = take_stream(&mut resource);
+        for instrumentation_logs in &resource_log.instrumentation_library_logs {
+            let mut scope = resource.clone();
+            if let Some(lib) = &instrumentation_logs.instrumentation_library {
+                scope.insert(
+                    "instrumentation_library_name".to_string(),
+                    lib.name.clone().into(),
+                );
+                scope.insert(
+                    "instrumentation_library_version".to_string(),
+                    lib.version.clone().into(),
+                );
+            }
+            for log_record in &instrumentation_logs.log_records {
+                let mut rec = scope.clone();
+                let ts = if log_record.time_unix_nano != 0 {
+                    log_record.time_unix_nano
+                } else {
+                    log_record.observed_time_unix_nano
+                };
+                if ts != 0 {
+                    rec.insert(CONFIG.common.column_timestamp.clone(), (ts / 1000).into());
+                }
+                let severity = if log_record.severity_text.is_empty() {
+                    get_severity_value(log_record.severity_number)
+                } else {
+                    log_record.severity_text.clone()
+                };
+                rec.insert("severity".to_string(), severity.into());
+                rec.insert("body".to_string(), get_val(&log_record.body));
+                for item in &log_record.attributes {
+                    rec.insert(item.key.clone(), get_val(&item.value));
+                }
+                rec.insert(
+                    "dropped_attributes_count".to_string(),
+                    log_record.dropped_attributes_count.into(),
+                );
+                if let Some(id) = span_ref_id(&hex::encode(&log_record.trace_id), 32) {
+                    rec.insert("trace_id".to_string(), id.into());
+                }
+                if let Some(id) = span_ref_id(&hex::encode(&log_record.span_id), 16) {
+                    rec.insert("span_id".to_string(), id.into());
+                }
+                records.push((stream.clone(), rec));
+            }
+        }
+    }
+    records
+}
+
+/// records of the OTLP/JSON encoding, ids are hex strings and 64 bit integers
+/// can be strings
+fn json_records(body: &json::Value) -> Result<Vec<Record>, anyhow::Error> {
+    let Some(resource_logs) = body.get("resourceLogs").and_then(|v| v.as_array()) else {
+        return Err(anyhow::anyhow!(
+            "Invalid json: the structure must be {{\"resourceLogs\":[]}}"
+        ));
+    };
+    let mut records = vec![];
+    for resource_log in resource_logs {
+        let mut resource = json_attributes(
+            resource_log
+                .get("resource")
+                .and_then(|v| v.get("attributes")),
+        );
+        let stream = take_stream(&mut resource);
+        let scope_logs = resource_log
+            .get("scopeLogs")
+            .or_else(|| resource_log.get("instrumentationLibraryLogs"))
+            .and_then(|v| v.as_array());
+        for scope_log in scope_logs.into_iter().flatten() {
+            let mut scope = resource.clone();
+            if let Some(lib) = scope_log
+                .get("scope")
+                .or_else(|| scope_log.get("instrumentationLibrary"))
+            {
+                for (key, field) in [
+                    ("instrumentation_library_name", "name"),
+                    ("instrumentation_library_version", "version"),
+                ] {
+                    let value = lib.get(field).and_then(|v| v.as_str()).unwrap_or_default();
+                    scope.insert(key.to_string(), value.into());
+                }
+            }
+            let log_records = scope_log.get("logRecords").and_then(|v| v.as_array());
+            for log_record in log_records.into_iter().flatten() {
+                let mut rec = scope.clone();
+                let ts = match json_u64(log_record.get("timeUnixNano")) {
+                    0 => json_u64(log_record.get("observedTimeUnixNano")),
+                    ts => ts,
+                };
+                if ts != 0 {
+                    rec.insert(CONFIG.common.column_timestamp.clone(), (ts / 1000).into());
+                }
+                let severity = match log_record.get("severityText").and_then(|v| v.as_str()) {
+                    Some(v) if !v.is_empty() => v.to_string(),
+                    _ => get_severity_value(json_u64(log_record.get("severityNumber")) as i32),
+                };
+                rec.insert("severity".to_string(), severity.into());
+                rec.insert(
+                    "body".to_string(),
+                    log_record
+                        .get("body")
+                        .map(json_any_value)
+                        .unwrap_or(json::Value::Null),
+                );
+                rec.extend(json_attributes(log_record.get("attributes")));
+                rec.insert(
+                    "dropped_attributes_count".to_string(),
+                    json_u64(log_record.get("droppedAttributesCount")).into(),
+                );
+                for (key, field, len) in [("trace_id", "traceId", 32), ("span_id", "spanId", 16)] {
+                    let id = log_record.get(field).and_then(|v| v.as_str());
+                    if let Some(id) = id.and_then(|id| span_ref_id(id, len)) {
+                        rec.insert(key.to_string(), id.into());
+                    }
+                }
+                records.push((stream.clone(), rec));
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// the hex id if it is valid and not all zeros
+fn span_ref_id(id: &str, len: usize) -> Option<String> {
+    if id.len() == len && id.chars().any(|c| c != '0') {
+        Some(id.to_lowercase())
+    } else {
+        None
+    }
+}
+
+fn json_u64(value: Option<&json::Value>) -> u64 {
+    match value {
+        Some(json::Value::Number(v)) => v.as_u64().unwrap_or_default(),
+        Some(json::Value::String(v)) => v.parse().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+fn json_attributes(attributes: Option<&json::Value>) -> json::Map<String, json::Value> {
+    let mut map = json::Map::new();
+    for item in attributes.and_then(|v| v.as_array()).into_iter().flatten() {
+        if let Some(key) = item.get("key").and_then(|v| v.as_str()) {
+            let value = item
+                .get("value")
+                .map(json_any_value)
+                .unwrap_or(json::Value::Null);
+            map.insert(key.to_string(), value);
+        }
+    }
+    map
+}
+
+/// the json encoding of `AnyValue`, converted the same way as `get_val`
+fn json_any_value(value: &json::Value) -> json::Value {
+    let Some((kind, inner)) = value.as_object().and_then(|v| v.iter().next()) else {
+        return json::Value::Null;
+    };
+    match kind.as_str() {
+        "stringValue" | "bytesValue" => inner.as_str().unwrap_or_default().into(),
+        "boolValue" | "intValue" | "doubleValue" => match inner {
+            json::Value::String(v) => v.clone().into(),
+            v => v.to_string().into(),
+        },
+        "arrayValue" => inner
+            .get("values")
+            .and_then(|v| v.as_array())
+            .map(|values| values.iter().map(json_any_value).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into(),
+        "kvlistValue" => json::Value::Object(json_attributes(inner.get("values"))),
+        _ => json::Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_records() {
+        let body = json::json!({
+            "resourceLogs": [{
+                "resource": {"attributes": [
+                    {"key": "service.name", "value": {"stringValue": "web"}},
+                    {"key": "stream.name", "value": {"stringValue": "nginx"}}
+                ]},
+                "scopeLogs": [{
+                    "scope": {"name": "otel", "version": "1.0"},
+                    "logRecords": [{
+                        "timeUnixNano": "1581452773000000789",
+                        "severityNumber": 9,
+                        "body": {"kvlistValue": {"values": [
+                            {"key": "msg", "value": {"stringValue": "hello"}},
+                            {"key": "code", "value": {"intValue": "200"}}
+                        ]}},
+                        "attributes": [{"key": "app", "value": {"boolValue": true}}],
+                        "traceId": "5B8EFFF798038103D269B633813FC60C",
+                        "spanId": "0000000000000000"
+                    }]
+                }]
+            }]
+        });
+        let records = json_records(&body).unwrap();
+        assert_eq!(records.len(), 1);
+        let (stream, rec) = &records[0];
+        assert_eq!(stream.as_deref(), Some("nginx"));
+        assert_eq!(rec["service.name"], "web");
+        assert!(!rec.contains_key(STREAM_NAME_ATTR));
+        assert_eq!(rec["instrumentation_library_name"], "otel");
+        assert_eq!(rec[&CONFIG.common.column_timestamp], 1581452773000000_u64);
+        assert_eq!(rec["severity"], "Info");
+        assert_eq!(rec["body"], json::json!({"msg": "hello", "code": "200"}));
+        assert_eq!(rec["app"], "true");
+        assert_eq!(rec["trace_id"], "5b8efff798038103d269b633813fc60c");
+        assert!(!rec.contains_key("span_id"));
+
+        assert!(json_records(&json::json!({"resourceSpans": []})).is_err());
+    }
+
+    #[test]
+    fn test_proto_records() {
+        use opentelemetry_proto::tonic::{
+            common::v1::{any_value::Value, AnyValue, KeyValue},
+            logs::v1::{InstrumentationLibraryLogs, LogRecord, ResourceLogs},
+            resource::v1::Resource,
+        };
+
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource {
+                    attributes: vec![KeyValue {
+                        key: STREAM_NAME_ATTR.to_string(),
+                        value: Some(AnyValue {
+                            value: Some(Value::StringValue("app".to_string())),
+                        }),
+                    }],
+                    ..Default::default()
+                }),
+                instrumentation_library_logs: vec![InstrumentationLibraryLogs {
+                    log_records: vec![LogRecord {
+                        observed_time_unix_nano: 1581452773000000789,
+                        severity_text: "Warn".to_string(),
+                        body: Some(AnyValue {
+                            value: Some(Value::StringValue("hello".to_string())),
+                        }),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let records = proto_records(&request);
+        assert_eq!(records.len(), 1);
+        let (stream, rec) = &records[0];
+        assert_eq!(stream.as_deref(), Some("app"));
+        assert_eq!(rec[&CONFIG.common.column_timestamp], 1581452773000000_u64);
+        assert_eq!(rec["severity"], "Warn");
+        assert_eq!(rec["body"], "hello");
+        assert!(!rec.contains_key("trace_id"));
+    }
+}
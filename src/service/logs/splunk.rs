@@ -0,0 +1,379 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::web;
+use ahash::AHashMap as HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::common::infra::{config::CONFIG, ider};
+use crate::common::meta::{
+    ingestion::{HecAckRequest, HecAckResponse, HecParams, HecResponse, IngestionResponse},
+    usage::UsageType,
+};
+use crate::common::utils::json::{Map, Value};
+use crate::service::{db, format_stream_name, ingestion::quota::QuotaExceeded};
+
+/// field of the event when it is not an object
+const MESSAGE_FIELD: &str = "message";
+
+const DEFAULT_STREAM: &str = "default";
+
+/// the pending acks kept per channel, the oldest are dropped above it
+const MAX_CHANNEL_ACKS: usize = 10_000;
+
+/// the pending acks of a channel are checked against the limit once every so
+/// many acks issued by the node
+const TRUNCATE_INTERVAL: u64 = 100;
+
+static ACKS_ISSUED: AtomicU64 = AtomicU64::new(0);
+
+type Record = (String, Map<String, Value>);
+
+/// `/services/collector/event`, concatenated json events
+pub async fn event(
+    org_id: &str,
+    thread_id: usize,
+    params: &HecParams,
+    body: web::Bytes,
+) -> HecResponse {
+    match event_records(params, &body) {
+        Ok(records) => ingest(org_id, thread_id, params, records).await,
+        Err(e) => e,
+    }
+}
+
+/// `/services/collector/raw`, one event per line
+pub async fn raw(
+    org_id: &str,
+    thread_id: usize,
+    params: &HecParams,
+    body: web::Bytes,
+) -> HecResponse {
+    let records = raw_records(params, &body);
+    if records.is_empty() {
+        return HecResponse::new(HecResponse::NO_DATA, "No data");
+    }
+    ingest(org_id, thread_id, params, records).await
+}
+
+/// `/services/collector/ack`, an ack id is true once for the channel it was
+/// issued to. events are written before the response, so the ack ids issued
+/// to a channel are acknowledged until they are queried. they are kept in the
+/// cluster db, the queries can reach any node.
+pub async fn ack(org_id: &str, channel: &str, req: &HecAckRequest) -> HecAckResponse {
+    let mut acks = HashMap::with_capacity(req.acks.len());
+    for id in &req.acks {
+        let acked = match db::splunk_ack::take(org_id, channel, *id).await {
+            Ok(v) => v,
+            Err(e) => {
+                log::error!("splunk hec ack {org_id}/{channel}/{id}: {e}");
+                false
+            }
+        };
+        acks.insert(id.to_string(), acked);
+    }
+    HecAckResponse { acks }
+}
+
+async fn issue_ack(org_id: &str, channel: &str) -> Result<u64, anyhow::Error> {
+    let id = ider::generate_number();
+    db::splunk_ack::set(org_id, channel, id).await?;
+    if ACKS_ISSUED.fetch_add(1, Ordering::Relaxed) % TRUNCATE_INTERVAL == 0 {
+        db::splunk_ack::truncate(org_id, channel, MAX_CHANNEL_ACKS).await?;
+    }
+    Ok(id)
+}
+
+async fn ingest(
+    org_id: &str,
+    thread_id: usize,
+    params: &HecParams,
+    records: Vec<Record>,
+) -> HecResponse {
+    let ret = match super::ingest_streams(
+        org_id,
+        thread_id,
+        super::group_by_stream(records),
+        UsageType::SplunkHec,
+        "/splunk/org/services/collector",
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            if let Some(e) = e.downcast_ref::<QuotaExceeded>() {
+                return HecResponse {
+                    retry_after: Some(e.retry_after),
+                    ..HecResponse::new(HecResponse::SERVER_BUSY, &e.to_string())
+                };
+            }
+            return HecResponse::new(HecResponse::SERVER_ERROR, &e.to_string());
+        }
+    };
+    let mut res = response(&ret);
+    if res.code != HecResponse::SUCCESS {
+        return res;
+    }
+    if let Some(channel) = params.channel.as_deref() {
+        match issue_ack(org_id, channel).await {
+            Ok(id) => res.ack_id = Some(id),
+            Err(e) => log::error!("splunk hec issue ack {org_id}/{channel}: {e}"),
+        }
+    }
+    res
+}
+
+/// the request fails when no event is written. once some are, it succeeds and
+/// is acknowledged with the failures in the text, the clients would duplicate
+/// the written events if they sent the request again.
+fn response(ret: &IngestionResponse) -> HecResponse {
+    let errors = ret
+        .status
+        .iter()
+        .filter(|s| s.status.failed > 0)
+        .map(|s| format!("{}: {} failed, {}", s.name, s.status.failed, s.status.error))
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        return HecResponse::success();
+    }
+    if ret.status.iter().all(|s| s.status.successful == 0) {
+        return HecResponse::new(HecResponse::INVALID_DATA_FORMAT, &errors.join("; "));
+    }
+    HecResponse::new(
+        HecResponse::SUCCESS,
+        &format!("Success, with failed events: {}", errors.join("; ")),
+    )
+}
+
+fn event_records(params: &HecParams, body: &[u8]) -> Result<Vec<Record>, HecResponse> {
+    let mut records = vec![];
+    let events = serde_json::Deserializer::from_slice(body).into_iter::<Value>();
+    for (i, event) in events.enumerate() {
+        let Ok(Value::Object(mut event)) = event else {
+            return Err(HecResponse::invalid_event(
+                HecResponse::INVALID_DATA_FORMAT,
+                "Invalid data format",
+                i,
+            ));
+        };
+        let mut rec = Map::new();
+        match event.remove("event") {
+            None | Some(Value::Null) => {
+                return Err(HecResponse::invalid_event(
+                    HecResponse::EVENT_REQUIRED,
+                    "Event field is required",
+                    i,
+                ))
+            }
+            Some(Value::String(v)) if v.is_empty() => {
+                return Err(HecResponse::invalid_event(
+                    HecResponse::EVENT_BLANK,
+                    "Event field cannot be blank",
+                    i,
+                ))
+            }
+            Some(Value::Object(v)) => rec.extend(v),
+            Some(v) => {
+                rec.insert(MESSAGE_FIELD.to_string(), v);
+            }
+        }
+        if let Some(Value::Object(fields)) = event.remove("fields") {
+            rec.extend(fields);
+        }
+        let meta = |name: &str, default: &Option<String>| {
+            event
+                .get(name)
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+                .or_else(|| default.clone())
+        };
+        let index = meta("index", &params.index);
+        let sourcetype = meta("sourcetype", &params.sourcetype);
+        let host = meta("host", &params.host);
+        let source = meta("source", &params.source);
+        if let Some(timestamp) = event.get("time").and_then(parse_time) {
+            rec.insert(CONFIG.common.column_timestamp.clone(), timestamp.into());
+        }
+        records.push(record(rec, index, sourcetype, host, source));
+    }
+    if records.is_empty() {
+        return Err(HecResponse::new(HecResponse::NO_DATA, "No data"));
+    }
+    Ok(records)
+}
+
+fn raw_records(params: &HecParams, body: &[u8]) -> Vec<Record> {
+    String::from_utf8_lossy(body)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut rec = Map::new();
+            rec.insert(MESSAGE_FIELD.to_string(), line.into());
+            record(
+                rec,
+                params.index.clone(),
+                params.sourcetype.clone(),
+                params.host.clone(),
+                params.source.clone(),
+            )
+        })
+        .collect()
+}
+
+/// the target stream is the index, or else the sourcetype
+fn record(
+    mut rec: Map<String, Value>,
+    index: Option<String>,
+    sourcetype: Option<String>,
+    host: Option<String>,
+    source: Option<String>,
+) -> Record {
+    let stream = [&index, &sourcetype]
+        .into_iter()
+        .flatten()
+        .find(|v| !v.is_empty())
+        .map(|v| v.as_str())
+        .unwrap_or(DEFAULT_STREAM);
+    let stream = format_stream_name(stream);
+    for (name, value) in [
+        ("sourcetype", sourcetype),
+        ("host", host),
+        ("source", source),
+    ] {
+        if let Some(value) = value {
+            rec.insert(name.to_string(), value.into());
+        }
+    }
+    (stream, rec)
+}
+
+/// epoch seconds, with an optional fraction, in microseconds
+fn parse_time(value: &Value) -> Option<i64> {
+    let secs = match value {
+        Value::Number(v) => v.as_f64(),
+        Value::String(v) => v.parse::<f64>().ok(),
+        _ => None,
+    }?;
+    Some((secs * 1_000_000.0).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_records() {
+        let params = HecParams {
+            host: Some("web1".to_string()),
+            ..Default::default()
+        };
+        let body = r#"{"time":1426279439.5,"index":"app","event":"hello"}
+{"sourcetype":"access","event":{"path":"/"},"fields":{"env":"prod"},"host":"web2"}"#;
+        let records = event_records(&params, body.as_bytes()).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let (stream, rec) = &records[0];
+        assert_eq!(stream, "app");
+        assert_eq!(rec[MESSAGE_FIELD], "hello");
+        assert_eq!(rec["host"], "web1");
+        assert_eq!(
+            rec[&CONFIG.common.column_timestamp],
+            1_426_279_439_500_000_i64
+        );
+
+        let (stream, rec) = &records[1];
+        assert_eq!(stream, "access");
+        assert_eq!(rec["path"], "/");
+        assert_eq!(rec["env"], "prod");
+        assert_eq!(rec["host"], "web2");
+        assert_eq!(rec["sourcetype"], "access");
+    }
+
+    #[test]
+    fn test_event_records_errors() {
+        let params = HecParams::default();
+        let res = event_records(&params, br#"{"event":"a"}{"time":1}"#).unwrap_err();
+        assert_eq!(res.code, HecResponse::EVENT_REQUIRED);
+        assert_eq!(res.invalid_event_number, Some(1));
+        let res = event_records(&params, b"not json").unwrap_err();
+        assert_eq!(res.code, HecResponse::INVALID_DATA_FORMAT);
+        let res = event_records(&params, b"").unwrap_err();
+        assert_eq!(res.code, HecResponse::NO_DATA);
+    }
+
+    #[test]
+    fn test_raw_records() {
+        let params = HecParams {
+            sourcetype: Some("syslog".to_string()),
+            ..Default::default()
+        };
+        let records = raw_records(&params, b"line 1\n\nline 2\n");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].0, "syslog");
+        assert_eq!(records[1].1[MESSAGE_FIELD], "line 2");
+    }
+
+    #[test]
+    fn test_response() {
+        use crate::common::meta::ingestion::StreamStatus;
+
+        let mut ok = StreamStatus::new("ok");
+        ok.status.successful = 2;
+        let mut bad = StreamStatus::new("bad");
+        bad.status.failed = 1;
+        bad.status.error = "invalid".to_string();
+
+        let ret = IngestionResponse::new(200, vec![ok.clone()]);
+        assert_eq!(response(&ret).text, "Success");
+        // the written events are acknowledged, they must not be sent again
+        let ret = IngestionResponse::new(200, vec![ok, bad.clone()]);
+        let res = response(&ret);
+        assert_eq!(res.code, HecResponse::SUCCESS);
+        assert_eq!(
+            res.text,
+            "Success, with failed events: bad: 1 failed, invalid"
+        );
+        let ret = IngestionResponse::new(200, vec![bad]);
+        assert_eq!(response(&ret).code, HecResponse::INVALID_DATA_FORMAT);
+    }
+
+    #[actix_web::test]
+    async fn test_ack() {
+        let a = issue_ack("org1", "chan-a").await.unwrap();
+        let b = issue_ack("org1", "chan-b").await.unwrap();
+        let req = HecAckRequest { acks: vec![a, b] };
+        let res = ack("org1", "chan-a", &req).await;
+        assert!(res.acks[&a.to_string()]);
+        assert!(!res.acks[&b.to_string()]);
+        // an ack id is only acknowledged once
+        assert!(!ack("org1", "chan-a", &req).await.acks[&a.to_string()]);
+        assert!(!ack("org2", "chan-b", &req).await.acks[&b.to_string()]);
+        assert!(ack("org1", "chan-b", &req).await.acks[&b.to_string()]);
+    }
+
+    #[actix_web::test]
+    async fn test_truncate_acks() {
+        let ids = [
+            issue_ack("org1", "chan-c").await.unwrap(),
+            issue_ack("org1", "chan-c").await.unwrap(),
+            issue_ack("org1", "chan-c").await.unwrap(),
+        ];
+        db::splunk_ack::truncate("org1", "chan-c", 2).await.unwrap();
+        let req = HecAckRequest { acks: ids.to_vec() };
+        let res = ack("org1", "chan-c", &req).await;
+        assert!(!res.acks[&ids[0].to_string()]);
+        assert!(res.acks[&ids[1].to_string()]);
+        assert!(res.acks[&ids[2].to_string()]);
+    }
+}
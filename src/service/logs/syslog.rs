@@ -34,7 +34,11 @@ use crate::common::meta::{
     StreamType,
 };
 use crate::common::utils::{flatten, json, time::parse_timestamp_micro_from_value};
-use crate::service::{db, format_stream_name, ingestion::write_file, schema::stream_schema_exists};
+use crate::service::{
+    db, format_stream_name,
+    ingestion::{quota, write_file},
+    schema::stream_schema_exists,
+};
 
 pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse, ()> {
     let start = std::time::Instant::now();
@@ -77,6 +81,10 @@ pub async fn ingest(msg: &str, addr: SocketAddr) -> Result<HttpResponse, ()> {
         );
     }
 
+    if let Err(e) = quota::check(org_id, &[(stream_name.as_str(), 1, msg.len() as u64)]).await {
+        return Ok(e.to_response());
+    }
+
     let mut stream_schema_map: AHashMap<String, Schema> = AHashMap::new();
     let mut stream_alerts_map: AHashMap<String, Vec<Alert>> = AHashMap::new();
     let mut stream_status = StreamStatus::new(stream_name);
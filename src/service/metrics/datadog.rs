@@ -0,0 +1,279 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::web;
+use anyhow::Result;
+use prost::Message;
+use serde::Deserialize;
+
+use super::format_label_name;
+use crate::common::infra::config::CONFIG;
+use crate::common::meta::{
+    ingestion::IngestionResponse,
+    prom::{NAME_LABEL, TYPE_LABEL, VALUE_LABEL},
+    usage::UsageType,
+};
+use crate::common::utils::json;
+
+pub(crate) mod agentpayload {
+    include!(concat!(env!("OUT_DIR"), "/datadog.agentpayload.rs"));
+}
+
+/// `{"series":[{"metric","points":[[<seconds>,<value>]],"host","tags"}]}`
+#[derive(Deserialize)]
+struct SeriesV1Request {
+    series: Vec<SeriesV1>,
+}
+
+#[derive(Deserialize)]
+struct SeriesV1 {
+    metric: String,
+    points: Vec<(f64, f64)>,
+    #[serde(default)]
+    host: Option<String>,
+    #[serde(default)]
+    device_name: Option<String>,
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+}
+
+/// `{"series":[{"metric","points":[{"timestamp","value"}],"resources","tags"}]}`
+#[derive(Deserialize)]
+struct SeriesV2Request {
+    series: Vec<SeriesV2>,
+}
+
+#[derive(Deserialize)]
+struct SeriesV2 {
+    metric: String,
+    points: Vec<PointV2>,
+    #[serde(default)]
+    resources: Vec<ResourceV2>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct PointV2 {
+    timestamp: i64,
+    value: f64,
+}
+
+#[derive(Deserialize)]
+struct ResourceV2 {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+}
+
+/// `/api/v1/series`
+pub async fn series_v1(
+    org_id: &str,
+    body: web::Bytes,
+    thread_id: usize,
+) -> Result<IngestionResponse> {
+    let records = v1_records(json::from_slice(&body)?)?;
+    ingest(org_id, records, thread_id).await
+}
+
+/// `/api/v2/series`, the agent sends the protobuf payload
+pub async fn series_v2(
+    org_id: &str,
+    body: web::Bytes,
+    is_proto: bool,
+    thread_id: usize,
+) -> Result<IngestionResponse> {
+    let series = if is_proto {
+        decode_payload(body)?
+    } else {
+        json::from_slice::<SeriesV2Request>(&body)?.series
+    };
+    ingest(org_id, v2_records(series)?, thread_id).await
+}
+
+fn v1_records(request: SeriesV1Request) -> Result<Vec<json::Value>> {
+    let mut records = vec![];
+    for series in request.series {
+        let mut labels = vec![];
+        if let Some(host) = series.host.filter(|v| !v.is_empty()) {
+            labels.push(("host".to_string(), host));
+        }
+        if let Some(device) = series.device_name.filter(|v| !v.is_empty()) {
+            labels.push(("device".to_string(), device));
+        }
+        let points = series
+            .points
+            .into_iter()
+            .map(|(ts, value)| (ts as i64, value));
+        push_records(
+            &mut records,
+            &series.metric,
+            labels,
+            &series.tags.unwrap_or_default(),
+            points,
+        )?;
+    }
+    Ok(records)
+}
+
+/// the resources, such as the host, are labels named by their type
+fn v2_records(series: Vec<SeriesV2>) -> Result<Vec<json::Value>> {
+    let mut records = vec![];
+    for series in series {
+        let labels = series
+            .resources
+            .into_iter()
+            .filter(|r| !r.kind.is_empty() && !r.name.is_empty())
+            .map(|r| (format_label_name(&r.kind), r.name))
+            .collect();
+        let points = series.points.into_iter().map(|p| (p.timestamp, p.value));
+        push_records(&mut records, &series.metric, labels, &series.tags, points)?;
+    }
+    Ok(records)
+}
+
+fn decode_payload(body: web::Bytes) -> Result<Vec<SeriesV2>> {
+    let payload = agentpayload::MetricPayload::decode(body)?;
+    Ok(payload
+        .series
+        .into_iter()
+        .map(|s| SeriesV2 {
+            metric: s.metric,
+            points: s
+                .points
+                .into_iter()
+                .map(|p| PointV2 {
+                    timestamp: p.timestamp,
+                    value: p.value,
+                })
+                .collect(),
+            resources: s
+                .resources
+                .into_iter()
+                .map(|r| ResourceV2 {
+                    kind: r.r#type,
+                    name: r.name,
+                })
+                .collect(),
+            tags: s.tags,
+        })
+        .collect())
+}
+
+async fn ingest(
+    org_id: &str,
+    records: Vec<json::Value>,
+    thread_id: usize,
+) -> Result<IngestionResponse> {
+    super::json::ingest_records(
+        org_id,
+        records,
+        thread_id,
+        UsageType::DatadogMetrics,
+        "/datadog/org/api/v1/series",
+    )
+    .await
+}
+
+/// counts and rates are the values of their interval, so every series is a
+/// gauge. the `<key>:<value>` tags are labels, the labels win over the tags.
+fn push_records(
+    records: &mut Vec<json::Value>,
+    metric: &str,
+    labels: Vec<(String, String)>,
+    tags: &[String],
+    points: impl Iterator<Item = (i64, f64)>,
+) -> Result<()> {
+    let mut base = json::Map::new();
+    for tag in tags.iter().filter(|v| !v.is_empty()) {
+        let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+        base.insert(format_label_name(key), value.into());
+    }
+    for (key, value) in labels {
+        base.insert(key, value.into());
+    }
+    base.insert(NAME_LABEL.to_string(), format_label_name(metric).into());
+    base.insert(TYPE_LABEL.to_string(), "gauge".into());
+    for (timestamp, value) in points.filter(|(_, v)| v.is_finite()) {
+        let timestamp = timestamp
+            .checked_mul(1_000_000)
+            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp: {timestamp} out of range"))?;
+        let mut record = base.clone();
+        record.insert(VALUE_LABEL.to_string(), value.into());
+        record.insert(CONFIG.common.column_timestamp.clone(), timestamp.into());
+        records.push(json::Value::Object(record));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_records() {
+        let body = r#"{"series":[{"metric":"system.load.1","points":[[1700000000,0.5],[1700000010,0.7]],"type":"gauge","host":"web-1","tags":["env:prod","host:other","canary"]}]}"#;
+        let records = v1_records(json::from_str(body).unwrap()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0][NAME_LABEL], "system_load_1");
+        assert_eq!(records[0]["host"], "web-1");
+        assert_eq!(records[0]["env"], "prod");
+        assert_eq!(records[0]["canary"], "");
+        assert_eq!(records[0][VALUE_LABEL], 0.5);
+        assert_eq!(
+            records[0][&CONFIG.common.column_timestamp],
+            1_700_000_000_000_000_i64
+        );
+        assert_eq!(records[1][VALUE_LABEL], 0.7);
+    }
+
+    #[test]
+    fn test_records_timestamp_overflow() {
+        let body = r#"{"series":[{"metric":"system.load.1","points":[[1e300,0.5]]}]}"#;
+        assert!(v1_records(json::from_str(body).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_v2_records() {
+        use agentpayload::metric_payload::{MetricPoint, MetricSeries, MetricType, Resource};
+
+        let payload = agentpayload::MetricPayload {
+            series: vec![MetricSeries {
+                metric: "requests".to_string(),
+                r#type: MetricType::Count as i32,
+                points: vec![MetricPoint {
+                    value: 3.0,
+                    timestamp: 1700000000,
+                }],
+                resources: vec![Resource {
+                    r#type: "host".to_string(),
+                    name: "web-1".to_string(),
+                }],
+                tags: vec!["env:prod".to_string()],
+                ..Default::default()
+            }],
+        };
+        let series = decode_payload(payload.encode_to_vec().into()).unwrap();
+        let records = v2_records(series).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0][NAME_LABEL], "requests");
+        assert_eq!(records[0]["host"], "web-1");
+        assert_eq!(records[0]["env"], "prod");
+        assert_eq!(records[0][VALUE_LABEL], 3.0);
+
+        let body = r#"{"series":[{"metric":"requests","type":1,"points":[{"timestamp":1700000000,"value":3}],"resources":[{"name":"web-1","type":"host"}],"tags":["env:prod"]}]}"#;
+        let series = json::from_str::<SeriesV2Request>(body).unwrap().series;
+        assert_eq!(v2_records(series).unwrap(), records);
+    }
+}
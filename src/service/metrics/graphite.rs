@@ -0,0 +1,112 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Result};
+
+use super::format_label_name;
+use crate::common::infra::config::CONFIG;
+use crate::common::meta::{
+    ingestion::IngestionResponse,
+    prom::{NAME_LABEL, TYPE_LABEL, VALUE_LABEL},
+    usage::UsageType,
+};
+use crate::common::utils::json;
+
+/// ingest graphite plaintext lines into the configured organization
+pub async fn ingest(lines: &str) -> Result<IngestionResponse> {
+    let records = parse_lines(lines)?;
+    super::json::ingest_records(
+        &CONFIG.tcp.graphite_org,
+        records,
+        0,
+        UsageType::Graphite,
+        "/api/org/ingest/metrics/_graphite",
+    )
+    .await
+}
+
+/// `<path>[;tag=value...] <value> [timestamp]`, the dots of the path are
+/// replaced in the metric name
+pub(crate) fn parse_lines(lines: &str) -> Result<Vec<json::Value>> {
+    let now = chrono::Utc::now().timestamp_micros();
+    let mut records = vec![];
+    for line in lines.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parts = line.split_whitespace().collect::<Vec<_>>();
+        if parts.len() < 2 || parts.len() > 3 {
+            return Err(anyhow!("invalid graphite line: {line}"));
+        }
+        let value = parts[1]
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| anyhow!("invalid graphite value: {line}"))?;
+        // -1 asks for the time of the receiver
+        let timestamp = match parts.get(2) {
+            Some(v) => match v.parse::<f64>() {
+                Ok(v) if v >= 0.0 => (v * 1_000_000.0) as i64,
+                Ok(_) => now,
+                Err(_) => return Err(anyhow!("invalid graphite timestamp: {line}")),
+            },
+            None => now,
+        };
+
+        let mut path = parts[0].split(';');
+        let name = path.next().unwrap_or_default();
+        if name.is_empty() {
+            return Err(anyhow!("invalid graphite path: {line}"));
+        }
+        let mut record = json::Map::new();
+        for tag in path {
+            let Some((key, value)) = tag.split_once('=') else {
+                return Err(anyhow!("invalid graphite tag: {line}"));
+            };
+            record.insert(format_label_name(key), value.into());
+        }
+        record.insert(NAME_LABEL.to_string(), format_label_name(name).into());
+        record.insert(TYPE_LABEL.to_string(), "gauge".into());
+        record.insert(VALUE_LABEL.to_string(), value.into());
+        record.insert(CONFIG.common.column_timestamp.clone(), timestamp.into());
+        records.push(json::Value::Object(record));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lines() {
+        let records =
+            parse_lines("servers.web-1.cpu.load 0.5 1700000000\ndisk.used;host=db1;dc=eu 42 -1\n")
+                .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0][NAME_LABEL], "servers_web_1_cpu_load");
+        assert_eq!(records[0][VALUE_LABEL], 0.5);
+        assert_eq!(
+            records[0][&CONFIG.common.column_timestamp],
+            1_700_000_000_000_000_i64
+        );
+        assert_eq!(records[1][NAME_LABEL], "disk_used");
+        assert_eq!(records[1]["host"], "db1");
+        assert_eq!(records[1]["dc"], "eu");
+
+        assert!(parse_lines("disk.used abc 1").is_err());
+        assert!(parse_lines("disk.used").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::web;
+use anyhow::{anyhow, Result};
+
+use super::format_label_name;
+use crate::common::infra::config::CONFIG;
+use crate::common::meta::{
+    ingestion::IngestionResponse,
+    prom::{NAME_LABEL, TYPE_LABEL, VALUE_LABEL},
+    usage::UsageType,
+};
+use crate::common::utils::json;
+
+/// the field named value keeps the measurement as the metric name
+const DEFAULT_FIELD: &str = "value";
+
+/// ingest the influxdb line protocol, `precision` is the unit of the timestamps
+pub async fn write(
+    org_id: &str,
+    precision: Option<&str>,
+    body: web::Bytes,
+    thread_id: usize,
+) -> Result<IngestionResponse> {
+    let records = parse_lines(&String::from_utf8_lossy(&body), precision.unwrap_or("ns"))?;
+    super::json::ingest_records(
+        org_id,
+        records,
+        thread_id,
+        UsageType::InfluxDb,
+        "/api/org/influxdb/write",
+    )
+    .await
+}
+
+/// one metric record per numeric field, named `<measurement>_<field>`
+pub(crate) fn parse_lines(body: &str, precision: &str) -> Result<Vec<json::Value>> {
+    let now = chrono::Utc::now().timestamp_micros();
+    let mut records = vec![];
+    for (i, line) in body.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line_err = |e: &str| anyhow!("line {}: {e}", i + 1);
+
+        let sections = split_unescaped(line, ' ');
+        let sections = sections
+            .into_iter()
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>();
+        if sections.len() < 2 || sections.len() > 3 {
+            return Err(line_err("invalid line protocol"));
+        }
+        let timestamp = match sections.get(2) {
+            Some(v) => {
+                let v = v
+                    .parse::<i64>()
+                    .map_err(|_| line_err("invalid timestamp"))?;
+                to_micros(v, precision).map_err(line_err)?
+            }
+            None => now,
+        };
+
+        let mut series = split_unescaped(sections[0], ',').into_iter();
+        let measurement = unescape(series.next().unwrap_or_default());
+        if measurement.is_empty() {
+            return Err(line_err("missing measurement"));
+        }
+        let mut labels = json::Map::new();
+        for tag in series {
+            let Some((key, value)) = split_pair(tag) else {
+                return Err(line_err("invalid tag"));
+            };
+            labels.insert(format_label_name(&key), value.into());
+        }
+
+        for field in split_unescaped(sections[1], ',') {
+            let Some((key, value)) = split_pair(field) else {
+                return Err(line_err("invalid field"));
+            };
+            // strings can not be metric values
+            let Some(value) = field_value(&value) else {
+                continue;
+            };
+            let name = if key == DEFAULT_FIELD {
+                measurement.clone()
+            } else {
+                format!("{measurement}_{key}")
+            };
+            let mut record = labels.clone();
+            record.insert(NAME_LABEL.to_string(), format_label_name(&name).into());
+            record.insert(TYPE_LABEL.to_string(), "gauge".into());
+            record.insert(VALUE_LABEL.to_string(), value.into());
+            record.insert(CONFIG.common.column_timestamp.clone(), timestamp.into());
+            records.push(json::Value::Object(record));
+        }
+    }
+    Ok(records)
+}
+
+/// the timestamp in microseconds, fails on an unknown precision or when it
+/// overflows
+fn to_micros(ts: i64, precision: &str) -> Result<i64, &'static str> {
+    let factor = match precision {
+        "ns" | "n" => return Ok(ts / 1000),
+        "us" | "u" => 1,
+        "ms" => 1000,
+        "s" => 1_000_000,
+        "m" => 60_000_000,
+        "h" => 3_600_000_000,
+        _ => return Err("invalid precision"),
+    };
+    ts.checked_mul(factor).ok_or("timestamp out of range")
+}
+
+/// numeric field value, integers end with `i` or `u` and booleans are 0 or 1
+fn field_value(value: &str) -> Option<f64> {
+    match value {
+        "t" | "T" | "true" | "True" | "TRUE" => return Some(1.0),
+        "f" | "F" | "false" | "False" | "FALSE" => return Some(0.0),
+        _ => {}
+    }
+    if value.starts_with('"') {
+        return None;
+    }
+    let number = value
+        .strip_suffix('i')
+        .or_else(|| value.strip_suffix('u'))
+        .unwrap_or(value);
+    number.parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// split `key=value`, the key is unescaped and the value unescaped when it is
+/// not a quoted string
+fn split_pair(pair: &str) -> Option<(String, String)> {
+    let mut escaped = false;
+    let (pos, _) = pair.char_indices().find(|&(_, c)| {
+        if escaped {
+            escaped = false;
+            return false;
+        }
+        escaped = c == '\\';
+        c == '='
+    })?;
+    let (key, rest) = (unescape(&pair[..pos]), &pair[pos + 1..]);
+    if key.is_empty() || rest.is_empty() {
+        return None;
+    }
+    let value = if rest.starts_with('"') {
+        rest.to_string()
+    } else {
+        unescape(rest)
+    };
+    Some((key, value))
+}
+
+/// split on the separator, skipping the escaped ones and the ones in quotes
+fn split_unescaped(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    let mut quoted = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(&next) = chars.peek() {
+                if matches!(next, ',' | '=' | ' ' | '"' | '\\') {
+                    out.push(next);
+                    chars.next();
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lines() {
+        let body = r#"
+# comment
+cpu,host=server\ 1,region=us-west usage_idle=97.5,usage_user=2i,msg="a b, c" 1465839830100400200
+mem value=1024u 1465839830
+"#;
+        let records = parse_lines(body, "ns").unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0][NAME_LABEL], "cpu_usage_idle");
+        assert_eq!(records[0]["host"], "server 1");
+        assert_eq!(records[0]["region"], "us-west");
+        assert_eq!(records[0][VALUE_LABEL], 97.5);
+        assert_eq!(
+            records[0][&CONFIG.common.column_timestamp],
+            1_465_839_830_100_400_i64
+        );
+        assert_eq!(records[1][NAME_LABEL], "cpu_usage_user");
+        assert_eq!(records[1][VALUE_LABEL], 2.0);
+        assert_eq!(records[2][NAME_LABEL], "mem");
+        assert_eq!(records[2][VALUE_LABEL], 1024.0);
+
+        let records = parse_lines("mem value=1 1465839830", "s").unwrap();
+        assert_eq!(
+            records[0][&CONFIG.common.column_timestamp],
+            1_465_839_830_000_000_i64
+        );
+    }
+
+    #[test]
+    fn test_parse_lines_errors() {
+        assert!(parse_lines("cpu", "ns").is_err());
+        assert!(parse_lines("cpu,host usage=1", "ns").is_err());
+        assert!(parse_lines("cpu usage=1 abc", "ns").is_err());
+        assert!(parse_lines("cpu usage=1 1", "x").is_err());
+        assert!(parse_lines("cpu usage=1 9223372036854775807", "s").is_err());
+    }
+
+    #[test]
+    fn test_split_unescaped() {
+        assert_eq!(
+            split_unescaped(r#"a\,b,c="d,e",f"#, ','),
+            vec![r"a\,b", r#"c="d,e""#, "f"]
+        );
+        assert_eq!(unescape(r"a\,b\ c\=d"), "a,b c=d");
+    }
+}
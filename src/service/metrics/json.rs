@@ -29,11 +29,32 @@ use crate::common::meta::{
 };
 use crate::common::utils::{flatten, json, time};
 use crate::service::{
-    db, ingestion::get_wal_time_key, ingestion::write_file, stream::unwrap_partition_time_level,
+    db,
+    ingestion::{get_wal_time_key, quota, redaction, write_file},
+    stream::unwrap_partition_time_level,
     usage::report_request_usage_stats,
 };
 
 pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<IngestionResponse> {
+    let reader: Vec<json::Value> = json::from_slice(&body)?;
+    ingest_records(
+        org_id,
+        reader,
+        thread_id,
+        UsageType::JsonMetrics,
+        "/api/org/ingest/metrics/_json",
+    )
+    .await
+}
+
+/// ingest records of `__name__`, `__type__`, labels, `value` and `_timestamp`
+pub(crate) async fn ingest_records(
+    org_id: &str,
+    reader: Vec<json::Value>,
+    thread_id: usize,
+    usage_type: UsageType,
+    endpoint: &str,
+) -> Result<IngestionResponse> {
     let start = std::time::Instant::now();
 
     if !cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
@@ -50,7 +71,6 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
     let mut stream_data_buf: AHashMap<String, AHashMap<String, Vec<String>>> = AHashMap::new();
     let mut stream_partitioning_map: AHashMap<String, PartitioningDetails> = AHashMap::new();
 
-    let reader: Vec<json::Value> = json::from_slice(&body)?;
     for record in reader.iter() {
         // JSON Flattening
         let mut record = flatten::flatten(record)?;
@@ -158,6 +178,14 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
                 }
             }
         }
+        redaction::redact(
+            &StreamParams {
+                org_id,
+                stream_name: &stream_name,
+                stream_type: StreamType::Metrics,
+            },
+            record,
+        );
         let record_str = json::to_string(&record).unwrap();
 
         // check schema
@@ -223,6 +251,8 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
             .or_insert(StreamStatus::new(&stream_name));
         stream_status.status.successful += 1;
     }
+    quota::check_buffered(org_id, &stream_data_buf).await?;
+
     let time = start.elapsed().as_secs_f64();
 
     for (stream_name, stream_data) in stream_data_buf {
@@ -262,7 +292,7 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
             org_id,
             &stream_name,
             StreamType::Metrics,
-            UsageType::JsonMetrics,
+            usage_type,
             0,
         )
         .await;
@@ -270,7 +300,7 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
 
     metrics::HTTP_RESPONSE_TIME
         .with_label_values(&[
-            "/api/org/ingest/metrics/_json",
+            endpoint,
             "200",
             org_id,
             "",
@@ -279,7 +309,7 @@ pub async fn ingest(org_id: &str, body: web::Bytes, thread_id: usize) -> Result<
         .observe(time);
     metrics::HTTP_INCOMING_REQUESTS
         .with_label_values(&[
-            "/api/org/ingest/metrics/_json",
+            endpoint,
             "200",
             org_id,
             "",
@@ -17,9 +17,14 @@ use datafusion::arrow::datatypes::Schema;
 use crate::common;
 use crate::common::meta::prom::{Metadata, METADATA_LABEL};
 
+pub mod datadog;
+pub mod graphite;
+pub mod influx;
 pub mod json;
 pub mod otlp;
+pub mod otlp_http;
 pub mod prom;
+pub mod statsd;
 
 pub fn get_prom_metadata_from_schema(schema: &Schema) -> Option<Metadata> {
     let metadata = schema.metadata.get(METADATA_LABEL)?;
@@ -27,6 +32,20 @@ pub fn get_prom_metadata_from_schema(schema: &Schema) -> Option<Metadata> {
     Some(metadata)
 }
 
+/// replace the characters that are not allowed in prometheus metric and
+/// label names
+pub(crate) fn format_label_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct Signature([u8; 32]);
 
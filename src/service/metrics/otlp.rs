@@ -31,7 +31,7 @@ use crate::{
         ingestion::{
             chk_schema_by_record,
             grpc::{get_exemplar_val, get_metric_val, get_val},
-            write_file,
+            quota, redaction, write_file,
         },
         schema::{set_schema_metadata, stream_schema_exists},
         stream::unwrap_partition_time_level,
@@ -185,6 +185,14 @@ pub async fn handle_grpc_request(
                         .as_i64()
                         .unwrap_or(Utc::now().timestamp_micros());
 
+                    redaction::redact(
+                        &StreamParams {
+                            org_id,
+                            stream_name: metric_name,
+                            stream_type: StreamType::Metrics,
+                        },
+                        val_map,
+                    );
                     let value_str = json::to_string(&val_map).unwrap();
                     chk_schema_by_record(
                         &mut metric_schema_map,
@@ -249,6 +257,10 @@ pub async fn handle_grpc_request(
         }
     }
 
+    if let Err(e) = quota::check_buffered(org_id, &metric_data_map).await {
+        return Ok(e.to_response());
+    }
+
     let time = start.elapsed().as_secs_f64();
     for (stream_name, stream_data) in metric_data_map {
         // stream_data could be empty if metric value is nan, check it
@@ -518,7 +530,7 @@ fn process_hist_data_point(
     bucket_recs.push(sum_rec);
 
     // add bucket records
-    let last_index = data_point.bucket_counts.len() - 1;
+    let last_index = data_point.bucket_counts.len().saturating_sub(1);
     for i in 0..last_index {
         let mut bucket_rec = rec.clone();
         if let Some(val) = data_point.bucket_counts.get(i) {
@@ -0,0 +1,401 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::{web, HttpResponse};
+use base64::Engine;
+use opentelemetry_proto::tonic::{
+    collector::metrics::v1::ExportMetricsServiceRequest,
+    common::v1::{any_value, AnyValue, ArrayValue, InstrumentationLibrary, KeyValue, KeyValueList},
+    metrics::v1::{
+        exemplar, exponential_histogram_data_point::Buckets, metric::Data, number_data_point,
+        summary_data_point::ValueAtQuantile, AggregationTemporality, Exemplar,
+        ExponentialHistogram, ExponentialHistogramDataPoint, Gauge, Histogram, HistogramDataPoint,
+        InstrumentationLibraryMetrics, Metric, NumberDataPoint, ResourceMetrics, Sum, Summary,
+        SummaryDataPoint,
+    },
+    resource::v1::Resource,
+};
+use prost::Message;
+
+use crate::common::utils::json;
+
+pub async fn metrics_proto(
+    org_id: &str,
+    thread_id: usize,
+    body: web::Bytes,
+) -> Result<HttpResponse, anyhow::Error> {
+    let request = ExportMetricsServiceRequest::decode(body)?;
+    super::otlp::handle_grpc_request(org_id, thread_id, request).await
+}
+
+pub async fn metrics_json(
+    org_id: &str,
+    thread_id: usize,
+    body: web::Bytes,
+) -> Result<HttpResponse, anyhow::Error> {
+    let body: json::Value = json::from_slice(&body)?;
+    let request = json_request(&body)?;
+    let resp = super::otlp::handle_grpc_request(org_id, thread_id, request).await?;
+    if !resp.status().is_success() {
+        return Ok(resp);
+    }
+    // json clients expect the json encoding of the export response
+    Ok(HttpResponse::Ok().json(json::json!({})))
+}
+
+/// Convert the OTLP/JSON encoding of the request to the protobuf one, so both
+/// encodings share the conversion of `otlp::handle_grpc_request`. Ids are hex
+/// strings, bytes are base64 and 64 bit integers can be strings.
+fn json_request(body: &json::Value) -> Result<ExportMetricsServiceRequest, anyhow::Error> {
+    let Some(items) = body.get("resourceMetrics").and_then(|v| v.as_array()) else {
+        return Err(anyhow::anyhow!(
+            "Invalid json: the structure must be {{\"resourceMetrics\":[]}}"
+        ));
+    };
+    let mut resource_metrics = vec![];
+    for item in items {
+        let resource = item.get("resource").map(|v| Resource {
+            attributes: key_values(v.get("attributes")),
+            dropped_attributes_count: to_u64(v.get("droppedAttributesCount")) as u32,
+        });
+        let scope_metrics = item
+            .get("scopeMetrics")
+            .or_else(|| item.get("instrumentationLibraryMetrics"));
+        let mut instrumentation_library_metrics = vec![];
+        for scope_metric in array(scope_metrics) {
+            let instrumentation_library = scope_metric
+                .get("scope")
+                .or_else(|| scope_metric.get("instrumentationLibrary"))
+                .map(|v| InstrumentationLibrary {
+                    name: to_str(v.get("name")),
+                    version: to_str(v.get("version")),
+                });
+            let metrics = array(scope_metric.get("metrics"))
+                .map(to_metric)
+                .collect::<Result<Vec<_>, _>>()?;
+            instrumentation_library_metrics.push(InstrumentationLibraryMetrics {
+                instrumentation_library,
+                metrics,
+                ..Default::default()
+            });
+        }
+        resource_metrics.push(ResourceMetrics {
+            resource,
+            instrumentation_library_metrics,
+            ..Default::default()
+        });
+    }
+    Ok(ExportMetricsServiceRequest { resource_metrics })
+}
+
+fn to_metric(value: &json::Value) -> Result<Metric, anyhow::Error> {
+    let data = if let Some(v) = value.get("gauge") {
+        Some(Data::Gauge(Gauge {
+            data_points: array(v.get("dataPoints")).map(number_point).collect(),
+        }))
+    } else if let Some(v) = value.get("sum") {
+        Some(Data::Sum(Sum {
+            data_points: array(v.get("dataPoints")).map(number_point).collect(),
+            aggregation_temporality: aggregation_temporality(v.get("aggregationTemporality")),
+            is_monotonic: v
+                .get("isMonotonic")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default(),
+        }))
+    } else if let Some(v) = value.get("histogram") {
+        Some(Data::Histogram(Histogram {
+            data_points: array(v.get("dataPoints")).map(histogram_point).collect(),
+            aggregation_temporality: aggregation_temporality(v.get("aggregationTemporality")),
+        }))
+    } else if let Some(v) = value.get("exponentialHistogram") {
+        Some(Data::ExponentialHistogram(ExponentialHistogram {
+            data_points: array(v.get("dataPoints"))
+                .map(exponential_histogram_point)
+                .collect(),
+            aggregation_temporality: aggregation_temporality(v.get("aggregationTemporality")),
+        }))
+    } else {
+        value.get("summary").map(|v| {
+            Data::Summary(Summary {
+                data_points: array(v.get("dataPoints")).map(summary_point).collect(),
+            })
+        })
+    };
+    let name = to_str(value.get("name"));
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("Invalid json: metric without name"));
+    }
+    Ok(Metric {
+        name,
+        description: to_str(value.get("description")),
+        unit: to_str(value.get("unit")),
+        data,
+    })
+}
+
+fn number_point(value: &json::Value) -> NumberDataPoint {
+    let point_value = if let Some(v) = value.get("asDouble") {
+        Some(number_data_point::Value::AsDouble(to_f64(Some(v))))
+    } else {
+        value
+            .get("asInt")
+            .map(|v| number_data_point::Value::AsInt(to_i64(Some(v))))
+    };
+    NumberDataPoint {
+        attributes: key_values(value.get("attributes")),
+        start_time_unix_nano: to_u64(value.get("startTimeUnixNano")),
+        time_unix_nano: to_u64(value.get("timeUnixNano")),
+        exemplars: array(value.get("exemplars")).map(to_exemplar).collect(),
+        flags: to_u64(value.get("flags")) as u32,
+        value: point_value,
+    }
+}
+
+fn histogram_point(value: &json::Value) -> HistogramDataPoint {
+    HistogramDataPoint {
+        attributes: key_values(value.get("attributes")),
+        start_time_unix_nano: to_u64(value.get("startTimeUnixNano")),
+        time_unix_nano: to_u64(value.get("timeUnixNano")),
+        count: to_u64(value.get("count")),
+        sum: to_f64(value.get("sum")).into(),
+        bucket_counts: array(value.get("bucketCounts"))
+            .map(|v| to_u64(Some(v)))
+            .collect(),
+        explicit_bounds: array(value.get("explicitBounds"))
+            .map(|v| to_f64(Some(v)))
+            .collect(),
+        exemplars: array(value.get("exemplars")).map(to_exemplar).collect(),
+        flags: to_u64(value.get("flags")) as u32,
+        ..Default::default()
+    }
+}
+
+fn exponential_histogram_point(value: &json::Value) -> ExponentialHistogramDataPoint {
+    let buckets = |v: &json::Value| Buckets {
+        offset: to_i64(v.get("offset")) as i32,
+        bucket_counts: array(v.get("bucketCounts"))
+            .map(|v| to_u64(Some(v)))
+            .collect(),
+    };
+    ExponentialHistogramDataPoint {
+        attributes: key_values(value.get("attributes")),
+        start_time_unix_nano: to_u64(value.get("startTimeUnixNano")),
+        time_unix_nano: to_u64(value.get("timeUnixNano")),
+        count: to_u64(value.get("count")),
+        sum: to_f64(value.get("sum")).into(),
+        scale: to_i64(value.get("scale")) as i32,
+        zero_count: to_u64(value.get("zeroCount")),
+        positive: value.get("positive").map(buckets),
+        negative: value.get("negative").map(buckets),
+        flags: to_u64(value.get("flags")) as u32,
+        exemplars: array(value.get("exemplars")).map(to_exemplar).collect(),
+        ..Default::default()
+    }
+}
+
+fn summary_point(value: &json::Value) -> SummaryDataPoint {
+    SummaryDataPoint {
+        attributes: key_values(value.get("attributes")),
+        start_time_unix_nano: to_u64(value.get("startTimeUnixNano")),
+        time_unix_nano: to_u64(value.get("timeUnixNano")),
+        count: to_u64(value.get("count")),
+        sum: to_f64(value.get("sum")),
+        quantile_values: array(value.get("quantileValues"))
+            .map(|v| ValueAtQuantile {
+                quantile: to_f64(v.get("quantile")),
+                value: to_f64(v.get("value")),
+            })
+            .collect(),
+        flags: to_u64(value.get("flags")) as u32,
+    }
+}
+
+fn to_exemplar(value: &json::Value) -> Exemplar {
+    let exemplar_value = if let Some(v) = value.get("asDouble") {
+        Some(exemplar::Value::AsDouble(to_f64(Some(v))))
+    } else {
+        value
+            .get("asInt")
+            .map(|v| exemplar::Value::AsInt(to_i64(Some(v))))
+    };
+    Exemplar {
+        filtered_attributes: key_values(value.get("filteredAttributes")),
+        time_unix_nano: to_u64(value.get("timeUnixNano")),
+        span_id: hex::decode(to_str(value.get("spanId"))).unwrap_or_default(),
+        trace_id: hex::decode(to_str(value.get("traceId"))).unwrap_or_default(),
+        value: exemplar_value,
+    }
+}
+
+/// the temporality is its number or its name
+fn aggregation_temporality(value: Option<&json::Value>) -> i32 {
+    match value {
+        Some(json::Value::String(v)) => AggregationTemporality::from_str_name(v)
+            .map(|v| v as i32)
+            .unwrap_or_default(),
+        v => to_i64(v) as i32,
+    }
+}
+
+fn key_values(value: Option<&json::Value>) -> Vec<KeyValue> {
+    array(value)
+        .map(|v| KeyValue {
+            key: to_str(v.get("key")),
+            value: v.get("value").map(to_any_value),
+        })
+        .collect()
+}
+
+fn to_any_value(value: &json::Value) -> AnyValue {
+    let kind = value.as_object().and_then(|v| v.iter().next());
+    let value = match kind {
+        Some((kind, inner)) => match kind.as_str() {
+            "stringValue" => Some(any_value::Value::StringValue(to_str(Some(inner)))),
+            "boolValue" => Some(any_value::Value::BoolValue(
+                inner.as_bool().unwrap_or_default(),
+            )),
+            "intValue" => Some(any_value::Value::IntValue(to_i64(Some(inner)))),
+            "doubleValue" => Some(any_value::Value::DoubleValue(to_f64(Some(inner)))),
+            "arrayValue" => Some(any_value::Value::ArrayValue(ArrayValue {
+                values: array(inner.get("values")).map(to_any_value).collect(),
+            })),
+            "kvlistValue" => Some(any_value::Value::KvlistValue(KeyValueList {
+                values: key_values(inner.get("values")),
+            })),
+            "bytesValue" => Some(any_value::Value::BytesValue(
+                base64::engine::general_purpose::STANDARD
+                    .decode(to_str(Some(inner)))
+                    .unwrap_or_default(),
+            )),
+            _ => None,
+        },
+        None => None,
+    };
+    AnyValue { value }
+}
+
+fn array(value: Option<&json::Value>) -> impl Iterator<Item = &json::Value> {
+    value.and_then(|v| v.as_array()).into_iter().flatten()
+}
+
+fn to_str(value: Option<&json::Value>) -> String {
+    value
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn to_u64(value: Option<&json::Value>) -> u64 {
+    match value {
+        Some(json::Value::Number(v)) => v.as_u64().unwrap_or_default(),
+        Some(json::Value::String(v)) => v.parse().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+fn to_i64(value: Option<&json::Value>) -> i64 {
+    match value {
+        Some(json::Value::Number(v)) => v.as_i64().unwrap_or_default(),
+        Some(json::Value::String(v)) => v.parse().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+/// doubles can be numbers or the strings of the special values, e.g. "NaN"
+fn to_f64(value: Option<&json::Value>) -> f64 {
+    match value {
+        Some(json::Value::Number(v)) => v.as_f64().unwrap_or_default(),
+        Some(json::Value::String(v)) => v.parse().unwrap_or_default(),
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_request() {
+        let body = json::json!({
+            "resourceMetrics": [{
+                "resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "web"}}]},
+                "scopeMetrics": [{
+                    "scope": {"name": "otel", "version": "1.0"},
+                    "metrics": [{
+                        "name": "requests",
+                        "unit": "1",
+                        "sum": {
+                            "aggregationTemporality": "AGGREGATION_TEMPORALITY_DELTA",
+                            "isMonotonic": true,
+                            "dataPoints": [{
+                                "attributes": [{"key": "code", "value": {"intValue": "200"}}],
+                                "timeUnixNano": "1581452773000000789",
+                                "asInt": "3",
+                                "exemplars": [{
+                                    "timeUnixNano": "1581452773000000789",
+                                    "asDouble": 1.5,
+                                    "traceId": "5b8efff798038103d269b633813fc60c"
+                                }]
+                            }]
+                        }
+                    }, {
+                        "name": "latency",
+                        "histogram": {
+                            "aggregationTemporality": 2,
+                            "dataPoints": [{
+                                "count": "3",
+                                "sum": 7.5,
+                                "bucketCounts": ["1", "2"],
+                                "explicitBounds": [5.0]
+                            }]
+                        }
+                    }]
+                }]
+            }]
+        });
+        let request = json_request(&body).unwrap();
+        let resource_metrics = &request.resource_metrics[0];
+        assert_eq!(
+            resource_metrics.resource.as_ref().unwrap().attributes[0].key,
+            "service.name"
+        );
+        let scope = &resource_metrics.instrumentation_library_metrics[0];
+        assert_eq!(scope.instrumentation_library.as_ref().unwrap().name, "otel");
+
+        let Some(Data::Sum(sum)) = &scope.metrics[0].data else {
+            panic!("not a sum");
+        };
+        assert_eq!(
+            sum.aggregation_temporality,
+            AggregationTemporality::Delta as i32
+        );
+        assert!(sum.is_monotonic);
+        let point = &sum.data_points[0];
+        assert_eq!(point.time_unix_nano, 1581452773000000789);
+        assert_eq!(point.value, Some(number_data_point::Value::AsInt(3)));
+        assert_eq!(point.exemplars[0].trace_id.len(), 16);
+
+        let Some(Data::Histogram(hist)) = &scope.metrics[1].data else {
+            panic!("not a histogram");
+        };
+        assert_eq!(
+            hist.aggregation_temporality,
+            AggregationTemporality::Cumulative as i32
+        );
+        assert_eq!(hist.data_points[0].count, 3);
+        assert_eq!(hist.data_points[0].bucket_counts, vec![1, 2]);
+
+        assert!(json_request(&json::json!({"resourceLogs": []})).is_err());
+    }
+}
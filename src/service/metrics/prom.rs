@@ -39,7 +39,7 @@ use crate::common::meta::{
 use crate::common::utils::{json, time::parse_i64_to_timestamp_micros};
 use crate::service::{
     db,
-    ingestion::{chk_schema_by_record, write_file},
+    ingestion::{chk_schema_by_record, quota, redaction, write_file},
     schema::{set_schema_metadata, stream_schema_exists},
     search as search_service,
     stream::unwrap_partition_time_level,
@@ -263,6 +263,14 @@ pub async fn remote_write(
                 CONFIG.common.column_timestamp.clone(),
                 json::Value::Number(timestamp.into()),
             );
+            redaction::redact(
+                &StreamParams {
+                    org_id,
+                    stream_name: &metric_name,
+                    stream_type: StreamType::Metrics,
+                },
+                val_map,
+            );
             let value_str = crate::common::utils::json::to_string(&val_map).unwrap();
             chk_schema_by_record(
                 &mut metric_schema_map,
@@ -325,6 +333,8 @@ pub async fn remote_write(
         }
     }
 
+    quota::check_buffered(org_id, &metric_data_map).await?;
+
     let time = start.elapsed().as_secs_f64();
     for (stream_name, stream_data) in metric_data_map {
         // stream_data could be empty if metric value is nan, check it
@@ -0,0 +1,413 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ahash::{AHashMap, AHashSet};
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+
+use super::format_label_name;
+use crate::common::infra::config::CONFIG;
+use crate::common::meta::{
+    prom::{NAME_LABEL, TYPE_LABEL, VALUE_LABEL},
+    usage::UsageType,
+};
+use crate::common::utils::json;
+
+/// quantiles of the timers and histograms, written in the `quantile` label
+const QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+/// the flushes kept to write again after a failure, the oldest are dropped
+const MAX_PENDING_FLUSHES: usize = 10;
+
+static AGGREGATOR: Lazy<Mutex<Aggregator>> = Lazy::new(|| Mutex::new(Aggregator::default()));
+
+/// metric name and sorted tags
+type SeriesKey = (String, Vec<(String, String)>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Counter(f64),
+    /// a signed gauge value changes the current value
+    Gauge {
+        value: f64,
+        delta: bool,
+    },
+    /// timers, histograms and distributions
+    Timer(f64),
+    Set(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Sample {
+    pub name: String,
+    pub value: Value,
+    pub rate: f64,
+    pub tags: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+struct Timer {
+    /// values of the current interval
+    values: Vec<f64>,
+    count: f64,
+    sum: f64,
+}
+
+/// counters and the count and sum of the timers are cumulative, gauges keep
+/// their value, and sets are reset at every flush. only the series updated
+/// in the interval are written, the others are evicted.
+#[derive(Default)]
+pub(crate) struct Aggregator {
+    counters: AHashMap<SeriesKey, (f64, bool)>,
+    gauges: AHashMap<SeriesKey, (f64, bool)>,
+    timers: AHashMap<SeriesKey, Timer>,
+    sets: AHashMap<SeriesKey, AHashSet<String>>,
+    dropped: usize,
+    /// records of the flushes that failed, written with the next one
+    pending: VecDeque<Vec<json::Value>>,
+}
+
+/// aggregate the valid lines, the error counts the invalid ones
+pub fn ingest(lines: &str) -> Result<()> {
+    let mut invalid = 0;
+    let mut last_err = None;
+    let mut samples = vec![];
+    for line in lines.lines() {
+        match parse_line(line) {
+            Ok(Some(sample)) => samples.push(sample),
+            Ok(None) => {}
+            Err(e) => {
+                invalid += 1;
+                last_err = Some(e);
+            }
+        }
+    }
+    let mut aggregator = AGGREGATOR.lock();
+    for sample in samples {
+        aggregator.add(sample, CONFIG.limit.statsd_max_series);
+    }
+    drop(aggregator);
+    match last_err {
+        Some(e) => Err(anyhow!("{invalid} invalid statsd lines, last: {e}")),
+        None => Ok(()),
+    }
+}
+
+/// write the series aggregated since the last flush, and the records of the
+/// flushes that failed before
+pub async fn flush() -> Result<()> {
+    let timestamp = chrono::Utc::now().timestamp_micros();
+    let (records, dropped) = {
+        let mut aggregator = AGGREGATOR.lock();
+        let mut records = aggregator.pending.drain(..).flatten().collect::<Vec<_>>();
+        records.extend(aggregator.drain(timestamp));
+        (records, std::mem::take(&mut aggregator.dropped))
+    };
+    if dropped > 0 {
+        log::warn!(
+            "StatsD dropped {dropped} samples, the limit of {} series is reached",
+            CONFIG.limit.statsd_max_series
+        );
+    }
+    if records.is_empty() {
+        return Ok(());
+    }
+    if let Err(e) = super::json::ingest_records(
+        &CONFIG.tcp.statsd_org,
+        records.clone(),
+        0,
+        UsageType::StatsD,
+        "/api/org/ingest/metrics/_statsd",
+    )
+    .await
+    {
+        AGGREGATOR.lock().requeue(records);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// `<name>:<value>|<type>[|@<rate>][|#<tag>:<value>,...]`, `None` for the
+/// dogstatsd events and service checks
+pub(crate) fn parse_line(line: &str) -> Result<Option<Sample>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with("_e{") || line.starts_with("_sc|") {
+        return Ok(None);
+    }
+    let err = || anyhow!("invalid statsd line: {line}");
+    let mut sections = line.split('|');
+    let (name, raw_value) = sections
+        .next()
+        .and_then(|v| v.rsplit_once(':'))
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or_else(err)?;
+    let kind = sections.next().ok_or_else(err)?;
+
+    let mut rate = 1.0;
+    let mut tags = vec![];
+    for section in sections {
+        if let Some(v) = section.strip_prefix('@') {
+            rate = v
+                .parse::<f64>()
+                .ok()
+                .filter(|v| *v > 0.0 && *v <= 1.0)
+                .ok_or_else(err)?;
+        } else if let Some(v) = section.strip_prefix('#') {
+            for tag in v.split(',').filter(|v| !v.is_empty()) {
+                let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+                tags.push((format_label_name(key), value.to_string()));
+            }
+        }
+        // other dogstatsd extensions such as the timestamp or the container
+        // id are ignored
+    }
+    tags.sort();
+    tags.dedup_by(|a, b| a.0 == b.0);
+
+    let number = || {
+        raw_value
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(err)
+    };
+    let value = match kind {
+        "c" => Value::Counter(number()?),
+        "g" => Value::Gauge {
+            value: number()?,
+            delta: raw_value.starts_with(['+', '-']),
+        },
+        "ms" | "h" | "d" => Value::Timer(number()?),
+        "s" => Value::Set(raw_value.to_string()),
+        _ => return Err(err()),
+    };
+    Ok(Some(Sample {
+        name: format_label_name(name),
+        value,
+        rate,
+        tags,
+    }))
+}
+
+impl Aggregator {
+    pub(crate) fn add(&mut self, sample: Sample, max_series: usize) {
+        let key = (sample.name, sample.tags);
+        if !self.contains(&key) && self.len() >= max_series {
+            self.dropped += 1;
+            return;
+        }
+        match sample.value {
+            Value::Counter(v) => {
+                let entry = self.counters.entry(key).or_insert((0.0, false));
+                *entry = (entry.0 + v / sample.rate, true);
+            }
+            Value::Gauge { value, delta } => {
+                let entry = self.gauges.entry(key).or_insert((0.0, false));
+                *entry = (if delta { entry.0 + value } else { value }, true);
+            }
+            Value::Timer(v) => {
+                let entry = self.timers.entry(key).or_default();
+                entry.values.push(v);
+                entry.count += 1.0 / sample.rate;
+                entry.sum += v / sample.rate;
+            }
+            Value::Set(v) => {
+                self.sets.entry(key).or_default().insert(v);
+            }
+        }
+    }
+
+    /// metric records of the updated series, the series idle since the last
+    /// drain are evicted
+    pub(crate) fn drain(&mut self, timestamp: i64) -> Vec<json::Value> {
+        self.counters.retain(|_, (_, updated)| *updated);
+        self.gauges.retain(|_, (_, updated)| *updated);
+        self.timers.retain(|_, timer| !timer.values.is_empty());
+
+        let mut records = vec![];
+        let mut push = |name: &str, tags: &[(String, String)], kind: &str, value: f64| {
+            let mut record = json::Map::new();
+            for (key, value) in tags {
+                record.insert(key.clone(), value.clone().into());
+            }
+            record.insert(NAME_LABEL.to_string(), name.into());
+            record.insert(TYPE_LABEL.to_string(), kind.into());
+            record.insert(VALUE_LABEL.to_string(), value.into());
+            record.insert(CONFIG.common.column_timestamp.clone(), timestamp.into());
+            records.push(json::Value::Object(record));
+        };
+
+        for ((name, tags), (value, updated)) in self.counters.iter_mut() {
+            if std::mem::take(updated) {
+                push(name, tags, "counter", *value);
+            }
+        }
+        for ((name, tags), (value, updated)) in self.gauges.iter_mut() {
+            if std::mem::take(updated) {
+                push(name, tags, "gauge", *value);
+            }
+        }
+        for ((name, tags), timer) in self.timers.iter_mut() {
+            if timer.values.is_empty() {
+                continue;
+            }
+            timer.values.sort_by(|a, b| a.total_cmp(b));
+            for q in QUANTILES {
+                let mut tags = tags.clone();
+                tags.push(("quantile".to_string(), q.to_string()));
+                push(name, &tags, "gauge", quantile(&timer.values, q));
+            }
+            push(&format!("{name}_count"), tags, "counter", timer.count);
+            push(&format!("{name}_sum"), tags, "counter", timer.sum);
+            timer.values.clear();
+        }
+        for ((name, tags), values) in self.sets.drain() {
+            push(&name, &tags, "gauge", values.len() as f64);
+        }
+        records
+    }
+
+    /// keep the records of a failed flush for the next one
+    fn requeue(&mut self, records: Vec<json::Value>) {
+        self.pending.push_back(records);
+        while self.pending.len() > MAX_PENDING_FLUSHES {
+            let dropped = self.pending.pop_front().map_or(0, |v| v.len());
+            log::warn!("StatsD dropped {dropped} records of a failed flush");
+        }
+    }
+
+    fn contains(&self, key: &SeriesKey) -> bool {
+        self.counters.contains_key(key)
+            || self.gauges.contains_key(key)
+            || self.timers.contains_key(key)
+            || self.sets.contains_key(key)
+    }
+
+    fn len(&self) -> usize {
+        self.counters.len() + self.gauges.len() + self.timers.len() + self.sets.len()
+    }
+}
+
+/// nearest rank of the sorted values
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(records: &'a [json::Value], name: &str) -> Vec<&'a json::Value> {
+        records.iter().filter(|v| v[NAME_LABEL] == name).collect()
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let sample = parse_line("api.requests:2|c|@0.5|#env:prod,host:web-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(sample.name, "api_requests");
+        assert_eq!(sample.value, Value::Counter(2.0));
+        assert_eq!(sample.rate, 0.5);
+        assert_eq!(
+            sample.tags,
+            vec![
+                ("env".to_string(), "prod".to_string()),
+                ("host".to_string(), "web-1".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_line("temp:-3|g").unwrap().unwrap().value,
+            Value::Gauge {
+                value: -3.0,
+                delta: true
+            }
+        );
+        assert_eq!(
+            parse_line("users:alice|s").unwrap().unwrap().value,
+            Value::Set("alice".to_string())
+        );
+        assert!(parse_line("_e{5,4}:title|text").unwrap().is_none());
+        assert!(parse_line("api.requests:x|c").is_err());
+        assert!(parse_line("api.requests:1|x").is_err());
+        assert!(parse_line("api.requests:1|c|@2").is_err());
+        assert!(parse_line("api.requests").is_err());
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut aggregator = Aggregator::default();
+        for line in [
+            "hits:1|c|@0.1",
+            "hits:2|c",
+            "temp:10|g",
+            "temp:+5|g",
+            "latency:30|ms",
+            "latency:10|ms",
+            "latency:20|h",
+            "users:a|s",
+            "users:b|s",
+            "users:a|s",
+        ] {
+            aggregator.add(parse_line(line).unwrap().unwrap(), 100);
+        }
+        let records = aggregator.drain(1);
+        assert_eq!(find(&records, "hits")[0][VALUE_LABEL], 12.0);
+        assert_eq!(find(&records, "hits")[0][TYPE_LABEL], "counter");
+        assert_eq!(find(&records, "temp")[0][VALUE_LABEL], 15.0);
+        assert_eq!(find(&records, "users")[0][VALUE_LABEL], 2.0);
+        let latency = find(&records, "latency");
+        assert_eq!(latency.len(), 3);
+        assert!(latency
+            .iter()
+            .any(|v| v["quantile"] == "0.5" && v[VALUE_LABEL] == 20.0));
+        assert_eq!(find(&records, "latency_count")[0][VALUE_LABEL], 3.0);
+        assert_eq!(find(&records, "latency_sum")[0][VALUE_LABEL], 60.0);
+
+        // only the updated series are written, counters keep their total
+        aggregator.add(parse_line("hits:1|c").unwrap().unwrap(), 100);
+        let records = aggregator.drain(2);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0][VALUE_LABEL], 13.0);
+        assert!(aggregator.drain(3).is_empty());
+
+        // the idle series are evicted, a counter starts again
+        assert_eq!(aggregator.len(), 0);
+        aggregator.add(parse_line("hits:1|c").unwrap().unwrap(), 100);
+        assert_eq!(aggregator.drain(4)[0][VALUE_LABEL], 1.0);
+    }
+
+    #[test]
+    fn test_requeue() {
+        let mut aggregator = Aggregator::default();
+        for i in 0..MAX_PENDING_FLUSHES + 2 {
+            aggregator.requeue(vec![json::json!({ "flush": i })]);
+        }
+        assert_eq!(aggregator.pending.len(), MAX_PENDING_FLUSHES);
+        assert_eq!(aggregator.pending[0][0]["flush"], 2);
+    }
+
+    #[test]
+    fn test_max_series() {
+        let mut aggregator = Aggregator::default();
+        aggregator.add(parse_line("a:1|c").unwrap().unwrap(), 1);
+        aggregator.add(parse_line("b:1|c").unwrap().unwrap(), 1);
+        aggregator.add(parse_line("a:1|c").unwrap().unwrap(), 1);
+        assert_eq!(aggregator.dropped, 1);
+        assert_eq!(aggregator.drain(1).len(), 1);
+    }
+}
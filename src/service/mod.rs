@@ -20,6 +20,7 @@ pub mod db;
 pub mod enrichment;
 pub mod enrichment_table;
 pub mod file_list;
+pub mod fluent_routes;
 pub mod functions;
 pub mod ingestion;
 pub mod kv;
@@ -28,9 +29,12 @@ pub mod metrics;
 pub mod organization;
 pub mod promql;
 pub mod router;
+pub mod rules;
+pub mod sampling;
 pub mod schema;
 pub mod search;
 pub mod stream;
+pub mod stream_routes;
 pub mod syslogs_route;
 pub mod traces;
 pub mod triggers;
@@ -15,6 +15,7 @@
 use rand::distributions::{Alphanumeric, DistString};
 
 use super::stream::get_streams;
+use crate::common::infra::config::{USERS, USER_PASSCODES};
 use crate::common::meta::organization::{IngestionPasscode, OrgSummary};
 use crate::common::meta::user::UserOrg;
 use crate::common::utils::auth::is_root_user;
@@ -41,6 +42,20 @@ pub async fn get_passcode(org_id: Option<&str>, user_id: &str) -> IngestionPassc
     }
 }
 
+/// the user of the org whose ingestion passcode is the given one
+#[tracing::instrument(skip(passcode))]
+pub async fn get_passcode_user(org_id: &str, passcode: &str) -> Option<String> {
+    if passcode.is_empty() {
+        return None;
+    }
+    let user_id = USER_PASSCODES.get(&format!("{org_id}/{passcode}"))?.clone();
+    // the index may lag behind a passcode update of the user
+    USERS
+        .get(&format!("{org_id}/{user_id}"))
+        .filter(|user| user.token.eq(passcode))
+        .map(|_| user_id)
+}
+
 #[tracing::instrument]
 pub async fn update_passcode(org_id: Option<&str>, user_id: &str) -> IngestionPasscode {
     let mut local_org_id = "dummy";
@@ -29,7 +29,7 @@ use crate::service::{
 };
 
 mod storage;
-mod wal;
+pub(crate) mod wal;
 
 struct StorageProvider {
     session_id: String,
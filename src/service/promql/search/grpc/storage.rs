@@ -135,6 +135,7 @@ pub(crate) async fn create_context(
     let session = SearchSession {
         id: session_id.to_string(),
         storage_type,
+        cancel: Default::default(),
     };
 
     let ctx = register_table(
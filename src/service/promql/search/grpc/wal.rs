@@ -48,7 +48,7 @@ pub(crate) async fn create_context(
     _filters: &[(&str, &str)],
 ) -> Result<(SessionContext, Arc<Schema>, ScanStats)> {
     // get file list
-    let files = get_file_list(org_id, stream_name, time_range).await?;
+    let files = get_file_list(org_id, StreamType::Metrics, stream_name, time_range).await?;
     if files.is_empty() {
         return Ok((
             SessionContext::new(),
@@ -88,16 +88,19 @@ pub(crate) async fn create_context(
     let session = SearchSession {
         id: session_id.to_string(),
         storage_type: StorageType::Tmpfs,
+        cancel: Default::default(),
     };
 
     let ctx = register_table(&session, schema.clone(), stream_name, &[], FileType::JSON).await?;
     Ok((ctx, schema, scan_stats))
 }
 
-/// get file list from local cache, no need match_source, each file will be searched
+/// get the wal files of the stream from all the ingesters, no need match_source,
+/// each file will be searched
 #[tracing::instrument(name = "promql:search:grpc:wal:get_file_list")]
-async fn get_file_list(
+pub(crate) async fn get_file_list(
     org_id: &str,
+    stream_type: StreamType,
     stream_name: &str,
     time_range: (i64, i64),
 ) -> Result<Vec<cluster_rpc::MetricsWalFile>> {
@@ -116,6 +119,7 @@ async fn get_file_list(
             stream_name: stream_name.to_string(),
             start_time: time_range.0,
             end_time: time_range.1,
+            stream_type: stream_type.to_string(),
         };
         let grpc_span = info_span!("promql:search:grpc:wal:grpc_wal_file");
         let task: tokio::task::JoinHandle<
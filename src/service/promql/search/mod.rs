@@ -101,6 +101,7 @@ async fn search_in_cluster(req: cluster_rpc::MetricsQueryRequest) -> Result<Valu
         job: job_id,
         stage: 0,
         partition: 0,
+        user_id: "".to_string(),
     };
 
     // make cluster request
@@ -0,0 +1,163 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::{http::StatusCode, HttpResponse};
+use std::io;
+
+use crate::common::meta::http::HttpResponse as MetaHttpResponse;
+use crate::service::{
+    db::{self, rules::Rule},
+    format_stream_name,
+};
+
+/// a rule of a stream named by the user, stored at
+/// `{org_id}/{stream_name}/{name}`
+pub(crate) trait StreamRule: Rule + Clone + PartialEq {
+    /// the rule in the messages, like `Stream route`
+    const KIND: &'static str;
+
+    fn name(&self) -> &str;
+
+    /// the rule belongs to the stream, under the name
+    fn assign(&mut self, org_id: &str, stream_name: &str, name: &str);
+
+    fn check(&mut self) -> Result<(), String>;
+}
+
+fn stream_rule_key(org_id: &str, stream_name: &str, name: &str) -> String {
+    format!("{org_id}/{stream_name}/{name}")
+}
+
+pub(crate) async fn create_stream_rule<T: StreamRule>(
+    org_id: &str,
+    stream_name: &str,
+    mut rule: T,
+) -> Result<HttpResponse, io::Error> {
+    let stream_name = format_stream_name(stream_name);
+    let name = rule.name().to_string();
+    rule.assign(org_id, &stream_name, &name);
+    if let Err(e) = rule.check() {
+        return Ok(Response::BadRequest(e).into());
+    }
+    let key = stream_rule_key(org_id, &stream_name, &name);
+    match db::rules::get::<T>(&key).await {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return Ok(Response::BadRequest(format!(
+                "{} {name} already exists for stream {stream_name}",
+                T::KIND
+            ))
+            .into())
+        }
+        Err(e) => return Ok(Response::InternalServerError(e).into()),
+    }
+
+    if let Err(e) = db::rules::set(&key, &rule).await {
+        return Ok(Response::InternalServerError(e).into());
+    }
+    tracing::info!(name, "{} created", T::KIND);
+    Ok(HttpResponse::Created().json(rule))
+}
+
+pub(crate) async fn update_stream_rule<T: StreamRule>(
+    org_id: &str,
+    stream_name: &str,
+    name: &str,
+    mut rule: T,
+) -> Result<HttpResponse, io::Error> {
+    let stream_name = format_stream_name(stream_name);
+    let key = stream_rule_key(org_id, &stream_name, name);
+    let old_rule = match db::rules::get::<T>(&key).await {
+        Ok(Some(rule)) => rule,
+        Ok(None) => return Ok(Response::NotFound(T::KIND).into()),
+        Err(e) => return Ok(Response::InternalServerError(e).into()),
+    };
+    rule.assign(org_id, &stream_name, name);
+    if let Err(e) = rule.check() {
+        return Ok(Response::BadRequest(e).into());
+    }
+
+    if rule == old_rule {
+        return Ok(HttpResponse::Ok().json(rule));
+    }
+
+    if let Err(error) = db::rules::set(&key, &rule).await {
+        tracing::error!(%error, name, "Failed to save the {}", T::KIND);
+        return Ok(Response::InternalServerError(error).into());
+    }
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+pub(crate) async fn list_stream_rules<T: StreamRule>(
+    org_id: &str,
+    stream_name: &str,
+) -> Result<Vec<T>, anyhow::Error> {
+    let stream_name = format_stream_name(stream_name);
+    Ok(db::rules::list::<T>(&format!("{org_id}/{stream_name}/"))
+        .await?
+        .into_iter()
+        .map(|(_, rule)| rule)
+        .collect())
+}
+
+pub(crate) async fn delete_stream_rule<T: StreamRule>(
+    org_id: &str,
+    stream_name: &str,
+    name: &str,
+) -> Result<HttpResponse, io::Error> {
+    let stream_name = format_stream_name(stream_name);
+    let key = stream_rule_key(org_id, &stream_name, name);
+    Ok(delete_rule::<T>(&key, T::KIND).await)
+}
+
+/// delete the rule stored at the key, not found when there is none
+pub(crate) async fn delete_rule<T: Rule>(key: &str, kind: &'static str) -> HttpResponse {
+    match db::rules::get::<T>(key).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Response::NotFound(kind).into(),
+        Err(e) => return Response::InternalServerError(e).into(),
+    }
+    match db::rules::delete::<T>(key).await {
+        Ok(_) => Response::OkMessage(format!("{kind} deleted")).into(),
+        Err(e) => Response::InternalServerError(e).into(),
+    }
+}
+
+/// the answers of the rule APIs, `NotFound` holds the kind of the rule
+#[derive(Debug)]
+pub(crate) enum Response {
+    OkMessage(String),
+    NotFound(&'static str),
+    InternalServerError(anyhow::Error),
+    BadRequest(String),
+}
+
+impl From<Response> for HttpResponse {
+    fn from(resp: Response) -> Self {
+        match resp {
+            Response::OkMessage(message) => {
+                Self::Ok().json(MetaHttpResponse::message(StatusCode::OK.into(), message))
+            }
+            Response::NotFound(kind) => Self::NotFound().json(MetaHttpResponse::error(
+                StatusCode::NOT_FOUND.into(),
+                format!("{kind} not found"),
+            )),
+            Response::InternalServerError(err) => Self::InternalServerError().json(
+                MetaHttpResponse::error(StatusCode::INTERNAL_SERVER_ERROR.into(), err.to_string()),
+            ),
+            Response::BadRequest(err) => Self::BadRequest()
+                .json(MetaHttpResponse::error(StatusCode::BAD_REQUEST.into(), err)),
+        }
+    }
+}
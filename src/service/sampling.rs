@@ -0,0 +1,296 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use actix_web::HttpResponse;
+use once_cell::sync::Lazy;
+use sqlparser::ast::Expr as SqlExpr;
+use std::{io, sync::Arc};
+
+use crate::common::infra::{
+    config::{RwHashMap, STREAM_SAMPLING_RULES},
+    metrics,
+};
+use crate::common::meta::sampling::{SamplingAction, SamplingRule, SamplingRules};
+use crate::common::utils::{
+    json::{Map, Value},
+    sql_condition,
+};
+use crate::service::rules::{self, Response, StreamRule};
+
+/// the compiled rules of the streams with the rules they were compiled from
+static COMPILED_RULES: Lazy<RwHashMap<String, CompiledRules>> = Lazy::new(Default::default);
+
+impl StreamRule for SamplingRule {
+    const KIND: &'static str = "Sampling rule";
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn assign(&mut self, org_id: &str, stream_name: &str, name: &str) {
+        self.org_id = org_id.to_string();
+        self.stream_name = stream_name.to_string();
+        self.name = name.to_string();
+    }
+
+    fn check(&mut self) -> Result<(), String> {
+        check_rule(self)
+    }
+}
+
+#[tracing::instrument(skip(rule))]
+pub async fn create_rule(
+    org_id: &str,
+    stream_name: &str,
+    rule: SamplingRule,
+) -> Result<HttpResponse, io::Error> {
+    rules::create_stream_rule(org_id, stream_name, rule).await
+}
+
+#[tracing::instrument(skip(rule))]
+pub async fn update_rule(
+    org_id: &str,
+    stream_name: &str,
+    name: &str,
+    rule: SamplingRule,
+) -> Result<HttpResponse, io::Error> {
+    rules::update_stream_rule(org_id, stream_name, name, rule).await
+}
+
+#[tracing::instrument]
+pub async fn list_rules(org_id: &str, stream_name: &str) -> Result<HttpResponse, io::Error> {
+    match rules::list_stream_rules::<SamplingRule>(org_id, stream_name).await {
+        Ok(mut rules) => {
+            rules.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.name.cmp(&b.name)));
+            Ok(HttpResponse::Ok().json(SamplingRules { rules }))
+        }
+        Err(e) => Ok(Response::InternalServerError(e).into()),
+    }
+}
+
+#[tracing::instrument]
+pub async fn delete_rule(
+    org_id: &str,
+    stream_name: &str,
+    name: &str,
+) -> Result<HttpResponse, io::Error> {
+    rules::delete_stream_rule::<SamplingRule>(org_id, stream_name, name).await
+}
+
+fn check_rule(rule: &SamplingRule) -> Result<(), String> {
+    if rule.name.trim().is_empty() {
+        return Err("Please provide name for sampling rule".to_owned());
+    }
+    if rule.name.contains('/') {
+        return Err("Sampling rule name can not contain '/'".to_owned());
+    }
+    if !(0.0..=1.0).contains(&rule.sample_rate) {
+        return Err("Sample rate must be between 0 and 1".to_owned());
+    }
+    if rule
+        .sample_key
+        .as_ref()
+        .map_or(false, |v| v.trim().is_empty())
+    {
+        return Err("Sample key can not be empty".to_owned());
+    }
+    compile_rule(rule)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid sampling rule condition: {e}"))
+}
+
+struct CompiledRules {
+    source: Arc<Vec<SamplingRule>>,
+    rules: Vec<CompiledRule>,
+}
+
+struct CompiledRule {
+    name: String,
+    condition: Option<SqlExpr>,
+    action: SamplingAction,
+    sample_rate: f64,
+    sample_key: Option<String>,
+}
+
+impl CompiledRule {
+    fn matches(&self, record: &Map<String, Value>) -> bool {
+        self.condition
+            .as_ref()
+            .map_or(true, |expr| sql_condition::matches(expr, record))
+    }
+
+    fn keep(&self, record: &Map<String, Value>) -> bool {
+        match self.action {
+            SamplingAction::Keep => true,
+            SamplingAction::Drop => false,
+            SamplingAction::Sample => {
+                if self.sample_rate >= 1.0 {
+                    return true;
+                }
+                let key = self.sample_key.as_ref().and_then(|key| record.get(key));
+                let position = match key {
+                    // the hash of the key is the same for the related records
+                    Some(Value::String(v)) => hash_position(v.as_bytes()),
+                    Some(v) if !v.is_null() => hash_position(v.to_string().as_bytes()),
+                    _ => rand::random::<f64>(),
+                };
+                position < self.sample_rate
+            }
+        }
+    }
+}
+
+/// the position of the value in [0, 1)
+fn hash_position(value: &[u8]) -> f64 {
+    let hash = xxhash_rust::xxh3::xxh3_64(value);
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn compile_rule(rule: &SamplingRule) -> Result<CompiledRule, anyhow::Error> {
+    let condition = if rule.condition.trim().is_empty() {
+        None
+    } else {
+        Some(sql_condition::parse(&rule.condition)?)
+    };
+    Ok(CompiledRule {
+        name: rule.name.clone(),
+        condition,
+        action: rule.action,
+        sample_rate: rule.sample_rate,
+        sample_key: rule.sample_key.clone(),
+    })
+}
+
+/// the rules are compiled again when the cached rules of the stream change,
+/// the rules whose condition doesn't compile are skipped
+fn compile_rules(source: Arc<Vec<SamplingRule>>) -> CompiledRules {
+    let rules = source
+        .iter()
+        .filter_map(|rule| match compile_rule(rule) {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                log::error!(
+                    "sampling rule {} of {} is invalid: {e}",
+                    rule.name,
+                    rule.stream_name
+                );
+                None
+            }
+        })
+        .collect();
+    CompiledRules { source, rules }
+}
+
+/// evaluate the sampling rules of the stream on the record, returns whether
+/// the record is kept. the first matching rule decides
+pub fn keep_record(org_id: &str, stream_name: &str, record: &Map<String, Value>) -> bool {
+    let key = format!("{org_id}/{stream_name}");
+    let source = match STREAM_SAMPLING_RULES.get(&key) {
+        Some(rules) => rules.clone(),
+        None => return true,
+    };
+    let is_stale = COMPILED_RULES
+        .get(&key)
+        .map_or(true, |compiled| !Arc::ptr_eq(&compiled.source, &source));
+    if is_stale {
+        COMPILED_RULES.insert(key.clone(), compile_rules(source));
+    }
+    let Some(compiled) = COMPILED_RULES.get(&key) else {
+        return true;
+    };
+    let Some(rule) = compiled.rules.iter().find(|rule| rule.matches(record)) else {
+        return true;
+    };
+    let keep = rule.keep(record);
+    if !keep {
+        metrics::INGEST_SAMPLING_DROPPED_RECORDS
+            .with_label_values(&[org_id, stream_name, &rule.name])
+            .inc();
+    } else if rule.action == SamplingAction::Sample {
+        metrics::INGEST_SAMPLING_SAMPLED_RECORDS
+            .with_label_values(&[org_id, stream_name, &rule.name])
+            .inc();
+    }
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::utils::json;
+
+    fn rule(name: &str, condition: &str, action: SamplingAction, order: i32) -> SamplingRule {
+        SamplingRule {
+            org_id: "default".to_string(),
+            stream_name: "k8s".to_string(),
+            name: name.to_string(),
+            condition: condition.to_string(),
+            action,
+            sample_rate: 1.0,
+            sample_key: None,
+            order,
+        }
+    }
+
+    fn record(value: json::Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_keep_record() {
+        let mut debug = rule("debug", "level = 'debug'", SamplingAction::Sample, 2);
+        debug.sample_rate = 0.0;
+        let rules = vec![
+            rule("errors", "level = 'error'", SamplingAction::Keep, 0),
+            rule("health", "path = '/healthz'", SamplingAction::Drop, 1),
+            debug,
+        ];
+        STREAM_SAMPLING_RULES.insert("default/k8s".to_string(), Arc::new(rules));
+
+        let keep = |value| keep_record("default", "k8s", &record(value));
+        assert!(keep(json::json!({"level": "error", "path": "/healthz"})));
+        assert!(!keep(json::json!({"level": "info", "path": "/healthz"})));
+        assert!(!keep(json::json!({"level": "debug"})));
+        assert!(keep(json::json!({"level": "info"})));
+        assert!(keep_record("default", "other", &Map::new()));
+    }
+
+    #[test]
+    fn test_sample_key() {
+        let mut sample = rule("traces", "", SamplingAction::Sample, 0);
+        sample.sample_rate = 0.5;
+        sample.sample_key = Some("trace_id".to_string());
+        let compiled = compile_rule(&sample).unwrap();
+
+        let mut kept = 0;
+        for i in 0..1000 {
+            let first = record(json::json!({"trace_id": format!("{i:x}"), "span": 1}));
+            let second = record(json::json!({"trace_id": format!("{i:x}"), "span": 2}));
+            assert!(compiled.matches(&first));
+            let keep = compiled.keep(&first);
+            assert_eq!(keep, compiled.keep(&second));
+            kept += keep as usize;
+        }
+        assert!(kept > 400 && kept < 600);
+    }
+
+    #[test]
+    fn test_check_rule() {
+        assert!(check_rule(&rule("debug", "level = 'debug'", SamplingAction::Drop, 0)).is_ok());
+        assert!(check_rule(&rule("", "", SamplingAction::Drop, 0)).is_err());
+        assert!(check_rule(&rule("debug", "level = ", SamplingAction::Drop, 0)).is_err());
+        let mut sample = rule("debug", "", SamplingAction::Sample, 0);
+        sample.sample_rate = 2.0;
+        assert!(check_rule(&sample).is_err());
+    }
+}
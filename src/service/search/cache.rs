@@ -0,0 +1,514 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ahash::AHashMap as HashMap;
+use chrono::Duration;
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use prost::Message;
+use std::collections::BTreeMap;
+use tracing::{info_span, Instrument};
+
+use super::{cancel, server_internal_error, sql, streaming};
+use crate::common::infra::{cluster, config::CONFIG, errors::Error, file_list, metrics};
+use crate::common::meta::{
+    common::{FileKey, FileMeta},
+    sql::Sql as MetaSql,
+    StreamType,
+};
+use crate::common::utils::cancel::CancelToken;
+use crate::handler::grpc::cluster_rpc;
+
+/// search results, the responses of the nodes are kept per time bucket and a
+/// repeated search only runs over the buckets that are not cached, the cached
+/// responses are merged like the responses of more nodes so the partial
+/// aggregations of the buckets add up to the result
+static CACHE: Lazy<Mutex<ResultCache>> =
+    Lazy::new(|| Mutex::new(ResultCache::new(CONFIG.memory_cache.result_max_size)));
+
+/// a time range of the search, the cacheable ones are whole buckets
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Slice {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub cacheable: bool,
+}
+
+/// the part of a search answered by the cache and the slices left to search
+pub struct Plan {
+    key: String,
+    stream_key: String,
+    version: u64,
+    cached: Vec<cluster_rpc::SearchResponse>,
+    slices: Vec<Slice>,
+}
+
+impl Plan {
+    /// the file has data in one of the slices to search
+    pub fn matches(&self, file: &FileKey) -> bool {
+        if file.meta.min_ts == 0 || file.meta.max_ts == 0 {
+            return true;
+        }
+        self.slices
+            .iter()
+            .any(|s| file.meta.min_ts < s.end_time && file.meta.max_ts >= s.start_time)
+    }
+}
+
+struct ResultCache {
+    max_size: usize,
+    cur_size: usize,
+    entries: LruCache<String, Entry>,
+    /// bumped on every change of the older data of a stream, a bucket searched
+    /// before the change is not cached
+    versions: HashMap<String, u64>,
+}
+
+struct Entry {
+    stream_key: String,
+    /// by the start time of the bucket
+    buckets: BTreeMap<i64, Bucket>,
+}
+
+struct Bucket {
+    end_time: i64,
+    responses: Vec<cluster_rpc::SearchResponse>,
+    size: usize,
+}
+
+impl ResultCache {
+    fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            cur_size: 0,
+            entries: LruCache::unbounded(),
+            versions: HashMap::new(),
+        }
+    }
+
+    fn version(&self, stream_key: &str) -> u64 {
+        self.versions.get(stream_key).copied().unwrap_or_default()
+    }
+
+    fn get(&mut self, key: &str, slice: &Slice) -> Option<Vec<cluster_rpc::SearchResponse>> {
+        let bucket = self.entries.get(key)?.buckets.get(&slice.start_time)?;
+        if bucket.end_time != slice.end_time {
+            return None;
+        }
+        Some(bucket.responses.clone())
+    }
+
+    fn set(&mut self, key: &str, stream_key: &str, slice: &Slice, bucket: Bucket) {
+        if bucket.size > self.max_size {
+            return;
+        }
+        // release the least recently used searches
+        while self.cur_size + bucket.size > self.max_size {
+            let Some((_, entry)) = self.entries.pop_lru() else {
+                break;
+            };
+            self.cur_size -= entry.buckets.values().map(|b| b.size).sum::<usize>();
+        }
+        let entry = self.entries.get_or_insert_mut(key.to_string(), || Entry {
+            stream_key: stream_key.to_string(),
+            buckets: BTreeMap::new(),
+        });
+        self.cur_size += bucket.size;
+        if let Some(old) = entry.buckets.insert(slice.start_time, bucket) {
+            self.cur_size -= old.size;
+        }
+    }
+
+    /// drop the buckets of the stream overlapping the time range, all of
+    /// them when the time range is unknown
+    fn invalidate(&mut self, stream_key: &str, min_ts: i64, max_ts: i64) {
+        *self.versions.entry(stream_key.to_string()).or_default() += 1;
+        let unknown = min_ts == 0 || max_ts == 0;
+        let mut released = 0;
+        for (_, entry) in self.entries.iter_mut() {
+            if entry.stream_key != stream_key {
+                continue;
+            }
+            entry.buckets.retain(|start_time, bucket| {
+                let keep = !unknown && (bucket.end_time <= min_ts || *start_time > max_ts);
+                if !keep {
+                    released += bucket.size;
+                }
+                keep
+            });
+        }
+        self.cur_size -= released;
+    }
+}
+
+/// look up the buckets of the search in the cache, returns `None` when the
+/// search can't be cached or has no whole bucket
+pub fn plan(
+    req: &mut cluster_rpc::SearchRequest,
+    sql: &sql::Sql,
+    stream_type: StreamType,
+    time_min: i64,
+    time_max: i64,
+) -> Option<Plan> {
+    if !CONFIG.memory_cache.result_enabled || CONFIG.memory_cache.result_max_size == 0 {
+        return None;
+    }
+    let query = req.query.as_ref()?;
+    // a new version of a document of a stream with `upsert_by_id` changes the
+    // buckets already cached
+    if sql.upsert_by_id {
+        return None;
+    }
+    // a time range in the sql overrides the request, the buckets don't apply,
+    // a join isn't cached as the buckets only follow the source stream
+    let meta = MetaSql::new(&query.sql).ok()?;
+    if meta.time_range.map_or(false, |v| v != (0, 0)) || !meta.joins.is_empty() {
+        return None;
+    }
+    let slices = split(time_min, time_max, cacheable_end());
+    if !slices.iter().any(|s| s.cacheable) {
+        return None;
+    }
+    // the slices use the histogram intervals of the whole time range, the
+    // buckets are kept for the pinned query
+    let mut pinned = req.clone();
+    let pinned_query = pinned.query.as_mut()?;
+    pinned_query.sql = sql::pin_histogram_interval(&pinned_query.sql, sql.meta.time_range);
+    for agg in pinned.aggs.iter_mut() {
+        agg.sql = sql::pin_histogram_interval(&agg.sql, sql.meta.time_range);
+    }
+    let rewritten = pinned != *req;
+
+    let stream_key = format!("{}/{}/{}", sql.org_id, stream_type, sql.stream_name);
+    let key = format!("{stream_key}/{}", query_key(&pinned));
+    let mut cache = CACHE.lock();
+    let version = cache.version(&stream_key);
+    let mut cached = Vec::new();
+    let mut todo = Vec::new();
+    let (mut hits, mut misses) = (0, 0);
+    for slice in slices {
+        if slice.cacheable {
+            if let Some(responses) = cache.get(&key, &slice) {
+                cached.extend(responses);
+                hits += 1;
+                continue;
+            }
+            misses += 1;
+        }
+        todo.push(slice);
+    }
+    drop(cache);
+
+    // the query is only rewritten to use the cached buckets, without any the
+    // search runs as it is
+    if rewritten && hits == 0 {
+        return None;
+    }
+    if rewritten {
+        *req = pinned;
+    }
+
+    let stream_type = stream_type.to_string();
+    let labels = [
+        sql.org_id.as_str(),
+        sql.stream_name.as_str(),
+        stream_type.as_str(),
+    ];
+    metrics::QUERY_RESULT_CACHE_HITS
+        .with_label_values(&labels)
+        .inc_by(hits);
+    metrics::QUERY_RESULT_CACHE_MISSES
+        .with_label_values(&labels)
+        .inc_by(misses);
+    log::info!(
+        "search->cache: key: {key}, cached buckets: {hits}, slices to search: {}",
+        todo.len()
+    );
+
+    Some(Plan {
+        key,
+        stream_key,
+        version,
+        cached,
+        slices: todo,
+    })
+}
+
+/// search the slices of the plan on the nodes and cache the whole buckets,
+/// returns the responses of the cached and the searched slices
+pub async fn search(
+    plan: Plan,
+    requests: Vec<(cluster::Node, cluster_rpc::SearchRequest)>,
+    token: &CancelToken,
+) -> Result<Vec<cluster_rpc::SearchResponse>, Error> {
+    let Plan {
+        key,
+        stream_key,
+        version,
+        cached: mut responses,
+        slices,
+    } = plan;
+    if slices.is_empty() {
+        return Ok(responses);
+    }
+
+    let slices_req = slices
+        .iter()
+        .map(|s| cluster_rpc::TimeRange {
+            start_time: s.start_time,
+            end_time: s.end_time,
+        })
+        .collect::<Vec<_>>();
+    let mut tasks = Vec::with_capacity(requests.len());
+    for (node, req) in requests {
+        let req = cluster_rpc::SearchStreamRequest {
+            request: Some(req),
+            slices: slices_req.clone(),
+        };
+        let grpc_span = info_span!("service:search:cache:grpc_search", node_id = node.id);
+        let task = tokio::task::spawn(
+            async move {
+                let mut stream =
+                    streaming::search_node_stream(&node, req, CONFIG.grpc.timeout).await?;
+                let mut responses = Vec::new();
+                while let Some(resp) = stream.message().await.map_err(|err| {
+                    log::error!("search->cache: node: {}, err: {:?}", node.id, err);
+                    streaming::grpc_status_error(err)
+                })? {
+                    responses.push(resp);
+                }
+                Ok(responses)
+            }
+            .instrument(grpc_span),
+        );
+        tasks.push(task);
+    }
+    let results = cancel::join_all(tasks, token).await?;
+
+    // every node sent one response per slice in order
+    let mut results = results.into_iter().map(Vec::into_iter).collect::<Vec<_>>();
+    for slice in slices.iter() {
+        let slice_responses = results
+            .iter_mut()
+            .map(|r| r.next())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| server_internal_error("search node stream closed"))?;
+        if slice.cacheable {
+            set(&key, &stream_key, version, slice, &slice_responses);
+        }
+        responses.extend(slice_responses);
+    }
+    Ok(responses)
+}
+
+fn set(
+    key: &str,
+    stream_key: &str,
+    version: u64,
+    slice: &Slice,
+    responses: &[cluster_rpc::SearchResponse],
+) {
+    // a cached bucket doesn't scan anything when it is used again
+    let responses = responses
+        .iter()
+        .map(|resp| cluster_rpc::SearchResponse {
+            job: None,
+            took: 0,
+            scan_stats: Some(cluster_rpc::ScanStats::default()),
+            ..resp.clone()
+        })
+        .collect::<Vec<_>>();
+    let size = responses.iter().map(|resp| resp.encoded_len()).sum();
+    let mut cache = CACHE.lock();
+    // the data changed while searching, the bucket may be stale
+    if cache.version(stream_key) != version {
+        return;
+    }
+    let bucket = Bucket {
+        end_time: slice.end_time,
+        responses,
+        size,
+    };
+    cache.set(key, stream_key, slice, bucket);
+    metrics::QUERY_RESULT_CACHE_USED_BYTES
+        .with_label_values(&[])
+        .set(cache.cur_size as i64);
+}
+
+/// drop the cached buckets covering the data of a file that was added or
+/// deleted, e.g. by the compactor or the retention
+pub fn invalidate(key: &str, meta: &FileMeta) {
+    if !CONFIG.memory_cache.result_enabled {
+        return;
+    }
+    // the newer data is never cached
+    if meta.min_ts > 0 && meta.min_ts >= cacheable_end() {
+        return;
+    }
+    let Ok((stream_key, ..)) = file_list::parse_file_key_columns(key) else {
+        return;
+    };
+    let mut cache = CACHE.lock();
+    cache.invalidate(&stream_key, meta.min_ts, meta.max_ts);
+    metrics::QUERY_RESULT_CACHE_USED_BYTES
+        .with_label_values(&[])
+        .set(cache.cur_size as i64);
+}
+
+/// the wal holds data up to `ingest_allowed_upto` older than its files, which
+/// are kept up to the file retention and push interval, the data older than
+/// that is only changed through the file list, so the buckets ending before
+/// it can be cached
+fn cacheable_end() -> i64 {
+    let delay = Duration::hours(CONFIG.limit.ingest_allowed_upto)
+        + Duration::seconds(CONFIG.limit.max_file_retention_time as i64)
+        + Duration::seconds(CONFIG.limit.file_push_interval as i64);
+    chrono::Utc::now().timestamp_micros() - delay.num_microseconds().unwrap()
+}
+
+/// hourly buckets for up to two days, daily buckets for longer searches
+fn bucket_size(start: i64, end: i64) -> i64 {
+    let day = Duration::days(1).num_microseconds().unwrap();
+    if end - start > 2 * day {
+        day
+    } else {
+        Duration::hours(1).num_microseconds().unwrap()
+    }
+}
+
+/// split the time range into the whole buckets ending before `cacheable_end`
+/// and the partial ranges at both ends, ordered by time
+fn split(start: i64, end: i64, cacheable_end: i64) -> Vec<Slice> {
+    let size = bucket_size(start, end);
+    let mut first = start.div_euclid(size) * size;
+    if first < start {
+        first += size;
+    }
+    let last = end.min(cacheable_end).div_euclid(size) * size;
+    if first >= last {
+        return vec![];
+    }
+
+    let mut slices = Vec::new();
+    if start < first {
+        slices.push(Slice {
+            start_time: start,
+            end_time: first,
+            cacheable: false,
+        });
+    }
+    let mut bucket = first;
+    while bucket < last {
+        slices.push(Slice {
+            start_time: bucket,
+            end_time: bucket + size,
+            cacheable: true,
+        });
+        bucket += size;
+    }
+    if last < end {
+        slices.push(Slice {
+            start_time: last,
+            end_time: end,
+            cacheable: false,
+        });
+    }
+    slices
+}
+
+/// the search without its time range, equal searches share the buckets
+fn query_key(req: &cluster_rpc::SearchRequest) -> String {
+    let mut req = req.clone();
+    req.job = None;
+    req.file_list.clear();
+    req.joins.clear();
+    req.aggs.sort_by(|a, b| a.name.cmp(&b.name));
+    if let Some(query) = req.query.as_mut() {
+        query.sql = query.sql.split_whitespace().collect::<Vec<_>>().join(" ");
+        query.start_time = 0;
+        query.end_time = 0;
+    }
+    blake3::hash(&req.encode_to_vec()).to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let hour = Duration::hours(1).num_microseconds().unwrap();
+        let slices = split(10 * hour + 5, 14 * hour + 5, 13 * hour + 10);
+        assert_eq!(
+            slices
+                .iter()
+                .map(|s| (s.start_time, s.end_time, s.cacheable))
+                .collect::<Vec<_>>(),
+            vec![
+                (10 * hour + 5, 11 * hour, false),
+                (11 * hour, 12 * hour, true),
+                (12 * hour, 13 * hour, true),
+                (13 * hour, 14 * hour + 5, false),
+            ]
+        );
+        // no whole bucket
+        assert!(split(10 * hour + 5, 11 * hour + 5, 20 * hour).is_empty());
+        assert!(split(10 * hour, 14 * hour, 10 * hour).is_empty());
+
+        let day = Duration::days(1).num_microseconds().unwrap();
+        let slices = split(0, 7 * day, 7 * day);
+        assert_eq!(slices.len(), 7);
+        assert!(slices.iter().all(|s| s.cacheable));
+    }
+
+    #[test]
+    fn test_result_cache() {
+        let resp = cluster_rpc::SearchResponse {
+            hits: vec![0; 100],
+            ..Default::default()
+        };
+        let slice = |start_time, end_time| Slice {
+            start_time,
+            end_time,
+            cacheable: true,
+        };
+        let bucket = || Bucket {
+            end_time: 20,
+            responses: vec![resp.clone()],
+            size: 100,
+        };
+        let mut cache = ResultCache::new(250);
+        cache.set("org/logs/a/q1", "org/logs/a", &slice(10, 20), bucket());
+        cache.set("org/logs/b/q1", "org/logs/b", &slice(10, 20), bucket());
+        assert!(cache.get("org/logs/a/q1", &slice(10, 20)).is_some());
+        assert!(cache.get("org/logs/a/q1", &slice(10, 30)).is_none());
+
+        // the least recently used search is released
+        cache.set("org/logs/c/q1", "org/logs/c", &slice(10, 20), bucket());
+        assert!(cache.get("org/logs/b/q1", &slice(10, 20)).is_none());
+        assert_eq!(cache.cur_size, 200);
+
+        // only the overlapping buckets of the stream are dropped
+        cache.invalidate("org/logs/a", 20, 30);
+        assert!(cache.get("org/logs/a/q1", &slice(10, 20)).is_some());
+        cache.invalidate("org/logs/a", 15, 16);
+        assert!(cache.get("org/logs/a/q1", &slice(10, 20)).is_none());
+        assert!(cache.get("org/logs/c/q1", &slice(10, 20)).is_some());
+        assert_eq!(cache.cur_size, 100);
+        assert_eq!(cache.version("org/logs/a"), 2);
+        cache.invalidate("org/logs/c", 0, 0);
+        assert_eq!(cache.cur_size, 0);
+    }
+}
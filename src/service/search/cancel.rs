@@ -0,0 +1,195 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ahash::AHashMap as HashMap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::common::{
+    infra::errors::{Error, ErrorCodes},
+    utils::cancel::{cancelled_error, CancelToken},
+};
+
+/// searches running on this node by session id
+static SESSIONS: Lazy<Mutex<HashMap<String, Vec<Registration>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+struct Registration {
+    id: u64,
+    org_id: String,
+    /// the owner of the session, empty for the internal searches
+    user_id: String,
+    token: CancelToken,
+}
+
+/// Registration of a search on this node, dropping the guard cancels the
+/// search, so a search is stopped when the request driving it is dropped.
+pub struct CancelGuard {
+    id: u64,
+    session_id: String,
+    token: CancelToken,
+}
+
+impl CancelGuard {
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.token.cancel();
+        let mut sessions = SESSIONS.lock();
+        if let Some(list) = sessions.get_mut(&self.session_id) {
+            list.retain(|r| r.id != self.id);
+            if list.is_empty() {
+                sessions.remove(&self.session_id);
+            }
+        }
+    }
+}
+
+/// register a search of the session, a session can run several searches on
+/// the same node, e.g. the leader and the querier part of a search
+pub fn register(org_id: &str, user_id: &str, session_id: &str) -> CancelGuard {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let token = CancelToken::default();
+    SESSIONS
+        .lock()
+        .entry(session_id.to_string())
+        .or_default()
+        .push(Registration {
+            id,
+            org_id: org_id.to_string(),
+            user_id: user_id.to_string(),
+            token: token.clone(),
+        });
+    CancelGuard {
+        id,
+        session_id: session_id.to_string(),
+        token,
+    }
+}
+
+/// cancel the searches of the session running on this node, only the ones
+/// owned by `user_id` when it is given, returns false if there is none
+pub fn cancel(org_id: &str, session_id: &str, user_id: Option<&str>) -> bool {
+    let sessions = SESSIONS.lock();
+    let Some(list) = sessions.get(session_id) else {
+        return false;
+    };
+    let mut found = false;
+    for r in list
+        .iter()
+        .filter(|r| r.org_id == org_id && user_id.map_or(true, |v| r.user_id == v))
+    {
+        r.token.cancel();
+        found = true;
+    }
+    found
+}
+
+/// Aborts the tasks on drop.
+pub struct AbortOnDrop(Vec<AbortHandle>);
+
+impl AbortOnDrop {
+    pub fn new<T>(tasks: &[JoinHandle<T>]) -> Self {
+        Self(tasks.iter().map(|t| t.abort_handle()).collect())
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for handle in self.0.iter() {
+            handle.abort();
+        }
+    }
+}
+
+/// wait for the node tasks in order, the tasks still running are aborted when
+/// a task fails or the search is cancelled
+pub async fn join_all<T>(
+    tasks: Vec<JoinHandle<Result<T, Error>>>,
+    token: &CancelToken,
+) -> Result<Vec<T>, Error> {
+    let mut abort = AbortOnDrop::new(&tasks);
+    let join = async move {
+        let mut results = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            let result = task
+                .await
+                .map_err(|e| Error::ErrorCode(ErrorCodes::ServerInternalError(e.to_string())))?;
+            results.push(result?);
+        }
+        Ok(results)
+    };
+    let results = tokio::select! {
+        ret = join => ret?,
+        _ = token.cancelled() => return Err(cancelled_error()),
+    };
+    // all done, nothing to abort
+    abort.0.clear();
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancel_session() {
+        let guard = register("org1", "user1", "session1");
+        let token = guard.token();
+        let leader = register("org1", "user1", "session1");
+        assert!(!cancel("org2", "session1", None));
+        // only the owner or an admin cancels the session
+        assert!(!cancel("org1", "session1", Some("user2")));
+        assert!(!token.is_cancelled());
+
+        let waiter = tokio::spawn({
+            let token = token.clone();
+            async move { token.cancelled().await }
+        });
+        assert!(cancel("org1", "session1", Some("user1")));
+        waiter.await.unwrap();
+        assert!(token.check().is_err());
+        assert!(leader.token().is_cancelled());
+
+        drop(guard);
+        drop(leader);
+        assert!(!cancel("org1", "session1", None));
+    }
+
+    #[tokio::test]
+    async fn test_join_all_cancelled() {
+        let token = CancelToken::default();
+        let tasks = vec![
+            tokio::spawn(async { Ok::<_, Error>(1) }),
+            tokio::spawn(async {
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                Ok(2)
+            }),
+        ];
+        token.cancel();
+        let err = join_all(tasks, &token).await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::ErrorCode(ErrorCodes::SearchCancelled(_))
+        ));
+    }
+}
@@ -20,10 +20,10 @@ use datafusion::{
         json as arrowJson,
         record_batch::RecordBatch,
     },
-    common::{FileType, GetExt},
+    common::{Column, FileType, GetExt},
     config::ConfigOptions,
     datasource::{
-        file_format::{json::JsonFormat, parquet::ParquetFormat},
+        file_format::{json::JsonFormat, parquet::ParquetFormat, FileFormat},
         listing::{ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl},
         object_store::{DefaultObjectStoreRegistry, ObjectStoreRegistry},
     },
@@ -34,7 +34,7 @@ use datafusion::{
         runtime_env::{RuntimeConfig, RuntimeEnv},
     },
     logical_expr::expr::Alias,
-    prelude::{cast, col, lit, Expr, SessionContext},
+    prelude::{cast, col, lit, DataFrame, Expr, SessionContext},
     scalar::ScalarValue,
 };
 use once_cell::sync::Lazy;
@@ -44,18 +44,30 @@ use std::sync::Arc;
 
 use crate::common::infra::{
     cache::tmpfs,
-    config::{CONFIG, PARQUET_BATCH_SIZE},
+    config::{RwHashMap, CONFIG, PARQUET_BATCH_SIZE},
 };
 use crate::common::meta::{
     common::{FileKey, FileMeta},
     search::Session as SearchSession,
     sql,
+    stream::{DELETED_FIELD, ID_FIELD, VERSION_FIELD},
 };
 use crate::common::utils::{flatten, json};
-use crate::service::search::sql::Sql;
+use crate::service::{
+    db,
+    search::sql::{Sql, STREAM_COLUMN},
+};
 
+use super::row_group_format::RowGroupParquetFormat;
 use super::storage::{file_list, StorageType};
 use super::transform_udf::get_all_transform;
+use super::version_udf;
+
+/// error message of a cancelled execution
+pub const SEARCH_CANCELLED: &str = "search cancelled";
+
+/// rank of the versions of an `_id`, the latest is 1
+const ID_RANK_COLUMN: &str = "__id_rank";
 
 const AGGREGATE_UDF_LIST: [&str; 6] = ["min", "max", "count", "avg", "sum", "array_agg"];
 
@@ -74,9 +86,32 @@ pub async fn sql(
     if files.is_empty() {
         return Ok(HashMap::new());
     }
+    let ret = exec_sql(session, schema, rules, sql, files, file_type).await;
+    // the wal files of the joined streams are kept in tmpfs for the query
+    for join in sql.joins.iter() {
+        if !join.wal_files.is_empty() {
+            tmpfs::delete(&join_wal_session_id(session, &join.table), true).unwrap();
+        }
+    }
+    ret
+}
 
+async fn exec_sql(
+    session: &SearchSession,
+    schema: Arc<Schema>,
+    rules: &HashMap<String, DataType>,
+    sql: &Arc<Sql>,
+    files: &[FileKey],
+    file_type: FileType,
+) -> Result<HashMap<String, Vec<RecordBatch>>> {
     let start = std::time::Instant::now();
     let mut ctx = register_table(session, schema.clone(), "tbl", files, file_type.clone()).await?;
+    if sql.upsert_by_id {
+        let versions = db::doc_version::latest(&sql.org_id, &sql.stream_name);
+        register_id_view(&ctx, versions, false).await?;
+    }
+    register_stream_view(&ctx, sql).await?;
+    register_join_tables(&ctx, session, sql).await?;
 
     // register UDF
     register_udf(&mut ctx, &sql.org_id).await;
@@ -145,7 +180,7 @@ pub async fn sql(
             }
             df = df.select(exprs)?;
         }
-        let batches = df.collect().await?;
+        let batches = collect(df, session).await?;
         result.insert(format!("agg_{name}"), batches);
         log::info!(
             "Query agg:{name} took {:.3} seconds.",
@@ -155,6 +190,13 @@ pub async fn sql(
 
     // drop table
     ctx.deregister_table("tbl")?;
+    for join in sql.joins.iter() {
+        ctx.deregister_table(join.table.as_str())?;
+        if !join.wal_files.is_empty() {
+            ctx.deregister_table(format!("{}_files", join.table).as_str())?;
+            ctx.deregister_table(format!("{}_wal", join.table).as_str())?;
+        }
+    }
     log::info!(
         "Query all took {:.3} seconds.",
         start.elapsed().as_secs_f64()
@@ -185,9 +227,12 @@ async fn exec_query(
     // get used UDF
     let mut field_fns = vec![];
     let mut sql_parts = vec![];
-    for fn_name in crate::common::utils::functions::get_all_transform_keys(&sql.org_id).await {
-        if sql.origin_sql.contains(&fn_name) {
-            field_fns.push(fn_name.clone());
+    // a join calls the UDF directly, the rewrite only reads from `tbl`
+    if sql.joins.is_empty() {
+        for fn_name in crate::common::utils::functions::get_all_transform_keys(&sql.org_id).await {
+            if sql.origin_sql.contains(&fn_name) {
+                field_fns.push(fn_name.clone());
+            }
         }
     }
 
@@ -262,7 +307,7 @@ async fn exec_query(
     }
 
     if field_fns.is_empty() && sql.query_fn.is_none() {
-        let batches = df.clone().collect().await?;
+        let batches = collect(df, session).await?;
         log::info!("Query took {:.3} seconds.", start.elapsed().as_secs_f64());
         return Ok(batches);
     }
@@ -276,7 +321,7 @@ async fn exec_query(
             ctx.register_table("tbl", df.into_view())?;
         }
     } else if sql.query_fn.is_some() {
-        let batches = df.collect().await?;
+        let batches = collect(df, session).await?;
         let batches_ref: Vec<&RecordBatch> = batches.iter().collect();
         match handle_query_fn(sql.query_fn.clone().unwrap(), &batches_ref, &sql.org_id) {
             None => {
@@ -350,11 +395,22 @@ async fn exec_query(
             return Err(e);
         }
     };
-    let batches = df.clone().collect().await?;
+    let batches = collect(df, session).await?;
     log::info!("Query took {:.3} seconds.", start.elapsed().as_secs_f64());
     Ok(batches)
 }
 
+/// execute the query, the execution is dropped as soon as the search is
+/// cancelled
+async fn collect(df: DataFrame, session: &SearchSession) -> Result<Vec<RecordBatch>> {
+    tokio::select! {
+        ret = df.collect() => ret,
+        _ = session.cancel.cancelled() => Err(DataFusionError::Execution(
+            SEARCH_CANCELLED.to_string(),
+        )),
+    }
+}
+
 async fn get_fast_mode_ctx(
     session: &SearchSession,
     schema: Arc<Schema>,
@@ -379,9 +435,15 @@ async fn get_fast_mode_ctx(
     let fast_session = SearchSession {
         id: format!("{}-fast", session.id),
         storage_type: session.storage_type.clone(),
+        cancel: session.cancel.clone(),
     };
     let mut ctx =
         register_table(&fast_session, schema.clone(), "tbl", &new_files, file_type).await?;
+    if sql.upsert_by_id {
+        let versions = db::doc_version::latest(&sql.org_id, &sql.stream_name);
+        register_id_view(&ctx, versions, false).await?;
+    }
+    register_stream_view(&ctx, sql).await?;
 
     // register UDF
     register_udf(&mut ctx, &sql.org_id).await;
@@ -864,10 +926,13 @@ pub async fn convert_parquet_file(
     Ok(())
 }
 
+/// the files of a stream with `upsert_by_id` keep the latest version of every
+/// `_id` given its `versions`, including the tombstones
 pub async fn merge_parquet_files(
     session_id: &str,
     buf: &mut Vec<u8>,
     schema: Arc<Schema>,
+    versions: Option<Arc<RwHashMap<String, i64>>>,
 ) -> Result<FileMeta> {
     let start = std::time::Instant::now();
     // query data
@@ -887,6 +952,9 @@ pub async fn merge_parquet_files(
 
     let table = ListingTable::try_new(config)?;
     ctx.register_table("tbl", Arc::new(table))?;
+    if let Some(versions) = versions {
+        register_id_view(&ctx, versions, true).await?;
+    }
 
     // get meta data
     let meta_sql = format!(
@@ -898,7 +966,9 @@ pub async fn merge_parquet_files(
     let batches_ref: Vec<&RecordBatch> = batches.iter().collect();
     let result = arrowJson::writer::record_batches_to_json_rows(&batches_ref).unwrap();
     let record = result.first().unwrap();
-    let file_meta = if record.is_empty() {
+    // the versions of the merged files of a stream with `upsert_by_id` may all
+    // be older than the ones of other files
+    let file_meta = if record.is_empty() || record["num_records"].as_i64() == Some(0) {
         FileMeta::default()
     } else {
         FileMeta {
@@ -907,6 +977,8 @@ pub async fn merge_parquet_files(
             records: record["num_records"].as_i64().unwrap(),
             original_size: 0,
             compressed_size: 0,
+            index_size: 0,
+            stats_size: 0,
         }
     };
 
@@ -1013,11 +1085,163 @@ pub async fn register_table(
     file_type: FileType,
 ) -> Result<SessionContext> {
     let ctx = prepare_datafusion_context()?;
+    register_listing_table(&ctx, session, schema, table_name, files, file_type).await?;
+    Ok(ctx)
+}
+
+/// a multi-stream search reads the stream with the schema merged from all the
+/// streams, plus the column naming the stream
+async fn register_stream_view(ctx: &SessionContext, sql: &Sql) -> Result<()> {
+    let Some(streams_schema) = sql.streams_schema.as_ref() else {
+        return Ok(());
+    };
+    let df = ctx.table("tbl").await?;
+    let mut exprs = Vec::with_capacity(streams_schema.fields().len() + 1);
+    for field in streams_schema.fields() {
+        let column = Expr::Column(Column::from_name(field.name()));
+        let expr = match df.schema().field_with_unqualified_name(field.name()) {
+            Ok(f) if f.data_type() == field.data_type() => column,
+            Ok(_) => cast(column, field.data_type().clone()),
+            Err(_) => cast(lit(ScalarValue::Null), field.data_type().clone()),
+        };
+        exprs.push(Expr::Alias(Alias::new(expr, field.name().to_string())));
+    }
+    exprs.push(Expr::Alias(Alias::new(
+        lit(sql.stream_name.as_str()),
+        STREAM_COLUMN.to_string(),
+    )));
+    let df = df.select(exprs)?;
+    ctx.deregister_table("tbl")?;
+    ctx.register_table("tbl", df.into_view())?;
+    Ok(())
+}
+
+/// a stream with `upsert_by_id` is read with the latest version of every
+/// `_id` given the `versions` of the stream, the rows without `_id` are all
+/// kept. the compaction keeps the tombstones, and the latest version of the
+/// `_id`s of the merged files missing from the `versions` when they failed to
+/// be saved.
+async fn register_id_view(
+    ctx: &SessionContext,
+    versions: Arc<RwHashMap<String, i64>>,
+    compact: bool,
+) -> Result<()> {
+    let df = ctx.table("tbl").await?;
+    let schema = df.schema().clone();
+    if schema.field_with_unqualified_name(ID_FIELD).is_err()
+        || schema.field_with_unqualified_name(VERSION_FIELD).is_err()
+    {
+        return Ok(()); // no document with an id yet
+    }
+    let id = Expr::Column(Column::from_name(ID_FIELD));
+    let version = Expr::Column(Column::from_name(VERSION_FIELD));
+    let latest = version_udf::latest_version_udf(versions).call(vec![id.clone(), version]);
+    let mut filter = id.is_null().or(latest);
+    if !compact && schema.field_with_unqualified_name(DELETED_FIELD).is_ok() {
+        filter = filter.and(Expr::Column(Column::from_name(DELETED_FIELD)).is_not_true());
+    }
+    let df = df.filter(filter)?;
+    ctx.deregister_table("tbl")?;
+    ctx.register_table("tbl", df.into_view())?;
+    if !compact {
+        return Ok(());
+    }
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|f| format!("\"{}\"", f.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // the documents ingested before the setting have no version
+    let query = format!(
+        "SELECT {columns} FROM (SELECT *, ROW_NUMBER() OVER (PARTITION BY \"{ID_FIELD}\" ORDER BY \"{VERSION_FIELD}\" DESC NULLS LAST) AS {ID_RANK_COLUMN} FROM tbl) WHERE (\"{ID_FIELD}\" IS NULL OR {ID_RANK_COLUMN} = 1)"
+    );
+    let df = ctx.sql(&query).await?;
+    ctx.deregister_table("tbl")?;
+    ctx.register_table("tbl", df.into_view())?;
+    Ok(())
+}
+
+/// register the streams joined in the query, the nodes read the whole joined
+/// streams from the cache or the object storage, also when searching the wal,
+/// and the wal files of the joined streams sent with the request
+async fn register_join_tables(
+    ctx: &SessionContext,
+    session: &SearchSession,
+    sql: &Sql,
+) -> Result<()> {
+    for join in sql.joins.iter() {
+        let schema = Arc::new(join.schema.clone());
+        let join_session = SearchSession {
+            id: format!("{}-{}", session.id, join.table),
+            storage_type: StorageType::FsMemory,
+            cancel: session.cancel.clone(),
+        };
+        if join.wal_files.is_empty() {
+            register_listing_table(
+                ctx,
+                &join_session,
+                schema,
+                &join.table,
+                &join.files,
+                FileType::PARQUET,
+            )
+            .await?;
+            continue;
+        }
+
+        let files_table = format!("{}_files", join.table);
+        register_listing_table(
+            ctx,
+            &join_session,
+            schema.clone(),
+            &files_table,
+            &join.files,
+            FileType::PARQUET,
+        )
+        .await?;
+        let wal_session = SearchSession {
+            id: join_wal_session_id(session, &join.table),
+            storage_type: StorageType::Tmpfs,
+            cancel: session.cancel.clone(),
+        };
+        for file in join.wal_files.iter() {
+            let file_name = format!("/{}/{}", wal_session.id, file.name);
+            tmpfs::set(&file_name, file.body.clone().into()).expect("tmpfs set success");
+        }
+        let wal_table = format!("{}_wal", join.table);
+        register_listing_table(ctx, &wal_session, schema, &wal_table, &[], FileType::JSON).await?;
+        let df = ctx
+            .sql(&format!(
+                "SELECT * FROM {files_table} UNION ALL SELECT * FROM {wal_table}"
+            ))
+            .await?;
+        ctx.register_table(join.table.as_str(), df.into_view())?;
+    }
+    Ok(())
+}
+
+fn join_wal_session_id(session: &SearchSession, table: &str) -> String {
+    format!("{}-{table}-wal", session.id)
+}
+
+async fn register_listing_table(
+    ctx: &SessionContext,
+    session: &SearchSession,
+    schema: Arc<Schema>,
+    table_name: &str,
+    files: &[FileKey],
+    file_type: FileType,
+) -> Result<()> {
     // Configure listing options
     let listing_options = match file_type {
         FileType::PARQUET => {
-            let file_format = ParquetFormat::default().with_enable_pruning(Some(false));
-            ListingOptions::new(Arc::new(file_format))
+            let file_format: Arc<dyn FileFormat> = match file_list::get_row_groups(&session.id) {
+                Some(row_groups) => Arc::new(RowGroupParquetFormat::new(row_groups)),
+                None => Arc::new(ParquetFormat::default().with_enable_pruning(Some(false))),
+            };
+            ListingOptions::new(file_format)
                 .with_file_extension(FileType::PARQUET.get_ext())
                 .with_target_partitions(CONFIG.limit.cpu_num)
         }
@@ -1062,7 +1286,7 @@ pub async fn register_table(
     let table = ListingTable::try_new(config)?;
     ctx.register_table(table_name, Arc::new(table))?;
 
-    Ok(ctx)
+    Ok(())
 }
 
 fn handle_query_fn(
@@ -1188,4 +1412,83 @@ mod test {
 
         assert!(!res.is_empty())
     }
+
+    #[actix_web::test]
+    async fn test_register_id_view() {
+        use arrow::array::{BooleanArray, Int64Array, StringArray};
+        use datafusion::datasource::MemTable;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(ID_FIELD, DataType::Utf8, true),
+            Field::new(VERSION_FIELD, DataType::Int64, true),
+            Field::new(DELETED_FIELD, DataType::Boolean, true),
+            Field::new("f", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("a"),
+                    Some("b"),
+                    Some("b"),
+                    None,
+                    None,
+                    Some("c"),
+                    Some("c"),
+                ])),
+                Arc::new(Int64Array::from(vec![
+                    Some(1),
+                    Some(2),
+                    Some(1),
+                    Some(2),
+                    None,
+                    None,
+                    Some(1),
+                    Some(2),
+                ])),
+                Arc::new(BooleanArray::from(vec![
+                    None,
+                    None,
+                    None,
+                    Some(true),
+                    None,
+                    None,
+                    None,
+                    None,
+                ])),
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4, 5, 6, 7, 8])),
+            ],
+        )
+        .unwrap();
+        // the versions of c failed to be saved
+        let versions: Arc<RwHashMap<String, i64>> = Default::default();
+        versions.insert("a".to_string(), 2);
+        versions.insert("b".to_string(), 2);
+        async fn rows(
+            batch: RecordBatch,
+            versions: Arc<RwHashMap<String, i64>>,
+            compact: bool,
+        ) -> Vec<i64> {
+            let table = MemTable::try_new(batch.schema(), vec![vec![batch]]).unwrap();
+            let ctx = SessionContext::new();
+            ctx.register_table("tbl", Arc::new(table)).unwrap();
+            register_id_view(&ctx, versions, compact).await.unwrap();
+            let df = ctx.sql("SELECT f FROM tbl ORDER BY f").await.unwrap();
+            let batches = df.collect().await.unwrap();
+            let batches = batches.iter().collect::<Vec<_>>();
+            arrowJson::writer::record_batches_to_json_rows(&batches)
+                .unwrap()
+                .into_iter()
+                .map(|row| row["f"].as_i64().unwrap())
+                .collect()
+        }
+        // the latest version of a, b is deleted, the rows without _id are kept
+        assert_eq!(
+            rows(batch.clone(), versions.clone(), false).await,
+            vec![2, 5, 6, 7, 8]
+        );
+        // the compaction keeps the tombstone and the latest version of c
+        assert_eq!(rows(batch, versions, true).await, vec![2, 4, 5, 6, 8]);
+    }
 }
@@ -26,10 +26,12 @@ mod date_format_udf;
 pub mod exec;
 pub mod match_udf;
 pub mod regexp_udf;
+mod row_group_format;
 pub mod storage;
 mod time_range_udf;
 
 mod transform_udf;
+mod version_udf;
 
 /// The name of the match UDF given to DataFusion.
 pub const MATCH_UDF_NAME: &str = "str_match";
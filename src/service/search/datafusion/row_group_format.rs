@@ -0,0 +1,193 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use datafusion::{
+    arrow::datatypes::SchemaRef,
+    common::Statistics,
+    datasource::{
+        file_format::{parquet::ParquetFormat, FileFormat},
+        physical_plan::{FileMeta, FileScanConfig, ParquetExec, ParquetFileReaderFactory},
+    },
+    error::Result,
+    execution::context::SessionState,
+    physical_plan::{metrics::ExecutionPlanMetricsSet, ExecutionPlan, PhysicalExpr},
+};
+use futures::{future::BoxFuture, FutureExt};
+use object_store::{ObjectMeta, ObjectStore};
+use parquet::{
+    arrow::async_reader::{AsyncFileReader, ParquetObjectReader},
+    file::metadata::ParquetMetaData,
+};
+use std::{any::Any, collections::HashMap, ops::Range, sync::Arc};
+
+/// parquet format reading only the selected row groups of the files, the
+/// files without a selection are read whole
+#[derive(Debug)]
+pub struct RowGroupParquetFormat {
+    inner: ParquetFormat,
+    row_groups: Arc<HashMap<String, Vec<usize>>>,
+}
+
+impl RowGroupParquetFormat {
+    /// the row groups are keyed by the file key, pruning is disabled like for
+    /// the other searches
+    pub fn new(row_groups: Arc<HashMap<String, Vec<usize>>>) -> Self {
+        Self {
+            inner: ParquetFormat::default().with_enable_pruning(Some(false)),
+            row_groups,
+        }
+    }
+}
+
+#[async_trait]
+impl FileFormat for RowGroupParquetFormat {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn infer_schema(
+        &self,
+        state: &SessionState,
+        store: &Arc<dyn ObjectStore>,
+        objects: &[ObjectMeta],
+    ) -> Result<SchemaRef> {
+        self.inner.infer_schema(state, store, objects).await
+    }
+
+    async fn infer_stats(
+        &self,
+        state: &SessionState,
+        store: &Arc<dyn ObjectStore>,
+        table_schema: SchemaRef,
+        object: &ObjectMeta,
+    ) -> Result<Statistics> {
+        if self
+            .row_groups
+            .contains_key(file_key(&object.location.to_string()))
+        {
+            // the statistics of the whole file don't hold for the selected rows
+            return Ok(Statistics::default());
+        }
+        self.inner
+            .infer_stats(state, store, table_schema, object)
+            .await
+    }
+
+    async fn create_physical_plan(
+        &self,
+        state: &SessionState,
+        conf: FileScanConfig,
+        _filters: Option<&Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let store = state.runtime_env().object_store(&conf.object_store_url)?;
+        let factory = RowGroupReaderFactory {
+            store,
+            row_groups: self.row_groups.clone(),
+        };
+        Ok(Arc::new(
+            ParquetExec::new(conf, None, None).with_parquet_file_reader_factory(Arc::new(factory)),
+        ))
+    }
+}
+
+/// the files are listed as `/{session_id}/$$/{file_key}`
+fn file_key(location: &str) -> &str {
+    match location.find("/$$/") {
+        Some(p) => &location[p + 4..],
+        None => location,
+    }
+}
+
+#[derive(Debug)]
+struct RowGroupReaderFactory {
+    store: Arc<dyn ObjectStore>,
+    row_groups: Arc<HashMap<String, Vec<usize>>>,
+}
+
+impl ParquetFileReaderFactory for RowGroupReaderFactory {
+    fn create_reader(
+        &self,
+        _partition_index: usize,
+        file_meta: FileMeta,
+        metadata_size_hint: Option<usize>,
+        _metrics: &ExecutionPlanMetricsSet,
+    ) -> Result<Box<dyn AsyncFileReader + Send>> {
+        let row_groups = self
+            .row_groups
+            .get(file_key(&file_meta.location().to_string()))
+            .cloned();
+        let mut inner = ParquetObjectReader::new(self.store.clone(), file_meta.object_meta);
+        if let Some(hint) = metadata_size_hint {
+            inner = inner.with_footer_size_hint(hint);
+        }
+        Ok(Box::new(RowGroupReader { inner, row_groups }))
+    }
+}
+
+/// hides the row groups which are not selected from the file metadata, so the
+/// reader never fetches nor decodes them
+struct RowGroupReader {
+    inner: ParquetObjectReader,
+    row_groups: Option<Vec<usize>>,
+}
+
+impl AsyncFileReader for RowGroupReader {
+    fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, parquet::errors::Result<Bytes>> {
+        self.inner.get_bytes(range)
+    }
+
+    fn get_byte_ranges(
+        &mut self,
+        ranges: Vec<Range<usize>>,
+    ) -> BoxFuture<'_, parquet::errors::Result<Vec<Bytes>>> {
+        self.inner.get_byte_ranges(ranges)
+    }
+
+    fn get_metadata(&mut self) -> BoxFuture<'_, parquet::errors::Result<Arc<ParquetMetaData>>> {
+        async move {
+            let metadata = self.inner.get_metadata().await?;
+            let Some(row_groups) = self.row_groups.as_ref() else {
+                return Ok(metadata);
+            };
+            let groups = metadata
+                .row_groups()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| row_groups.contains(i))
+                .map(|(_, group)| group.clone())
+                .collect();
+            Ok(Arc::new(ParquetMetaData::new(
+                metadata.file_metadata().clone(),
+                groups,
+            )))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_key() {
+        assert_eq!(
+            file_key("/abc-0/$$/files/default/logs/a/2023/01/01/00/1.parquet"),
+            "files/default/logs/a/2023/01/01/00/1.parquet"
+        );
+        assert_eq!(file_key("files/a.parquet"), "files/a.parquet");
+    }
+}
@@ -15,11 +15,15 @@
 use chrono::{TimeZone, Utc};
 use object_store::ObjectMeta;
 use once_cell::sync::Lazy;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::common::infra::config::RwHashMap;
 use crate::common::meta::common::FileKey;
 
 pub static FILES: Lazy<RwHashMap<String, Vec<ObjectMeta>>> = Lazy::new(Default::default);
+/// the row groups to read by file key, the files without are read whole
+pub static ROW_GROUPS: Lazy<RwHashMap<String, Arc<HashMap<String, Vec<usize>>>>> =
+    Lazy::new(Default::default);
 
 pub fn get(session_id: &str) -> Result<Vec<ObjectMeta>, anyhow::Error> {
     let data = match FILES.get(session_id) {
@@ -44,6 +48,16 @@ pub async fn set(session_id: &str, files: &[FileKey]) {
     FILES.insert(session_id.to_string(), values);
 }
 
+pub fn get_row_groups(session_id: &str) -> Option<Arc<HashMap<String, Vec<usize>>>> {
+    ROW_GROUPS.get(session_id).map(|v| v.value().clone())
+}
+
+pub fn set_row_groups(session_id: &str, row_groups: Arc<HashMap<String, Vec<usize>>>) {
+    if !row_groups.is_empty() {
+        ROW_GROUPS.insert(session_id.to_string(), row_groups);
+    }
+}
+
 pub fn clear(session_id: &str) {
     let keys = FILES
         .iter()
@@ -53,4 +67,5 @@ pub fn clear(session_id: &str) {
     for key in keys {
         FILES.remove(&key);
     }
+    ROW_GROUPS.retain(|k, _| !k.starts_with(session_id));
 }
@@ -0,0 +1,124 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, BooleanArray, Int64Array, StringArray},
+        datatypes::DataType,
+    },
+    logical_expr::{ScalarFunctionImplementation, ScalarUDF, Volatility},
+    physical_plan::functions::make_scalar_function,
+    prelude::create_udf,
+};
+use std::iter::zip;
+use std::sync::Arc;
+
+use crate::common::infra::config::RwHashMap;
+
+/// The name of the latest_version UDF given to DataFusion.
+pub const LATEST_VERSION_UDF_NAME: &str = "latest_version";
+
+/// latest_version(_id, _version) is true for the latest version of an `_id`,
+/// given the latest versions of the `_id`s of the stream
+pub(crate) fn latest_version_udf(versions: Arc<RwHashMap<String, i64>>) -> ScalarUDF {
+    create_udf(
+        LATEST_VERSION_UDF_NAME,
+        vec![DataType::Utf8, DataType::Int64],
+        Arc::new(DataType::Boolean),
+        Volatility::Stable,
+        latest_version_expr_impl(versions),
+    )
+}
+
+fn latest_version_expr_impl(versions: Arc<RwHashMap<String, i64>>) -> ScalarFunctionImplementation {
+    let func = move |args: &[ArrayRef]| -> datafusion::error::Result<ArrayRef> {
+        let ids = args[0]
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .expect("cast failed");
+        let doc_versions = args[1]
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .expect("cast failed");
+        // an `_id` missing from the versions has a single version, the
+        // documents ingested before the setting have no version
+        let array = zip(ids.iter(), doc_versions.iter())
+            .map(|(id, version)| {
+                let latest = id.and_then(|id| versions.get(id).map(|v| *v));
+                Some(match (latest, version) {
+                    (None, _) => true,
+                    (Some(latest), Some(version)) => version >= latest,
+                    (Some(_), None) => false,
+                })
+            })
+            .collect::<BooleanArray>();
+        Ok(Arc::new(array) as ArrayRef)
+    };
+
+    make_scalar_function(func)
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::datasource::MemTable;
+    use datafusion::prelude::SessionContext;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_latest_version_udf() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("_id", DataType::Utf8, true),
+            Field::new("_version", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("a"),
+                    Some("b"),
+                    Some("a"),
+                ])),
+                Arc::new(Int64Array::from(vec![Some(1), Some(2), Some(1), None])),
+            ],
+        )
+        .unwrap();
+        let versions: Arc<RwHashMap<String, i64>> = Default::default();
+        versions.insert("a".to_string(), 2);
+
+        let ctx = SessionContext::new();
+        ctx.register_udf(latest_version_udf(versions));
+        let provider = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+        ctx.register_table("t", Arc::new(provider)).unwrap();
+        let df = ctx
+            .sql("SELECT latest_version(_id, _version) AS latest FROM t")
+            .await
+            .unwrap();
+        let batches = df.collect().await.unwrap();
+        let latest = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap()
+            .iter()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            latest,
+            vec![Some(false), Some(true), Some(true), Some(false)]
+        );
+    }
+}
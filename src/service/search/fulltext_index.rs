@@ -0,0 +1,342 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::Bytes;
+use datafusion::arrow::{
+    array::{Array, StringArray},
+    datatypes::{DataType, Schema},
+};
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ProjectionMask};
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{BinaryOperator, Expr as SqlExpr, FunctionArg, FunctionArgExpr, Value};
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Write,
+};
+
+use crate::common::{
+    infra::config::{CONFIG, FILE_EXT_INDEX, FILE_EXT_PARQUET, PARQUET_BATCH_SIZE},
+    meta::common::FileKey,
+    utils::{column_stats, json, stream::SQL_FULL_TEXT_SEARCH_FIELDS},
+};
+
+/// length of the terms in the index, match_all is translated to `LIKE '%term%'`
+/// so we index character trigrams which are safe for substring matching
+const TERM_LEN: usize = 3;
+/// give up building the index when a file has too many distinct terms
+const MAX_TERMS: usize = 1_000_000;
+
+/// Full text index of a parquet file, maps every term to the row groups that
+/// contain it in any of the indexed fields.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FulltextIndex {
+    pub fields: Vec<String>,
+    pub row_groups: u32,
+    pub terms: BTreeMap<String, Vec<u32>>,
+}
+
+impl FulltextIndex {
+    /// build the index for the given fields of a parquet file, returns None if
+    /// the file has too many distinct terms for the index to be useful
+    pub fn build(data: &Bytes, fields: &[String]) -> Result<Option<Self>, anyhow::Error> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(data.clone())?;
+        let arrow_schema = builder.schema().clone();
+        let row_groups = builder.metadata().num_row_groups();
+        let columns = fields
+            .iter()
+            .filter_map(|name| arrow_schema.index_of(name).ok())
+            .filter(|i| arrow_schema.field(*i).data_type().eq(&DataType::Utf8))
+            .collect::<Vec<_>>();
+
+        let mut index = FulltextIndex {
+            fields: fields.to_vec(),
+            row_groups: row_groups as u32,
+            terms: BTreeMap::new(),
+        };
+        if columns.is_empty() {
+            // none of the fields exist in the file, nothing can match
+            return Ok(Some(index));
+        }
+
+        for row_group in 0..row_groups {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(data.clone())?;
+            let mask = ProjectionMask::roots(builder.parquet_schema(), columns.clone());
+            let reader = builder
+                .with_row_groups(vec![row_group])
+                .with_projection(mask)
+                .with_batch_size(PARQUET_BATCH_SIZE)
+                .build()?;
+            let mut group_terms = HashSet::new();
+            for batch in reader {
+                let batch = batch?;
+                for column in batch.columns() {
+                    let Some(values) = column.as_any().downcast_ref::<StringArray>() else {
+                        continue;
+                    };
+                    for value in values.iter().flatten() {
+                        tokenize(value, &mut group_terms);
+                    }
+                }
+            }
+            for term in group_terms {
+                index.terms.entry(term).or_default().push(row_group as u32);
+            }
+            if index.terms.len() > MAX_TERMS {
+                return Ok(None);
+            }
+        }
+        Ok(Some(index))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let mut buf = zstd::Encoder::new(Vec::new(), 3)?;
+        buf.write_all(&json::to_vec(self)?)?;
+        Ok(buf.finish()?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, anyhow::Error> {
+        let uncompress = zstd::decode_all(data)?;
+        Ok(json::from_slice(&uncompress)?)
+    }
+
+    /// returns true if the index covers all the given fields
+    pub fn covers(&self, fields: &[String]) -> bool {
+        fields.iter().all(|f| self.fields.contains(f))
+    }
+
+    /// returns false only if no row group of the file contains all the terms
+    pub fn may_contain(&self, terms: &[String]) -> bool {
+        self.row_groups_for(terms)
+            .map_or(true, |groups| !groups.is_empty())
+    }
+
+    /// the row groups which contain all the terms, sorted, None when there is
+    /// no term to look for and every row group has to be read
+    pub fn row_groups_for(&self, terms: &[String]) -> Option<Vec<usize>> {
+        let mut candidates: Option<HashSet<u32>> = None;
+        for term in terms {
+            let Some(groups) = self.terms.get(term) else {
+                return Some(vec![]);
+            };
+            let groups = groups.iter().copied().collect::<HashSet<_>>();
+            let groups = match candidates {
+                Some(v) => v.intersection(&groups).copied().collect::<HashSet<_>>(),
+                None => groups,
+            };
+            if groups.is_empty() {
+                return Some(vec![]);
+            }
+            candidates = Some(groups);
+        }
+        let mut groups = candidates?
+            .into_iter()
+            .map(|v| v as usize)
+            .collect::<Vec<_>>();
+        groups.sort();
+        Some(groups)
+    }
+}
+
+/// the object key of the index file for a parquet file
+pub fn index_file_key(file: &str) -> String {
+    match file.strip_suffix(FILE_EXT_PARQUET) {
+        Some(name) => format!("{name}{FILE_EXT_INDEX}"),
+        None => format!("{file}{FILE_EXT_INDEX}"),
+    }
+}
+
+/// the object keys of the files together with their full text index and
+/// column stats files
+pub fn file_keys_with_sidecars(files: &[FileKey]) -> Vec<String> {
+    let mut keys = Vec::with_capacity(files.len());
+    for file in files {
+        keys.push(file.key.clone());
+        if file.meta.index_size > 0 {
+            keys.push(index_file_key(&file.key));
+        }
+        if file.meta.stats_size > 0 {
+            keys.push(column_stats::stats_file_key(&file.key));
+        }
+    }
+    keys
+}
+
+/// the fields used by match_all, fts fields from the stream settings or the
+/// default full text search fields, a field is used when its name is part of
+/// the joined names of those fields
+pub fn index_fields(schema: &Schema, fts_fields: &[String]) -> Vec<String> {
+    let match_all_fields = if !fts_fields.is_empty() {
+        fts_fields.iter().map(|v| v.to_lowercase()).collect()
+    } else {
+        SQL_FULL_TEXT_SEARCH_FIELDS
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<String>()
+    };
+    schema
+        .fields()
+        .iter()
+        .filter(|field| {
+            CONFIG.common.feature_fulltext_on_all_fields
+                || match_all_fields.contains(&field.name().to_lowercase())
+        })
+        .filter(|field| field.data_type().eq(&DataType::Utf8) && !field.name().starts_with('@'))
+        .map(|field| field.name().to_string())
+        .collect()
+}
+
+/// collect the terms every matched record must contain, only match_all
+/// functions joined by AND are used, anything under OR / NOT can't prune
+pub fn required_terms(selection: Option<&SqlExpr>) -> Vec<String> {
+    let mut terms = Vec::new();
+    if let Some(expr) = selection {
+        collect_terms(expr, &mut terms);
+    }
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+fn collect_terms(expr: &SqlExpr, terms: &mut Vec<String>) {
+    match expr {
+        SqlExpr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            collect_terms(left, terms);
+            collect_terms(right, terms);
+        }
+        SqlExpr::Nested(expr) => collect_terms(expr, terms),
+        SqlExpr::Function(f) => {
+            let name = f.name.to_string().to_lowercase();
+            if name != "match_all" && name != "match_all_ignore_case" {
+                return;
+            }
+            if let Some(FunctionArg::Unnamed(FunctionArgExpr::Expr(SqlExpr::Value(
+                Value::SingleQuotedString(value),
+            )))) = f.args.first()
+            {
+                terms.extend(query_terms(value));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// split a match_all value into terms, the value ends up in a LIKE pattern so
+/// the wildcard and escape characters break the literal parts
+fn query_terms(value: &str) -> Vec<String> {
+    let mut terms = HashSet::new();
+    for part in value.split(['%', '_', '\\']) {
+        tokenize(part, &mut terms);
+    }
+    terms.into_iter().collect()
+}
+
+fn tokenize(text: &str, terms: &mut HashSet<String>) {
+    let chars = text.to_lowercase().chars().collect::<Vec<_>>();
+    for window in chars.windows(TERM_LEN) {
+        terms.insert(window.iter().collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::meta::sql::Sql as MetaSql;
+
+    fn build_index(groups: &[&[&str]]) -> FulltextIndex {
+        let mut index = FulltextIndex {
+            fields: vec!["log".to_string()],
+            row_groups: groups.len() as u32,
+            terms: BTreeMap::new(),
+        };
+        for (i, values) in groups.iter().enumerate() {
+            let mut terms = HashSet::new();
+            for value in values.iter() {
+                tokenize(value, &mut terms);
+            }
+            for term in terms {
+                index.terms.entry(term).or_default().push(i as u32);
+            }
+        }
+        index
+    }
+
+    #[test]
+    fn test_fulltext_index_tokenize() {
+        let mut terms = HashSet::new();
+        tokenize("Error", &mut terms);
+        let mut terms = terms.into_iter().collect::<Vec<_>>();
+        terms.sort();
+        assert_eq!(terms, vec!["err", "ror", "rro"]);
+
+        assert!(query_terms("ab").is_empty());
+        let mut terms = query_terms("ab_cde%f");
+        terms.sort();
+        assert_eq!(terms, vec!["cde"]);
+    }
+
+    #[test]
+    fn test_fulltext_index_may_contain() {
+        let index = build_index(&[&["connection timeout"], &["disk full", "error"]]);
+        assert!(index.may_contain(&query_terms("timeout")));
+        assert!(index.may_contain(&query_terms("ERROR")));
+        assert!(index.may_contain(&[]));
+        assert!(!index.may_contain(&query_terms("panic")));
+        // terms exist but never in the same row group
+        let mut terms = query_terms("timeout");
+        terms.extend(query_terms("disk"));
+        assert!(!index.may_contain(&terms));
+
+        assert_eq!(index.row_groups_for(&query_terms("error")), Some(vec![1]));
+        assert_eq!(index.row_groups_for(&query_terms("panic")), Some(vec![]));
+        assert_eq!(index.row_groups_for(&[]), None);
+
+        let data = index.to_bytes().unwrap();
+        assert_eq!(FulltextIndex::from_bytes(&data).unwrap(), index);
+    }
+
+    #[test]
+    fn test_fulltext_index_fields() {
+        use datafusion::arrow::datatypes::Field;
+
+        let schema = Schema::new(vec![
+            Field::new("log", DataType::Utf8, true),
+            Field::new("msg", DataType::Utf8, true),
+            Field::new("code", DataType::Int64, true),
+        ]);
+        // the names match as part of the fts fields, like the search does
+        let fields = index_fields(&schema, &["logs".to_string(), "code".to_string()]);
+        assert_eq!(fields, vec!["log".to_string()]);
+    }
+
+    #[test]
+    fn test_fulltext_index_required_terms() {
+        let sql = MetaSql::new(
+            "select * from tbl where match_all('error') and (code = 500 and match_all_ignore_case('Disk'))",
+        )
+        .unwrap();
+        let mut expected = query_terms("error");
+        expected.extend(query_terms("disk"));
+        expected.sort();
+        assert_eq!(required_terms(sql.selection.as_ref()), expected);
+
+        let sql = MetaSql::new("select * from tbl where match_all('error') or code = 500").unwrap();
+        assert!(required_terms(sql.selection.as_ref()).is_empty());
+
+        assert_eq!(index_file_key("files/a/b.parquet"), "files/a/b.idx");
+    }
+}
@@ -18,6 +18,7 @@ use ::datafusion::{
 };
 use ahash::AHashMap as HashMap;
 use std::sync::Arc;
+use tokio::sync::mpsc;
 use tracing::{info_span, Instrument};
 
 use super::datafusion;
@@ -27,6 +28,7 @@ use crate::common::{
         errors::{Error, ErrorCodes},
     },
     meta::{common::FileKey, stream::ScanStats, StreamType},
+    utils::cancel::{cancelled_error, CancelToken},
 };
 use crate::handler::grpc::cluster_rpc;
 use crate::service::db;
@@ -39,11 +41,18 @@ pub type SearchResult = Result<(HashMap<String, Vec<RecordBatch>>, ScanStats), E
 #[tracing::instrument(name = "service:search:grpc:search", skip_all, fields(org_id = req.org_id))]
 pub async fn search(
     req: &cluster_rpc::SearchRequest,
+    cancel: &CancelToken,
 ) -> Result<cluster_rpc::SearchResponse, Error> {
     let start = std::time::Instant::now();
     let sql = Arc::new(super::sql::Sql::new(req).await?);
     let stream_type = StreamType::from(req.stream_type.as_str());
-    let session_id = Arc::new(req.job.as_ref().unwrap().session_id.to_string());
+    let mut session_id = req.job.as_ref().unwrap().session_id.to_string();
+    // the streams of a multi-stream search run on the node at the same time,
+    // the id must not be a prefix of the id of another stream
+    if let Some(i) = req.streams.iter().position(|s| s == &sql.stream_name) {
+        session_id = format!("{session_id}-{i}-stream");
+    }
+    let session_id = Arc::new(session_id);
 
     // check if we are allowed to search
     if db::compact::retention::is_deleting_stream(&sql.org_id, &sql.stream_name, stream_type, None)
@@ -60,11 +69,12 @@ pub async fn search(
     // search in WAL
     let session_id1 = session_id.clone();
     let sql1 = sql.clone();
+    let cancel1 = cancel.clone();
     let wal_span = info_span!("service:search:grpc:in_wal", org_id = sql.org_id,stream_name = sql.stream_name, stream_type = ?stream_type);
     let task1 = tokio::task::spawn(
         async move {
             if cluster::is_ingester(&cluster::LOCAL_NODE_ROLE) {
-                wal::search(&session_id1, sql1, stream_type).await
+                wal::search(&session_id1, sql1, stream_type, &cancel1).await
             } else {
                 Ok((HashMap::new(), ScanStats::default()))
             }
@@ -76,6 +86,7 @@ pub async fn search(
     let req_stype = req.stype;
     let session_id2 = session_id.clone();
     let sql2 = sql.clone();
+    let cancel2 = cancel.clone();
     let file_list: Vec<FileKey> = req.file_list.iter().map(FileKey::from).collect();
     let storage_span = info_span!("service:search:grpc:in_storage", org_id = sql.org_id,stream_name = sql.stream_name, stream_type = ?stream_type);
     let task2 = tokio::task::spawn(
@@ -83,7 +94,7 @@ pub async fn search(
             if req_stype == cluster_rpc::SearchType::WalOnly as i32 {
                 Ok((HashMap::new(), ScanStats::default()))
             } else {
-                storage::search(&session_id2, sql2, &file_list, stream_type).await
+                storage::search(&session_id2, sql2, &file_list, stream_type, &cancel2).await
             }
         }
         .instrument(storage_span),
@@ -129,11 +140,13 @@ pub async fn search(
     }
     scan_stats.add(&scan_stats2);
 
+    cancel.check()?;
+
     // merge all batches
     let (offset, limit) = (0, sql.meta.offset + sql.meta.limit);
     for (name, batches) in results.iter_mut() {
         let merge_sql = if name == "query" {
-            sql.origin_sql.clone()
+            sql.merge_sql()
         } else {
             sql.aggs
                 .get(name.strip_prefix("agg_").unwrap())
@@ -218,8 +231,48 @@ pub async fn search(
     Ok(result)
 }
 
+/// search the time slices one by one and send the result of every slice as
+/// soon as it is done, stops at the first error, when the receiver is gone or
+/// when the search is cancelled
+#[tracing::instrument(name = "service:search:grpc:search_stream", skip_all)]
+pub async fn search_stream(
+    req: cluster_rpc::SearchStreamRequest,
+    tx: mpsc::Sender<Result<cluster_rpc::SearchResponse, Error>>,
+    cancel: CancelToken,
+) {
+    let Some(base_req) = req.request else {
+        let _ = tx
+            .send(Err(Error::Message("search request is empty".to_string())))
+            .await;
+        return;
+    };
+    let session_id = base_req.job.as_ref().unwrap().session_id.clone();
+    for (i, slice) in req.slices.iter().enumerate() {
+        let mut req = base_req.clone();
+        if let Some(query) = req.query.as_mut() {
+            query.start_time = slice.start_time;
+            query.end_time = slice.end_time;
+        }
+        // every slice needs its own session for the datafusion storage
+        req.job.as_mut().unwrap().session_id = format!("{session_id}-{i}");
+        req.file_list.retain(|file| {
+            file.meta.as_ref().map_or(true, |meta| {
+                meta.min_ts < slice.end_time && meta.max_ts >= slice.start_time
+            })
+        });
+        let result = search(&req, &cancel).await;
+        let failed = result.is_err();
+        if tx.send(result).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
 pub fn handle_datafusion_error(err: DataFusionError) -> Error {
     let err = err.to_string();
+    if err.contains(datafusion::exec::SEARCH_CANCELLED) {
+        return cancelled_error();
+    }
     if err.contains("Schema error: No field named") {
         let pos = err.find("Schema error: No field named").unwrap();
         return match get_key_from_error(&err, pos) {
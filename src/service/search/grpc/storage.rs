@@ -28,10 +28,18 @@ use crate::common::meta::{
     common::FileKey,
     stream::{PartitionTimeLevel, ScanStats},
 };
+use crate::common::utils::{
+    cancel::{cancelled_error, CancelToken},
+    column_stats::{self, ColumnStats},
+};
 use crate::service::{
     db, file_list,
     search::{
-        datafusion::{exec, storage::StorageType},
+        datafusion::{
+            exec,
+            storage::{self as datafusion_storage, StorageType},
+        },
+        fulltext_index::{self, FulltextIndex},
         sql::Sql,
     },
     stream,
@@ -44,6 +52,7 @@ pub async fn search(
     sql: Arc<Sql>,
     file_list: &[FileKey],
     stream_type: meta::StreamType,
+    cancel: &CancelToken,
 ) -> super::SearchResult {
     // fetch all schema versions, group files by version
     let schema_versions =
@@ -83,6 +92,21 @@ pub async fn search(
         files.len(),
     );
 
+    // skip files by column stats
+    let files = filter_by_column_stats(&sql, files).await;
+    if files.is_empty() {
+        return Ok((HashMap::new(), ScanStats::default()));
+    }
+
+    // skip files by full text index
+    let index_fields =
+        fulltext_index::index_fields(schema_latest, &stream_settings.full_text_search_keys);
+    let (files, row_groups) = filter_by_fulltext_index(&sql, files, index_fields).await;
+    if files.is_empty() {
+        return Ok((HashMap::new(), ScanStats::default()));
+    }
+    cancel.check()?;
+
     let mut files_group: HashMap<usize, Vec<FileKey>> =
         HashMap::with_capacity(schema_versions.len());
     let mut scan_stats = ScanStats::new();
@@ -148,7 +172,10 @@ pub async fn search(
 
     // load files to local cache
     if storage_type == StorageType::FsMemory {
-        let deleted_files = cache_parquet_files(&files).await?;
+        let deleted_files = tokio::select! {
+            ret = cache_parquet_files(&files) => ret?,
+            _ = cancel.cancelled() => return Err(cancelled_error()),
+        };
         if !deleted_files.is_empty() {
             // remove deleted files from files_group
             for (_, g_files) in files_group.iter_mut() {
@@ -162,6 +189,7 @@ pub async fn search(
             scan_stats.files
         );
     }
+    cancel.check()?;
 
     let mut tasks = Vec::new();
     for (ver, files) in files_group {
@@ -171,9 +199,18 @@ pub async fn search(
                 .with_metadata(std::collections::HashMap::new()),
         );
         let sql = sql.clone();
+        let row_groups = files
+            .iter()
+            .filter_map(|f| row_groups.get(&f.key).map(|v| (f.key.clone(), v.clone())))
+            .collect::<std::collections::HashMap<_, _>>();
+        datafusion_storage::file_list::set_row_groups(
+            &format!("{session_id}-{ver}"),
+            Arc::new(row_groups),
+        );
         let session = meta::search::Session {
             id: format!("{session_id}-{ver}"),
             storage_type: storage_type.clone(),
+            cancel: cancel.clone(),
         };
         // cacluate the diff between latest schema and group schema
         let mut diff_fields = HashMap::new();
@@ -309,3 +346,160 @@ async fn cache_parquet_files(files: &[FileKey]) -> Result<Vec<String>, Error> {
 
     Ok(delete_files)
 }
+
+/// skips the files and the row groups which can't contain the match_all terms,
+/// returns the files to read with the row groups to read by file key, the
+/// files without an entry are read whole
+#[tracing::instrument(
+    name = "service:search:grpc:storage:filter_by_fulltext_index",
+    skip_all
+)]
+async fn filter_by_fulltext_index(
+    sql: &Sql,
+    files: Vec<FileKey>,
+    index_fields: Vec<String>,
+) -> (Vec<FileKey>, HashMap<String, Vec<usize>>) {
+    let terms = fulltext_index::required_terms(sql.meta.selection.as_ref());
+    if terms.is_empty() || !files.iter().any(|f| f.meta.index_size > 0) {
+        return (files, HashMap::new());
+    }
+
+    let terms = Arc::new(terms);
+    let index_fields = Arc::new(index_fields);
+    let mut tasks = Vec::with_capacity(files.len());
+    let semaphore = std::sync::Arc::new(Semaphore::new(CONFIG.limit.query_thread_num));
+    for file in files.iter() {
+        if file.meta.index_size == 0 {
+            tasks.push(None);
+            continue;
+        }
+        let file_name = file.key.clone();
+        let terms = terms.clone();
+        let index_fields = index_fields.clone();
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        // None reads the whole file
+        let task: tokio::task::JoinHandle<Option<(Vec<usize>, u32)>> =
+            tokio::task::spawn(async move {
+                let row_groups = match load_fulltext_index(&file_name).await {
+                    Ok(index) if index.covers(&index_fields) => index
+                        .row_groups_for(&terms)
+                        .map(|groups| (groups, index.row_groups)),
+                    Ok(_) => None,
+                    Err(e) => {
+                        log::error!(
+                            "search->storage: load fulltext index for file: {} err: {}",
+                            file_name,
+                            e
+                        );
+                        None
+                    }
+                };
+                drop(permit);
+                row_groups
+            });
+        tasks.push(Some(task));
+    }
+
+    let files_num = files.len();
+    let mut ret = Vec::with_capacity(files_num);
+    let mut row_groups = HashMap::new();
+    let mut skip_row_groups = 0;
+    for (file, task) in files.into_iter().zip(tasks) {
+        let groups = match task {
+            None => None,
+            Some(task) => task.await.unwrap_or(None),
+        };
+        match groups {
+            Some((groups, _)) if groups.is_empty() => continue,
+            Some((groups, total)) if groups.len() < total as usize => {
+                skip_row_groups += total as usize - groups.len();
+                row_groups.insert(file.key.clone(), groups);
+            }
+            _ => {}
+        }
+        ret.push(file);
+    }
+    log::info!(
+        "search->storage: org {}, stream {}, skip files {}, row groups {} by fulltext index",
+        &sql.org_id,
+        &sql.stream_name,
+        files_num - ret.len(),
+        skip_row_groups,
+    );
+    (ret, row_groups)
+}
+
+async fn load_fulltext_index(file: &str) -> Result<FulltextIndex, anyhow::Error> {
+    let index_key = fulltext_index::index_file_key(file);
+    let data = match file_data::get(&index_key) {
+        Some(data) => data,
+        None => file_data::download(&index_key).await?,
+    };
+    FulltextIndex::from_bytes(&data)
+}
+
+/// skips the files whose column stats prove no record can match the where
+/// clause, files without stats are kept
+#[tracing::instrument(name = "service:search:grpc:storage:filter_by_column_stats", skip_all)]
+async fn filter_by_column_stats(sql: &Sql, files: Vec<FileKey>) -> Vec<FileKey> {
+    if sql.column_filters.is_empty() || !files.iter().any(|f| f.meta.stats_size > 0) {
+        return files;
+    }
+
+    let filters = Arc::new(sql.column_filters.clone());
+    let mut tasks = Vec::with_capacity(files.len());
+    let semaphore = std::sync::Arc::new(Semaphore::new(CONFIG.limit.query_thread_num));
+    for file in files.iter() {
+        if file.meta.stats_size == 0 {
+            tasks.push(None);
+            continue;
+        }
+        let file_name = file.key.clone();
+        let filters = filters.clone();
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let task: tokio::task::JoinHandle<bool> = tokio::task::spawn(async move {
+            let matched = match load_column_stats(&file_name).await {
+                Ok(stats) => column_stats::may_match(&stats, &filters),
+                Err(e) => {
+                    log::error!(
+                        "search->storage: load column stats for file: {} err: {}",
+                        file_name,
+                        e
+                    );
+                    true
+                }
+            };
+            drop(permit);
+            matched
+        });
+        tasks.push(Some(task));
+    }
+
+    let files_num = files.len();
+    let mut ret = Vec::with_capacity(files_num);
+    for (file, task) in files.into_iter().zip(tasks) {
+        let matched = match task {
+            None => true,
+            Some(task) => task.await.unwrap_or(true),
+        };
+        if matched {
+            ret.push(file);
+        }
+    }
+    log::info!(
+        "search->storage: org {}, stream {}, skip files {} by column stats",
+        &sql.org_id,
+        &sql.stream_name,
+        files_num - ret.len(),
+    );
+    ret
+}
+
+async fn load_column_stats(file: &str) -> Result<Vec<ColumnStats>, anyhow::Error> {
+    let stats_key = column_stats::stats_file_key(file);
+    let data = match file_data::get(&stats_key) {
+        Some(data) => data,
+        None => file_data::download(&stats_key).await?,
+    };
+    column_stats::from_bytes(&data)
+}
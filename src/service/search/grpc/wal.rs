@@ -27,7 +27,10 @@ use crate::common::infra::{
     wal,
 };
 use crate::common::meta::{self, common::FileKey, stream::ScanStats};
-use crate::common::utils::file::{get_file_contents, get_file_meta, scan_files};
+use crate::common::utils::{
+    cancel::CancelToken,
+    file::{get_file_contents, get_file_meta, scan_files},
+};
 use crate::service::{
     db,
     search::{
@@ -42,6 +45,7 @@ pub async fn search(
     session_id: &str,
     sql: Arc<Sql>,
     stream_type: meta::StreamType,
+    cancel: &CancelToken,
 ) -> super::SearchResult {
     // get file list
     let mut files = get_file_list(&sql, stream_type).await?;
@@ -159,6 +163,7 @@ pub async fn search(
             meta::search::Session {
                 id: session_id.to_string(),
                 storage_type: StorageType::Tmpfs,
+                cancel: cancel.clone(),
             }
         } else {
             let id = format!("{session_id}-{ver}");
@@ -175,6 +180,7 @@ pub async fn search(
             meta::search::Session {
                 id,
                 storage_type: StorageType::Tmpfs,
+                cancel: cancel.clone(),
             }
         };
         let datafusion_span = info_span!(
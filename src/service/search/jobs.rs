@@ -0,0 +1,528 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ::datafusion::arrow::json as arrow_json;
+use ahash::AHashMap as HashMap;
+use bytes::Bytes;
+use chrono::{Duration, Utc};
+use once_cell::sync::Lazy;
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    file::properties::WriterProperties,
+};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+use super::{queue::Priority, server_internal_error, streaming};
+use crate::common::infra::{
+    cluster,
+    config::{get_parquet_compression, CONFIG},
+    dist_lock,
+    errors::{Error, ErrorCodes},
+    ider, storage,
+};
+use crate::common::meta::{
+    search::{self, Job, JobResultFile, JobStatus, StreamEvent},
+    sql::Sql as MetaSql,
+    StreamType,
+};
+use crate::common::utils::json;
+use crate::service::db;
+
+/// rows per record batch of the result files
+const RESULT_BATCH_SIZE: usize = 1024;
+/// seconds between the submits of a job rejected by the search queue
+const RETRY_INTERVAL: u64 = 5;
+
+/// serializes the status checks and the updates of the jobs on this node, the
+/// distributed lock does it between the nodes
+static JOB_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// submit a search job, the job runs in the background on this node and the
+/// hits are written to the object storage slice by slice
+pub async fn submit(
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: &str,
+    mut req: search::Request,
+) -> Result<Job, Error> {
+    if let Err(err) = MetaSql::new(&req.query.sql) {
+        return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(
+            err.to_string(),
+        )));
+    }
+    let now = Utc::now().timestamp_micros();
+    // pin the time range, the job may wait in the queue for a while
+    if req.query.end_time == 0 {
+        req.query.end_time = now;
+    }
+    req.query.size = req.query.size.min(CONFIG.limit.search_job_max_hits);
+
+    let job = Job {
+        id: ider::generate(),
+        org_id: org_id.to_string(),
+        stream_type,
+        user_id: user_id.to_string(),
+        node: cluster::LOCAL_NODE_UUID.clone(),
+        session_id: "".to_string(),
+        status: JobStatus::Pending,
+        request: req,
+        created_at: now,
+        started_at: 0,
+        finished_at: 0,
+        expires_at: now
+            + Duration::hours(CONFIG.limit.search_job_ttl)
+                .num_microseconds()
+                .unwrap(),
+        total_files: 0,
+        scan_files: 0,
+        scan_size: 0,
+        total: 0,
+        hits: 0,
+        took: 0,
+        results: vec![],
+        aggs_file: "".to_string(),
+        error: None,
+    };
+    db::search_job::put(&job)
+        .await
+        .map_err(server_internal_error)?;
+    tokio::task::spawn(run(job.clone()));
+    Ok(job)
+}
+
+/// the job if it was submitted by the user
+pub async fn get(org_id: &str, user_id: &str, job_id: &str) -> Option<Job> {
+    db::search_job::get(org_id, job_id)
+        .await
+        .ok()
+        .filter(|job| job.user_id == user_id)
+}
+
+/// the jobs submitted by the user
+pub async fn list(org_id: &str, user_id: &str) -> Result<Vec<Job>, Error> {
+    let mut jobs = db::search_job::list(org_id)
+        .await
+        .map_err(server_internal_error)?;
+    jobs.retain(|job| job.user_id == user_id);
+    jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(jobs)
+}
+
+/// a page of the job hits, the hits found so far are returned while the job
+/// is still running
+pub async fn results(
+    org_id: &str,
+    user_id: &str,
+    job_id: &str,
+    from: usize,
+    size: usize,
+) -> Result<Option<search::Response>, Error> {
+    let Some(job) = get(org_id, user_id, job_id).await else {
+        return Ok(None);
+    };
+    let mut resp = search::Response::new(from, size);
+    let mut skip = from;
+    for file in job.results.iter() {
+        if resp.hits.len() >= size {
+            break;
+        }
+        if skip >= file.hits {
+            skip -= file.hits;
+            continue;
+        }
+        let data = storage::get(&file.key)
+            .await
+            .map_err(server_internal_error)?;
+        let hits = read_hits(data).map_err(server_internal_error)?;
+        let take = size - resp.hits.len();
+        resp.hits.extend(hits.into_iter().skip(skip).take(take));
+        skip = 0;
+    }
+    if !job.aggs_file.is_empty() {
+        let data = storage::get(&job.aggs_file)
+            .await
+            .map_err(server_internal_error)?;
+        resp.aggs = json::from_slice(&data).map_err(server_internal_error)?;
+    }
+    resp.took = job.took;
+    resp.set_total(job.total);
+    resp.set_scan_size(job.scan_size);
+    Ok(Some(resp))
+}
+
+/// cancel a pending or running job, the running search is cancelled on all
+/// the nodes and the job keeps the results saved before the cancel
+pub async fn cancel(org_id: &str, user_id: &str, job_id: &str) -> Result<Option<Job>, Error> {
+    if get(org_id, user_id, job_id).await.is_none() {
+        return Ok(None);
+    }
+    let ret = update_job(org_id, job_id, |job| {
+        if job.status.is_done() {
+            return false;
+        }
+        job.status = JobStatus::Cancelled;
+        job.finished_at = Utc::now().timestamp_micros();
+        true
+    })
+    .await
+    .map_err(server_internal_error)?;
+    let Some((job, cancelled)) = ret else {
+        return Ok(None);
+    };
+    if cancelled && !job.session_id.is_empty() {
+        super::cancel(org_id, &job.session_id, None).await?;
+    }
+    Ok(Some(job))
+}
+
+/// cancel the job and delete it with its results
+pub async fn delete(org_id: &str, user_id: &str, job_id: &str) -> Result<bool, Error> {
+    if cancel(org_id, user_id, job_id).await?.is_none() {
+        return Ok(false);
+    }
+    delete_job(org_id, job_id)
+        .await
+        .map_err(server_internal_error)?;
+    Ok(true)
+}
+
+/// delete the expired jobs and fail the jobs whose node is gone
+pub async fn clean() -> Result<(), anyhow::Error> {
+    let now = Utc::now().timestamp_micros();
+    for job in db::search_job::list("").await? {
+        if job.expires_at < now {
+            log::info!("[SEARCH JOB] delete expired job: {}/{}", job.org_id, job.id);
+            delete_job(&job.org_id, &job.id).await?;
+        } else if !job.status.is_done() && cluster::get_node_by_uuid(&job.node).is_none() {
+            log::warn!("[SEARCH JOB] node of job {}/{} is gone", job.org_id, job.id);
+            update_job(&job.org_id, &job.id, |job| {
+                if job.status.is_done() {
+                    return false;
+                }
+                job.status = JobStatus::Failed;
+                job.finished_at = now;
+                job.error = Some("the node running the job stopped".to_string());
+                true
+            })
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn delete_job(org_id: &str, job_id: &str) -> Result<(), anyhow::Error> {
+    let files = storage::list(&result_prefix(org_id, job_id)).await?;
+    let files = files.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+    storage::del(&files).await?;
+    db::search_job::delete(org_id, job_id).await
+}
+
+fn result_prefix(org_id: &str, job_id: &str) -> String {
+    format!("search_jobs/{org_id}/{job_id}/")
+}
+
+/// delete the result files the stored job doesn't refer to, the files written
+/// by the search after the job was cancelled or deleted
+async fn delete_orphan_results(org_id: &str, job_id: &str) -> Result<(), anyhow::Error> {
+    let kept = match db::search_job::get(org_id, job_id).await {
+        Ok(job) => job
+            .results
+            .into_iter()
+            .map(|v| v.key)
+            .chain(std::iter::once(job.aggs_file))
+            .collect::<Vec<_>>(),
+        Err(_) => vec![],
+    };
+    let files = storage::list(&result_prefix(org_id, job_id)).await?;
+    let files = files
+        .iter()
+        .filter(|v| !kept.contains(v))
+        .map(|v| v.as_str())
+        .collect::<Vec<_>>();
+    if !files.is_empty() {
+        storage::del(&files).await?;
+    }
+    Ok(())
+}
+
+/// apply `f` to the stored job holding the lock of the job, the job is saved
+/// when `f` returns true, returns None if the job doesn't exist
+async fn update_job(
+    org_id: &str,
+    job_id: &str,
+    f: impl FnOnce(&mut Job) -> bool,
+) -> Result<Option<(Job, bool)>, anyhow::Error> {
+    let _local = JOB_LOCK.lock().await;
+    let mut locker = dist_lock::lock(
+        &format!("/search_job_lock/{org_id}/{job_id}"),
+        CONFIG.etcd.command_timeout,
+    )
+    .await?;
+    let ret = match db::search_job::get(org_id, job_id).await {
+        Ok(mut job) => {
+            let updated = f(&mut job);
+            match updated {
+                true => db::search_job::put(&job).await.map(|_| Some((job, true))),
+                false => Ok(Some((job, false))),
+            }
+        }
+        Err(_) => Ok(None),
+    };
+    dist_lock::unlock(&mut locker).await?;
+    ret
+}
+
+async fn run(mut job: Job) {
+    let options = streaming::StreamOptions {
+        exact: true,
+        timeout: CONFIG.limit.search_job_timeout,
+    };
+    // jobs wait for a free slot instead of failing with too many requests, up
+    // to the retry limit
+    let mut retries = 0;
+    let mut rx = loop {
+        match streaming::search_with_options(
+            &job.org_id,
+            job.stream_type,
+            &job.user_id,
+            Priority::Batch,
+            &job.request,
+            options,
+        )
+        .await
+        {
+            Ok(rx) => break rx,
+            Err(Error::ErrorCode(ErrorCodes::SearchTooManyRequests(msg)))
+                if retries < CONFIG.limit.search_job_max_retries =>
+            {
+                log::info!(
+                    "[SEARCH JOB] job {}/{} waits for the search queue: {msg}",
+                    job.org_id,
+                    job.id
+                );
+                retries += 1;
+                if is_cancelled(&job).await {
+                    return;
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(RETRY_INTERVAL)).await;
+            }
+            Err(err) => {
+                finish(&mut job, Err(err.to_string())).await;
+                return;
+            }
+        }
+    };
+
+    job.status = JobStatus::Running;
+    job.started_at = Utc::now().timestamp_micros();
+    if !save(&job).await {
+        return;
+    }
+    let ret = collect(&mut job, &mut rx).await;
+    // dropping the receiver stops the search
+    drop(rx);
+    finish(&mut job, ret).await;
+}
+
+/// store the results of the search events into the job, returns false when
+/// the job was cancelled
+async fn collect(job: &mut Job, rx: &mut mpsc::Receiver<StreamEvent>) -> Result<bool, String> {
+    let prefix = result_prefix(&job.org_id, &job.id);
+    let mut aggs: HashMap<String, Vec<json::Value>> = HashMap::new();
+    let mut done = false;
+    while let Some(event) = rx.recv().await {
+        match event {
+            StreamEvent::Start {
+                session_id, files, ..
+            } => {
+                job.session_id = session_id;
+                job.total_files = files;
+                if !save(job).await {
+                    return Ok(false);
+                }
+            }
+            StreamEvent::Slice {
+                hits,
+                aggs: slice_aggs,
+                total,
+                scan_files,
+                scan_size,
+                ..
+            } => {
+                if !hits.is_empty() {
+                    let key = format!("{prefix}{}.parquet", job.results.len());
+                    let data = write_hits(&hits).map_err(|e| e.to_string())?;
+                    storage::put(&key, data).await.map_err(|e| e.to_string())?;
+                    job.hits += hits.len();
+                    job.results.push(JobResultFile {
+                        key,
+                        hits: hits.len(),
+                    });
+                }
+                for (name, rows) in slice_aggs {
+                    aggs.entry(name).or_default().extend(rows);
+                }
+                job.total += total;
+                job.scan_files += scan_files;
+                job.scan_size += scan_size;
+                if !save(job).await {
+                    return Ok(false);
+                }
+            }
+            StreamEvent::Done { took, .. } => {
+                job.took = took;
+                done = true;
+            }
+            StreamEvent::Error {
+                message,
+                error_detail,
+                ..
+            } => {
+                return Err(match error_detail {
+                    Some(detail) if !detail.is_empty() && detail != message => {
+                        format!("{message}: {detail}")
+                    }
+                    _ => message,
+                });
+            }
+        }
+    }
+    if !done {
+        return Err("search stopped unexpectedly".to_string());
+    }
+    if !aggs.is_empty() {
+        let key = format!("{prefix}aggs.json");
+        let data = json::to_vec(&aggs).map_err(|e| e.to_string())?;
+        storage::put(&key, data.into())
+            .await
+            .map_err(|e| e.to_string())?;
+        job.aggs_file = key;
+    }
+    Ok(true)
+}
+
+async fn finish(job: &mut Job, ret: Result<bool, String>) {
+    let cancelled = match ret {
+        Ok(true) => {
+            job.status = JobStatus::Finished;
+            false
+        }
+        Ok(false) => true,
+        Err(err) => {
+            log::error!("[SEARCH JOB] job {}/{} failed: {err}", job.org_id, job.id);
+            job.status = JobStatus::Failed;
+            job.error = Some(err);
+            false
+        }
+    };
+    if !cancelled {
+        job.finished_at = Utc::now().timestamp_micros();
+        if save(job).await {
+            return;
+        }
+    }
+    // the job was cancelled or deleted while the search was running
+    if let Err(e) = delete_orphan_results(&job.org_id, &job.id).await {
+        log::error!(
+            "[SEARCH JOB] delete results of job {}/{} error: {e}",
+            job.org_id,
+            job.id
+        );
+    }
+}
+
+async fn is_cancelled(job: &Job) -> bool {
+    // a deleted job counts as cancelled
+    db::search_job::get(&job.org_id, &job.id)
+        .await
+        .map_or(true, |v| v.status == JobStatus::Cancelled)
+}
+
+/// save the progress of the job, returns false if the job was cancelled, the
+/// check and the save hold the lock of the job so a cancel can't be overwritten
+async fn save(job: &Job) -> bool {
+    let ret = update_job(&job.org_id, &job.id, |stored| {
+        if stored.status == JobStatus::Cancelled {
+            return false;
+        }
+        *stored = job.clone();
+        true
+    })
+    .await;
+    match ret {
+        Ok(Some((_, saved))) => saved,
+        // a deleted job counts as cancelled
+        Ok(None) => false,
+        Err(e) => {
+            log::error!("[SEARCH JOB] save job {}/{} error: {e}", job.org_id, job.id);
+            true
+        }
+    }
+}
+
+fn write_hits(hits: &[json::Value]) -> Result<Bytes, anyhow::Error> {
+    let schema = arrow_json::reader::infer_json_schema_from_iterator(hits.iter().map(Ok))?;
+    let schema = Arc::new(schema);
+    let mut decoder = arrow_json::ReaderBuilder::new(schema.clone())
+        .with_batch_size(RESULT_BATCH_SIZE)
+        .with_coerce_primitive(true)
+        .build_decoder()?;
+    let props = WriterProperties::builder()
+        .set_compression(get_parquet_compression())
+        .build();
+    let mut buf = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buf, schema, Some(props))?;
+    for chunk in hits.chunks(RESULT_BATCH_SIZE) {
+        for hit in chunk {
+            decoder.decode(json::to_string(hit)?.as_bytes())?;
+        }
+        if let Some(batch) = decoder.flush()? {
+            writer.write(&batch)?;
+        }
+    }
+    writer.close()?;
+    Ok(buf.into())
+}
+
+fn read_hits(data: Bytes) -> Result<Vec<json::Value>, anyhow::Error> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(data)?.build()?;
+    let batches = reader.collect::<Result<Vec<_>, _>>()?;
+    let batches = batches.iter().collect::<Vec<_>>();
+    Ok(arrow_json::writer::record_batches_to_json_rows(&batches)?
+        .into_iter()
+        .map(json::Value::Object)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_result_file() {
+        let hits = (0..RESULT_BATCH_SIZE + 10)
+            .map(|i| {
+                if i % 2 == 0 {
+                    json::json!({"_timestamp": i, "log": format!("line {i}")})
+                } else {
+                    json::json!({"_timestamp": i, "code": i})
+                }
+            })
+            .collect::<Vec<_>>();
+        let data = write_hits(&hits).unwrap();
+        let read = read_hits(data).unwrap();
+        assert_eq!(read, hits);
+    }
+}
@@ -14,9 +14,8 @@
 
 use ::datafusion::arrow::{datatypes::Schema, ipc, json as arrow_json, record_batch::RecordBatch};
 use ahash::AHashMap as HashMap;
-use once_cell::sync::Lazy;
-use std::{cmp::min, io::Cursor, sync::Arc, time::Duration};
-use tokio::sync::Mutex;
+use regex::{NoExpand, Regex};
+use std::{cmp::min, io::Cursor, time::Duration};
 use tonic::{codec::CompressionEncoding, metadata::MetadataValue, transport::Channel, Request};
 use tracing::{info_span, Instrument};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
@@ -25,39 +24,141 @@ use uuid::Uuid;
 use crate::common::infra::{
     cluster,
     config::CONFIG,
-    dist_lock,
     errors::{Error, ErrorCodes},
 };
 use crate::common::meta::{
     common::FileKey,
     search,
+    sql::Sql as MetaSql,
     stream::{PartitionTimeLevel, ScanStats, StreamParams},
     StreamType,
 };
-use crate::common::utils::{flatten, json, str::find};
+use crate::common::utils::{
+    cancel::{cancelled_error, CancelToken},
+    flatten, json,
+    str::find,
+};
 use crate::handler::grpc::cluster_rpc;
 use crate::service::{db, file_list, format_partition_key, format_stream_name, stream};
 
+pub(crate) mod cache;
+pub(crate) mod cancel;
 pub(crate) mod datafusion;
+pub(crate) mod fulltext_index;
 pub(crate) mod grpc;
+pub(crate) mod jobs;
+pub(crate) mod ppl;
+pub(crate) mod queue;
 pub(crate) mod sql;
-
-pub(crate) static QUEUE_LOCKER: Lazy<Arc<Mutex<bool>>> =
-    Lazy::new(|| Arc::new(Mutex::const_new(false)));
+pub(crate) mod streaming;
 
 #[tracing::instrument(name = "service:search:enter", skip(req))]
 pub async fn search(
     org_id: &str,
     stream_type: StreamType,
+    user_id: &str,
+    priority: queue::Priority,
+    req: &search::Request,
+) -> Result<search::Response, Error> {
+    let session_id = Uuid::new_v4().to_string();
+    search_with_session(org_id, stream_type, &session_id, user_id, priority, req).await
+}
+
+/// search with a session id given by the caller, so that the search can be
+/// cancelled while it is still running
+#[tracing::instrument(name = "service:search:session", skip(req))]
+pub async fn search_with_session(
+    org_id: &str,
+    stream_type: StreamType,
+    session_id: &str,
+    user_id: &str,
+    priority: queue::Priority,
     req: &search::Request,
 ) -> Result<search::Response, Error> {
-    let mut req: cluster_rpc::SearchRequest = req.to_owned().into();
+    let mut req: cluster_rpc::SearchRequest = ppl::compile_request(req)?.into();
     req.org_id = org_id.to_string();
     req.stype = cluster_rpc::SearchType::User as i32;
     req.stream_type = stream_type.to_string();
-    tokio::task::spawn(async move { search_in_cluster(req).await })
+    let user_id = user_id.to_string();
+    // the search is cancelled when the caller drops this future, e.g. the
+    // http client disconnected
+    let session_id = session_id.to_string();
+    let guard = cancel::register(org_id, &user_id, &session_id);
+    let token = guard.token();
+    let task_session_id = session_id.clone();
+    let ret = tokio::task::spawn(async move {
+        search_in_cluster(req, &task_session_id, &user_id, priority, token).await
+    })
+    .await
+    .map_err(server_internal_error)?;
+    drop(guard);
+    ret.map(|mut res| {
+        res.session_id = session_id;
+        res
+    })
+}
+
+/// cancel the searches of the session on all the nodes, only the ones of
+/// `user_id` when it is given, returns false if the session isn't running
+/// anywhere
+pub async fn cancel(org_id: &str, session_id: &str, user_id: Option<&str>) -> Result<bool, Error> {
+    let nodes = cluster::get_cached_online_query_nodes().unwrap_or_default();
+    let mut tasks = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        if node.uuid == *cluster::LOCAL_NODE_UUID {
+            continue;
+        }
+        let req = cluster_rpc::CancelRequest {
+            org_id: org_id.to_string(),
+            session_id: session_id.to_string(),
+            user_id: user_id.unwrap_or_default().to_string(),
+        };
+        tasks.push(tokio::task::spawn(async move {
+            cancel_node(&node, req).await.map_err(|err| {
+                log::error!("search->cancel: node: {}, err: {:?}", node.id, err);
+                err
+            })
+        }));
+    }
+    let mut cancelled = cancel::cancel(org_id, session_id, user_id);
+    for task in tasks {
+        // a node that can't be reached has nothing to cancel for us
+        if let Ok(Ok(true)) = task.await {
+            cancelled = true;
+        }
+    }
+    Ok(cancelled)
+}
+
+async fn cancel_node(node: &cluster::Node, req: cluster_rpc::CancelRequest) -> Result<bool, Error> {
+    let org_id: MetadataValue<_> = req
+        .org_id
+        .parse()
+        .map_err(|_| Error::Message("invalid org_id".to_string()))?;
+    let mut request = tonic::Request::new(req);
+    request.set_timeout(Duration::from_secs(CONFIG.grpc.timeout));
+    let token: MetadataValue<_> = cluster::get_internal_grpc_token()
+        .parse()
+        .map_err(|_| Error::Message("invalid token".to_string()))?;
+    let channel = Channel::from_shared(node.grpc_addr.clone())
+        .unwrap()
+        .connect()
         .await
-        .map_err(server_internal_error)?
+        .map_err(|_| server_internal_error("connect search node error"))?;
+    let mut client = cluster_rpc::search_client::SearchClient::with_interceptor(
+        channel,
+        move |mut req: Request<()>| {
+            req.metadata_mut().insert("authorization", token.clone());
+            req.metadata_mut()
+                .insert(CONFIG.grpc.org_header_key.as_str(), org_id.clone());
+            Ok(req)
+        },
+    );
+    let resp = client
+        .cancel(request)
+        .await
+        .map_err(|err| server_internal_error(err.message()))?;
+    Ok(resp.into_inner().cancelled)
 }
 
 async fn get_times(sql: &sql::Sql, stream_type: StreamType) -> (i64, i64) {
@@ -111,20 +212,89 @@ async fn get_file_list(
     files
 }
 
+/// get the files of the streams joined in the query, every node reads all the
+/// files and the wal files of the joined streams
+#[tracing::instrument(skip(sql), fields(org_id = sql.org_id, stream_name = sql.stream_name))]
+async fn get_join_file_lists(sql: &sql::Sql) -> Result<Vec<cluster_rpc::SearchJoin>, Error> {
+    let mut joins = Vec::with_capacity(sql.joins.len());
+    for join in sql.joins.iter() {
+        let stream_settings = stream::stream_settings(&join.schema).unwrap_or_default();
+        let time_level = stream::unwrap_partition_time_level(
+            stream_settings.partition_time_level,
+            join.stream_type,
+        );
+        // enrichment tables are read whole
+        let (time_min, mut time_max) = match join.stream_type {
+            StreamType::EnrichmentTables => (0, 0),
+            _ => sql.meta.time_range.unwrap_or_default(),
+        };
+        if time_max == 0 {
+            time_max = chrono::Utc::now().timestamp_micros();
+        }
+        let mut file_list = file_list::query(
+            &sql.org_id,
+            &join.stream_name,
+            join.stream_type,
+            time_level,
+            time_min,
+            time_max,
+        )
+        .await
+        .unwrap_or_default();
+        file_list.sort_by(|a, b| a.key.cmp(&b.key));
+        // the recent data is still in the wal of the ingesters
+        let wal_files = match join.stream_type {
+            StreamType::EnrichmentTables => vec![],
+            _ => crate::service::promql::search::grpc::wal::get_file_list(
+                &sql.org_id,
+                join.stream_type,
+                &join.stream_name,
+                (time_min, time_max),
+            )
+            .await
+            .map_err(server_internal_error)?,
+        };
+        log::info!(
+            "search->join: stream: {}/{}, num: {}, wal num: {}",
+            join.stream_type,
+            join.stream_name,
+            file_list.len(),
+            wal_files.len()
+        );
+        joins.push(cluster_rpc::SearchJoin {
+            stream_type: join.stream_type.to_string(),
+            stream_name: join.stream_name.clone(),
+            file_list: file_list.iter().map(cluster_rpc::FileKey::from).collect(),
+            wal_files,
+        });
+    }
+    Ok(joins)
+}
+
 #[tracing::instrument(
     name = "service:search:cluster",
     skip(req),
     fields(org_id = req.org_id)
 )]
-async fn search_in_cluster(req: cluster_rpc::SearchRequest) -> Result<search::Response, Error> {
+async fn search_in_cluster(
+    req: cluster_rpc::SearchRequest,
+    session_id: &str,
+    user_id: &str,
+    priority: queue::Priority,
+    token: CancelToken,
+) -> Result<search::Response, Error> {
     let start = std::time::Instant::now();
 
     // handle request time range
     let stream_type = StreamType::from(req.stream_type.as_str());
-    let meta = sql::Sql::new(&req).await?;
+    let org_id = req.org_id.clone();
+    let mut streams = stream_requests(req, stream_type).await?;
 
-    // get a cluster search queue lock
-    let mut locker = dist_lock::lock("search/cluster_queue", 0).await?;
+    // wait for a search slot
+    let permit = tokio::select! {
+        ret = queue::acquire(&org_id, user_id, priority) => ret?,
+        _ = token.cancelled() => return Err(cancelled_error()),
+    };
     let took_wait = start.elapsed().as_millis() as usize;
 
     // get nodes from cluster
@@ -133,6 +303,143 @@ async fn search_in_cluster(req: cluster_rpc::SearchRequest) -> Result<search::Re
     nodes.sort_by_key(|x| x.id);
     let nodes = nodes;
 
+    // partition request, here plus 1 second, because division is integer, maybe lose some precision
+    let job = cluster_rpc::Job {
+        session_id: session_id.to_string(),
+        job: session_id[30..].to_string(), // take the last 6 characters as job id
+        stage: 0,
+        partition: 0,
+        user_id: user_id.to_string(),
+    };
+
+    let mut plan = None;
+    let mut requests = Vec::new();
+    let single_stream = streams.len() == 1;
+    for (req, meta) in streams.iter_mut() {
+        let stream_settings = stream::stream_settings(&meta.schema).unwrap_or_default();
+        let partition_time_level =
+            stream::unwrap_partition_time_level(stream_settings.partition_time_level, stream_type);
+
+        // the buckets found in the result cache are not searched again
+        if single_stream {
+            let (time_min, time_max) = get_times(meta, stream_type).await;
+            plan = cache::plan(req, meta, stream_type, time_min, time_max);
+        }
+
+        let mut file_list = get_file_list(meta, stream_type, partition_time_level).await;
+        if let Some(plan) = plan.as_ref() {
+            file_list.retain(|file| plan.matches(file));
+        }
+        req.joins = get_join_file_lists(meta).await?;
+        log::info!(
+            "search->file_list: stream: {}, time_range: {:?}, num: {}",
+            meta.stream_name,
+            meta.meta.time_range,
+            file_list.len()
+        );
+
+        // make cluster request
+        requests.extend(node_requests(req, &job, &nodes, &file_list));
+    }
+    let results = match plan {
+        Some(plan) => cache::search(plan, requests, &token).await?,
+        None => {
+            let tasks = requests
+                .into_iter()
+                .map(|(node, req)| {
+                    let grpc_span =
+                        info_span!("service:search:cluster:grpc_search", org_id = req.org_id);
+                    tokio::task::spawn(search_node(node, req).instrument(grpc_span))
+                })
+                .collect();
+            cancel::join_all(tasks, &token).await?
+        }
+    };
+    // search done, release the slot
+    drop(permit);
+
+    // merge multiple instances data, the streams share the merge sql
+    let (req, meta) = &streams[0];
+    let query_type = req.query.as_ref().unwrap().query_type.to_lowercase();
+    let mut result = merge_grpc_result(meta, &query_type, results).await?;
+    result.set_cluster_took(start.elapsed().as_millis() as usize, took_wait);
+
+    log::info!(
+        "search->result: total: {}, took: {}, scan_size: {}",
+        result.total,
+        result.took,
+        result.scan_size,
+    );
+
+    Ok(result)
+}
+
+/// A stream pattern like `FROM "svc_*"` or `FROM "svc_a,svc_b"` searches all
+/// the matching streams, every stream gets its own request and the results of
+/// the streams are merged like the results of the nodes, with `_stream` added.
+async fn stream_requests(
+    req: cluster_rpc::SearchRequest,
+    stream_type: StreamType,
+) -> Result<Vec<(cluster_rpc::SearchRequest, sql::Sql)>, Error> {
+    let source = match MetaSql::new(&req.query.as_ref().unwrap().sql) {
+        Ok(meta) => meta.source,
+        Err(_) => "".to_string(), // reported by the sql parser below
+    };
+    if !source.contains('*') && !source.contains(',') {
+        let meta = sql::Sql::new(&req).await?;
+        return Ok(vec![(req, meta)]);
+    }
+    let streams = db::schema::list(&req.org_id, Some(stream_type), false)
+        .await
+        .unwrap_or_default();
+    let streams = match_streams(&source, streams.into_iter().map(|v| v.stream_name));
+    if streams.is_empty() {
+        return Err(Error::ErrorCode(ErrorCodes::SearchStreamNotFound(source)));
+    }
+
+    let re = Regex::new(&format!(
+        r#"(?i) from[ '"]+{}[ '"]?"#,
+        regex::escape(&source)
+    ))
+    .unwrap();
+    let mut requests = Vec::with_capacity(streams.len());
+    for stream_name in streams.iter() {
+        let mut req = req.clone();
+        let query = req.query.as_mut().unwrap();
+        let table = format!(" FROM \"{stream_name}\" ");
+        query.sql = re.replace(&query.sql, NoExpand(&table)).to_string();
+        query.query_context = query.query_context.replace(&source, stream_name);
+        req.streams = streams.clone();
+        let meta = sql::Sql::new(&req).await?;
+        requests.push((req, meta));
+    }
+    Ok(requests)
+}
+
+/// the streams matching a list of stream names, `*` matches any characters
+fn match_streams(source: &str, streams: impl Iterator<Item = String>) -> Vec<String> {
+    let patterns = source
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| Regex::new(&format!("^{}$", regex::escape(v).replace(r"\*", ".*"))).unwrap())
+        .collect::<Vec<_>>();
+    let mut streams = streams
+        .filter(|name| patterns.iter().any(|re| re.is_match(name)))
+        .collect::<Vec<_>>();
+    streams.sort();
+    streams.dedup();
+    streams
+}
+
+/// split the files between the queriers, every node gets the request for its
+/// part of the search, the ingesters search their wal
+fn node_requests(
+    req: &cluster_rpc::SearchRequest,
+    job: &cluster_rpc::Job,
+    nodes: &[cluster::Node],
+    file_list: &[FileKey],
+) -> Vec<(cluster::Node, cluster_rpc::SearchRequest)> {
     let querier_num = match nodes
         .iter()
         .filter(|node| cluster::is_querier(&node.role))
@@ -141,34 +448,14 @@ async fn search_in_cluster(req: cluster_rpc::SearchRequest) -> Result<search::Re
         0 => 1,
         n => n,
     };
-
-    let stream_settings = stream::stream_settings(&meta.schema).unwrap_or_default();
-    let partition_time_level =
-        stream::unwrap_partition_time_level(stream_settings.partition_time_level, stream_type);
-
-    let file_list = get_file_list(&meta, stream_type, partition_time_level).await;
     let file_num = file_list.len();
     let offset = if querier_num >= file_num {
         1
     } else {
         (file_num / querier_num) + 1
     };
-    log::info!(
-        "search->file_list: time_range: {:?}, num: {file_num}, offset: {offset}",
-        meta.meta.time_range
-    );
 
-    // partition request, here plus 1 second, because division is integer, maybe lose some precision
-    let mut session_id = Uuid::new_v4().to_string();
-    let job = cluster_rpc::Job {
-        session_id: session_id.clone(),
-        job: session_id.split_off(30), // take the last 6 characters as job id
-        stage: 0,
-        partition: 0,
-    };
-
-    // make cluster request
-    let mut tasks = Vec::new();
+    let mut requests = Vec::with_capacity(nodes.len());
     let mut offset_start: usize = 0;
     for (partition_no, node) in nodes.iter().cloned().enumerate() {
         let mut req = req.clone();
@@ -176,12 +463,10 @@ async fn search_in_cluster(req: cluster_rpc::SearchRequest) -> Result<search::Re
         job.partition = partition_no as i32;
         req.job = Some(job);
         req.stype = cluster_rpc::SearchType::WalOnly as i32;
-        let is_querier = cluster::is_querier(&node.role);
-        if is_querier {
+        if cluster::is_querier(&node.role) {
             if offset_start < file_num {
                 req.stype = cluster_rpc::SearchType::Cluster as i32;
                 req.file_list = file_list[offset_start..min(offset_start + offset, file_num)]
-                    .to_vec()
                     .iter()
                     .map(cluster_rpc::FileKey::from)
                     .collect();
@@ -190,97 +475,84 @@ async fn search_in_cluster(req: cluster_rpc::SearchRequest) -> Result<search::Re
                 continue; // no need more querier
             }
         }
-
-        let node_addr = node.grpc_addr.clone();
-        let grpc_span = info_span!("service:search:cluster:grpc_search", org_id = req.org_id);
-        let task = tokio::task::spawn(
-            async move {
-                let org_id: MetadataValue<_> = req
-                    .org_id
-                    .parse()
-                    .map_err(|_| Error::Message("invalid org_id".to_string()))?;
-                let mut request = tonic::Request::new(req);
-                request.set_timeout(Duration::from_secs(CONFIG.grpc.timeout));
-
-                opentelemetry::global::get_text_map_propagator(|propagator| {
-                    propagator.inject_context(
-                        &tracing::Span::current().context(),
-                        &mut MetadataMap(request.metadata_mut()),
-                    )
-                });
-
-                let token: MetadataValue<_> = cluster::get_internal_grpc_token()
-                    .parse()
-                    .map_err(|_| Error::Message("invalid token".to_string()))?;
-                let channel = Channel::from_shared(node_addr)
-                    .unwrap()
-                    .connect()
-                    .await
-                    .map_err(|err| {
-                        log::error!("search->grpc: node: {}, connect err: {:?}", node.id, err);
-                        server_internal_error("connect search node error")
-                    })?;
-                let mut client = cluster_rpc::search_client::SearchClient::with_interceptor(
-                    channel,
-                    move |mut req: Request<()>| {
-                        req.metadata_mut().insert("authorization", token.clone());
-                        req.metadata_mut()
-                            .insert(CONFIG.grpc.org_header_key.as_str(), org_id.clone());
-                        Ok(req)
-                    },
-                );
-                client = client
-                    .send_compressed(CompressionEncoding::Gzip)
-                    .accept_compressed(CompressionEncoding::Gzip);
-                let response: cluster_rpc::SearchResponse = match client.search(request).await {
-                    Ok(res) => res.into_inner(),
-                    Err(err) => {
-                        log::error!("search->grpc: node: {}, search err: {:?}", node.id, err);
-                        if err.code() == tonic::Code::Internal {
-                            let err = ErrorCodes::from_json(err.message())?;
-                            return Err(Error::ErrorCode(err));
-                        }
-                        return Err(server_internal_error("search node error"));
-                    }
-                };
-
-                log::info!(
-                    "search->grpc: result node: {}, is_querier: {}, total: {}, took: {}, files: {}, scan_size: {}",
-                    node.id,
-                    is_querier,
-                    response.total,
-                    response.took,
-                    response.scan_stats.as_ref().unwrap().files,
-                    response.scan_stats.as_ref().unwrap().original_size,
-                );
-                Ok(response)
-            }
-            .instrument(grpc_span),
-        );
-        tasks.push(task);
+        requests.push((node, req));
     }
+    requests
+}
 
-    let mut results = Vec::new();
-    for task in tasks {
-        let result = task
-            .await
-            .map_err(|err| Error::ErrorCode(ErrorCodes::ServerInternalError(err.to_string())))?;
-        match result {
-            Ok(res) => results.push(res),
-            Err(err) => {
-                // search done, release lock
-                dist_lock::unlock(&mut locker).await?;
-                return Err(err);
+async fn search_node(
+    node: cluster::Node,
+    req: cluster_rpc::SearchRequest,
+) -> Result<cluster_rpc::SearchResponse, Error> {
+    let org_id: MetadataValue<_> = req
+        .org_id
+        .parse()
+        .map_err(|_| Error::Message("invalid org_id".to_string()))?;
+    let mut request = tonic::Request::new(req);
+    request.set_timeout(Duration::from_secs(CONFIG.grpc.timeout));
+
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(
+            &tracing::Span::current().context(),
+            &mut MetadataMap(request.metadata_mut()),
+        )
+    });
+
+    let token: MetadataValue<_> = cluster::get_internal_grpc_token()
+        .parse()
+        .map_err(|_| Error::Message("invalid token".to_string()))?;
+    let channel = Channel::from_shared(node.grpc_addr.clone())
+        .unwrap()
+        .connect()
+        .await
+        .map_err(|err| {
+            log::error!("search->grpc: node: {}, connect err: {:?}", node.id, err);
+            server_internal_error("connect search node error")
+        })?;
+    let mut client = cluster_rpc::search_client::SearchClient::with_interceptor(
+        channel,
+        move |mut req: Request<()>| {
+            req.metadata_mut().insert("authorization", token.clone());
+            req.metadata_mut()
+                .insert(CONFIG.grpc.org_header_key.as_str(), org_id.clone());
+            Ok(req)
+        },
+    );
+    client = client
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip);
+    let response: cluster_rpc::SearchResponse = match client.search(request).await {
+        Ok(res) => res.into_inner(),
+        Err(err) => {
+            log::error!("search->grpc: node: {}, search err: {:?}", node.id, err);
+            if err.code() == tonic::Code::Internal {
+                let err = ErrorCodes::from_json(err.message())?;
+                return Err(Error::ErrorCode(err));
             }
+            return Err(server_internal_error("search node error"));
         }
-    }
-    // search done, release lock
-    dist_lock::unlock(&mut locker).await?;
+    };
+
+    log::info!(
+        "search->grpc: result node: {}, is_querier: {}, total: {}, took: {}, files: {}, scan_size: {}",
+        node.id,
+        cluster::is_querier(&node.role),
+        response.total,
+        response.took,
+        response.scan_stats.as_ref().unwrap().files,
+        response.scan_stats.as_ref().unwrap().original_size,
+    );
+    Ok(response)
+}
 
-    // merge multiple instances data
+/// merge the responses of the nodes into the final result
+async fn merge_grpc_result(
+    sql: &sql::Sql,
+    query_type: &str,
+    results: Vec<cluster_rpc::SearchResponse>,
+) -> Result<search::Response, Error> {
     let mut scan_stats = ScanStats::new();
     let mut batches: HashMap<String, Vec<Vec<RecordBatch>>> = HashMap::new();
-    let sql = Arc::new(meta);
     for resp in results {
         scan_stats.add(&resp.scan_stats.as_ref().unwrap().into());
         // handle hits
@@ -306,7 +578,7 @@ async fn search_in_cluster(req: cluster_rpc::SearchRequest) -> Result<search::Re
     // merge all batches
     for (name, batch) in batches.iter_mut() {
         let merge_sql = if name == "query" {
-            sql.origin_sql.clone()
+            sql.merge_sql()
         } else {
             sql.aggs
                 .get(name.strip_prefix("agg_").unwrap())
@@ -336,7 +608,6 @@ async fn search_in_cluster(req: cluster_rpc::SearchRequest) -> Result<search::Re
     let mut result = search::Response::new(sql.meta.offset, sql.meta.limit);
 
     // hits
-    let query_type = req.query.as_ref().unwrap().query_type.to_lowercase();
     let empty_vec = vec![];
     let batches_query = match batches.get("query") {
         Some(batches) => batches,
@@ -400,7 +671,6 @@ async fn search_in_cluster(req: cluster_rpc::SearchRequest) -> Result<search::Re
     result.aggs.remove("_count");
 
     result.set_total(total);
-    result.set_cluster_took(start.elapsed().as_millis() as usize, took_wait);
     result.set_file_count(scan_stats.files as usize);
     result.set_scan_size(scan_stats.original_size as usize);
 
@@ -408,13 +678,6 @@ async fn search_in_cluster(req: cluster_rpc::SearchRequest) -> Result<search::Re
         result.response_type = "matrix".to_string();
     }
 
-    log::info!(
-        "search->result: total: {}, took: {}, scan_size: {}",
-        result.total,
-        result.took,
-        result.scan_size,
-    );
-
     Ok(result)
 }
 
@@ -549,6 +812,19 @@ pub fn server_internal_error(error: impl ToString) -> Error {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_match_streams() {
+        let streams = ["svc_b", "svc_a", "svc.x", "other", "svc_a"];
+        let streams = || streams.iter().map(|v| v.to_string());
+        assert_eq!(match_streams("svc_*", streams()), vec!["svc_a", "svc_b"]);
+        assert_eq!(
+            match_streams("other, svc.*", streams()),
+            vec!["other", "svc.x"]
+        );
+        assert_eq!(match_streams("svc_a,svc_c", streams()), vec!["svc_a"]);
+        assert!(match_streams("foo*", streams()).is_empty());
+    }
+
     #[test]
     fn test_matches_by_partition_key() {
         let path = "files/default/logs/gke-fluentbit/2023/04/14/08/kuberneteshost=gke-dev1/kubernetesnamespacename=ziox-qqx/7052558621820981249.parquet";
@@ -0,0 +1,799 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::sql::SqlMode;
+use crate::common::{
+    infra::{
+        config::CONFIG,
+        errors::{Error, ErrorCodes},
+    },
+    meta::search,
+};
+
+/// `sql_mode` of the piped queries, e.g.
+/// `stream=nginx | where status>=500 | stats count() by host | sort -count`
+pub const SQL_MODE_PPL: &str = "ppl";
+
+/// name of the time bucket column of `span()` and `timechart`
+const TIME_BUCKET: &str = "_time";
+
+const KEYWORDS: [&str; 11] = [
+    "and", "or", "not", "like", "ilike", "in", "between", "is", "null", "true", "false",
+];
+
+const AGGREGATIONS: [&str; 5] = ["count", "sum", "avg", "min", "max"];
+
+static RE_SOURCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?is)^\s*(?:stream|source)\s*=\s*("[^"]*"|`[^`]*`|\S+)(.*)$"#).unwrap()
+});
+static RE_SPAN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\d+)(s|m|h|d|w)$").unwrap());
+
+/// compile the piped query of the request to sql of the full mode, requests of
+/// the other modes are returned as they are
+pub fn compile_request(req: &search::Request) -> Result<search::Request, Error> {
+    let mut req = req.to_owned();
+    if req.query.sql_mode.eq_ignore_ascii_case(SQL_MODE_PPL) {
+        req.query.sql = compile(&req.query.sql)
+            .map_err(|e| Error::ErrorCode(ErrorCodes::SearchSQLNotValid(e.to_string())))?;
+        req.query.sql_mode = SqlMode::Full.to_string();
+    }
+    Ok(req)
+}
+
+/// Compile a piped query to a single sql statement over the stream. Commands:
+/// - `stream=<name> [terms]`: source, `field=value` terms and full text words
+/// - `search <terms>` / `where <expr>`: filters, `where` after `stats` filters
+///   the aggregations
+/// - `parse <field> "<regex>"` / `rex field=<field> "<regex>"`: extracts the
+///   named groups of the regex as fields
+/// - `eval <name>=<expr>[, ...]`: computed fields
+/// - `fields <field>[, ...]`: output fields
+/// - `stats <agg>[ as <name>][, ...] [by <field|span([field, ]<span>)>, ...]`
+/// - `timechart [span=<span>] <agg>[, ...] [by <field>]`
+/// - `sort [-|+]<field>[, ...]` and `head [n]`
+pub fn compile(query: &str) -> Result<String> {
+    let mut segments = split_pipes(query).into_iter();
+    let source = segments.next().unwrap_or_default();
+    let caps = RE_SOURCE
+        .captures(&source)
+        .ok_or_else(|| anyhow!("piped query must start with stream=<name>"))?;
+    let stream = caps
+        .get(1)
+        .unwrap()
+        .as_str()
+        .trim_matches(|c| c == '"' || c == '`');
+    let mut pipeline = Pipeline::new(stream);
+    let terms = tokenize(caps.get(2).unwrap().as_str())?;
+    if !terms.is_empty() {
+        let filter = pipeline.search_terms(&terms)?;
+        pipeline.filters.push(filter);
+    }
+    for segment in segments {
+        pipeline.command(&segment)?;
+    }
+    Ok(pipeline.sql())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(String),
+    Op(String),
+    Comma,
+    LParen,
+    RParen,
+}
+
+#[derive(Default)]
+struct Stats {
+    /// (alias, expr)
+    groups: Vec<(String, String)>,
+    /// (alias, expr)
+    aggs: Vec<(String, String)>,
+}
+
+struct Pipeline {
+    stream: String,
+    /// fields extracted or computed by the pipeline, (name, expr)
+    fields: Vec<(String, String)>,
+    projection: Vec<String>,
+    filters: Vec<String>,
+    stats: Option<Stats>,
+    having: Vec<String>,
+    order_by: Vec<(String, bool)>,
+    limit: Option<usize>,
+}
+
+impl Pipeline {
+    fn new(stream: &str) -> Self {
+        Pipeline {
+            stream: stream.to_string(),
+            fields: vec![],
+            projection: vec![],
+            filters: vec![],
+            stats: None,
+            having: vec![],
+            order_by: vec![],
+            limit: None,
+        }
+    }
+
+    fn command(&mut self, segment: &str) -> Result<()> {
+        let segment = segment.trim();
+        let (name, rest) = match segment.find(char::is_whitespace) {
+            Some(pos) => (&segment[..pos], &segment[pos..]),
+            None => (segment, ""),
+        };
+        let tokens = tokenize(rest)?;
+        let name = name.to_lowercase();
+        if self.stats.is_some() && matches!(name.as_str(), "search" | "parse" | "rex" | "eval") {
+            bail!("{name} is not supported after stats");
+        }
+        match name.as_str() {
+            "where" => {
+                let expr = self.expr(&tokens)?;
+                if self.stats.is_some() {
+                    self.having.push(expr);
+                } else {
+                    self.filters.push(expr);
+                }
+            }
+            "search" => {
+                let filter = self.search_terms(&tokens)?;
+                self.filters.push(filter);
+            }
+            "parse" | "rex" => self.parse(&tokens)?,
+            "eval" => self.eval(&tokens)?,
+            "fields" => self.fields(&tokens)?,
+            "stats" => self.stats(&tokens, false)?,
+            "timechart" => self.timechart(&tokens)?,
+            "sort" => self.sort(&tokens)?,
+            "head" | "limit" => self.head(&tokens)?,
+            "" => bail!("empty command in piped query"),
+            _ => bail!("unknown command: {name}"),
+        }
+        Ok(())
+    }
+
+    /// the sql of a field, fields of the pipeline are replaced by their
+    /// expressions
+    fn column(&self, name: &str) -> String {
+        if let Some(stats) = &self.stats {
+            if let Some((_, expr)) = stats.aggs.iter().find(|(alias, _)| alias == name) {
+                return expr.clone();
+            }
+        }
+        match self.fields.iter().find(|(field, _)| field == name) {
+            Some((_, expr)) => format!("({expr})"),
+            None => ident_sql(name),
+        }
+    }
+
+    fn set_field(&mut self, name: &str, expr: String) {
+        self.fields.retain(|(field, _)| field != name);
+        self.fields.push((name.to_string(), expr));
+    }
+
+    fn expr(&self, tokens: &[Token]) -> Result<String> {
+        if tokens.is_empty() {
+            bail!("missing expression");
+        }
+        let mut sql = String::new();
+        for (i, token) in tokens.iter().enumerate() {
+            let prev = if i > 0 { tokens.get(i - 1) } else { None };
+            let part = match token {
+                Token::Ident(name) if KEYWORDS.contains(&name.to_lowercase().as_str()) => {
+                    name.to_uppercase()
+                }
+                Token::Ident(name) if tokens.get(i + 1) == Some(&Token::LParen) => {
+                    name.to_lowercase()
+                }
+                Token::Ident(name) => self.column(name),
+                Token::Str(value) => quote_str(value),
+                Token::Num(value) => value.clone(),
+                Token::Op(op) if op == "==" => "=".to_string(),
+                Token::Op(op) => op.clone(),
+                Token::Comma => ",".to_string(),
+                Token::LParen => "(".to_string(),
+                Token::RParen => ")".to_string(),
+            };
+            let tight = sql.is_empty()
+                || matches!(token, Token::Comma | Token::RParen)
+                || prev == Some(&Token::LParen)
+                || (token == &Token::LParen && matches!(prev, Some(Token::Ident(_))));
+            if !tight {
+                sql.push(' ');
+            }
+            sql.push_str(&part);
+        }
+        Ok(sql)
+    }
+
+    /// `field=value` terms and full text words, adjacent terms are ANDed
+    fn search_terms(&self, tokens: &[Token]) -> Result<String> {
+        let mut parts: Vec<String> = vec![];
+        let mut need_and = false;
+        let mut i = 0;
+        while i < tokens.len() {
+            match &tokens[i] {
+                Token::Ident(kw) if matches!(kw.to_lowercase().as_str(), "and" | "or") => {
+                    parts.push(kw.to_uppercase());
+                    need_and = false;
+                }
+                Token::Ident(kw) if kw.eq_ignore_ascii_case("not") => {
+                    if need_and {
+                        parts.push("AND".to_string());
+                    }
+                    parts.push("NOT".to_string());
+                    need_and = false;
+                }
+                Token::LParen => {
+                    if need_and {
+                        parts.push("AND".to_string());
+                    }
+                    parts.push("(".to_string());
+                    need_and = false;
+                }
+                Token::RParen => {
+                    parts.push(")".to_string());
+                    need_and = true;
+                }
+                token => {
+                    if need_and {
+                        parts.push("AND".to_string());
+                    }
+                    need_and = true;
+                    if let (Token::Ident(field), Some(Token::Op(op)), Some(value)) =
+                        (token, tokens.get(i + 1), tokens.get(i + 2))
+                    {
+                        if is_comparison(op) {
+                            let op = if op == "==" { "=" } else { op };
+                            parts.push(format!("{} {op} {}", self.column(field), literal(value)?));
+                            i += 3;
+                            continue;
+                        }
+                    }
+                    let word = match token {
+                        Token::Ident(v) | Token::Str(v) | Token::Num(v) => v,
+                        _ => bail!("unexpected search term: {token:?}"),
+                    };
+                    parts.push(format!("match_all({})", quote_str(word)));
+                }
+            }
+            i += 1;
+        }
+        Ok(parts.join(" ").replace("( ", "(").replace(" )", ")"))
+    }
+
+    fn parse(&mut self, tokens: &[Token]) -> Result<()> {
+        let (field, pattern) = match tokens {
+            [Token::Ident(field), Token::Str(pattern)] => (field, pattern),
+            [Token::Ident(key), Token::Op(op), Token::Ident(field), Token::Str(pattern)]
+                if key == "field" && op == "=" =>
+            {
+                (field, pattern)
+            }
+            _ => bail!("usage: parse <field> \"<regex with named groups>\""),
+        };
+        let re = Regex::new(pattern)?;
+        let source = self.column(field);
+        let mut found = false;
+        for (i, name) in re.capture_names().enumerate() {
+            if let Some(name) = name {
+                let expr = format!("regexp_match({source}, {})[{i}]", quote_str(pattern));
+                self.set_field(name, expr);
+                found = true;
+            }
+        }
+        if !found {
+            bail!("the regex of parse has no named group");
+        }
+        Ok(())
+    }
+
+    fn eval(&mut self, tokens: &[Token]) -> Result<()> {
+        for item in split_commas(tokens) {
+            match item {
+                [Token::Ident(name), Token::Op(op), expr @ ..] if op == "=" => {
+                    let expr = self.expr(expr)?;
+                    self.set_field(name, expr);
+                }
+                _ => bail!("usage: eval <name>=<expr>[, ...]"),
+            }
+        }
+        Ok(())
+    }
+
+    fn fields(&mut self, tokens: &[Token]) -> Result<()> {
+        let mut names = vec![];
+        for token in tokens {
+            match token {
+                Token::Ident(name) => names.push(name.clone()),
+                Token::Comma => {}
+                _ => bail!("usage: fields <field>[, ...]"),
+            }
+        }
+        if names.is_empty() {
+            bail!("usage: fields <field>[, ...]");
+        }
+        self.projection = names;
+        Ok(())
+    }
+
+    fn stats(&mut self, tokens: &[Token], time_bucket: bool) -> Result<()> {
+        if self.stats.is_some() {
+            bail!("only one stats or timechart is supported");
+        }
+        let by = tokens
+            .iter()
+            .position(|t| matches!(t, Token::Ident(v) if v.eq_ignore_ascii_case("by")));
+        let (aggs, groups) = match by {
+            Some(pos) => (&tokens[..pos], &tokens[pos + 1..]),
+            None => (tokens, &[][..]),
+        };
+        if aggs.is_empty() {
+            bail!("stats needs at least one aggregation");
+        }
+
+        let mut stats = Stats::default();
+        if time_bucket {
+            stats.groups.push((
+                TIME_BUCKET.to_string(),
+                histogram_sql(&CONFIG.common.column_timestamp, None)?,
+            ));
+        }
+        for group in split_commas(groups) {
+            match group {
+                [Token::Ident(f), Token::LParen, args @ .., Token::RParen]
+                    if f.eq_ignore_ascii_case("span") =>
+                {
+                    let (field, span) = match args {
+                        [Token::Ident(span)] => (CONFIG.common.column_timestamp.as_str(), span),
+                        [Token::Ident(field), Token::Comma, Token::Ident(span)] => {
+                            (field.as_str(), span)
+                        }
+                        _ => bail!("usage: span([field, ]<span>)"),
+                    };
+                    stats.groups.push((
+                        TIME_BUCKET.to_string(),
+                        histogram_sql(&self.column(field), Some(span))?,
+                    ));
+                }
+                [Token::Ident(name)] => stats.groups.push((name.clone(), self.column(name))),
+                _ => bail!("stats can only group by fields and span()"),
+            }
+        }
+
+        for agg in split_commas(aggs) {
+            let (call, alias) = match agg {
+                [call @ .., Token::Ident(kw), Token::Ident(alias)]
+                    if kw.eq_ignore_ascii_case("as") =>
+                {
+                    (call, Some(alias))
+                }
+                _ => (agg, None),
+            };
+            let (func, args) = match call {
+                [Token::Ident(func)] => (func.to_lowercase(), &[][..]),
+                [Token::Ident(func), Token::LParen, args @ .., Token::RParen] => {
+                    (func.to_lowercase(), args)
+                }
+                _ => bail!("invalid aggregation: {call:?}"),
+            };
+            if !AGGREGATIONS.contains(&func.as_str()) {
+                bail!("unsupported aggregation: {func}");
+            }
+            let (expr, name) = match args {
+                [] if func == "count" => ("count(*)".to_string(), func),
+                [Token::Op(op)] if op == "*" && func == "count" => ("count(*)".to_string(), func),
+                [Token::Ident(field)] => (
+                    format!("{func}({})", self.column(field)),
+                    format!("{func}_{field}"),
+                ),
+                _ => bail!("{func} needs a field"),
+            };
+            stats.aggs.push((alias.cloned().unwrap_or(name), expr));
+        }
+        self.stats = Some(stats);
+        Ok(())
+    }
+
+    fn timechart(&mut self, tokens: &[Token]) -> Result<()> {
+        let (span, tokens) = match tokens {
+            [Token::Ident(kw), Token::Op(op), Token::Ident(span), rest @ ..]
+                if kw.eq_ignore_ascii_case("span") && op == "=" =>
+            {
+                (Some(span), rest)
+            }
+            _ => (None, tokens),
+        };
+        self.stats(tokens, true)?;
+        if let Some(span) = span {
+            let stats = self.stats.as_mut().unwrap();
+            stats.groups[0].1 = histogram_sql(&CONFIG.common.column_timestamp, Some(span))?;
+        }
+        Ok(())
+    }
+
+    fn sort(&mut self, tokens: &[Token]) -> Result<()> {
+        for item in split_commas(tokens) {
+            let (name, desc) = match item {
+                [Token::Op(op), Token::Ident(name)] if op == "-" || op == "+" => (name, op == "-"),
+                [Token::Ident(name)] => (name, false),
+                [Token::Ident(name), Token::Ident(dir)]
+                    if dir.eq_ignore_ascii_case("asc") || dir.eq_ignore_ascii_case("desc") =>
+                {
+                    (name, dir.eq_ignore_ascii_case("desc"))
+                }
+                _ => bail!("usage: sort [-|+]<field>[, ...]"),
+            };
+            self.order_by.push((name.clone(), desc));
+        }
+        if self.order_by.is_empty() {
+            bail!("usage: sort [-|+]<field>[, ...]");
+        }
+        Ok(())
+    }
+
+    fn head(&mut self, tokens: &[Token]) -> Result<()> {
+        let limit = match tokens {
+            [] => 10,
+            [Token::Num(n)] => n.parse()?,
+            _ => bail!("usage: head [n]"),
+        };
+        self.limit = Some(self.limit.map_or(limit, |v| v.min(limit)));
+        Ok(())
+    }
+
+    fn sql(&self) -> String {
+        let mut select = vec![];
+        let mut group_by = vec![];
+        match &self.stats {
+            Some(stats) => {
+                for (alias, expr) in stats.groups.iter().chain(stats.aggs.iter()) {
+                    if self.projection.is_empty() || self.projection.contains(alias) {
+                        select.push(select_sql(alias, expr));
+                    }
+                }
+                group_by = stats
+                    .groups
+                    .iter()
+                    .map(|(alias, _)| ident_sql(alias))
+                    .collect();
+            }
+            None if !self.projection.is_empty() => {
+                for name in self.projection.iter() {
+                    match self.fields.iter().find(|(field, _)| field == name) {
+                        Some((_, expr)) => select.push(select_sql(name, expr)),
+                        None => select.push(ident_sql(name)),
+                    }
+                }
+            }
+            None => {
+                select.push("*".to_string());
+                for (name, expr) in self.fields.iter() {
+                    select.push(select_sql(name, expr));
+                }
+            }
+        }
+
+        let mut sql = format!("SELECT {} FROM \"{}\"", select.join(", "), self.stream);
+        if !self.filters.is_empty() {
+            sql.push_str(&format!(" WHERE {}", and_sql(&self.filters)));
+        }
+        if !group_by.is_empty() {
+            sql.push_str(&format!(" GROUP BY {}", group_by.join(", ")));
+        }
+        if !self.having.is_empty() {
+            sql.push_str(&format!(" HAVING {}", and_sql(&self.having)));
+        }
+        let order_by = if !self.order_by.is_empty() {
+            self.order_by
+                .iter()
+                .map(|(name, desc)| {
+                    format!("{} {}", ident_sql(name), if *desc { "DESC" } else { "ASC" })
+                })
+                .collect::<Vec<_>>()
+        } else {
+            match &self.stats {
+                Some(stats) if stats.groups.iter().any(|(alias, _)| alias == TIME_BUCKET) => {
+                    vec![format!("{} ASC", ident_sql(TIME_BUCKET))]
+                }
+                Some(_) => vec![],
+                None => vec![format!("{} DESC", CONFIG.common.column_timestamp)],
+            }
+        };
+        if !order_by.is_empty() {
+            sql.push_str(&format!(" ORDER BY {}", order_by.join(", ")));
+        }
+        if let Some(limit) = self.limit {
+            sql.push_str(&format!(" LIMIT {limit}"));
+        }
+        sql
+    }
+}
+
+/// split the query at the pipes which are not quoted
+fn split_pipes(query: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut cur = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for c in query.chars() {
+        match quote {
+            Some(q) => {
+                if !escaped && c == q {
+                    quote = None;
+                }
+                escaped = !escaped && c == '\\';
+                cur.push(c);
+            }
+            None => match c {
+                '"' | '\'' | '`' => {
+                    quote = Some(c);
+                    cur.push(c);
+                }
+                '|' => parts.push(std::mem::take(&mut cur)),
+                _ => cur.push(c),
+            },
+        }
+    }
+    parts.push(cur);
+    parts
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '"' | '\'' | '`' => {
+                // only an escaped quote is unescaped, regex patterns keep their
+                // backslashes
+                let mut value = String::new();
+                let mut j = i + 1;
+                let mut closed = false;
+                while j < chars.len() {
+                    if chars[j] == '\\' && chars.get(j + 1) == Some(&c) {
+                        value.push(c);
+                        j += 2;
+                    } else if chars[j] == c {
+                        closed = true;
+                        j += 1;
+                        break;
+                    } else {
+                        value.push(chars[j]);
+                        j += 1;
+                    }
+                }
+                if !closed {
+                    bail!("unterminated quote in piped query");
+                }
+                tokens.push(if c == '`' {
+                    Token::Ident(value)
+                } else {
+                    Token::Str(value)
+                });
+                i = j;
+            }
+            c if c.is_ascii_digit() => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+                let value: String = chars[start..i].iter().collect();
+                // spans like 5m are words
+                if value.chars().all(|c| c.is_ascii_digit() || c == '.') {
+                    tokens.push(Token::Num(value));
+                } else {
+                    tokens.push(Token::Ident(value));
+                }
+            }
+            c if c.is_alphabetic() || c == '_' || c == '@' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '@' | '.'))
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            _ => {
+                let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+                if [">=", "<=", "!=", "==", "<>"].contains(&two.as_str()) {
+                    tokens.push(Token::Op(two));
+                    i += 2;
+                } else if "=<>+-*/%".contains(c) {
+                    tokens.push(Token::Op(c.to_string()));
+                    i += 1;
+                } else {
+                    bail!("unexpected character '{c}' in piped query");
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// split the tokens at the commas which are not in parentheses
+fn split_commas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LParen => depth += 1,
+            Token::RParen => depth -= 1,
+            Token::Comma if depth == 0 => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    parts
+}
+
+fn is_comparison(op: &str) -> bool {
+    matches!(op, "=" | "==" | "!=" | "<>" | "<" | "<=" | ">" | ">=")
+}
+
+/// the value of a search term, bare words are strings
+fn literal(token: &Token) -> Result<String> {
+    match token {
+        Token::Num(v) => Ok(v.clone()),
+        Token::Str(v) | Token::Ident(v) => Ok(quote_str(v)),
+        _ => bail!("invalid value in search term: {token:?}"),
+    }
+}
+
+fn quote_str(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn ident_sql(name: &str) -> String {
+    if name == CONFIG.common.column_timestamp {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+fn select_sql(alias: &str, expr: &str) -> String {
+    let ident = ident_sql(alias);
+    if ident == expr {
+        ident
+    } else {
+        format!("{expr} AS {ident}")
+    }
+}
+
+fn and_sql(exprs: &[String]) -> String {
+    if exprs.len() == 1 {
+        return exprs[0].clone();
+    }
+    exprs
+        .iter()
+        .map(|e| format!("({e})"))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+fn histogram_sql(field: &str, span: Option<&String>) -> Result<String> {
+    let Some(span) = span else {
+        return Ok(format!("histogram({field})"));
+    };
+    let caps = RE_SPAN
+        .captures(span)
+        .ok_or_else(|| anyhow!("invalid span: {span}, e.g. 30s, 5m, 1h, 1d"))?;
+    let unit = match caps.get(2).unwrap().as_str() {
+        "s" => "second",
+        "m" => "minute",
+        "h" => "hour",
+        "d" => "day",
+        _ => "week",
+    };
+    Ok(format!(
+        "histogram({field}, '{} {unit}')",
+        caps.get(1).unwrap().as_str()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile() {
+        let cases = [
+            (
+                "stream=nginx | where status>=500 | stats count() by host | sort -count",
+                r#"SELECT "host", count(*) AS "count" FROM "nginx" WHERE "status" >= 500 GROUP BY "host" ORDER BY "count" DESC"#,
+            ),
+            (
+                "stream=nginx error host=web1 | head 20",
+                r#"SELECT * FROM "nginx" WHERE match_all('error') AND "host" = 'web1' ORDER BY _timestamp DESC LIMIT 20"#,
+            ),
+            (
+                r#"stream=nginx | where method == "GET" and lower(path) like '/api%' | fields path, status"#,
+                r#"SELECT "path", "status" FROM "nginx" WHERE "method" = 'GET' AND lower("path") LIKE '/api%' ORDER BY _timestamp DESC"#,
+            ),
+            (
+                r#"stream=app | parse log "user=(?P<user>\w+) took=(?P<took>\d+)" | stats avg(took) as avg, count by user | where count > 10"#,
+                r#"SELECT (regexp_match("log", 'user=(?P<user>\w+) took=(?P<took>\d+)')[1]) AS "user", avg((regexp_match("log", 'user=(?P<user>\w+) took=(?P<took>\d+)')[2])) AS "avg", count(*) AS "count" FROM "app" GROUP BY "user" HAVING count(*) > 10"#,
+            ),
+            (
+                "stream=app | eval ms = took * 1000 | sort ms desc | head",
+                r#"SELECT *, "took" * 1000 AS "ms" FROM "app" ORDER BY "ms" DESC LIMIT 10"#,
+            ),
+            (
+                "stream=nginx | timechart span=5m count() by status",
+                r#"SELECT histogram(_timestamp, '5 minute') AS "_time", "status", count(*) AS "count" FROM "nginx" GROUP BY "_time", "status" ORDER BY "_time" ASC"#,
+            ),
+            (
+                "stream=nginx | stats max(took) by span(1h), host",
+                r#"SELECT histogram(_timestamp, '1 hour') AS "_time", "host", max("took") AS "max_took" FROM "nginx" GROUP BY "_time", "host" ORDER BY "_time" ASC"#,
+            ),
+        ];
+        for (query, sql) in cases {
+            assert_eq!(compile(query).unwrap(), sql, "{query}");
+        }
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        for query in [
+            "nginx | head 10",
+            "stream=nginx | unknown x",
+            "stream=nginx | stats median(took)",
+            "stream=nginx | stats count() | eval a = 1",
+            "stream=nginx | parse log \"(\\d+)\"",
+            "stream=nginx | timechart span=5x count()",
+            "stream=nginx | where msg = 'abc",
+        ] {
+            assert!(compile(query).is_err(), "{query}");
+        }
+    }
+
+    #[test]
+    fn test_split_pipes() {
+        assert_eq!(
+            split_pipes(r#"stream=a | where b = "x|y" | head"#),
+            vec!["stream=a ", r#" where b = "x|y" "#, " head"]
+        );
+    }
+}
@@ -0,0 +1,502 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, time::Duration};
+use tokio::sync::oneshot;
+
+use crate::common::{
+    infra::{
+        cluster::LOCAL_NODE_UUID,
+        config::CONFIG,
+        errors::{Error, ErrorCodes},
+    },
+    meta::search::NodeSearchRunning,
+};
+use crate::service::db;
+
+const SECOND_MICROS: i64 = 1_000_000;
+
+static QUEUE: Lazy<SearchQueue> = Lazy::new(SearchQueue::new);
+
+/// Priority class of a search, a higher class is always admitted first.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// alert evaluation, late results mean late notifications
+    Alert,
+    /// searches from the UI, someone is waiting for the result
+    #[default]
+    Interactive,
+    /// API clients, reports and internal jobs
+    Batch,
+}
+
+impl Priority {
+    fn index(&self) -> usize {
+        match self {
+            Priority::Alert => 0,
+            Priority::Interactive => 1,
+            Priority::Batch => 2,
+        }
+    }
+}
+
+impl From<&str> for Priority {
+    fn from(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "alert" => Priority::Alert,
+            "batch" | "api" => Priority::Batch,
+            _ => Priority::Interactive,
+        }
+    }
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Priority::Alert => write!(f, "alert"),
+            Priority::Interactive => write!(f, "interactive"),
+            Priority::Batch => write!(f, "batch"),
+        }
+    }
+}
+
+/// Concurrency limits, `total`, `org` and `user` are cluster wide and checked
+/// against the searches of every querier, `node` only limits this node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Limits {
+    total: usize,
+    node: usize,
+    org: usize,
+    user: usize,
+    queue: usize,
+}
+
+impl Limits {
+    fn from_config() -> Self {
+        let limit = |limit: usize| if limit == 0 { usize::MAX } else { limit };
+        Limits {
+            total: limit(CONFIG.limit.search_max_concurrent),
+            // without a cluster limit every node runs a search per cpu
+            node: if CONFIG.limit.search_max_concurrent == 0 {
+                CONFIG.limit.cpu_num.max(1)
+            } else {
+                usize::MAX
+            },
+            org: limit(CONFIG.limit.search_max_concurrent_per_org),
+            user: limit(CONFIG.limit.search_max_concurrent_per_user),
+            queue: CONFIG.limit.search_max_queue_size,
+        }
+    }
+
+    fn is_cluster_wide(&self) -> bool {
+        self.total != usize::MAX || self.org != usize::MAX || self.user != usize::MAX
+    }
+}
+
+struct Waiter {
+    id: u64,
+    org_id: String,
+    user_id: String,
+    sender: oneshot::Sender<()>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    next_id: u64,
+    running: usize,
+    running_ids: HashSet<u64>,
+    running_orgs: HashMap<String, usize>,
+    running_users: HashMap<(String, String), usize>,
+    waiting: [VecDeque<Waiter>; 3],
+    /// the searches running on the other queriers at the last sync
+    others: NodeSearchRunning,
+}
+
+impl QueueState {
+    fn waiting_num(&self) -> usize {
+        self.waiting.iter().map(|v| v.len()).sum()
+    }
+
+    /// searches of the org running in the cluster
+    fn org_running(&self, org_id: &str) -> usize {
+        self.running_orgs.get(org_id).copied().unwrap_or_default()
+            + self.others.orgs.get(org_id).copied().unwrap_or_default()
+    }
+
+    /// searches of the user running in the cluster
+    fn user_running(&self, org_id: &str, user_id: &str) -> usize {
+        self.running_users
+            .get(&(org_id.to_string(), user_id.to_string()))
+            .copied()
+            .unwrap_or_default()
+            + self
+                .others
+                .users
+                .get(&format!("{org_id}/{user_id}"))
+                .copied()
+                .unwrap_or_default()
+    }
+
+    fn start(&mut self, id: u64, org_id: &str, user_id: &str) {
+        self.running_ids.insert(id);
+        self.running += 1;
+        *self.running_orgs.entry(org_id.to_string()).or_default() += 1;
+        *self
+            .running_users
+            .entry((org_id.to_string(), user_id.to_string()))
+            .or_default() += 1;
+    }
+
+    /// free the slot of a search, a search already finished is ignored
+    fn finish(&mut self, id: u64, org_id: &str, user_id: &str) {
+        if !self.running_ids.remove(&id) {
+            return;
+        }
+        self.running -= 1;
+        if let Some(v) = self.running_orgs.get_mut(org_id) {
+            *v -= 1;
+            if *v == 0 {
+                self.running_orgs.remove(org_id);
+            }
+        }
+        let key = (org_id.to_string(), user_id.to_string());
+        if let Some(v) = self.running_users.get_mut(&key) {
+            *v -= 1;
+            if *v == 0 {
+                self.running_users.remove(&key);
+            }
+        }
+    }
+
+    /// pick the next waiter to run, the highest priority class first, inside
+    /// a class the user with the fewest running searches goes first so one
+    /// user can't take all the slots
+    fn next_waiter(&self, limits: &Limits) -> Option<(usize, usize)> {
+        if self.running >= limits.node || self.running + self.others.running >= limits.total {
+            return None;
+        }
+        for (class, waiting) in self.waiting.iter().enumerate() {
+            let mut candidate: Option<(usize, usize)> = None;
+            for (i, waiter) in waiting.iter().enumerate() {
+                if self.org_running(&waiter.org_id) >= limits.org {
+                    continue;
+                }
+                let user_running = self.user_running(&waiter.org_id, &waiter.user_id);
+                if user_running >= limits.user {
+                    continue;
+                }
+                if candidate.map_or(true, |(_, running)| user_running < running) {
+                    candidate = Some((i, user_running));
+                }
+                if user_running == 0 {
+                    break;
+                }
+            }
+            if let Some((i, _)) = candidate {
+                return Some((class, i));
+            }
+        }
+        None
+    }
+
+    /// admit waiters while there are free slots
+    fn dispatch(&mut self, limits: &Limits) {
+        while let Some((class, i)) = self.next_waiter(limits) {
+            let waiter = self.waiting[class].remove(i).unwrap();
+            self.start(waiter.id, &waiter.org_id, &waiter.user_id);
+            if waiter.sender.send(()).is_err() {
+                // the search timed out while waiting
+                self.finish(waiter.id, &waiter.org_id, &waiter.user_id);
+            }
+        }
+    }
+
+    /// the searches running on this node, to publish to the other queriers
+    fn running(&self, now: i64) -> NodeSearchRunning {
+        NodeSearchRunning {
+            updated_at: now,
+            running: self.running,
+            orgs: self
+                .running_orgs
+                .iter()
+                .map(|(k, v)| (k.clone(), *v))
+                .collect(),
+            users: self
+                .running_users
+                .iter()
+                .map(|((org_id, user_id), v)| (format!("{org_id}/{user_id}"), *v))
+                .collect(),
+        }
+    }
+}
+
+struct SearchQueue {
+    state: Mutex<QueueState>,
+}
+
+impl SearchQueue {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(QueueState::default()),
+        }
+    }
+
+    async fn acquire(
+        &'static self,
+        org_id: &str,
+        user_id: &str,
+        priority: Priority,
+        limits: Limits,
+        timeout: Duration,
+    ) -> Result<Permit, Error> {
+        let (id, receiver) = {
+            let mut state = self.state.lock();
+            if state.waiting_num() >= limits.queue {
+                return Err(too_many_requests(format!(
+                    "search queue is full, {} searches are waiting",
+                    state.waiting_num()
+                )));
+            }
+            state.next_id += 1;
+            let id = state.next_id;
+            let (sender, receiver) = oneshot::channel();
+            state.waiting[priority.index()].push_back(Waiter {
+                id,
+                org_id: org_id.to_string(),
+                user_id: user_id.to_string(),
+                sender,
+            });
+            state.dispatch(&limits);
+            (id, receiver)
+        };
+
+        // the permit also cleans up the queue if the search is given up
+        let permit = Permit {
+            queue: self,
+            id,
+            priority,
+            org_id: org_id.to_string(),
+            user_id: user_id.to_string(),
+        };
+        if tokio::time::timeout(timeout, receiver).await.is_err() {
+            let state = self.state.lock();
+            if !state.running_ids.contains(&id) {
+                drop(state);
+                return Err(too_many_requests(format!(
+                    "search waited in queue for more than {} seconds",
+                    timeout.as_secs()
+                )));
+            }
+            // admitted right at the timeout, keep the slot
+        }
+        Ok(permit)
+    }
+
+    fn release(&self, permit: &Permit) {
+        let mut state = self.state.lock();
+        let waiting = &mut state.waiting[permit.priority.index()];
+        if let Some(i) = waiting.iter().position(|w| w.id == permit.id) {
+            // still waiting, nothing is running
+            waiting.remove(i);
+            return;
+        }
+        state.finish(permit.id, &permit.org_id, &permit.user_id);
+        state.dispatch(&Limits::from_config());
+    }
+}
+
+/// A slot of the search queue, the slot is released on drop.
+pub struct Permit {
+    queue: &'static SearchQueue,
+    id: u64,
+    priority: Priority,
+    org_id: String,
+    user_id: String,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.queue.release(self);
+    }
+}
+
+fn too_many_requests(msg: String) -> Error {
+    Error::ErrorCode(ErrorCodes::SearchTooManyRequests(msg))
+}
+
+/// wait for a slot to run a search, fails with `SearchTooManyRequests` when
+/// the queue is full or the search waits longer than the queue timeout
+pub async fn acquire(org_id: &str, user_id: &str, priority: Priority) -> Result<Permit, Error> {
+    QUEUE
+        .acquire(
+            org_id,
+            user_id,
+            priority,
+            Limits::from_config(),
+            Duration::from_secs(CONFIG.limit.search_queue_timeout),
+        )
+        .await
+}
+
+/// publish the searches running on this node and collect the ones running on
+/// the other queriers, the limits are checked against the cluster wide sums
+pub async fn sync() -> Result<(), anyhow::Error> {
+    let limits = Limits::from_config();
+    if !limits.is_cluster_wide() {
+        return Ok(());
+    }
+    let now = Utc::now().timestamp_micros();
+    let local = QUEUE.state.lock().running(now);
+    db::search_queue::set_running(&LOCAL_NODE_UUID, &local).await?;
+    let stale_at = now - 3 * CONFIG.limit.search_queue_sync_interval.max(1) as i64 * SECOND_MICROS;
+    let mut others = NodeSearchRunning::default();
+    for (node, running) in db::search_queue::list_running().await? {
+        if node == *LOCAL_NODE_UUID {
+            continue;
+        }
+        // the node left or stopped syncing, its searches count no more
+        if running.updated_at < stale_at {
+            db::search_queue::delete_running(&node).await?;
+            continue;
+        }
+        add_running(&mut others, &running);
+    }
+    let mut state = QUEUE.state.lock();
+    state.others = others;
+    state.dispatch(&limits);
+    Ok(())
+}
+
+fn add_running(total: &mut NodeSearchRunning, running: &NodeSearchRunning) {
+    total.running += running.running;
+    for (org_id, v) in running.orgs.iter() {
+        *total.orgs.entry(org_id.clone()).or_default() += v;
+    }
+    for (user, v) in running.users.iter() {
+        *total.users.entry(user.clone()).or_default() += v;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(total: usize, org: usize, user: usize) -> Limits {
+        Limits {
+            total,
+            node: usize::MAX,
+            org,
+            user,
+            queue: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_queue_limits() {
+        let queue: &'static SearchQueue = Box::leak(Box::new(SearchQueue::new()));
+        let acquire = |org_id: &'static str, priority| {
+            queue.acquire(
+                org_id,
+                "u1",
+                priority,
+                limits(2, 1, 9),
+                Duration::from_millis(50),
+            )
+        };
+        let p1 = acquire("org1", Priority::Interactive).await.unwrap();
+        // org1 is at its limit
+        assert!(acquire("org1", Priority::Interactive).await.is_err());
+        let p2 = acquire("org2", Priority::Batch).await.unwrap();
+        // the node is at its limit
+        assert!(acquire("org3", Priority::Alert).await.is_err());
+        assert_eq!(queue.state.lock().running, 2);
+        drop(p1);
+        drop(p2);
+        let state = queue.state.lock();
+        assert_eq!(state.running, 0);
+        assert_eq!(state.waiting_num(), 0);
+        assert!(state.running_orgs.is_empty() && state.running_users.is_empty());
+    }
+
+    #[test]
+    fn test_search_queue_finish_once() {
+        let mut state = QueueState::default();
+        state.start(1, "org1", "u1");
+        state.start(2, "org1", "u1");
+        state.finish(1, "org1", "u1");
+        state.finish(1, "org1", "u1");
+        assert_eq!(state.running, 1);
+        assert_eq!(state.org_running("org1"), 1);
+        state.finish(2, "org1", "u1");
+        assert_eq!(state.running, 0);
+        assert!(state.running_orgs.is_empty() && state.running_users.is_empty());
+    }
+
+    #[test]
+    fn test_search_queue_cluster_limits() {
+        let mut state = QueueState::default();
+        let mut others = NodeSearchRunning::default();
+        add_running(
+            &mut others,
+            &NodeSearchRunning {
+                running: 1,
+                orgs: [("org1".to_string(), 1)].into_iter().collect(),
+                users: [("org1/u1".to_string(), 1)].into_iter().collect(),
+                ..Default::default()
+            },
+        );
+        state.others = others;
+        let (sender, _) = oneshot::channel();
+        state.waiting[1].push_back(Waiter {
+            id: 1,
+            org_id: "org1".to_string(),
+            user_id: "u1".to_string(),
+            sender,
+        });
+        // the search running on the other querier takes the org slot
+        assert_eq!(state.next_waiter(&limits(9, 1, 9)), None);
+        assert_eq!(state.next_waiter(&limits(1, 9, 9)), None);
+        assert_eq!(state.next_waiter(&limits(2, 2, 9)), Some((1, 0)));
+    }
+
+    #[test]
+    fn test_search_queue_next_waiter() {
+        let mut state = QueueState::default();
+        let limits = limits(2, 9, 9);
+        state.start(1, "org1", "busy");
+        for (class, user_id) in [(2, "u1"), (1, "busy"), (1, "idle"), (0, "alert")] {
+            let (sender, _) = oneshot::channel();
+            state.waiting[class].push_back(Waiter {
+                id: 0,
+                org_id: "org1".to_string(),
+                user_id: user_id.to_string(),
+                sender,
+            });
+        }
+        // alert first
+        assert_eq!(state.next_waiter(&limits), Some((0, 0)));
+        state.waiting[0].clear();
+        // the idle user goes before the busy one
+        assert_eq!(state.next_waiter(&limits), Some((1, 1)));
+        state.start(2, "org1", "idle");
+        assert_eq!(state.next_waiter(&limits), None);
+        assert_eq!(Priority::from("API"), Priority::Batch);
+    }
+}
@@ -14,17 +14,28 @@
 
 use ahash::AHashMap;
 use chrono::Duration;
-use datafusion::arrow::datatypes::{DataType, Schema};
+use datafusion::arrow::datatypes::Schema;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sqlparser::{
+    ast::{
+        BinaryOperator, Expr as SqlExpr, Ident, JoinConstraint, JoinOperator, ObjectName, SetExpr,
+        Statement, TableAlias, TableFactor,
+    },
+    dialect::GenericDialect,
+    parser::Parser,
+};
 use std::{
     collections::HashMap,
     fmt::{Display, Formatter},
 };
 
 use crate::common::meta::{sql::Sql as MetaSql, stream::StreamParams, StreamType};
-use crate::common::utils::str::find;
+use crate::common::utils::{
+    column_stats::{self, ColumnFilter},
+    str::find,
+};
 use crate::common::{
     infra::{
         config::CONFIG,
@@ -33,13 +44,20 @@ use crate::common::{
     meta::common::FileKey,
 };
 use crate::handler::grpc::cluster_rpc;
-use crate::service::{db, search::match_source, stream::get_stream_setting_fts_fields};
+use crate::service::{
+    db, schema,
+    search::{fulltext_index, match_source},
+    stream::{self, get_stream_setting_fts_fields},
+};
 
 const SQL_DELIMITERS: [u8; 12] = [
     b' ', b'*', b'(', b')', b'<', b'>', b',', b';', b'=', b'!', b'\r', b'\n',
 ];
 const SQL_DEFAULT_FULL_MODE_LIMIT: usize = 1000;
 
+/// the column naming the stream of a row in a multi-stream search
+pub const STREAM_COLUMN: &str = "_stream";
+
 static RE_ONLY_SELECT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)select \*").unwrap());
 static RE_ONLY_GROUPBY: Lazy<Regex> =
     Lazy::new(|| Regex::new(r#"(?i) group[ ]+by[ ]+([a-zA-Z0-9'"._-]+)"#).unwrap());
@@ -56,6 +74,8 @@ static RE_HISTOGRAM: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)histogram\(([^\
 static RE_MATCH_ALL: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)match_all\('([^']*)'\)").unwrap());
 static RE_MATCH_ALL_IGNORE_CASE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(?i)match_all_ignore_case\('([^']*)'\)").unwrap());
+static RE_JOIN_TAIL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i) (group[ ]+by|having|order[ ]+by|limit) ").unwrap());
 
 #[derive(Clone, Debug, Serialize)]
 pub struct Sql {
@@ -72,6 +92,23 @@ pub struct Sql {
     pub query_context: String,
     pub uses_zo_fn: bool,
     pub query_fn: Option<String>,
+    pub column_filters: Vec<ColumnFilter>,
+    pub joins: Vec<JoinTable>,
+    pub table_aliases: Vec<String>, // the source first, then the joined tables
+    pub streams_schema: Option<Schema>, // merged schema of a multi-stream search
+    pub upsert_by_id: bool,         // read the latest version of every _id
+}
+
+/// a stream joined to the source, registered as `table` in the context
+#[derive(Clone, Debug, Serialize)]
+pub struct JoinTable {
+    pub table: String,
+    pub stream_type: StreamType,
+    pub stream_name: String,
+    pub schema: Schema,
+    pub files: Vec<FileKey>,
+    #[serde(skip)]
+    pub wal_files: Vec<cluster_rpc::MetricsWalFile>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -145,15 +182,60 @@ impl Sql {
 
         // check SQL limitation
         // in context mode, disallow, [limit|offset|group by|having|join|union]
-        // in full    mode, disallow, [join|union]
+        // in full    mode, disallow, [union]
         if sql_mode.eq(&SqlMode::Context)
-            && (meta.offset > 0 || meta.limit > 0 || !meta.group_by.is_empty())
+            && (meta.offset > 0
+                || meta.limit > 0
+                || !meta.group_by.is_empty()
+                || !meta.joins.is_empty())
         {
             return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(
                 "sql_mode=context, Query SQL does not supported [limit|offset|group by|having|join|union]".to_string()
             )));
         }
 
+        // check joined streams, every node reads the whole joined streams
+        let mut joins = Vec::with_capacity(meta.joins.len());
+        for (i, join) in meta.joins.iter().enumerate() {
+            let join_type = join
+                .stream_type
+                .as_deref()
+                .map_or(stream_type, StreamType::from);
+            let schema = db::schema::get(&org_id, &join.stream_name, join_type)
+                .await
+                .unwrap_or_else(|_| Schema::empty());
+            if schema.fields().is_empty() {
+                return Err(Error::ErrorCode(ErrorCodes::SearchStreamNotFound(
+                    join.stream_name.clone(),
+                )));
+            }
+            let req_joins = req.joins.iter().filter(|v| {
+                v.stream_name == join.stream_name
+                    && StreamType::from(v.stream_type.as_str()) == join_type
+            });
+            let files = req_joins
+                .clone()
+                .flat_map(|v| v.file_list.iter().map(FileKey::from))
+                .collect();
+            let wal_files = req_joins.flat_map(|v| v.wal_files.clone()).collect();
+            joins.push(JoinTable {
+                table: format!("join_{i}"),
+                stream_type: join_type,
+                stream_name: join.stream_name.clone(),
+                schema,
+                files,
+                wal_files,
+            });
+        }
+        if !joins.is_empty() {
+            fast_mode = false;
+            if !req_query.query_fn.is_empty() {
+                return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(
+                    "Query function is not supported with join".to_string(),
+                )));
+            }
+        }
+
         // check Agg SQL
         // 1. must from query
         // 2. disallow select *
@@ -206,15 +288,18 @@ impl Sql {
 
         // Hack for table name
         // DataFusion disallow use `k8s-logs-2022.09.11` as table name
+        // the tables of a join are renamed together with the time range below
         let stream_name = meta.source.clone();
-        let re = Regex::new(&format!(r#"(?i) from[ '"]+{stream_name}[ '"]?"#)).unwrap();
-        let caps = match re.captures(origin_sql.as_str()) {
-            Some(caps) => caps,
-            None => {
-                return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(origin_sql)));
-            }
-        };
-        origin_sql = origin_sql.replace(caps.get(0).unwrap().as_str(), " FROM tbl ");
+        if joins.is_empty() {
+            let re = Regex::new(&format!(r#"(?i) from[ '"]+{stream_name}[ '"]?"#)).unwrap();
+            let caps = match re.captures(origin_sql.as_str()) {
+                Some(caps) => caps,
+                None => {
+                    return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(origin_sql)));
+                }
+            };
+            origin_sql = origin_sql.replace(caps.get(0).unwrap().as_str(), " FROM tbl ");
+        }
 
         // Hack _timestamp
         if !sql_mode.eq(&SqlMode::Full) && meta.order_by.is_empty() && !origin_sql.contains('*') {
@@ -258,22 +343,27 @@ impl Sql {
             }
             meta.time_range = Some(req_time_range); // update meta
         };
-        if let Some(time_range) = meta.time_range {
-            let time_range_sql = if time_range.0 > 0 && time_range.1 > 0 {
-                format!(
-                    "({} >= {} AND {} < {})",
-                    CONFIG.common.column_timestamp,
-                    time_range.0,
-                    CONFIG.common.column_timestamp,
-                    time_range.1
-                )
-            } else if time_range.0 > 0 {
-                format!("{} >= {}", CONFIG.common.column_timestamp, time_range.0)
-            } else if time_range.1 > 0 {
-                format!("{} < {}", CONFIG.common.column_timestamp, time_range.1)
-            } else {
-                "".to_string()
+        let mut table_aliases = vec![];
+        if !joins.is_empty() {
+            // enrichment tables have no time range
+            let join_time_range = joins
+                .iter()
+                .map(|v| v.stream_type != StreamType::EnrichmentTables)
+                .collect::<Vec<_>>();
+            (origin_sql, table_aliases) = match rewrite_join_sql(
+                &origin_sql,
+                meta.time_range,
+                meta_time_range_is_empty,
+                &join_time_range,
+            ) {
+                Ok(ret) => ret,
+                Err(err) => {
+                    log::error!("parse sql error: {}, sql: {}", err, origin_sql);
+                    return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(origin_sql)));
+                }
             };
+        } else if let Some(time_range) = meta.time_range {
+            let time_range_sql = time_range_sql(&CONFIG.common.column_timestamp, time_range);
             if !time_range_sql.is_empty() && meta_time_range_is_empty {
                 match RE_TIMESTAMP_EMPTY.captures(origin_sql.as_str()) {
                     Some(caps) => {
@@ -333,6 +423,29 @@ impl Sql {
             Err(_) => Schema::empty(),
         };
         let schema_fields = schema.fields().to_vec();
+        let upsert_by_id = stream::stream_settings(&schema)
+            .map(|v| v.upsert_by_id)
+            .unwrap_or_default();
+
+        // the streams of a multi-stream search are read with the merged schema
+        let streams_schema = if req.streams.is_empty() {
+            None
+        } else {
+            let mut schemas = Vec::with_capacity(req.streams.len());
+            for name in req.streams.iter() {
+                if let Ok(schema) = db::schema::get(&org_id, name, stream_type).await {
+                    schemas.push(schema.with_metadata(HashMap::new()));
+                }
+            }
+            match schema::try_merge(schemas) {
+                Ok(schema) => Some(schema),
+                Err(err) => {
+                    return Err(Error::ErrorCode(ErrorCodes::SearchSQLNotValid(format!(
+                        "streams schema can't be merged: {err}"
+                    ))));
+                }
+            }
+        };
 
         // get sql where tokens
         let where_tokens = split_sql_token(&origin_sql);
@@ -363,30 +476,23 @@ impl Sql {
         }
         // fetch fts fields
         let fts_fields = get_stream_setting_fts_fields(&schema).unwrap();
-        let match_all_fields = if !fts_fields.is_empty() {
-            fts_fields.iter().map(|v| v.to_lowercase()).collect()
-        } else {
-            crate::common::utils::stream::SQL_FULL_TEXT_SEARCH_FIELDS
-                .iter()
-                .map(|v| v.to_string())
-                .collect::<String>()
+        let match_all_fields = fulltext_index::index_fields(&schema, &fts_fields);
+        // match_all searches the source of a join
+        let qualifier = match table_aliases.first() {
+            Some(alias) => format!("{alias}."),
+            None => "".to_string(),
         };
         for item in fulltext.iter() {
             let mut fulltext_search = Vec::new();
-            for field in &schema_fields {
-                if !CONFIG.common.feature_fulltext_on_all_fields
-                    && !match_all_fields.contains(&field.name().to_lowercase())
-                {
-                    continue;
-                }
-                if !field.data_type().eq(&DataType::Utf8) || field.name().starts_with('@') {
-                    continue;
-                }
+            for field in &match_all_fields {
                 let mut func = "LIKE";
                 if item.0.to_lowercase().contains("_ignore_case") {
                     func = "ILIKE";
                 }
-                fulltext_search.push(format!("\"{}\" {} '%{}%'", field.name(), func, item.1));
+                fulltext_search.push(format!(
+                    "{}\"{}\" {} '%{}%'",
+                    qualifier, field, func, item.1
+                ));
             }
             if fulltext_search.is_empty() {
                 return Err(Error::ErrorCode(ErrorCodes::FullTextSearchFieldNotFound));
@@ -444,6 +550,12 @@ impl Sql {
                 .map(|v| v.trim().trim_matches(|v| v == '\'' || v == '"'))
                 .collect::<Vec<&str>>();
             let field = attrs.first().unwrap();
+            // keep the table qualifier of a join
+            let field = if !joins.is_empty() && field.contains('.') {
+                field.to_string()
+            } else {
+                format!("\"{field}\"")
+            };
             let interval = match attrs.get(1) {
                 Some(v) => match v.parse::<u16>() {
                     Ok(v) => generate_histogram_interval(meta.time_range, v),
@@ -454,7 +566,7 @@ impl Sql {
             origin_sql = origin_sql.replace(
                 cap.get(0).unwrap().as_str(),
                 &format!(
-                    "date_bin(interval '{interval}', to_timestamp_micros(\"{field}\"), to_timestamp('2001-01-01T00:00:00'))",
+                    "date_bin(interval '{interval}', to_timestamp_micros({field}), to_timestamp('2001-01-01T00:00:00'))",
                 )
             );
         }
@@ -581,6 +693,15 @@ impl Sql {
             Some(req_query.query_fn.clone())
         };
 
+        // with a join an unqualified column may belong to the joined stream and
+        // the where clause may keep source rows without a match, so the source
+        // files are not pruned by it
+        let column_filters = if joins.is_empty() {
+            column_stats::column_filters(meta.selection.as_ref(), &meta.source)
+        } else {
+            vec![]
+        };
+
         let mut sql = Sql {
             origin_sql,
             org_id,
@@ -595,6 +716,11 @@ impl Sql {
             query_context: req_query.query_context.clone(),
             uses_zo_fn: req_query.uses_zo_fn,
             query_fn,
+            column_filters,
+            joins,
+            table_aliases,
+            streams_schema,
+            upsert_by_id,
         };
 
         // calculate all needs fields
@@ -619,6 +745,16 @@ impl Sql {
         Ok(sql)
     }
 
+    /// the sql merging the results of the nodes, the tables of a join are
+    /// already joined by the nodes so the joined rows are merged from `tbl`
+    pub fn merge_sql(&self) -> String {
+        if self.joins.is_empty() {
+            self.origin_sql.clone()
+        } else {
+            join_merge_sql(&self.origin_sql, &self.table_aliases)
+        }
+    }
+
     /// match a source is a valid file or not
     pub async fn match_source(
         &self,
@@ -649,6 +785,130 @@ impl Sql {
     }
 }
 
+fn time_range_sql(column: &str, time_range: (i64, i64)) -> String {
+    if time_range.0 > 0 && time_range.1 > 0 {
+        format!(
+            "({} >= {} AND {} < {})",
+            column, time_range.0, column, time_range.1
+        )
+    } else if time_range.0 > 0 {
+        format!("{} >= {}", column, time_range.0)
+    } else if time_range.1 > 0 {
+        format!("{} < {}", column, time_range.1)
+    } else {
+        "".to_string()
+    }
+}
+
+/// Rewrite a join query for the tables registered by the nodes, the source is
+/// read from `tbl` and the joined tables from `join_{i}`, keeping the original
+/// names as the aliases. The source gets the time range in the where clause if
+/// `source_time_range` is set, the joined streams get it in the join condition.
+/// Returns the query and the table aliases, the source first.
+fn rewrite_join_sql(
+    sql: &str,
+    time_range: Option<(i64, i64)>,
+    source_time_range: bool,
+    join_time_range: &[bool],
+) -> Result<(String, Vec<String>), anyhow::Error> {
+    let dialect = GenericDialect {};
+    let mut statements = Parser::parse_sql(&dialect, sql)?;
+    let Some(Statement::Query(query)) = statements.first_mut() else {
+        return Err(anyhow::anyhow!("We only support Query at the moment"));
+    };
+    let SetExpr::Select(select) = query.body.as_mut() else {
+        return Err(anyhow::anyhow!("We only support Select Query at the moment"));
+    };
+    let select = select.as_mut();
+    let Some(table) = select.from.first_mut() else {
+        return Err(anyhow::anyhow!("Query SQL has no data source"));
+    };
+    let time_filter = |alias: &Ident| -> Result<Option<SqlExpr>, anyhow::Error> {
+        let Some(time_range) = time_range else {
+            return Ok(None);
+        };
+        let column = format!("{alias}.{}", CONFIG.common.column_timestamp);
+        let filter = time_range_sql(&column, time_range);
+        if filter.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            Parser::new(&dialect).try_with_sql(&filter)?.parse_expr()?,
+        ))
+    };
+
+    let mut aliases = Vec::with_capacity(table.joins.len() + 1);
+    let alias = rename_table(&mut table.relation, "tbl")?;
+    if source_time_range {
+        if let Some(filter) = time_filter(&alias)? {
+            select.selection = Some(match select.selection.take() {
+                Some(expr) => and_expr(filter, expr),
+                None => filter,
+            });
+        }
+    }
+    aliases.push(alias.to_string());
+    for (i, join) in table.joins.iter_mut().enumerate() {
+        let alias = rename_table(&mut join.relation, &format!("join_{i}"))?;
+        if join_time_range.get(i).copied().unwrap_or_default() {
+            if let (
+                Some(filter),
+                JoinOperator::Inner(JoinConstraint::On(expr))
+                | JoinOperator::LeftOuter(JoinConstraint::On(expr)),
+            ) = (time_filter(&alias)?, &mut join.join_operator)
+            {
+                *expr = and_expr(filter, expr.clone());
+            }
+        }
+        aliases.push(alias.to_string());
+    }
+    Ok((statements[0].to_string(), aliases))
+}
+
+/// point the table to `table_name`, the original name is kept as the alias
+fn rename_table(relation: &mut TableFactor, table_name: &str) -> Result<Ident, anyhow::Error> {
+    let TableFactor::Table { name, alias, .. } = relation else {
+        return Err(anyhow::anyhow!("We only support table"));
+    };
+    let table_alias = match alias {
+        Some(alias) => alias.name.clone(),
+        None => name.0.last().unwrap().clone(),
+    };
+    *name = ObjectName(vec![Ident::new(table_name)]);
+    *alias = Some(TableAlias {
+        name: table_alias.clone(),
+        columns: vec![],
+    });
+    Ok(table_alias)
+}
+
+fn and_expr(left: SqlExpr, right: SqlExpr) -> SqlExpr {
+    SqlExpr::BinaryOp {
+        left: Box::new(left),
+        op: BinaryOperator::And,
+        right: Box::new(SqlExpr::Nested(Box::new(right))),
+    }
+}
+
+/// the merge of a join query selects the joined columns from `tbl`, the where
+/// clause was applied by the nodes and the table qualifiers are removed
+fn join_merge_sql(sql: &str, aliases: &[String]) -> String {
+    let from_pos = match sql.to_lowercase().find(" from ") {
+        Some(pos) => pos,
+        None => return sql.to_string(),
+    };
+    let tail = match RE_JOIN_TAIL.find(&sql[from_pos..]) {
+        Some(m) => &sql[from_pos + m.start()..],
+        None => "",
+    };
+    let mut sql = format!("{} FROM tbl{}", &sql[..from_pos], tail);
+    for alias in aliases {
+        let re = Regex::new(&format!(r"(?i)(^|[ ,(]){}\.", regex::escape(alias))).unwrap();
+        sql = re.replace_all(&sql, "${1}").to_string();
+    }
+    sql
+}
+
 fn check_field_in_use(sql: &Sql, field: &str) -> bool {
     let re = Regex::new(&format!(r"\b{field}\b")).unwrap();
     if find(sql.origin_sql.as_str(), field) && re.is_match(sql.origin_sql.as_str()) {
@@ -662,6 +922,24 @@ fn check_field_in_use(sql: &Sql, field: &str) -> bool {
     false
 }
 
+/// replace the histogram intervals derived from the time range with the fixed
+/// ones, so a part of the time range is searched with the same intervals
+pub fn pin_histogram_interval(sql: &str, time_range: Option<(i64, i64)>) -> String {
+    RE_HISTOGRAM
+        .replace_all(sql, |cap: &regex::Captures| {
+            let attrs = cap[1].split(',').map(|v| v.trim()).collect::<Vec<&str>>();
+            let interval = match attrs.get(1) {
+                Some(v) => match v.trim_matches(|v| v == '\'' || v == '"').parse::<u16>() {
+                    Ok(v) => generate_histogram_interval(time_range, v),
+                    Err(_) => return cap[0].to_string(),
+                },
+                None => generate_histogram_interval(time_range, 0),
+            };
+            format!("histogram({}, '{interval}')", attrs[0])
+        })
+        .into_owned()
+}
+
 fn generate_histogram_interval(time_range: Option<(i64, i64)>, num: u16) -> String {
     if time_range.is_none() || time_range.unwrap().eq(&(0, 0)) {
         return "1 hour".to_string();
@@ -786,6 +1064,65 @@ fn split_sql_token(text: &str) -> Vec<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_pin_histogram_interval() {
+        let hour = Duration::hours(1).num_microseconds().unwrap();
+        let time_range = Some((0, 24 * hour));
+        assert_eq!(
+            pin_histogram_interval(
+                "select histogram(_timestamp) AS key, count(*) from t group by key",
+                time_range
+            ),
+            "select histogram(_timestamp, '30 minute') AS key, count(*) from t group by key"
+        );
+        assert_eq!(
+            pin_histogram_interval("select histogram(_timestamp, 24) from t", time_range),
+            "select histogram(_timestamp, '3600 second') from t"
+        );
+        let sql = "select histogram(_timestamp, '1 day') from t";
+        assert_eq!(pin_histogram_interval(sql, time_range), sql);
+    }
+
+    #[test]
+    fn test_rewrite_join_sql() {
+        let sql = "select l.code, t.duration, g.country from logs l join traces.spans t on l.trace_id = t.trace_id left join enrichment_tables.geo g on l.ip = g.ip where l.code = 500";
+        let (sql, aliases) = rewrite_join_sql(sql, Some((10, 20)), true, &[true, false]).unwrap();
+        assert_eq!(aliases, vec!["l", "t", "g"]);
+        assert!(sql.contains(" FROM tbl AS l "));
+        assert!(sql.contains(
+            " join_0 AS t ON (t._timestamp >= 10 AND t._timestamp < 20) AND (l.trace_id = t.trace_id)"
+        ));
+        assert!(sql.contains(" join_1 AS g ON l.ip = g.ip "));
+        assert!(
+            sql.ends_with(" WHERE (l._timestamp >= 10 AND l._timestamp < 20) AND (l.code = 500)")
+        );
+
+        let (sql, aliases) = rewrite_join_sql(
+            "select * from logs join logs2 on logs.a = logs2.a",
+            None,
+            true,
+            &[true],
+        )
+        .unwrap();
+        assert_eq!(aliases, vec!["logs", "logs2"]);
+        assert!(sql.contains(" FROM tbl AS logs "));
+        assert!(!sql.contains("WHERE"));
+    }
+
+    #[test]
+    fn test_join_merge_sql() {
+        let sql = "SELECT l.a, g.country, count(*) AS cnt FROM tbl AS l JOIN join_0 AS g ON l.ip = g.ip WHERE l.code = 500 GROUP BY l.a, g.country ORDER BY cnt DESC LIMIT 10";
+        assert_eq!(
+            join_merge_sql(sql, &["l".to_string(), "g".to_string()]),
+            "SELECT a, country, count(*) AS cnt FROM tbl GROUP BY a, country ORDER BY cnt DESC LIMIT 10"
+        );
+        let sql = "SELECT l.a FROM tbl AS l JOIN join_0 AS g ON l.ip = g.ip";
+        assert_eq!(
+            join_merge_sql(sql, &["l".to_string(), "g".to_string()]),
+            "SELECT a FROM tbl"
+        );
+    }
+
     #[actix_web::test]
     async fn test_sql_works() {
         let org_id = "test_org";
@@ -0,0 +1,478 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::Duration;
+use std::{cmp::min, time::Instant};
+use tokio::sync::mpsc;
+use tonic::{codec::CompressionEncoding, metadata::MetadataValue, transport::Channel, Request};
+use tracing::{info_span, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use uuid::Uuid;
+
+use super::{cancel, queue, server_internal_error, sql, MetadataMap};
+use crate::common::infra::{
+    cluster,
+    config::CONFIG,
+    errors::{Error, ErrorCodes},
+};
+use crate::common::meta::{search, sql::Sql as MetaSql, StreamType};
+use crate::common::utils::cancel::{cancelled_error, CancelToken};
+use crate::handler::grpc::cluster_rpc;
+use crate::service::stream;
+
+/// start a streaming search, the errors found before the search starts are
+/// returned directly, later errors are sent as the last event of the stream
+pub async fn search(
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: &str,
+    priority: queue::Priority,
+    req: &search::Request,
+) -> Result<mpsc::Receiver<search::StreamEvent>, Error> {
+    search_with_options(
+        org_id,
+        stream_type,
+        user_id,
+        priority,
+        req,
+        StreamOptions::default(),
+    )
+    .await
+}
+
+/// how a streaming search is split and how long the nodes may take
+#[derive(Clone, Copy, Debug)]
+pub struct StreamOptions {
+    /// only split searches whose slices can simply be put together, the
+    /// aggregations and the grouped queries run as a single slice
+    pub exact: bool,
+    /// timeout of the node requests in seconds
+    pub timeout: u64,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            exact: false,
+            timeout: CONFIG.grpc.timeout,
+        }
+    }
+}
+
+#[tracing::instrument(name = "service:search:stream:enter", skip(req))]
+pub async fn search_with_options(
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: &str,
+    priority: queue::Priority,
+    req: &search::Request,
+    options: StreamOptions,
+) -> Result<mpsc::Receiver<search::StreamEvent>, Error> {
+    let start = Instant::now();
+    let (offset, limit) = (req.query.from, req.query.size);
+    let mut req: cluster_rpc::SearchRequest = super::ppl::compile_request(req)?.into();
+    req.org_id = org_id.to_string();
+    req.stype = cluster_rpc::SearchType::User as i32;
+    req.stream_type = stream_type.to_string();
+    let has_aggs = !req.aggs.is_empty();
+    let query = req.query.as_mut().unwrap();
+    if query.end_time == 0 {
+        query.end_time = chrono::Utc::now().timestamp_micros();
+    }
+    // every slice returns up to offset + limit hits, the offset is applied over
+    // all the slices by the coordinator
+    query.from = 0;
+    query.size = (offset + limit) as i32;
+    // a time range in the sql overrides the request, such queries can't be sliced,
+    // neither can joins as the joined streams are read for the whole time range
+    let sliceable = MetaSql::new(&query.sql).map_or(false, |meta| {
+        // raw rows in time order, the slices add up to the whole result
+        let concatenable = !has_aggs
+            && meta.group_by.is_empty()
+            && meta
+                .order_by
+                .first()
+                .map_or(false, |(field, _)| field == &CONFIG.common.column_timestamp);
+        meta.time_range.map_or(true, |v| v == (0, 0))
+            && meta.joins.is_empty()
+            && (concatenable || !options.exact)
+    });
+
+    let streams = super::stream_requests(req, stream_type).await?;
+    let permit = queue::acquire(org_id, user_id, priority).await?;
+    let took_wait = start.elapsed().as_millis() as usize;
+
+    let session_id = Uuid::new_v4().to_string();
+    let guard = cancel::register(org_id, user_id, &session_id);
+    let user_id = user_id.to_string();
+    let (tx, rx) = mpsc::channel(1);
+    tokio::task::spawn(async move {
+        let _permit = permit;
+        let params = StreamParams {
+            session_id,
+            user_id,
+            token: guard.token(),
+            offset,
+            limit,
+            sliceable,
+            timeout: options.timeout,
+            start,
+            took_wait,
+        };
+        let ret = search_in_cluster(streams, params, &tx).await;
+        drop(guard);
+        if let Err(err) = ret {
+            log::error!("search->stream: error: {:?}", err);
+            let err = match err {
+                Error::ErrorCode(code) => code,
+                err => ErrorCodes::ServerInternalError(err.to_string()),
+            };
+            let event = search::StreamEvent::Error {
+                code: err.get_code(),
+                message: err.get_message(),
+                error_detail: Some(err.get_error_detail()),
+            };
+            let _ = tx.send(event).await;
+        }
+    });
+    Ok(rx)
+}
+
+struct StreamParams {
+    session_id: String,
+    user_id: String,
+    token: CancelToken,
+    offset: usize,
+    limit: usize,
+    sliceable: bool,
+    timeout: u64,
+    start: Instant,
+    took_wait: usize,
+}
+
+#[tracing::instrument(
+    name = "service:search:stream:cluster",
+    skip_all,
+    fields(org_id = req.org_id)
+)]
+async fn search_in_cluster(
+    mut streams: Vec<(cluster_rpc::SearchRequest, sql::Sql)>,
+    params: StreamParams,
+    tx: &mpsc::Sender<search::StreamEvent>,
+) -> Result<(), Error> {
+    let (req, sql) = &streams[0];
+    let stream_type = StreamType::from(req.stream_type.as_str());
+    let query_type = req.query.as_ref().unwrap().query_type.to_lowercase();
+
+    // get nodes from cluster
+    let mut nodes = cluster::get_cached_online_query_nodes().unwrap();
+    // sort nodes by node_id this will improve hit cache ratio
+    nodes.sort_by_key(|x| x.id);
+    let nodes = nodes;
+
+    let mut file_lists = Vec::with_capacity(streams.len());
+    for (req, sql) in streams.iter_mut() {
+        let stream_settings = stream::stream_settings(&sql.schema).unwrap_or_default();
+        let partition_time_level =
+            stream::unwrap_partition_time_level(stream_settings.partition_time_level, stream_type);
+        file_lists.push(super::get_file_list(sql, stream_type, partition_time_level).await);
+        req.joins = super::get_join_file_lists(sql).await?;
+    }
+    let file_num = file_lists.iter().map(|v| v.len()).sum::<usize>();
+    let (req, sql) = &streams[0];
+
+    // the hits of the slices are put together in the order of the slices, the
+    // slices go from the oldest to the newest for hits in ascending time order
+    let time_order = sql
+        .meta
+        .order_by
+        .first()
+        .filter(|(field, _)| field == &CONFIG.common.column_timestamp)
+        .map(|(_, desc)| *desc);
+    let slices = if params.sliceable {
+        let (time_min, time_max) = super::get_times(sql, stream_type).await;
+        let mut slices = time_slices(time_min, time_max);
+        if time_order == Some(false) {
+            slices.reverse();
+        }
+        slices
+    } else {
+        let query = req.query.as_ref().unwrap();
+        vec![(query.start_time, query.end_time)]
+    };
+    log::info!(
+        "search->stream: time_range: {:?}, files: {file_num}, slices: {}",
+        sql.meta.time_range,
+        slices.len()
+    );
+    let slices_req = slices
+        .iter()
+        .map(|(start_time, end_time)| cluster_rpc::TimeRange {
+            start_time: *start_time,
+            end_time: *end_time,
+        })
+        .collect::<Vec<_>>();
+    let event = search::StreamEvent::Start {
+        session_id: params.session_id.clone(),
+        files: file_num,
+        slices: slices.len(),
+    };
+    if tx.send(event).await.is_err() {
+        return Ok(());
+    }
+
+    let job = cluster_rpc::Job {
+        session_id: params.session_id.clone(),
+        job: params.session_id[30..].to_string(), // take the last 6 characters as job id
+        stage: 0,
+        partition: 0,
+        user_id: params.user_id.clone(),
+    };
+
+    // make cluster request, every node sends the responses of its slices in order
+    let (resp_tx, mut resp_rx) = mpsc::channel(nodes.len().max(1));
+    let timeout = params.timeout;
+    let requests = streams
+        .iter()
+        .zip(file_lists.iter())
+        .flat_map(|((req, _), file_list)| super::node_requests(req, &job, &nodes, file_list))
+        .collect::<Vec<_>>();
+    let node_num = requests.len();
+    let mut tasks = Vec::with_capacity(node_num);
+    for (node_idx, (node, req)) in requests.into_iter().enumerate() {
+        let req = cluster_rpc::SearchStreamRequest {
+            request: Some(req),
+            slices: slices_req.clone(),
+        };
+        let resp_tx = resp_tx.clone();
+        let grpc_span = info_span!("service:search:stream:grpc_search", node_id = node.id);
+        let task = tokio::task::spawn(
+            async move {
+                let mut stream = match search_node_stream(&node, req, timeout).await {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        let _ = resp_tx.send((node_idx, Err(err))).await;
+                        return;
+                    }
+                };
+                loop {
+                    let resp = match stream.message().await {
+                        Ok(Some(resp)) => Ok(resp),
+                        Ok(None) => break,
+                        Err(err) => {
+                            log::error!("search->stream: node: {}, err: {:?}", node.id, err);
+                            Err(grpc_status_error(err))
+                        }
+                    };
+                    let failed = resp.is_err();
+                    if resp_tx.send((node_idx, resp)).await.is_err() || failed {
+                        break;
+                    }
+                }
+            }
+            .instrument(grpc_span),
+        );
+        tasks.push(task);
+    }
+    drop(resp_tx);
+    // stop the node streams when the search ends for any reason
+    let _abort = cancel::AbortOnDrop::new(&tasks);
+
+    // merge a slice once every node sent it, in the order of the slices
+    let ordered_by_time = time_order.is_some();
+    let mut received = vec![0; node_num];
+    let mut pending = vec![vec![]; slices.len()];
+    let mut next_slice = 0;
+    let mut skip = params.offset;
+    let (mut hits_num, mut total, mut scan_size) = (0, 0, 0);
+    'receive: while next_slice < slices.len() {
+        let (node_idx, resp) = tokio::select! {
+            resp = resp_rx.recv() => match resp {
+                Some(resp) => resp,
+                None => return Err(server_internal_error("search node stream closed")),
+            },
+            // the client is gone
+            _ = tx.closed() => return Ok(()),
+            _ = params.token.cancelled() => return Err(cancelled_error()),
+        };
+        let slice = received[node_idx];
+        received[node_idx] += 1;
+        if slice >= slices.len() {
+            continue;
+        }
+        pending[slice].push(resp?);
+
+        while next_slice < slices.len() && pending[next_slice].len() == node_num {
+            let results = std::mem::take(&mut pending[next_slice]);
+            let resp = super::merge_grpc_result(sql, &query_type, results).await?;
+            // the offset and the limit apply to the hits of all the slices
+            let mut hits = resp.hits;
+            let n = min(skip, hits.len());
+            hits.drain(..n);
+            skip -= n;
+            hits.truncate(params.limit.saturating_sub(hits_num));
+            hits_num += hits.len();
+            total += resp.total;
+            scan_size += resp.scan_size;
+            let (start_time, end_time) = slices[next_slice];
+            let event = search::StreamEvent::Slice {
+                start_time,
+                end_time,
+                hits,
+                aggs: resp.aggs,
+                total: resp.total,
+                scan_files: resp.file_count,
+                scan_size: resp.scan_size,
+            };
+            if tx.send(event).await.is_err() {
+                // the client is gone
+                return Ok(());
+            }
+            next_slice += 1;
+            // the first hits in time order are all sent and there is nothing else
+            // to compute
+            if ordered_by_time && sql.aggs.is_empty() && hits_num >= params.limit {
+                break 'receive;
+            }
+        }
+    }
+
+    let took = params.start.elapsed().as_millis() as usize;
+    log::info!(
+        "search->stream: result: total: {total}, took: {took}, scan_size: {scan_size}, slices: {next_slice}"
+    );
+    let _ = tx
+        .send(search::StreamEvent::Done {
+            took,
+            total,
+            scan_size,
+            wait_queue: params.took_wait,
+        })
+        .await;
+    Ok(())
+}
+
+pub(super) async fn search_node_stream(
+    node: &cluster::Node,
+    req: cluster_rpc::SearchStreamRequest,
+    timeout: u64,
+) -> Result<tonic::Streaming<cluster_rpc::SearchResponse>, Error> {
+    let org_id: MetadataValue<_> = req
+        .request
+        .as_ref()
+        .unwrap()
+        .org_id
+        .parse()
+        .map_err(|_| Error::Message("invalid org_id".to_string()))?;
+    let mut request = tonic::Request::new(req);
+    request.set_timeout(std::time::Duration::from_secs(timeout));
+
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(
+            &tracing::Span::current().context(),
+            &mut MetadataMap(request.metadata_mut()),
+        )
+    });
+
+    let token: MetadataValue<_> = cluster::get_internal_grpc_token()
+        .parse()
+        .map_err(|_| Error::Message("invalid token".to_string()))?;
+    let channel = Channel::from_shared(node.grpc_addr.clone())
+        .unwrap()
+        .connect()
+        .await
+        .map_err(|err| {
+            log::error!("search->stream: node: {}, connect err: {:?}", node.id, err);
+            server_internal_error("connect search node error")
+        })?;
+    let mut client = cluster_rpc::search_client::SearchClient::with_interceptor(
+        channel,
+        move |mut req: Request<()>| {
+            req.metadata_mut().insert("authorization", token.clone());
+            req.metadata_mut()
+                .insert(CONFIG.grpc.org_header_key.as_str(), org_id.clone());
+            Ok(req)
+        },
+    );
+    client = client
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip);
+    match client.search_stream(request).await {
+        Ok(res) => Ok(res.into_inner()),
+        Err(err) => {
+            log::error!("search->stream: node: {}, search err: {:?}", node.id, err);
+            Err(grpc_status_error(err))
+        }
+    }
+}
+
+pub(super) fn grpc_status_error(err: tonic::Status) -> Error {
+    if err.code() == tonic::Code::Internal {
+        match ErrorCodes::from_json(err.message()) {
+            Ok(code) => Error::ErrorCode(code),
+            Err(err) => err,
+        }
+    } else {
+        server_internal_error("search node error")
+    }
+}
+
+/// split the time range into slices from the newest to the oldest, the first
+/// slice starts at the last full hour and every next slice doubles in size
+fn time_slices(start: i64, end: i64) -> Vec<(i64, i64)> {
+    let hour = Duration::hours(1).num_microseconds().unwrap();
+    let mut slices = Vec::new();
+    let mut slice_end = end;
+    let mut slice_start = if end % hour == 0 {
+        end - hour
+    } else {
+        end - end % hour
+    };
+    let mut size = hour;
+    while slice_end > start {
+        let slice_start_at = slice_start.max(start);
+        slices.push((slice_start_at, slice_end));
+        slice_end = slice_start_at;
+        slice_start = slice_end - size;
+        size *= 2;
+    }
+    if slices.is_empty() {
+        slices.push((start, end));
+    }
+    slices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_slices() {
+        let hour = Duration::hours(1).num_microseconds().unwrap();
+        let end = 100 * hour + 10;
+        let slices = time_slices(90 * hour, end);
+        assert_eq!(
+            slices,
+            vec![
+                (100 * hour, end),
+                (99 * hour, 100 * hour),
+                (97 * hour, 99 * hour),
+                (93 * hour, 97 * hour),
+                (90 * hour, 93 * hour),
+            ]
+        );
+        assert_eq!(time_slices(hour, 2 * hour), vec![(hour, 2 * hour)]);
+        assert_eq!(time_slices(10, 20), vec![(10, 20)]);
+    }
+}
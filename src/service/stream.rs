@@ -28,7 +28,10 @@ use crate::common::meta::{
     StreamType,
 };
 use crate::common::utils::{json, stream::SQL_FULL_TEXT_SEARCH_FIELDS};
-use crate::service::{db, search as SearchService};
+use crate::service::{
+    db,
+    search::{self as SearchService, queue::Priority},
+};
 
 use super::metrics::get_prom_metadata_from_schema;
 
@@ -384,7 +387,15 @@ async fn get_stream_stats(
         aggs: HashMap::new(),
         encoding: meta::search::RequestEncoding::Empty,
     };
-    match SearchService::search(&CONFIG.common.usage_org, meta::StreamType::Logs, &req).await {
+    match SearchService::search(
+        &CONFIG.common.usage_org,
+        meta::StreamType::Logs,
+        "",
+        Priority::Batch,
+        &req,
+    )
+    .await
+    {
         Ok(res) => {
             let mut all_stats = HashMap::new();
             for item in res.hits {
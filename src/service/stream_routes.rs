@@ -0,0 +1,301 @@
+// Copyright 2023 Zinc Labs Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use actix_web::HttpResponse;
+use sqlparser::ast::Expr as SqlExpr;
+use std::io;
+use vector_enrichment::TableRegistry;
+use vrl::compiler::runtime::Runtime;
+
+use crate::common::infra::config::STREAM_ROUTES;
+use crate::common::meta::{
+    functions::VRLRuntimeConfig,
+    routing::{RouteConditionType, StreamRoute, StreamRoutes},
+};
+use crate::common::utils::{
+    json::{Map, Value},
+    sql_condition,
+};
+use crate::service::{
+    format_stream_name, ingestion,
+    rules::{self, Response, StreamRule},
+};
+
+impl StreamRule for StreamRoute {
+    const KIND: &'static str = "Stream route";
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn assign(&mut self, org_id: &str, stream_name: &str, name: &str) {
+        self.org_id = org_id.to_string();
+        self.stream_name = stream_name.to_string();
+        self.name = name.to_string();
+    }
+
+    fn check(&mut self) -> Result<(), String> {
+        check_route(self)
+    }
+}
+
+#[tracing::instrument(skip(route))]
+pub async fn create_route(
+    org_id: &str,
+    stream_name: &str,
+    route: StreamRoute,
+) -> Result<HttpResponse, io::Error> {
+    rules::create_stream_rule(org_id, stream_name, route).await
+}
+
+#[tracing::instrument(skip(route))]
+pub async fn update_route(
+    org_id: &str,
+    stream_name: &str,
+    name: &str,
+    route: StreamRoute,
+) -> Result<HttpResponse, io::Error> {
+    rules::update_stream_rule(org_id, stream_name, name, route).await
+}
+
+#[tracing::instrument]
+pub async fn list_routes(org_id: &str, stream_name: &str) -> Result<HttpResponse, io::Error> {
+    match rules::list_stream_rules::<StreamRoute>(org_id, stream_name).await {
+        Ok(mut routes) => {
+            routes.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(HttpResponse::Ok().json(StreamRoutes { routes }))
+        }
+        Err(e) => Ok(Response::InternalServerError(e).into()),
+    }
+}
+
+#[tracing::instrument]
+pub async fn delete_route(
+    org_id: &str,
+    stream_name: &str,
+    name: &str,
+) -> Result<HttpResponse, io::Error> {
+    rules::delete_stream_rule::<StreamRoute>(org_id, stream_name, name).await
+}
+
+fn check_route(route: &mut StreamRoute) -> Result<(), String> {
+    route.destination = format_stream_name(route.destination.trim());
+    if route.name.trim().is_empty() || route.destination.is_empty() {
+        return Err("Please provide name/destination for route".to_owned());
+    }
+    if route.name.contains('/') {
+        return Err("Route name can not contain '/'".to_owned());
+    }
+    if route.destination == route.stream_name {
+        return Err("Route destination must be another stream".to_owned());
+    }
+    compile_condition(route, &route.org_id)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid route condition: {e}"))
+}
+
+/// a stream route ready to be evaluated on the records
+pub struct CompiledRoute {
+    pub destination: String,
+    pub copy: bool,
+    condition: Condition,
+}
+
+enum Condition {
+    Sql(SqlExpr),
+    Vrl(VRLRuntimeConfig),
+}
+
+fn compile_condition(route: &StreamRoute, org_id: &str) -> Result<Condition, anyhow::Error> {
+    match route.condition_type {
+        RouteConditionType::Sql => Ok(Condition::Sql(sql_condition::parse(&route.condition)?)),
+        RouteConditionType::Vrl => {
+            let vrl_runtime = ingestion::compile_vrl_function(&route.condition, org_id)?;
+            let registry = vrl_runtime.config.get_custom::<TableRegistry>().unwrap();
+            registry.finish_load();
+            Ok(Condition::Vrl(vrl_runtime))
+        }
+    }
+}
+
+/// compile the routes of the stream, the routes whose condition doesn't
+/// compile are skipped
+pub fn register_stream_routes(org_id: &str, stream_name: &str) -> Vec<CompiledRoute> {
+    let prefix = format!("{org_id}/{stream_name}/");
+    let mut routes = STREAM_ROUTES
+        .iter()
+        .filter(|r| r.key().starts_with(&prefix))
+        .map(|r| r.value().clone())
+        .collect::<Vec<_>>();
+    routes.sort_by(|a, b| a.name.cmp(&b.name));
+    routes
+        .into_iter()
+        .filter_map(|route| match compile_condition(&route, org_id) {
+            Ok(condition) => Some(CompiledRoute {
+                destination: route.destination,
+                copy: route.copy,
+                condition,
+            }),
+            Err(e) => {
+                log::error!(
+                    "stream route {} of {stream_name} is invalid: {e}",
+                    route.name
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+/// send the record to the destinations of the matching routes, returns
+/// whether the record stays in the source stream. the routed records are not
+/// routed again in their destination streams
+pub fn route_record(
+    routes: &[CompiledRoute],
+    value: &Value,
+    runtime: &mut Runtime,
+    routed: &mut Vec<(String, Map<String, Value>)>,
+) -> bool {
+    let Some(record) = value.as_object() else {
+        return true;
+    };
+    let mut keep = true;
+    for route in routes {
+        let matched = match &route.condition {
+            Condition::Sql(expr) => sql_condition::matches(expr, record),
+            Condition::Vrl(vrl_runtime) => matches!(
+                ingestion::apply_vrl_fn(runtime, vrl_runtime, value),
+                Value::Bool(true)
+            ),
+        };
+        if matched {
+            routed.push((route.destination.clone(), record.clone()));
+            keep = keep && route.copy;
+        }
+    }
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::utils::json;
+
+    fn sql_route(destination: &str, condition: &str, copy: bool) -> CompiledRoute {
+        let route = StreamRoute {
+            org_id: "default".to_string(),
+            stream_name: "k8s".to_string(),
+            name: destination.to_string(),
+            destination: destination.to_string(),
+            condition: condition.to_string(),
+            condition_type: RouteConditionType::Sql,
+            copy,
+        };
+        CompiledRoute {
+            destination: route.destination.clone(),
+            copy,
+            condition: compile_condition(&route, "default").unwrap(),
+        }
+    }
+
+    fn sql_matches(condition: &str, record: &Value) -> bool {
+        let route = sql_route("dest", condition, false);
+        let Condition::Sql(expr) = &route.condition else {
+            unreachable!()
+        };
+        sql_condition::matches(expr, record.as_object().unwrap())
+    }
+
+    #[test]
+    fn test_sql_condition() {
+        let record = json::json!({
+            "level": "error",
+            "code": 502,
+            "kubernetes_container_name": "nginx-ingress",
+            "audit": true,
+        });
+        assert!(sql_matches("level = 'error'", &record));
+        assert!(!sql_matches("level != 'error'", &record));
+        assert!(sql_matches("code >= 500 AND code < 600", &record));
+        assert!(sql_matches("code = '502'", &record));
+        assert!(sql_matches("code BETWEEN 500 AND 599", &record));
+        assert!(sql_matches("level IN ('warn', 'error')", &record));
+        assert!(sql_matches(
+            "kubernetes.container_name LIKE 'nginx%'",
+            &record
+        ));
+        assert!(sql_matches(
+            "Kubernetes_Container_Name ILIKE 'NGINX_%'",
+            &record
+        ));
+        assert!(sql_matches(
+            "str_match(kubernetes_container_name, 'ingress')",
+            &record
+        ));
+        assert!(sql_matches("audit IS TRUE OR missing = 1", &record));
+        assert!(sql_matches(
+            "missing IS NULL AND NOT (level = 'info')",
+            &record
+        ));
+        assert!(!sql_matches("missing = 1", &record));
+        assert!(!sql_matches("missing != 1", &record));
+    }
+
+    #[test]
+    fn test_invalid_sql_condition() {
+        let mut route = StreamRoute {
+            org_id: "default".to_string(),
+            stream_name: "k8s".to_string(),
+            name: "errors".to_string(),
+            destination: "app_errors".to_string(),
+            condition: "level = 'error' extra".to_string(),
+            condition_type: RouteConditionType::Sql,
+            copy: false,
+        };
+        assert!(check_route(&mut route).is_err());
+        route.condition = "level = (SELECT 1)".to_string();
+        assert!(check_route(&mut route).is_err());
+        route.condition = "level = 'error'".to_string();
+        assert!(check_route(&mut route).is_ok());
+        route.destination = "k8s".to_string();
+        assert!(check_route(&mut route).is_err());
+    }
+
+    #[test]
+    fn test_route_record() {
+        let routes = vec![
+            sql_route("nginx", "kubernetes_container_name = 'nginx'", false),
+            sql_route("app_errors", "level = 'error'", false),
+            sql_route("audit", "audit = true", true),
+        ];
+        let mut runtime = ingestion::init_functions_runtime();
+        let mut routed = vec![];
+
+        let record = json::json!({"kubernetes_container_name": "app", "level": "info"});
+        assert!(route_record(&routes, &record, &mut runtime, &mut routed));
+        assert!(routed.is_empty());
+
+        let record = json::json!({"kubernetes_container_name": "nginx", "level": "error"});
+        assert!(!route_record(&routes, &record, &mut runtime, &mut routed));
+        assert_eq!(routed.len(), 2);
+        assert_eq!(routed[0].0, "nginx");
+        assert_eq!(routed[1].0, "app_errors");
+
+        routed.clear();
+        let record = json::json!({"kubernetes_container_name": "app", "audit": true});
+        assert!(route_record(&routes, &record, &mut runtime, &mut routed));
+        assert_eq!(routed.len(), 1);
+        assert_eq!(routed[0].0, "audit");
+    }
+}
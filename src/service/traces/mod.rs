@@ -43,7 +43,7 @@ use crate::service::{
 };
 
 use super::{
-    ingestion::{grpc::get_val, write_file},
+    ingestion::{grpc::get_val, quota, redaction, write_file},
     usage::report_request_usage_stats,
 };
 
@@ -218,6 +218,14 @@ pub async fn handle_trace_request(
                     json::Value::Number(timestamp.into()),
                 );
 
+                redaction::redact(
+                    &StreamParams {
+                        org_id,
+                        stream_name: traces_stream_name,
+                        stream_type: StreamType::Traces,
+                    },
+                    val_map,
+                );
                 let value_str = crate::common::utils::json::to_string(&val_map).unwrap();
 
                 // get hour key
@@ -281,6 +289,13 @@ pub async fn handle_trace_request(
         }
     }
 
+    if quota::has_quotas(org_id) {
+        let (events, bytes) = quota::buffered_usage(data_buf.values());
+        if let Err(e) = quota::check(org_id, &[(traces_stream_name, events, bytes)]).await {
+            return Ok(e.to_response());
+        }
+    }
+
     let mut traces_file_name = "".to_string();
     let mut req_stats = write_file(
         data_buf,
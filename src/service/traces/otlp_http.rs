@@ -34,7 +34,7 @@ use crate::common::{
 };
 use crate::service::{
     db, format_partition_key, format_stream_name,
-    ingestion::write_file,
+    ingestion::{quota, redaction, write_file},
     logs::get_value,
     schema::{add_stream_schema, stream_schema_exists},
     usage::report_request_usage_stats,
@@ -306,6 +306,14 @@ pub async fn traces_json(
                         json::Value::Number(timestamp.into()),
                     );
 
+                    redaction::redact(
+                        &StreamParams {
+                            org_id,
+                            stream_name: traces_stream_name,
+                            stream_type: StreamType::Traces,
+                        },
+                        val_map,
+                    );
                     let value_str = crate::common::utils::json::to_string(&val_map).unwrap();
                     // get hour key
                     let mut hour_key = crate::service::ingestion::get_wal_time_key(
@@ -367,6 +375,13 @@ pub async fn traces_json(
         }
     }
 
+    if quota::has_quotas(org_id) {
+        let (events, bytes) = quota::buffered_usage(data_buf.values());
+        if let Err(e) = quota::check(org_id, &[(traces_stream_name, events, bytes)]).await {
+            return Ok(e.to_response());
+        }
+    }
+
     let mut traces_file_name = "".to_string();
     let mut req_stats = write_file(
         data_buf,
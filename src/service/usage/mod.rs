@@ -113,6 +113,48 @@ pub async fn report_request_usage_stats(
     }
 }
 
+/// the records rejected by the ingestion quotas, reported apart from the
+/// ingestion as they are not written
+pub async fn report_quota_exceeded(
+    org_id: &str,
+    stream_name: &str,
+    limit: &str,
+    records: u64,
+    bytes: u64,
+) {
+    metrics::INGEST_QUOTA_REJECTED_RECORDS
+        .with_label_values(&[org_id, stream_name, limit])
+        .inc_by(records);
+    metrics::INGEST_QUOTA_REJECTED_BYTES
+        .with_label_values(&[org_id, stream_name, limit])
+        .inc_by(bytes);
+
+    if !CONFIG.common.usage_enabled {
+        return;
+    }
+    let now = Utc::now();
+    let usage = vec![UsageData {
+        event: UsageEvent::QuotaExceeded,
+        year: now.year(),
+        month: now.month(),
+        day: now.day(),
+        hour: now.hour(),
+        org_id: org_id.to_owned(),
+        request_body: format!("{}/{limit}", UsageType::QuotaExceeded.to_string()),
+        size: bytes as f64 / (1024.0 * 1024.0),
+        unit: "MB".to_owned(),
+        user_email: "".to_owned(),
+        response_time: 0.0,
+        num_records: records as i64,
+        stream_type: StreamType::Logs,
+        stream_name: stream_name.to_owned(),
+        min_ts: None,
+        max_ts: None,
+        compressed_size: None,
+    }];
+    publish_usage(usage).await;
+}
+
 pub async fn report_compression_stats(
     stats: RequestStats,
     org_id: &str,
@@ -29,7 +29,10 @@ use crate::common::meta::{
 };
 use crate::common::utils::json;
 use crate::handler::grpc::cluster_rpc;
-use crate::service::{db, search as SearchService};
+use crate::service::{
+    db,
+    search::{self as SearchService, queue::Priority},
+};
 
 use super::ingestion_service;
 
@@ -79,7 +82,15 @@ pub async fn publish_stats() -> Result<(), anyhow::Error> {
             encoding: meta::search::RequestEncoding::Empty,
         };
         // do search
-        match SearchService::search(&CONFIG.common.usage_org, meta::StreamType::Logs, &req).await {
+        match SearchService::search(
+            &CONFIG.common.usage_org,
+            meta::StreamType::Logs,
+            "",
+            Priority::Batch,
+            &req,
+        )
+        .await
+        {
             Ok(res) => {
                 if !res.hits.is_empty() {
                     match report_stats(res.hits, &org_id, last_query_ts, current_ts).await {
@@ -143,7 +154,15 @@ async fn get_last_stats(
         aggs: HashMap::new(),
         encoding: meta::search::RequestEncoding::Empty,
     };
-    match SearchService::search(&CONFIG.common.usage_org, meta::StreamType::Logs, &req).await {
+    match SearchService::search(
+        &CONFIG.common.usage_org,
+        meta::StreamType::Logs,
+        "",
+        Priority::Batch,
+        &req,
+    )
+    .await
+    {
         Ok(res) => Ok(res.hits),
         Err(err) => match &err {
             crate::common::infra::errors::Error::ErrorCode(
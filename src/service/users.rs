@@ -313,7 +313,7 @@ pub async fn remove_user_from_org(org_id: &str, email_id: &str) -> Result<HttpRe
                     let resp = db::user::set(user).await;
                     //special case as we cache flattened user struct
                     if resp.is_ok() {
-                        USERS.remove(&format!("{org_id}/{email_id}"));
+                        db::user::uncache_user(&format!("{org_id}/{email_id}"));
                     }
                 }
                 Ok(HttpResponse::Ok().json(MetaHttpResponse::message(